@@ -0,0 +1,123 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{collections::HashSet, sync::Arc};
+
+/// Bearer token required to access the HTTP API, along with the paths that are accessible
+/// without it.
+pub(crate) struct TokenAuth {
+    token: String,
+    health_paths: HashSet<String>,
+}
+
+impl TokenAuth {
+    /// The health endpoints of the agent (`/healthz`) and of each of the named cameras
+    /// (`/{camera}/healthz`) are accessible without the token, so that probes do not need it.
+    pub(crate) fn new<'a>(token: &str, cameras: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut health_paths: HashSet<String> = cameras
+            .into_iter()
+            .map(|camera| format!("/{camera}/healthz"))
+            .collect();
+        health_paths.insert("/healthz".to_owned());
+
+        Self {
+            token: token.to_owned(),
+            health_paths,
+        }
+    }
+}
+
+/// Middleware that rejects requests which do not present the bearer token, other than those to
+/// health endpoints.
+pub(crate) async fn require_token(
+    State(auth): State<Arc<TokenAuth>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    if auth.health_paths.contains(request.uri().path())
+        || satori_common::check_bearer_token(authorization, &auth.token)
+    {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn get_status(authorization: Option<&str>, uri: &str) -> StatusCode {
+        let app = Router::new()
+            .route("/cam1/jpeg", get(|| async { "frame" }))
+            .route("/cam1/healthz", get(|| async { "ok" }))
+            .route("/healthz", get(|| async { "ok" }))
+            .route("/cam1/hls/healthz", get(|| async { "segment" }))
+            .route("/cam2/healthz", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::new(TokenAuth::new("secret", ["cam1"])),
+                require_token,
+            ));
+
+        let mut request = Request::builder().uri(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_require_token() {
+        assert_eq!(
+            get_status(Some("Bearer secret"), "/cam1/jpeg").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_status(Some("Bearer wrong"), "/cam1/jpeg").await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_status(None, "/cam1/jpeg").await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_does_not_require_token() {
+        assert_eq!(get_status(None, "/cam1/healthz").await, StatusCode::OK);
+        assert_eq!(get_status(None, "/healthz").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_only_health_endpoints_exempt() {
+        // Other paths ending in the name of the health endpoint
+        assert_eq!(
+            get_status(None, "/cam1/hls/healthz").await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Cameras that are not configured
+        assert_eq!(
+            get_status(None, "/cam2/healthz").await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -0,0 +1,525 @@
+use crate::{
+    config::{CameraConfig, Config},
+    dash::{self, ManifestParams},
+    ffmpeg::Streamer,
+    health,
+    master_playlist::{self, MASTER_PLAYLIST_FILENAME},
+    mjpeg::{self, MjpegBroadcaster, MjpegParams, SharedImageData},
+    pruning,
+    snapshot::{SnapshotCache, SnapshotParams},
+    utils,
+};
+use axum::{
+    body::Body,
+    extract::Query,
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+use bytes::Bytes;
+use satori_common::{hls::Playlist, SegmentFormat};
+use std::{fs, path::PathBuf, time::Duration};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+};
+use tokio_stream::wrappers::BroadcastStream;
+use tower_http::services::ServeDir;
+use tracing::{debug, error, warn};
+
+/// A single camera handled by the agent.
+pub(crate) struct Camera {
+    config: CameraConfig,
+    streamer: Streamer,
+    default_playlist_window: Option<Duration>,
+
+    frame_image: SharedImageData,
+    mjpeg_tx: broadcast::Sender<Bytes>,
+    frame_handle: JoinHandle<()>,
+}
+
+impl Camera {
+    pub(crate) fn new(config: &Config, camera: CameraConfig) -> Self {
+        // Create video output directory
+        fs::create_dir_all(&camera.video_directory)
+            .expect("should be able to create output directory");
+        for rendition in &camera.stream.renditions {
+            fs::create_dir_all(camera.video_directory.join(&rendition.name))
+                .expect("should be able to create rendition output directory");
+        }
+
+        // Channel for JPEG frames
+        let (jpeg_tx, mut jpeg_rx) = broadcast::channel(8);
+
+        let streamer = Streamer::new(config, camera.clone(), jpeg_tx);
+
+        let frame_image = SharedImageData::default();
+        let mut mjpeg_broadcaster =
+            MjpegBroadcaster::new(frame_image.clone(), Duration::from_secs(1));
+        let mjpeg_tx = mjpeg_broadcaster.sender();
+
+        let frame_handle = tokio::spawn(async move {
+            loop {
+                match jpeg_rx.recv().await {
+                    Ok(image) => {
+                        mjpeg_broadcaster.handle_frame(image);
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Self {
+            config: camera,
+            streamer,
+            default_playlist_window: config.default_playlist_window,
+            frame_image,
+            mjpeg_tx,
+            frame_handle,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub(crate) async fn start(&mut self) {
+        self.streamer.start().await;
+    }
+
+    pub(crate) async fn stop(&mut self) {
+        self.streamer.stop().await;
+        self.frame_handle.abort();
+    }
+
+    /// HTTP endpoints for this camera.
+    fn router(&self) -> Router {
+        let video_directory = self.config.video_directory.clone();
+        let frame_image = self.frame_image.clone();
+        let snapshot_cache = SnapshotCache::new(Duration::from_secs(1));
+        let mjpeg_tx = self.mjpeg_tx.clone();
+        let mjpeg_max_fps = self.config.mjpeg_max_fps;
+        let camera = self.config.clone();
+        let default_playlist_window = self.default_playlist_window;
+        let master_playlist_camera = self.config.clone();
+
+        Router::new()
+            .route(
+                "/healthz",
+                get(move || async move { health::readiness(&video_directory) }),
+            )
+            .route("/player", get(Html(include_str!("player.html"))))
+            .route(
+                "/jpeg",
+                get(move |Query(params): Query<SnapshotParams>| async move {
+                    if let Err(e) = params.validate() {
+                        return (StatusCode::BAD_REQUEST, e).into_response();
+                    }
+
+                    let image = match frame_image.lock().unwrap().as_ref() {
+                        Some(image) => image.clone(),
+                        None => return StatusCode::NOT_FOUND.into_response(),
+                    };
+
+                    let image = if params.is_original() {
+                        image
+                    } else {
+                        match snapshot_cache.get_or_encode(image, params).await {
+                            Ok(image) => image,
+                            Err(e) => {
+                                error!("Failed to encode snapshot, err={}", e);
+                                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                            }
+                        }
+                    };
+
+                    ([(header::CONTENT_TYPE, "image/jpeg")], image).into_response()
+                }),
+            )
+            .route(
+                "/mjpeg",
+                get(move |Query(params): Query<MjpegParams>| async move {
+                    if let Err(e) = params.validate() {
+                        return (StatusCode::BAD_REQUEST, e).into_response();
+                    }
+
+                    let stream = mjpeg::throttle(
+                        BroadcastStream::new(mjpeg_tx.subscribe()),
+                        params.frame_interval(mjpeg_max_fps),
+                    );
+                    let body = Body::from_stream(stream);
+
+                    (
+                        [(
+                            header::CONTENT_TYPE,
+                            "multipart/x-mixed-replace; boundary=frame",
+                        )],
+                        body,
+                    )
+                        .into_response()
+                }),
+            )
+            .route(
+                "/dash/manifest.mpd",
+                get(move |Query(params): Query<ManifestParams>| async move {
+                    if let Err(e) = params.validate() {
+                        return (StatusCode::BAD_REQUEST, e).into_response();
+                    }
+
+                    let params = params.or_default_window(default_playlist_window);
+
+                    let playlist = match camera.get_playlist() {
+                        Ok(playlist) => playlist,
+                        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+                    };
+
+                    match dash::manifest(&camera, playlist, &params) {
+                        Ok(mpd) => {
+                            ([(header::CONTENT_TYPE, "application/dash+xml")], mpd).into_response()
+                        }
+                        Err(e) => {
+                            error!("Failed to generate DASH manifest, err={}", e);
+                            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                        }
+                    }
+                }),
+            )
+            .route(
+                &format!("/hls/{MASTER_PLAYLIST_FILENAME}"),
+                get(move || async move {
+                    match master_playlist::master_playlist(&master_playlist_camera) {
+                        Some(Ok(playlist)) => (
+                            [(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")],
+                            playlist,
+                        )
+                            .into_response(),
+                        Some(Err(_)) | None => StatusCode::NOT_FOUND.into_response(),
+                    }
+                }),
+            )
+            .nest_service("/hls", ServeDir::new(self.config.video_directory.clone()))
+    }
+
+    /// Prunes old segments and updates the metrics for this camera.
+    #[tracing::instrument(skip_all, fields(camera = self.config.name))]
+    pub(crate) fn housekeeping(&self) {
+        metrics::gauge!(
+            crate::METRIC_MJPEG_CLIENTS,
+            self.mjpeg_tx.receiver_count() as f64,
+            "camera" => self.config.name.clone()
+        );
+        self.prune_segments();
+        self.update_segment_count_metric();
+        self.update_segment_duration_metric();
+        self.update_segment_gap_metric();
+        self.update_disk_usage_metric();
+    }
+
+    fn prune_segments(&self) {
+        let max_disk_usage = match self.config.max_disk_usage {
+            Some(max_disk_usage) => max_disk_usage,
+            None => return,
+        };
+
+        debug!("Pruning segments");
+
+        // Segments in the playlist must not be deleted, so do nothing if the playlist is not known
+        let playlist = match self.config.get_playlist() {
+            Ok(playlist) => playlist,
+            Err(e) => {
+                warn!("Failed to read playlist, not pruning segments, err={}", e);
+                return;
+            }
+        };
+
+        match pruning::prune_segments(
+            &self.config.video_directory,
+            max_disk_usage,
+            &playlist,
+            self.config.stream.hls_segment_type,
+        ) {
+            Ok(deleted) => {
+                metrics::counter!(
+                    crate::METRIC_PRUNED_SEGMENTS,
+                    deleted as u64,
+                    "camera" => self.config.name.clone()
+                );
+            }
+            Err(e) => {
+                warn!("Failed to prune segments, err={}", e);
+            }
+        }
+    }
+
+    fn update_segment_count_metric(&self) {
+        debug!("Updating segment count metric");
+
+        match std::fs::read_dir(&self.config.video_directory) {
+            Ok(contents) => {
+                let segment_count = contents
+                    .filter_map(|i| i.ok())
+                    .map(|i| i.path())
+                    .filter(|i| i.is_file() && SegmentFormat::from_path(i).is_some())
+                    .count();
+
+                metrics::gauge!(
+                    crate::METRIC_SEGMENTS,
+                    segment_count as f64,
+                    "camera" => self.config.name.clone()
+                );
+            }
+            Err(e) => {
+                warn!("Failed to read video directory, err={}", e);
+            }
+        }
+    }
+
+    fn update_segment_duration_metric(&self) {
+        debug!("Updating segment duration metric");
+
+        match self.config.get_playlist() {
+            Ok(playlist) => {
+                if let Some(duration) = utils::get_average_segment_duration(&playlist) {
+                    metrics::gauge!(
+                        crate::METRIC_SEGMENT_DURATION,
+                        duration.as_secs_f64(),
+                        "camera" => self.config.name.clone()
+                    );
+                }
+            }
+            Err(e) => {
+                warn!("Failed to read playlist, err={}", e);
+            }
+        }
+    }
+
+    fn update_segment_gap_metric(&self) {
+        debug!("Updating segment gap metric");
+
+        let format = self.config.stream.hls_segment_type;
+        let playlist = self.config.get_playlist().and_then(|playlist| {
+            Playlist::parse(playlist, format.segment_filename_format()).map_err(|e| e.to_string())
+        });
+
+        match playlist {
+            Ok(playlist) => {
+                let threshold = Duration::from_secs(2 * self.config.stream.hls_segment_time as u64);
+                metrics::gauge!(
+                    crate::METRIC_SEGMENT_GAPS,
+                    utils::count_segment_gaps(&playlist, threshold) as f64,
+                    "camera" => self.config.name.clone()
+                );
+            }
+            Err(e) => {
+                warn!("Failed to read playlist, err={}", e);
+            }
+        }
+    }
+
+    fn update_disk_usage_metric(&self) {
+        debug!("Updating disk usage metric");
+
+        match self.config.get_disk_usage() {
+            Ok(disk_usage) => {
+                metrics::gauge!(
+                    crate::METRIC_DISK_USAGE,
+                    disk_usage.get_bytes() as f64,
+                    "camera" => self.config.name.clone()
+                );
+            }
+            Err(e) => {
+                warn!("Failed to update disk usage, err={}", e);
+            }
+        }
+    }
+}
+
+/// HTTP endpoints for all cameras, each nested under the name of the camera.
+///
+/// The top level health endpoint reports ready only once every camera is ready.
+pub(crate) fn router(cameras: &[Camera]) -> Router {
+    let video_directories: Vec<PathBuf> = cameras
+        .iter()
+        .map(|camera| camera.config.video_directory.clone())
+        .collect();
+
+    let router = Router::new().route(
+        "/healthz",
+        get(move || async move {
+            if video_directories
+                .iter()
+                .all(|d| health::readiness(d) == StatusCode::OK)
+            {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+        }),
+    );
+
+    cameras.iter().fold(router, |router, camera| {
+        router.nest(&format!("/{}", camera.name()), camera.router())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn get(app: &Router, uri: &str) -> (StatusCode, Bytes) {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_routes_per_camera() {
+        let dir_1 = tempfile::tempdir().unwrap();
+        let dir_2 = tempfile::tempdir().unwrap();
+
+        let config = crate::config::test::config(&[("cam1", dir_1.path()), ("cam2", dir_2.path())]);
+        let cameras: Vec<Camera> = config
+            .cameras
+            .iter()
+            .map(|camera| Camera::new(&config, camera.clone()))
+            .collect();
+
+        let app = router(&cameras);
+
+        // Neither camera has produced any video yet
+        assert_eq!(
+            get(&app, "/healthz").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            get(&app, "/cam1/hls/stream.m3u8").await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            get(&app, "/cam1/dash/manifest.mpd").await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get(&app, "/cam1/jpeg").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(
+            get(&app, "/cam1/jpeg?quality=101").await.0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            get(&app, "/cam1/jpeg?width=big").await.0,
+            StatusCode::BAD_REQUEST
+        );
+
+        for (dir, segment) in [
+            (&dir_1, "2023-01-01T00_00_00+0000.ts"),
+            (&dir_2, "2023-01-01T00_00_02+0000.ts"),
+        ] {
+            std::fs::write(
+                dir.path().join(crate::ffmpeg::HLS_PLAYLIST_FILENAME),
+                format!("#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2.000000,\n{segment}\n"),
+            )
+            .unwrap();
+            std::fs::write(dir.path().join(segment), segment).unwrap();
+        }
+
+        // Each camera serves its own video
+        let (status, body) = get(&app, "/cam1/hls/stream.m3u8").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(String::from_utf8_lossy(&body).contains("2023-01-01T00_00_00+0000.ts"));
+
+        let (status, body) = get(&app, "/cam2/hls/stream.m3u8").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(String::from_utf8_lossy(&body).contains("2023-01-01T00_00_02+0000.ts"));
+
+        let (status, body) = get(&app, "/cam2/hls/2023-01-01T00_00_02+0000.ts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, Bytes::from("2023-01-01T00_00_02+0000.ts"));
+
+        assert_eq!(
+            get(&app, "/cam1/hls/2023-01-01T00_00_02+0000.ts").await.0,
+            StatusCode::NOT_FOUND
+        );
+
+        // Each camera serves a DASH manifest of the same segments
+        let (status, body) = get(&app, "/cam1/dash/manifest.mpd").await;
+        assert_eq!(status, StatusCode::OK);
+        let mpd = String::from_utf8_lossy(&body);
+        assert!(mpd.contains("<MPD "));
+        assert_eq!(mpd.matches("<SegmentURL ").count(), 1);
+        assert!(mpd.contains(r#"media="../hls/2023-01-01T00_00_00+0000.ts""#));
+
+        assert_eq!(
+            get(
+                &app,
+                "/cam1/dash/manifest.mpd?last=60&since=2023-01-01T00:00:00Z"
+            )
+            .await
+            .0,
+            StatusCode::BAD_REQUEST
+        );
+
+        assert_eq!(get(&app, "/cam1/healthz").await.0, StatusCode::OK);
+        assert_eq!(get(&app, "/cam2/healthz").await.0, StatusCode::OK);
+        assert_eq!(get(&app, "/healthz").await.0, StatusCode::OK);
+
+        assert_eq!(get(&app, "/cam3/jpeg").await.0, StatusCode::NOT_FOUND);
+
+        // There is no master playlist without renditions
+        assert_eq!(
+            get(&app, "/cam1/hls/master.m3u8").await.0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_master_playlist_route() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut config = crate::config::test::config(&[("cam1", dir.path())]);
+        config.cameras[0].stream.renditions = vec![crate::config::RenditionConfig {
+            name: "low".into(),
+            width: 640,
+            height: 360,
+            bitrate: 500,
+        }];
+        let cameras = vec![Camera::new(&config, config.cameras[0].clone())];
+        assert!(dir.path().join("low").is_dir());
+
+        let app = router(&cameras);
+
+        // The source playlist is needed to estimate its bandwidth
+        assert_eq!(
+            get(&app, "/cam1/hls/master.m3u8").await.0,
+            StatusCode::NOT_FOUND
+        );
+
+        std::fs::write(
+            dir.path().join(crate::ffmpeg::HLS_PLAYLIST_FILENAME),
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2.000000,\n2023-01-01T00_00_00+0000.ts\n",
+        )
+        .unwrap();
+
+        let (status, body) = get(&app, "/cam1/hls/master.m3u8").await;
+        assert_eq!(status, StatusCode::OK);
+        let playlist = String::from_utf8_lossy(&body);
+        assert!(playlist.contains("#EXT-X-STREAM-INF:"));
+        assert!(playlist.contains("\nstream.m3u8\n"));
+        assert!(playlist.contains("RESOLUTION=640x360"));
+        assert!(playlist.contains("\nlow/stream.m3u8\n"));
+
+        // The playlists of the source and renditions are still served as files
+        assert_eq!(get(&app, "/cam1/hls/stream.m3u8").await.0, StatusCode::OK);
+    }
+}
@@ -1,24 +1,149 @@
 use byte_unit::Byte;
+use satori_common::{
+    camera_config::{check_camera_names, CameraConfigError},
+    SegmentFormat,
+};
 use serde::Deserialize;
 use serde_with::{serde_as, DurationSeconds};
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashSet, path::PathBuf, time::Duration};
 use url::Url;
 
 #[serde_as]
 #[derive(Clone, Deserialize)]
 pub(crate) struct Config {
+    /// Delay before restarting ffmpeg after it exits unexpectedly, doubled on each consecutive
+    /// failure.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub(crate) ffmpeg_restart_delay: Duration,
+
+    /// Upper limit on the delay before restarting ffmpeg.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[serde(default = "default_ffmpeg_restart_max_delay")]
+    pub(crate) ffmpeg_restart_max_delay: Duration,
+
+    /// Duration of video included in a DASH manifest when no segments are explicitly selected,
+    /// all retained video is included if not set.
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    #[serde(default)]
+    pub(crate) default_playlist_window: Option<Duration>,
+
+    /// Bearer token required by the HTTP API, which is unauthenticated if not set.
+    #[serde(default)]
+    pub(crate) auth_token: Option<String>,
+
+    /// Origins from which cross-origin requests to the HTTP API are allowed, only same-origin
+    /// requests are allowed if empty.
+    #[serde(default)]
+    pub(crate) cors_allowed_origins: Vec<String>,
+
+    pub(crate) cameras: Vec<CameraConfig>,
+}
+
+fn default_ffmpeg_restart_max_delay() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_audio() -> bool {
+    true
+}
+
+impl Config {
+    /// Checks the configuration of all cameras, reporting every problem found.
+    pub(crate) fn validate(&self) -> Result<(), CameraConfigError> {
+        let mut problems = check_camera_names(self.cameras.iter().map(|c| c.name.as_str()));
+
+        for origin in &self.cors_allowed_origins {
+            if let Err(e) = crate::cors::validate_origin(origin) {
+                problems.push(e);
+            }
+        }
+
+        for camera in &self.cameras {
+            // Camera names are used as a path segment of HTTP endpoints
+            if camera.name.contains('/') {
+                problems.push(format!(
+                    "camera name \"{}\" must not contain '/'",
+                    camera.name
+                ));
+            }
+        }
+
+        for camera in &self.cameras {
+            if camera.mjpeg_max_fps == Some(0) {
+                problems.push(format!(
+                    "mjpeg_max_fps of camera \"{}\" must be at least 1",
+                    camera.name
+                ));
+            }
+        }
+
+        for camera in &self.cameras {
+            let mut names = HashSet::new();
+            for rendition in &camera.stream.renditions {
+                if rendition.name.is_empty() || rendition.name.contains('/') {
+                    problems.push(format!(
+                        "rendition name \"{}\" of camera \"{}\" must be non-empty and must not contain '/'",
+                        rendition.name, camera.name
+                    ));
+                } else if !names.insert(&rendition.name) {
+                    problems.push(format!(
+                        "rendition name \"{}\" of camera \"{}\" is used more than once",
+                        rendition.name, camera.name
+                    ));
+                }
+            }
+        }
+
+        let mut video_directories = HashSet::new();
+        for camera in &self.cameras {
+            if !video_directories.insert(&camera.video_directory) {
+                problems.push(format!(
+                    "video directory {} is used by more than one camera",
+                    camera.video_directory.display()
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(CameraConfigError(problems))
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub(crate) struct CameraConfig {
+    /// Name of the camera, used as the path prefix of its HTTP endpoints.
+    pub(crate) name: String,
+
     pub(crate) video_directory: PathBuf,
 
     pub(crate) stream: StreamConfig,
 
-    #[serde_as(as = "DurationSeconds<u64>")]
-    pub(crate) ffmpeg_restart_delay: Duration,
+    /// Maximum disk usage of the video directory, beyond which the oldest segments that are no
+    /// longer in the playlist are deleted.
+    #[serde(default)]
+    pub(crate) max_disk_usage: Option<Byte>,
+
+    /// Maximum frame rate of the MJPEG stream sent to each client.
+    #[serde(default)]
+    pub(crate) mjpeg_max_fps: Option<u32>,
 }
 
-impl Config {
+impl CameraConfig {
     pub(crate) fn get_disk_usage(&self) -> std::io::Result<Byte> {
         crate::utils::get_size(&self.video_directory)
     }
+
+    pub(crate) fn get_playlist(&self) -> Result<m3u8_rs::MediaPlaylist, String> {
+        let data = std::fs::read(
+            self.video_directory
+                .join(crate::ffmpeg::HLS_PLAYLIST_FILENAME),
+        )
+        .map_err(|e| e.to_string())?;
+        m3u8_rs::parse_media_playlist_res(&data).map_err(|e| e.to_string())
+    }
 }
 
 #[derive(Clone, Deserialize)]
@@ -29,4 +154,175 @@ pub(crate) struct StreamConfig {
 
     pub(crate) hls_segment_time: i32,
     pub(crate) hls_retained_segment_count: i32,
+
+    /// Container format of the HLS segments.
+    #[serde(default)]
+    pub(crate) hls_segment_type: SegmentFormat,
+
+    /// Record audio from the camera, if it provides any.
+    #[serde(default = "default_audio")]
+    pub(crate) audio: bool,
+
+    /// Transcoded renditions produced in addition to the stream as received from the camera.
+    #[serde(default)]
+    pub(crate) renditions: Vec<RenditionConfig>,
+}
+
+/// A transcoded, typically lower bit rate, version of a camera stream.
+#[derive(Clone, Deserialize)]
+pub(crate) struct RenditionConfig {
+    /// Name of the rendition, used as the subdirectory of the video directory in which its
+    /// segments are saved.
+    pub(crate) name: String,
+
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+
+    /// Video bit rate in kbit/s.
+    pub(crate) bitrate: u32,
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::path::Path;
+
+    /// Builds a configuration with the given camera names and video directories.
+    pub(crate) fn config(cameras: &[(&str, &Path)]) -> Config {
+        Config {
+            ffmpeg_restart_delay: Duration::from_secs(1),
+            ffmpeg_restart_max_delay: default_ffmpeg_restart_max_delay(),
+            default_playlist_window: None,
+            auth_token: None,
+            cors_allowed_origins: Vec::new(),
+            cameras: cameras
+                .iter()
+                .map(|(name, video_directory)| CameraConfig {
+                    name: name.to_string(),
+                    video_directory: video_directory.to_path_buf(),
+                    stream: StreamConfig {
+                        url: Url::parse(&format!("rtsp://localhost/{name}")).unwrap(),
+                        ffmpeg_input_args: Vec::new(),
+                        hls_segment_time: 2,
+                        hls_retained_segment_count: 10,
+                        hls_segment_type: Default::default(),
+                        audio: true,
+                        renditions: Vec::new(),
+                    },
+                    max_disk_usage: None,
+                    mjpeg_max_fps: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let config: Config = toml::from_str(indoc::indoc! {r#"
+            ffmpeg_restart_delay = 5
+            default_playlist_window = 60
+            cors_allowed_origins = ["https://dashboard.example.com"]
+
+            [[cameras]]
+            name = "front"
+            video_directory = "/mnt/video/front"
+
+            [cameras.stream]
+            url = "rtsp://front/stream"
+            ffmpeg_input_args = []
+            hls_segment_time = 6
+            hls_retained_segment_count = 100
+
+            [[cameras]]
+            name = "back"
+            video_directory = "/mnt/video/back"
+            max_disk_usage = "10 GB"
+            mjpeg_max_fps = 5
+
+            [cameras.stream]
+            url = "rtsp://back/stream"
+            ffmpeg_input_args = ["-timeout", "5000000"]
+            hls_segment_time = 2
+            hls_retained_segment_count = 300
+            hls_segment_type = "fmp4"
+            audio = false
+
+            [[cameras.stream.renditions]]
+            name = "low"
+            width = 640
+            height = 360
+            bitrate = 500
+        "#})
+        .unwrap();
+
+        assert_eq!(config.ffmpeg_restart_delay, Duration::from_secs(5));
+        assert_eq!(config.ffmpeg_restart_max_delay, Duration::from_secs(300));
+        assert_eq!(
+            config.default_playlist_window,
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            config.cors_allowed_origins,
+            vec!["https://dashboard.example.com".to_owned()]
+        );
+
+        assert_eq!(config.cameras.len(), 2);
+        assert_eq!(config.cameras[0].name, "front");
+        assert_eq!(config.cameras[0].max_disk_usage, None);
+        assert_eq!(config.cameras[1].name, "back");
+        assert_eq!(
+            config.cameras[1].video_directory,
+            PathBuf::from("/mnt/video/back")
+        );
+        assert_eq!(
+            config.cameras[1].max_disk_usage,
+            Some(Byte::from_bytes(10_000_000_000))
+        );
+        assert_eq!(config.cameras[0].mjpeg_max_fps, None);
+        assert_eq!(config.cameras[1].mjpeg_max_fps, Some(5));
+        assert_eq!(config.cameras[1].stream.hls_segment_time, 2);
+        assert!(config.cameras[0].stream.audio);
+        assert!(!config.cameras[1].stream.audio);
+        assert!(config.cameras[0].stream.renditions.is_empty());
+        assert_eq!(config.cameras[1].stream.renditions.len(), 1);
+        assert_eq!(config.cameras[1].stream.renditions[0].name, "low");
+        assert_eq!(config.cameras[1].stream.renditions[0].bitrate, 500);
+        assert_eq!(
+            config.cameras[0].stream.hls_segment_type,
+            SegmentFormat::Mpegts
+        );
+        assert_eq!(
+            config.cameras[1].stream.hls_segment_type,
+            SegmentFormat::Fmp4
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let dir = Path::new("/tmp/video");
+
+        assert!(
+            config(&[("front", &dir.join("front")), ("back", &dir.join("back"))])
+                .validate()
+                .is_ok()
+        );
+
+        let err = config(&[
+            ("front", &dir.join("front")),
+            ("front", &dir.join("back")),
+            ("side/left", &dir.join("back")),
+            ("", &dir.join("empty")),
+        ])
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            err.0,
+            vec![
+                "camera name \"front\" is used more than once".to_owned(),
+                "camera name must not be empty".to_owned(),
+                "camera name \"side/left\" must not contain '/'".to_owned(),
+                "video directory /tmp/video/back is used by more than one camera".to_owned(),
+            ]
+        );
+    }
 }
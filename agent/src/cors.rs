@@ -0,0 +1,75 @@
+use axum::http::{header, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Checks that an allowed origin is a bare origin, e.g. "https://dashboard.example.com".
+pub(crate) fn validate_origin(origin: &str) -> Result<(), String> {
+    let url = url::Url::parse(origin).map_err(|e| format!("invalid origin \"{origin}\": {e}"))?;
+
+    if url.origin().ascii_serialization() == origin {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid origin \"{origin}\": must only contain a scheme, host and optional port"
+        ))
+    }
+}
+
+/// CORS layer allowing read only cross-origin requests from the given origins.
+pub(crate) fn layer(allowed_origins: &[String]) -> CorsLayer {
+    let allowed_origins = allowed_origins.iter().map(|origin| {
+        HeaderValue::from_str(origin).expect("origin should be a valid header value")
+    });
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(allowed_origins))
+        .allow_methods([Method::GET, Method::HEAD])
+        .allow_headers([header::AUTHORIZATION, header::RANGE])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, extract::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn allow_origin_header(origin: &str) -> Option<HeaderValue> {
+        let app = Router::new()
+            .route("/cam1/jpeg", get(|| async { "frame" }))
+            .layer(layer(&["https://dashboard.example.com".to_owned()]));
+
+        let request = Request::builder()
+            .uri("/cam1/jpeg")
+            .header(header::ORIGIN, origin)
+            .body(Body::empty())
+            .unwrap();
+
+        app.oneshot(request)
+            .await
+            .unwrap()
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn test_allowed_origin() {
+        assert_eq!(
+            allow_origin_header("https://dashboard.example.com").await,
+            Some(HeaderValue::from_static("https://dashboard.example.com"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_origin() {
+        assert_eq!(allow_origin_header("https://evil.example.com").await, None);
+    }
+
+    #[test]
+    fn test_validate_origin() {
+        assert!(validate_origin("https://dashboard.example.com").is_ok());
+        assert!(validate_origin("http://localhost:8080").is_ok());
+        assert!(validate_origin("https://dashboard.example.com/").is_err());
+        assert!(validate_origin("https://dashboard.example.com/path").is_err());
+        assert!(validate_origin("dashboard.example.com").is_err());
+    }
+}
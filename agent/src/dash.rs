@@ -0,0 +1,338 @@
+use crate::config::CameraConfig;
+use chrono::{DateTime, FixedOffset, Utc};
+use satori_common::{
+    hls::{Playlist, SegmentFile},
+    SegmentFormat,
+};
+use serde::Deserialize;
+use std::{fmt::Write, time::Duration};
+
+/// Timescale of the segment timeline, in ticks per second.
+const TIMESCALE: u64 = 1000;
+
+/// Path of the HLS segments, relative to the manifest.
+const SEGMENT_BASE: &str = "../hls/";
+
+/// Parameters used to select the segments included in a manifest.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub(crate) struct ManifestParams {
+    /// Only include segments that end after this time.
+    since: Option<DateTime<FixedOffset>>,
+
+    /// Only include segments that start before this time.
+    until: Option<DateTime<FixedOffset>>,
+
+    /// Only include the most recent segments covering this many seconds.
+    last: Option<u64>,
+}
+
+impl ManifestParams {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.last.is_some() && (self.since.is_some() || self.until.is_some()) {
+            return Err("last cannot be combined with since or until".into());
+        }
+
+        if let (Some(since), Some(until)) = (self.since, self.until) {
+            if since > until {
+                return Err("since must not be after until".into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies a default window, i.e. the equivalent of `last`, if no segments were explicitly
+    /// selected.
+    pub(crate) fn or_default_window(self, window: Option<Duration>) -> Self {
+        if self.since.is_none() && self.until.is_none() && self.last.is_none() {
+            Self {
+                last: window.map(|window| window.as_secs()),
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    fn select<'a>(&self, playlist: &'a Playlist) -> Vec<&'a SegmentFile> {
+        match self.last {
+            Some(last) => playlist.last(Duration::from_secs(last)),
+            None => playlist.between(
+                self.since
+                    .unwrap_or_else(|| DateTime::<Utc>::MIN_UTC.fixed_offset()),
+                self.until
+                    .unwrap_or_else(|| DateTime::<Utc>::MAX_UTC.fixed_offset()),
+            ),
+        }
+    }
+}
+
+/// Generates a static MPEG-DASH manifest for the segments of a camera's HLS playlist.
+///
+/// The manifest references the same segment files that are served for HLS, so no additional
+/// video is stored.
+pub(crate) fn manifest(
+    camera: &CameraConfig,
+    playlist: m3u8_rs::MediaPlaylist,
+    params: &ManifestParams,
+) -> Result<String, String> {
+    let format = camera.stream.hls_segment_type;
+    let playlist =
+        Playlist::parse(playlist, format.segment_filename_format()).map_err(|e| e.to_string())?;
+
+    let segments = params.select(&playlist);
+    let sizes = segments
+        .iter()
+        .map(|s| std::fs::metadata(camera.video_directory.join(&s.filename)).map(|m| m.len()))
+        .sum::<std::io::Result<u64>>()
+        .unwrap_or_default();
+
+    Ok(render_manifest(format, &segments, sizes))
+}
+
+fn render_manifest(format: SegmentFormat, segments: &[&SegmentFile], total_size: u64) -> String {
+    let total_duration: Duration = segments.iter().map(|s| s.duration).sum();
+    let max_duration = segments
+        .iter()
+        .map(|s| s.duration)
+        .max()
+        .unwrap_or_default();
+
+    // Average bit rate of the selected segments, as required for each representation
+    let bandwidth = match total_duration.as_secs_f64() {
+        secs if secs > 0.0 => (total_size as f64 * 8.0 / secs) as u64,
+        _ => 0,
+    };
+
+    let (profile, mime_type) = match format {
+        SegmentFormat::Mpegts => ("urn:mpeg:dash:profile:mp2t-simple:2011", "video/mp2t"),
+        SegmentFormat::Fmp4 => ("urn:mpeg:dash:profile:isoff-live:2011", "video/mp4"),
+    };
+
+    let mut mpd = String::new();
+    let _ = writeln!(mpd, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        mpd,
+        r#"<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" profiles="{profile}" mediaPresentationDuration="{}" minBufferTime="{}">"#,
+        xs_duration(total_duration),
+        xs_duration(max_duration),
+    );
+    let _ = writeln!(mpd, r#"  <Period id="0" start="PT0S">"#);
+    let _ = writeln!(
+        mpd,
+        r#"    <AdaptationSet mimeType="{mime_type}" segmentAlignment="true">"#
+    );
+    let _ = writeln!(
+        mpd,
+        r#"      <Representation id="0" bandwidth="{bandwidth}">"#
+    );
+    let _ = writeln!(mpd, r#"        <SegmentList timescale="{TIMESCALE}">"#);
+
+    if let Some(init) = segments.first().and_then(|s| s.init.as_deref()) {
+        let _ = writeln!(
+            mpd,
+            r#"          <Initialization sourceURL="{}"/>"#,
+            escape(&segment_url(init))
+        );
+    }
+
+    let _ = writeln!(mpd, "          <SegmentTimeline>");
+    for segment in segments {
+        let _ = writeln!(
+            mpd,
+            r#"            <S d="{}"/>"#,
+            segment.duration.as_millis() as u64 * TIMESCALE / 1000
+        );
+    }
+    let _ = writeln!(mpd, "          </SegmentTimeline>");
+
+    for segment in segments {
+        let _ = writeln!(
+            mpd,
+            r#"          <SegmentURL media="{}"/>"#,
+            escape(&segment_url(&segment.uri))
+        );
+    }
+
+    let _ = writeln!(mpd, "        </SegmentList>");
+    let _ = writeln!(mpd, "      </Representation>");
+    let _ = writeln!(mpd, "    </AdaptationSet>");
+    let _ = writeln!(mpd, "  </Period>");
+    let _ = writeln!(mpd, "</MPD>");
+
+    mpd
+}
+
+/// URL of a segment relative to the manifest, given its URI in the HLS playlist.
+fn segment_url(uri: &str) -> String {
+    if url::Url::parse(uri).is_ok() || uri.starts_with('/') {
+        uri.to_owned()
+    } else {
+        format!("{SEGMENT_BASE}{uri}")
+    }
+}
+
+/// Formats a duration as an `xs:duration`, e.g. "PT12.5S".
+fn xs_duration(duration: Duration) -> String {
+    format!("PT{}S", duration.as_millis() as f64 / 1000.0)
+}
+
+/// Escapes text for use in an XML attribute value.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn playlist(playlist: &str, format: SegmentFormat) -> Playlist {
+        Playlist::parse(
+            m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap(),
+            format.segment_filename_format(),
+        )
+        .unwrap()
+    }
+
+    const TS_PLAYLIST: &str = indoc::indoc! {"
+        #EXTM3U
+        #EXT-X-VERSION:3
+        #EXT-X-TARGETDURATION:6
+        #EXTINF:6.000000,
+        2023-01-01T00_00_00+0000.ts
+        #EXTINF:6.000000,
+        2023-01-01T00_00_06+0000.ts
+        #EXTINF:4.500000,
+        2023-01-01T00_00_12+0000.ts
+    "};
+
+    #[test]
+    fn test_render_manifest() {
+        let playlist = playlist(TS_PLAYLIST, SegmentFormat::Mpegts);
+        let segments: Vec<_> = playlist.segments.iter().collect();
+
+        let mpd = render_manifest(SegmentFormat::Mpegts, &segments, 16_500);
+
+        assert!(mpd.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        assert!(mpd.contains(r#"type="static""#));
+        assert!(mpd.contains(r#"mediaPresentationDuration="PT16.5S""#));
+        assert!(mpd.contains(r#"minBufferTime="PT6S""#));
+        assert!(mpd.contains(r#"mimeType="video/mp2t""#));
+        assert!(mpd.contains(r#"bandwidth="8000""#));
+        assert!(!mpd.contains("<Initialization"));
+
+        assert_eq!(mpd.matches("<SegmentURL ").count(), 3);
+        assert!(mpd.contains(r#"<SegmentURL media="../hls/2023-01-01T00_00_06+0000.ts"/>"#));
+        assert_eq!(mpd.matches(r#"<S d="6000"/>"#).count(), 2);
+        assert_eq!(mpd.matches(r#"<S d="4500"/>"#).count(), 1);
+    }
+
+    #[test]
+    fn test_render_manifest_fmp4() {
+        let playlist = playlist(
+            indoc::indoc! {r#"
+                #EXTM3U
+                #EXT-X-VERSION:7
+                #EXT-X-TARGETDURATION:6
+                #EXT-X-MAP:URI="init.mp4"
+                #EXTINF:6.000000,
+                2023-01-01T00_00_00+0000.m4s
+                #EXTINF:6.000000,
+                https://cdn.example.com/2023-01-01T00_00_06+0000.m4s?a=1&b=2
+            "#},
+            SegmentFormat::Fmp4,
+        );
+        let segments: Vec<_> = playlist.segments.iter().collect();
+
+        let mpd = render_manifest(SegmentFormat::Fmp4, &segments, 0);
+
+        assert!(mpd.contains(r#"mimeType="video/mp4""#));
+        assert!(mpd.contains(r#"<Initialization sourceURL="../hls/init.mp4"/>"#));
+        assert!(mpd.contains(
+            r#"<SegmentURL media="https://cdn.example.com/2023-01-01T00_00_06+0000.m4s?a=1&amp;b=2"/>"#
+        ));
+    }
+
+    #[test]
+    fn test_select() {
+        let playlist = playlist(TS_PLAYLIST, SegmentFormat::Mpegts);
+
+        assert_eq!(ManifestParams::default().select(&playlist).len(), 3);
+
+        let params = ManifestParams {
+            last: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(params.select(&playlist).len(), 2);
+
+        let params = ManifestParams {
+            since: Some(DateTime::parse_from_rfc3339("2023-01-01T00:00:07Z").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(params.select(&playlist).len(), 2);
+
+        let params = ManifestParams {
+            until: Some(DateTime::parse_from_rfc3339("2023-01-01T00:00:05Z").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(params.select(&playlist).len(), 1);
+    }
+
+    #[test]
+    fn test_default_window() {
+        let playlist = playlist(TS_PLAYLIST, SegmentFormat::Mpegts);
+        let window = Some(Duration::from_secs(5));
+
+        // Omitting all parameters only selects the segments within the default window
+        let params = ManifestParams::default().or_default_window(window);
+        assert_eq!(params.last, Some(5));
+        let segments = params.select(&playlist);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(
+            segments[0].filename,
+            std::path::PathBuf::from("2023-01-01T00_00_06+0000.ts")
+        );
+
+        // Without a default window all segments are selected
+        let params = ManifestParams::default().or_default_window(None);
+        assert_eq!(params.select(&playlist).len(), 3);
+
+        // Explicit parameters are used as is
+        let params = ManifestParams {
+            since: Some(DateTime::parse_from_rfc3339("1970-01-01T00:00:00Z").unwrap()),
+            ..Default::default()
+        }
+        .or_default_window(window);
+        assert!(params.validate().is_ok());
+        assert_eq!(params.select(&playlist).len(), 3);
+
+        let params = ManifestParams {
+            last: Some(60),
+            ..Default::default()
+        }
+        .or_default_window(window);
+        assert_eq!(params.last, Some(60));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ManifestParams::default().validate().is_ok());
+
+        let params = ManifestParams {
+            last: Some(60),
+            since: Some(Utc::now().fixed_offset()),
+            ..Default::default()
+        };
+        assert!(params.validate().is_err());
+
+        let params = ManifestParams {
+            since: Some(Utc::now().fixed_offset()),
+            until: Some(Utc::now().fixed_offset() - chrono::Duration::seconds(1)),
+            ..Default::default()
+        };
+        assert!(params.validate().is_err());
+    }
+}
@@ -1,5 +1,5 @@
 mod streamer;
-pub(crate) use self::streamer::Streamer;
+pub(crate) use self::streamer::{Streamer, HLS_PLAYLIST_FILENAME};
 
 mod version;
 pub(crate) use self::version::get_ffmpeg_version;
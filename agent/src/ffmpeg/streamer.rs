@@ -1,38 +1,53 @@
-use crate::{config::Config, jpeg_frame_decoder::JpegFrameDecoder};
+use crate::{
+    config::{CameraConfig, Config, RenditionConfig, StreamConfig},
+    jpeg_frame_decoder::JpegFrameDecoder,
+};
 use bytes::Bytes;
 use futures::StreamExt;
 use nix::{
     sys::signal::{self, Signal},
     unistd::{self, Pid},
 };
+use satori_common::SegmentFormat;
 use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
     process::Stdio,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
-    process::Command,
-    sync::broadcast::Sender,
+    process::{Child, Command},
+    sync::{broadcast::Sender, Notify},
     task::JoinHandle,
 };
 use tokio_util::codec::FramedRead;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, warn, Instrument};
 
-const HLS_PLAYLIST_FILENAME: &str = "stream.m3u8";
+pub(crate) const HLS_PLAYLIST_FILENAME: &str = "stream.m3u8";
 
 pub(crate) struct Streamer {
-    config: Config,
+    camera: CameraConfig,
+    restart_delay: Duration,
+    restart_max_delay: Duration,
+    program: PathBuf,
     terminate: Arc<Mutex<bool>>,
+    terminate_notify: Arc<Notify>,
     ffmpeg_pid: Arc<Mutex<Option<Pid>>>,
     handle: Option<JoinHandle<()>>,
     jpeg_tx: Sender<Bytes>,
 }
 
 impl Streamer {
-    pub(crate) fn new(config: Config, jpeg_tx: Sender<Bytes>) -> Self {
+    pub(crate) fn new(config: &Config, camera: CameraConfig, jpeg_tx: Sender<Bytes>) -> Self {
         Self {
-            config,
+            camera,
+            restart_delay: config.ffmpeg_restart_delay,
+            restart_max_delay: config.ffmpeg_restart_max_delay,
+            program: "ffmpeg".into(),
             terminate: Arc::new(Mutex::new(false)),
+            terminate_notify: Default::default(),
             ffmpeg_pid: Default::default(),
             handle: None,
             jpeg_tx,
@@ -41,135 +56,96 @@ impl Streamer {
 
     #[tracing::instrument(skip_all)]
     pub(crate) async fn start(&mut self) {
-        let config = self.config.clone();
+        let camera = self.camera.clone();
+        let restart_delay = self.restart_delay;
+        let restart_max_delay = self.restart_max_delay;
+        let program = self.program.clone();
         let ffmpeg_pid = self.ffmpeg_pid.clone();
         let terminate = self.terminate.clone();
+        let terminate_notify = self.terminate_notify.clone();
         let jpeg_tx = self.jpeg_tx.clone();
 
-        self.handle = Some(tokio::spawn(async move {
-            loop {
-                // Start ffmpeg as a child process
-                let mut ffmpeg_process = unsafe {
-                    Command::new("ffmpeg")
-                        // Always overwrite files
-                        .arg("-y")
-                        // Stream config
-                        .args(&config.stream.ffmpeg_input_args)
-                        .arg("-i")
-                        .arg(config.stream.url.to_string())
-                        .arg("-c:v")
-                        .arg("copy")
-                        .arg("-c:a")
-                        .arg("copy")
-                        // HLS output stream
-                        .arg("-f")
-                        .arg("hls")
-                        .arg("-hls_time")
-                        .arg(config.stream.hls_segment_time.to_string())
-                        .arg("-hls_list_size")
-                        .arg(config.stream.hls_retained_segment_count.to_string())
-                        .arg("-hls_flags")
-                        .arg("append_list+delete_segments")
-                        .arg("-hls_segment_filename")
-                        .arg(
-                            config
-                                .video_directory
-                                .join(satori_common::SEGMENT_FILENAME_FORMAT),
-                        )
-                        .arg("-strftime")
-                        .arg("1")
-                        .arg(config.video_directory.join(HLS_PLAYLIST_FILENAME))
-                        // Output preview frames as JPEG
-                        .arg("-vf")
-                        .arg("fps=1")
-                        .arg("-f")
-                        .arg("image2")
-                        .arg("-update")
-                        .arg("1")
-                        .arg("pipe:1")
-                        // Do nothing with stdin
-                        .stdin(Stdio::null())
-                        // Capture stdout and stderr
-                        .stdout(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        // Call setsid, required for correct exit signal handling
-                        .pre_exec(|| {
-                            unistd::setsid()?;
-                            Ok(())
-                        })
-                        .spawn()
-                        .expect("ffmpeg process should be started")
-                };
-                debug!("ffmpeg process: {:?}", ffmpeg_process);
-
-                // Get and store the ffmpeg PID
-                *ffmpeg_pid.lock().unwrap() = {
-                    let pid = Pid::from_raw(
-                        ffmpeg_process
-                            .id()
-                            .expect("ffmpeg process should have a PID")
-                            as i32,
-                    );
-                    info!("ffmpeg PID: {:?}", pid);
-                    Some(pid)
-                };
+        let span = tracing::info_span!("streamer", camera = self.camera.name);
 
-                // Increment ffmpeg invocation count
-                metrics::counter!(crate::METRIC_FFMPEG_INVOCATIONS, 1);
+        self.handle = Some(tokio::spawn(
+            async move {
+                let mut consecutive_failures = 0;
 
-                let stdout = ffmpeg_process.stdout.take().unwrap();
-                let mut stdout_frame = FramedRead::new(stdout, JpegFrameDecoder);
+                loop {
+                    // Start ffmpeg as a child process
+                    let ffmpeg_process = unsafe {
+                        Command::new(&program)
+                            .args(ffmpeg_args(&camera))
+                            // Do nothing with stdin
+                            .stdin(Stdio::null())
+                            // Capture stdout and stderr
+                            .stdout(Stdio::piped())
+                            .stderr(Stdio::piped())
+                            // Call setsid, required for correct exit signal handling
+                            .pre_exec(|| {
+                                unistd::setsid()?;
+                                Ok(())
+                            })
+                            .spawn()
+                    };
 
-                let stderr = ffmpeg_process.stderr.take().unwrap();
-                let mut stderr_reader = BufReader::new(stderr).lines();
+                    match ffmpeg_process {
+                        Ok(ffmpeg_process) => {
+                            // Increment ffmpeg invocation count
+                            metrics::counter!(
+                                crate::METRIC_FFMPEG_INVOCATIONS,
+                                1,
+                                "camera" => camera.name.clone()
+                            );
 
-                loop {
-                    tokio::select! {
-                        // Handle JPEG data via stdout
-                        Some(frame) = stdout_frame.next() => {
-                            match frame {
-                                Ok(frame) => {
-                                    debug!("Got JPEG frame ({} bytes)", frame.len());
-                                    if let Err(e ) = jpeg_tx.send(frame) {
-                                        error!("JPEG frame channel error: {}", e);
-                                    }
-                                }
-                                Err(e) => error!("ffmpeg stdout frame errror: {:?}", e),
-                            }
-                        }
-                        // Output stderr to log with prefix
-                        line = stderr_reader.next_line() => {
-                            match line {
-                                Ok(Some(line)) => info!("ffmpeg stderr: {line}"),
-                                Err(e) => {
-                                    warn!("ffmpeg stderr closed: {e}");
-                                    break;
-                                },
-                                _ => (),
+                            metrics::gauge!(
+                                crate::METRIC_FFMPEG_RUNNING,
+                                1.0,
+                                "camera" => camera.name.clone()
+                            );
+                            let got_frames =
+                                run_ffmpeg(ffmpeg_process, &ffmpeg_pid, &jpeg_tx).await;
+                            metrics::gauge!(
+                                crate::METRIC_FFMPEG_RUNNING,
+                                0.0,
+                                "camera" => camera.name.clone()
+                            );
+
+                            // Only back off further if ffmpeg never managed to produce any output,
+                            // i.e. the camera is likely still unavailable
+                            if got_frames {
+                                consecutive_failures = 0;
                             }
                         }
-                        // Wait for ffmpeg process to exit
-                        result = ffmpeg_process.wait() => {
-                            info!("ffmpeg exited, ok={}", result.is_ok());
-                            *ffmpeg_pid.lock().unwrap() = None;
-                            break;
+                        Err(e) => {
+                            error!("Failed to start ffmpeg: {e}");
                         }
                     }
-                }
 
-                let expected_shutdown = *terminate.lock().unwrap();
-                if expected_shutdown {
-                    info!("Termination requested, not restarting ffmpeg");
-                    break;
-                } else {
-                    warn!(
-                        "ffmpeg exited unexpectedly, restarting in {:?}",
-                        config.ffmpeg_restart_delay
+                    if *terminate.lock().unwrap() {
+                        info!("Termination requested, not restarting ffmpeg");
+                        break;
+                    }
+
+                    let delay = calculate_restart_delay(
+                        restart_delay,
+                        restart_max_delay,
+                        consecutive_failures,
                     );
-                    tokio::time::sleep(config.ffmpeg_restart_delay).await;
+                    consecutive_failures += 1;
+                    warn!("ffmpeg exited unexpectedly, restarting in {:?}", delay);
+
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = terminate_notify.notified() => {
+                            info!("Termination requested, not restarting ffmpeg");
+                            break;
+                        }
+                    }
                 }
             }
-        }));
+            .instrument(span),
+        ));
     }
 
     #[tracing::instrument(skip_all)]
@@ -179,10 +155,15 @@ impl Streamer {
         // Set terminate flag to ensure ffmpeg is not restarted
         *self.terminate.lock().unwrap() = true;
 
+        // Interrupt waiting to restart ffmpeg
+        self.terminate_notify.notify_one();
+
         // Request ffmpeg to terminate
         info!("Sending {} to ffmpeg process", FFMPEG_EXIT_SIGNAL);
         if let Some(ffmpeg_pid) = *self.ffmpeg_pid.lock().unwrap() {
-            signal::kill(ffmpeg_pid, FFMPEG_EXIT_SIGNAL).unwrap();
+            if let Err(e) = signal::kill(ffmpeg_pid, FFMPEG_EXIT_SIGNAL) {
+                warn!("Failed to signal ffmpeg process: {e}");
+            }
         }
 
         // Wait for ffmpeg to exit
@@ -191,3 +172,315 @@ impl Streamer {
         }
     }
 }
+
+/// Arguments of the ffmpeg invocation that records a camera.
+fn ffmpeg_args(camera: &CameraConfig) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec![
+        // Always overwrite files
+        "-y".into(),
+    ];
+
+    // Stream config
+    args.extend(camera.stream.ffmpeg_input_args.iter().map(OsString::from));
+    args.extend(["-i".into(), camera.stream.url.to_string().into()]);
+
+    // HLS output stream, as received from the camera
+    args.extend(["-c:v", "copy"].map(OsString::from));
+    args.extend(audio_args(&camera.stream, "copy"));
+    args.extend(hls_output_args(&camera.stream, &camera.video_directory));
+
+    // HLS output stream of each transcoded rendition
+    for rendition in &camera.stream.renditions {
+        args.extend(rendition_args(
+            &camera.stream,
+            rendition,
+            &camera.video_directory.join(&rendition.name),
+        ));
+    }
+
+    // Output preview frames as JPEG
+    args.extend(["-vf", "fps=1", "-f", "image2", "-update", "1", "pipe:1"].map(OsString::from));
+
+    args
+}
+
+/// Arguments of a HLS output written to a directory.
+fn hls_output_args(stream: &StreamConfig, directory: &Path) -> Vec<OsString> {
+    vec![
+        "-f".into(),
+        "hls".into(),
+        "-hls_time".into(),
+        stream.hls_segment_time.to_string().into(),
+        "-hls_list_size".into(),
+        stream.hls_retained_segment_count.to_string().into(),
+        "-hls_flags".into(),
+        "append_list+delete_segments".into(),
+        "-hls_segment_type".into(),
+        segment_type_arg(stream.hls_segment_type).into(),
+        "-hls_segment_filename".into(),
+        directory
+            .join(stream.hls_segment_type.segment_filename_format())
+            .into(),
+        "-strftime".into(),
+        "1".into(),
+        directory.join(HLS_PLAYLIST_FILENAME).into(),
+    ]
+}
+
+/// Arguments of a transcoded HLS output.
+fn rendition_args(
+    stream: &StreamConfig,
+    rendition: &RenditionConfig,
+    directory: &Path,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["-map".into(), "0:v:0".into()];
+    if stream.audio {
+        args.extend(["-map", "0:a:0?"].map(OsString::from));
+    }
+    args.extend([
+        "-vf".into(),
+        format!("scale={}:{}", rendition.width, rendition.height).into(),
+        "-c:v".into(),
+        "libx264".into(),
+        "-preset".into(),
+        "veryfast".into(),
+        "-b:v".into(),
+        format!("{}k", rendition.bitrate).into(),
+        "-maxrate".into(),
+        format!("{}k", rendition.bitrate).into(),
+        "-bufsize".into(),
+        format!("{}k", rendition.bitrate * 2).into(),
+        // Keyframes at segment boundaries, so that segments align with the source stream
+        "-force_key_frames".into(),
+        format!("expr:gte(t,n_forced*{})", stream.hls_segment_time).into(),
+    ]);
+    args.extend(audio_args(stream, "aac"));
+    args.extend(hls_output_args(stream, directory));
+    args
+}
+
+/// Arguments selecting the audio codec of an output, or disabling audio.
+fn audio_args(stream: &StreamConfig, codec: &str) -> Vec<OsString> {
+    if stream.audio {
+        vec!["-c:a".into(), codec.into()]
+    } else {
+        vec!["-an".into()]
+    }
+}
+
+/// Handles the output of a running ffmpeg process until it exits.
+///
+/// Returns true if any JPEG frames were received from ffmpeg.
+async fn run_ffmpeg(
+    mut ffmpeg_process: Child,
+    ffmpeg_pid: &Mutex<Option<Pid>>,
+    jpeg_tx: &Sender<Bytes>,
+) -> bool {
+    debug!("ffmpeg process: {:?}", ffmpeg_process);
+
+    // Get and store the ffmpeg PID
+    *ffmpeg_pid.lock().unwrap() = ffmpeg_process.id().map(|pid| {
+        let pid = Pid::from_raw(pid as i32);
+        info!("ffmpeg PID: {:?}", pid);
+        pid
+    });
+
+    let stdout = ffmpeg_process.stdout.take().unwrap();
+    let mut stdout_frame = FramedRead::new(stdout, JpegFrameDecoder);
+
+    let stderr = ffmpeg_process.stderr.take().unwrap();
+    let mut stderr_reader = BufReader::new(stderr).lines();
+
+    let mut got_frames = false;
+
+    loop {
+        tokio::select! {
+            // Handle JPEG data via stdout
+            Some(frame) = stdout_frame.next() => {
+                match frame {
+                    Ok(frame) => {
+                        debug!("Got JPEG frame ({} bytes)", frame.len());
+                        got_frames = true;
+                        if let Err(e ) = jpeg_tx.send(frame) {
+                            error!("JPEG frame channel error: {}", e);
+                        }
+                    }
+                    Err(e) => error!("ffmpeg stdout frame errror: {:?}", e),
+                }
+            }
+            // Output stderr to log with prefix
+            line = stderr_reader.next_line() => {
+                match line {
+                    Ok(Some(line)) => info!("ffmpeg stderr: {line}"),
+                    Err(e) => {
+                        warn!("ffmpeg stderr closed: {e}");
+                        break;
+                    },
+                    _ => (),
+                }
+            }
+            // Wait for ffmpeg process to exit
+            result = ffmpeg_process.wait() => {
+                info!("ffmpeg exited, ok={}", result.is_ok());
+                break;
+            }
+        }
+    }
+
+    *ffmpeg_pid.lock().unwrap() = None;
+
+    got_frames
+}
+
+/// Calculates the delay before restarting ffmpeg, doubling with each consecutive failure up to
+/// the maximum delay.
+fn calculate_restart_delay(base: Duration, max: Duration, consecutive_failures: u32) -> Duration {
+    base.saturating_mul(2_u32.saturating_pow(consecutive_failures))
+        .min(max)
+}
+
+/// Value of the ffmpeg `hls_segment_type` option for a segment format.
+fn segment_type_arg(format: SegmentFormat) -> &'static str {
+    match format {
+        SegmentFormat::Mpegts => "mpegts",
+        SegmentFormat::Fmp4 => "fmp4",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_restart_delay() {
+        let base = Duration::from_secs(2);
+        let max = Duration::from_secs(60);
+
+        assert_eq!(
+            calculate_restart_delay(base, max, 0),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            calculate_restart_delay(base, max, 1),
+            Duration::from_secs(4)
+        );
+        assert_eq!(
+            calculate_restart_delay(base, max, 4),
+            Duration::from_secs(32)
+        );
+        assert_eq!(calculate_restart_delay(base, max, 5), max);
+        assert_eq!(calculate_restart_delay(base, max, 100), max);
+    }
+
+    #[test]
+    fn test_ffmpeg_args_without_renditions() {
+        let config = crate::config::test::config(&[("camera1", Path::new("/video"))]);
+        let args = ffmpeg_args(&config.cameras[0]);
+
+        assert_eq!(
+            args.iter().filter(|arg| *arg == "hls").count(),
+            1,
+            "{args:?}"
+        );
+        assert!(args.contains(&OsString::from("/video/stream.m3u8")));
+        assert!(!args.contains(&OsString::from("libx264")));
+        assert_eq!(args.last().unwrap(), "pipe:1");
+    }
+
+    #[test]
+    fn test_ffmpeg_args_with_renditions() {
+        let mut config = crate::config::test::config(&[("camera1", Path::new("/video"))]);
+        config.cameras[0].stream.renditions = vec![RenditionConfig {
+            name: "low".into(),
+            width: 640,
+            height: 360,
+            bitrate: 500,
+        }];
+        let args = ffmpeg_args(&config.cameras[0]);
+
+        assert_eq!(
+            args.iter().filter(|arg| *arg == "hls").count(),
+            2,
+            "{args:?}"
+        );
+        assert!(args.contains(&OsString::from("/video/stream.m3u8")));
+        assert!(args.contains(&OsString::from("/video/low/stream.m3u8")));
+        assert!(args.contains(&OsString::from("/video/low/%Y-%m-%dT%H_%M_%S%z.ts")));
+        assert!(args.contains(&OsString::from("scale=640:360")));
+        assert!(args.contains(&OsString::from("500k")));
+        assert_eq!(args.last().unwrap(), "pipe:1");
+    }
+
+    #[test]
+    fn test_ffmpeg_args_audio() {
+        let mut config = crate::config::test::config(&[("camera1", Path::new("/video"))]);
+        config.cameras[0].stream.renditions = vec![RenditionConfig {
+            name: "low".into(),
+            width: 640,
+            height: 360,
+            bitrate: 500,
+        }];
+
+        let args = ffmpeg_args(&config.cameras[0]);
+        assert!(!args.contains(&OsString::from("-an")));
+        assert!(args.contains(&OsString::from("0:a:0?")));
+        assert!(args.contains(&OsString::from("aac")));
+
+        config.cameras[0].stream.audio = false;
+        let args = ffmpeg_args(&config.cameras[0]);
+        assert_eq!(args.iter().filter(|arg| *arg == "-an").count(), 2);
+        assert!(!args.contains(&OsString::from("-c:a")));
+        assert!(!args.contains(&OsString::from("0:a:0?")));
+    }
+
+    #[tokio::test]
+    async fn test_restart_after_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let invocations_file = dir.path().join("invocations");
+
+        // Fake ffmpeg that exits immediately, recording each invocation
+        let program = dir.path().join("ffmpeg");
+        std::fs::write(
+            &program,
+            format!(
+                "#!/bin/sh\necho run >> {}\nexit 1\n",
+                invocations_file.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = crate::config::test::config(&[("camera1", dir.path())]);
+        config.ffmpeg_restart_delay = Duration::from_millis(10);
+        config.ffmpeg_restart_max_delay = Duration::from_millis(40);
+
+        let (jpeg_tx, _) = tokio::sync::broadcast::channel(8);
+        let mut streamer = Streamer::new(&config, config.cameras[0].clone(), jpeg_tx);
+        streamer.program = program;
+        streamer.start().await;
+
+        let count_invocations = || {
+            std::fs::read_to_string(&invocations_file)
+                .map(|s| s.lines().count())
+                .unwrap_or(0)
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while count_invocations() < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("ffmpeg should be restarted");
+
+        tokio::time::timeout(Duration::from_secs(5), streamer.stop())
+            .await
+            .expect("streamer should stop");
+
+        // No further restarts once stopped
+        let count = count_invocations();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(count_invocations(), count);
+    }
+}
@@ -0,0 +1,65 @@
+use axum::http::StatusCode;
+use std::path::Path;
+use tracing::debug;
+
+/// Reports if the agent is ready to serve video, i.e. ffmpeg has written a playlist to
+/// `video_directory` that references at least one segment.
+pub(crate) fn readiness(video_directory: &Path) -> StatusCode {
+    let playlist = std::fs::read(video_directory.join(crate::ffmpeg::HLS_PLAYLIST_FILENAME))
+        .map_err(|e| e.to_string())
+        .and_then(|data| m3u8_rs::parse_media_playlist_res(&data).map_err(|e| e.to_string()));
+
+    match playlist {
+        Ok(playlist) if !playlist.segments.is_empty() => StatusCode::OK,
+        Ok(_) => {
+            debug!("Playlist contains no segments");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        Err(e) => {
+            debug!("Failed to read playlist: {e}");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ffmpeg::HLS_PLAYLIST_FILENAME;
+
+    #[test]
+    fn test_readiness() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // No playlist yet
+        assert_eq!(readiness(dir.path()), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Playlist without any segments
+        std::fs::write(
+            dir.path().join(HLS_PLAYLIST_FILENAME),
+            indoc::indoc! {"
+                #EXTM3U
+                #EXT-X-VERSION:3
+                #EXT-X-TARGETDURATION:6
+                #EXT-X-MEDIA-SEQUENCE:0
+            "},
+        )
+        .unwrap();
+        assert_eq!(readiness(dir.path()), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Playlist once the first segment has been written
+        std::fs::write(
+            dir.path().join(HLS_PLAYLIST_FILENAME),
+            indoc::indoc! {"
+                #EXTM3U
+                #EXT-X-VERSION:3
+                #EXT-X-TARGETDURATION:6
+                #EXT-X-MEDIA-SEQUENCE:0
+                #EXTINF:6.000000,
+                2023-01-01T00_00_00+0000.ts
+            "},
+        )
+        .unwrap();
+        assert_eq!(readiness(dir.path()), StatusCode::OK);
+    }
+}
@@ -0,0 +1,131 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+
+pub(crate) const METRIC_HTTP_REQUESTS: &str = "satori_http_requests";
+pub(crate) const METRIC_HTTP_REQUEST_DURATION: &str = "satori_http_request_duration_seconds";
+
+const SERVICE: &str = "agent";
+
+pub(crate) fn describe() {
+    metrics::describe_counter!(
+        METRIC_HTTP_REQUESTS,
+        metrics::Unit::Count,
+        "Number of HTTP requests handled"
+    );
+
+    metrics::describe_histogram!(
+        METRIC_HTTP_REQUEST_DURATION,
+        metrics::Unit::Seconds,
+        "Time taken to handle HTTP requests"
+    );
+}
+
+/// Middleware that records the count and latency of HTTP requests.
+///
+/// Requests are labeled with the route template rather than the raw request path to avoid
+/// cardinality blowup from requests for individual files.
+pub(crate) async fn record(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_owned(),
+        None => "unmatched".to_owned(),
+    };
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let duration = start.elapsed();
+
+    let status = response.status().as_u16().to_string();
+
+    metrics::counter!(
+        METRIC_HTTP_REQUESTS,
+        1,
+        "service" => SERVICE,
+        "method" => method.clone(),
+        "path" => path.clone(),
+        "status" => status.clone()
+    );
+
+    metrics::histogram!(
+        METRIC_HTTP_REQUEST_DURATION,
+        duration.as_secs_f64(),
+        "service" => SERVICE,
+        "method" => method,
+        "path" => path,
+        "status" => status
+    );
+
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, routing::get, Router};
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_request_metrics_recorded() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::set_boxed_recorder(Box::new(recorder)).unwrap();
+
+        let app = Router::new()
+            .route("/thing/:name", get(|| async { "hello" }))
+            .layer(axum::middleware::from_fn(record));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/thing/one")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/nothing")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let rendered = handle.render();
+
+        let requests_line = rendered
+            .lines()
+            .find(|l| l.starts_with(METRIC_HTTP_REQUESTS) && l.contains("/thing/:name"))
+            .expect("request counter should be recorded");
+        assert!(requests_line.contains(r#"service="agent""#));
+        assert!(requests_line.contains(r#"method="GET""#));
+        assert!(requests_line.contains(r#"status="200""#));
+        assert!(requests_line.ends_with(" 1"));
+
+        let duration_line = rendered
+            .lines()
+            .find(|l| {
+                l.starts_with(&format!("{METRIC_HTTP_REQUEST_DURATION}_count"))
+                    && l.contains("/thing/:name")
+            })
+            .expect("request latency should be recorded");
+        assert!(duration_line.contains(r#"status="200""#));
+        assert!(duration_line.ends_with(" 1"));
+
+        // Raw request paths are never used as labels
+        assert!(!rendered.contains("/thing/one"));
+        assert!(!rendered.contains("/nothing"));
+        assert!(rendered.contains(r#"path="unmatched""#));
+    }
+}
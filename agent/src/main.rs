@@ -1,39 +1,39 @@
+mod auth;
+mod camera;
 mod config;
+mod cors;
+mod dash;
 mod ffmpeg;
+mod health;
+mod http_metrics;
 mod jpeg_frame_decoder;
+mod master_playlist;
+mod mjpeg;
+mod pruning;
+mod snapshot;
+mod trace_context;
 mod utils;
 
-use axum::{
-    body::Body,
-    http::header,
-    response::{Html, IntoResponse},
-    routing::get,
-    Router,
-};
-use bytes::{BufMut, Bytes};
 use clap::Parser;
 use metrics_exporter_prometheus::PrometheusBuilder;
-use std::{
-    fs,
-    net::SocketAddr,
-    path::PathBuf,
-    sync::{Arc, Mutex},
-    time::Duration,
-};
+use satori_common::LogFormat;
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 use tokio::net::TcpListener;
-use tokio_stream::wrappers::BroadcastStream;
-use tower_http::services::ServeDir;
-use tracing::{debug, info, warn};
+use tracing::{error, info};
 
 const METRIC_DISK_USAGE: &str = "satori_agent_disk_usage";
 const METRIC_FFMPEG_INVOCATIONS: &str = "satori_agent_ffmpeg_invocations";
+const METRIC_FFMPEG_RUNNING: &str = "satori_agent_ffmpeg_running";
+const METRIC_MJPEG_CLIENTS: &str = "satori_agent_mjpeg_clients";
+const METRIC_PRUNED_SEGMENTS: &str = "satori_agent_pruned_segments";
 const METRIC_SEGMENTS: &str = "satori_agent_segments";
-
-type SharedImageData = Arc<Mutex<Option<Bytes>>>;
+const METRIC_SEGMENT_DURATION: &str = "satori_agent_segment_duration";
+const METRIC_SEGMENT_GAPS: &str = "satori_agent_segment_gaps";
 
 /// Run the camera agent.
 ///
-/// Handles restreaming a single camera as HLS with history.
+/// Handles restreaming one or more cameras as HLS (MPEG-TS or fragmented MP4 segments) with
+/// history.
 #[derive(Clone, Parser)]
 #[command(author, version = satori_common::version!(), about, long_about = None)]
 pub(crate) struct Cli {
@@ -41,21 +41,33 @@ pub(crate) struct Cli {
     #[arg(short, long, env = "CONFIG_FILE", value_name = "FILE")]
     config: PathBuf,
 
-    /// Address to listen on for observability/metrics endpoints
+    /// Address to listen on for the HTTP API of the cameras (video, frames and health)
     #[clap(long, env = "HTTP_SERVER_ADDRESS", default_value = "127.0.0.1:8000")]
     http_server_address: SocketAddr,
 
     /// Address to listen on for observability/metrics endpoints
     #[clap(long, env = "OBSERVABILITY_ADDRESS", default_value = "127.0.0.1:9090")]
     observability_address: SocketAddr,
+
+    /// File to append the details of any panic to
+    #[arg(long, env = "CRASH_FILE", value_name = "FILE")]
+    crash_file: Option<PathBuf>,
+
+    /// Format of log output
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t)]
+    log_format: LogFormat,
 }
 
 #[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
-
+async fn main() -> Result<(), ()> {
     let cli = Cli::parse();
+    let _tracing = satori_common::init_tracing(env!("CARGO_PKG_NAME"), cli.log_format);
+    satori_common::install_panic_hook(cli.crash_file.clone());
     let config: config::Config = satori_common::load_config_file(&cli.config);
+    if let Err(err) = config.validate() {
+        error!("{err}");
+        return Err(());
+    }
 
     info!("FFmpeg version: {}", ffmpeg::get_ffmpeg_version());
 
@@ -69,7 +81,7 @@ async fn main() {
     metrics::describe_gauge!(
         METRIC_DISK_USAGE,
         metrics::Unit::Bytes,
-        "Disk usage of this camera's output video directory"
+        "Disk usage of the output video directory of each camera"
     );
 
     metrics::describe_counter!(
@@ -78,21 +90,54 @@ async fn main() {
         "Number of times ffmpeg has been invoked"
     );
 
+    metrics::describe_gauge!(
+        METRIC_FFMPEG_RUNNING,
+        metrics::Unit::Count,
+        "If ffmpeg is currently running (1) or waiting to be restarted (0)"
+    );
+
+    metrics::describe_gauge!(
+        METRIC_MJPEG_CLIENTS,
+        metrics::Unit::Count,
+        "Number of clients connected to the MJPEG stream"
+    );
+
+    metrics::describe_counter!(
+        METRIC_PRUNED_SEGMENTS,
+        metrics::Unit::Count,
+        "Number of segments deleted to keep disk usage under the limit"
+    );
+
     metrics::describe_gauge!(
         METRIC_SEGMENTS,
         metrics::Unit::Count,
-        "Number of MPEG-TS segments generated"
+        "Number of segments in the output video directory of each camera"
     );
 
-    // Create video output directory
-    fs::create_dir_all(&config.video_directory).expect("should be able to create output directory");
+    metrics::describe_gauge!(
+        METRIC_SEGMENT_DURATION,
+        metrics::Unit::Seconds,
+        "Average duration of the segments in the HLS playlist"
+    );
 
-    // Channel for JPEG frames
-    let (jpeg_tx, mut jpeg_rx) = tokio::sync::broadcast::channel(8);
+    metrics::describe_gauge!(
+        METRIC_SEGMENT_GAPS,
+        metrics::Unit::Count,
+        "Number of gaps of more than two segment durations between segments in the HLS playlist"
+    );
 
-    // Start streamer
-    let mut streamer = ffmpeg::Streamer::new(config.clone(), jpeg_tx);
-    streamer.start().await;
+    http_metrics::describe();
+
+    // Start streamers
+    let mut cameras: Vec<camera::Camera> = config
+        .cameras
+        .iter()
+        .map(|camera| camera::Camera::new(&config, camera.clone()))
+        .collect();
+    for camera in cameras.iter_mut() {
+        info!("Starting camera: {}", camera.name());
+        camera.start().await;
+    }
 
     // Configure HTTP server listener
     let listener = TcpListener::bind(&cli.http_server_address)
@@ -100,44 +145,23 @@ async fn main() {
         .unwrap_or_else(|_| panic!("tcp listener should bind to {}", cli.http_server_address));
 
     // Configure HTTP server endpoints
-    let frame_image = SharedImageData::default();
-    let (jpeg_multipart_tx, _) = tokio::sync::broadcast::channel::<Bytes>(8);
-
-    let app = {
-        let frame_image = frame_image.clone();
-        let jpeg_multipart_tx = jpeg_multipart_tx.clone();
-
-        Router::new()
-            .route("/player", get(Html(include_str!("player.html"))))
-            .route(
-                "/jpeg",
-                get(move || async move {
-                    match frame_image.lock().unwrap().as_ref() {
-                        Some(image) => {
-                            ([(header::CONTENT_TYPE, "image/jpeg")], image.clone()).into_response()
-                        }
-                        None => axum::http::StatusCode::NOT_FOUND.into_response(),
-                    }
-                }),
-            )
-            .route(
-                "/mjpeg",
-                get(move || async move {
-                    let stream = BroadcastStream::new(jpeg_multipart_tx.subscribe());
-                    let body = Body::from_stream(stream);
-
-                    (
-                        [(
-                            header::CONTENT_TYPE,
-                            "multipart/x-mixed-replace; boundary=frame",
-                        )],
-                        body,
-                    )
-                        .into_response()
-                }),
-            )
-            .nest_service("/", ServeDir::new(config.video_directory.clone()))
-    };
+    let mut app = camera::router(&cameras);
+    if let Some(token) = &config.auth_token {
+        app = app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(auth::TokenAuth::new(
+                token,
+                cameras.iter().map(|camera| camera.name()),
+            )),
+            auth::require_token,
+        ));
+    }
+    if !config.cors_allowed_origins.is_empty() {
+        // Outside of authentication, as preflight requests do not carry credentials
+        app = app.layer(cors::layer(&config.cors_allowed_origins));
+    }
+    let app = app
+        .layer(axum::middleware::from_fn(http_metrics::record))
+        .layer(axum::middleware::from_fn(trace_context::continue_trace));
 
     // Start HTTP server
     info!("Starting HTTP server on {}", cli.http_server_address);
@@ -148,20 +172,10 @@ async fn main() {
     let mut metrics_interval = tokio::time::interval(Duration::from_secs(30));
     loop {
         tokio::select! {
-            Ok(image) = jpeg_rx.recv() => {
-                let mut body = bytes::BytesMut::new();
-                body.put_slice(b"--frame\r\n");
-                body.put_slice(format!("{}: image/jpeg\r\n", header::CONTENT_TYPE).as_bytes());
-                body.put_slice(format!("{}: {}\r\n", header::CONTENT_LENGTH, image.len()).as_bytes());
-                body.put_slice(b"\r\n");
-                body.put_slice(&image);
-                let _ = jpeg_multipart_tx.send(body.into());
-
-                frame_image.lock().unwrap().replace(image);
-            }
             _ = metrics_interval.tick() => {
-                update_segment_count_metric(&config);
-                update_disk_usage_metric(&config);
+                for camera in &cameras {
+                    camera.housekeeping();
+                }
             }
             _ = tokio::signal::ctrl_c() => {
                 info!("Exiting");
@@ -170,55 +184,15 @@ async fn main() {
         }
     }
 
-    // Stop streamer
-    streamer.stop().await;
+    // Stop streamers
+    for camera in cameras.iter_mut() {
+        camera.stop().await;
+    }
 
     // Stop server
     info!("Stopping HTTP server");
     server_handle.abort();
     let _ = server_handle.await;
-}
 
-#[tracing::instrument(skip_all)]
-fn update_segment_count_metric(config: &config::Config) {
-    debug!("Updating segment count metric");
-
-    match std::fs::read_dir(&config.video_directory) {
-        Ok(contents) => {
-            let ts_file_count = contents
-                .filter_map(|i| i.ok())
-                .map(|i| i.path())
-                .filter(|i| {
-                    if i.is_file() {
-                        if let Some(ext) = i.extension() {
-                            ext.to_str() == Some("ts")
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    }
-                })
-                .count();
-
-            metrics::gauge!(METRIC_SEGMENTS, ts_file_count as f64);
-        }
-        Err(e) => {
-            warn!("Failed to read video directory, err={}", e);
-        }
-    }
-}
-
-#[tracing::instrument(skip_all)]
-fn update_disk_usage_metric(config: &config::Config) {
-    debug!("Updating disk usage metric");
-
-    match config.get_disk_usage() {
-        Ok(disk_usage) => {
-            metrics::gauge!(METRIC_DISK_USAGE, disk_usage.get_bytes() as f64);
-        }
-        Err(e) => {
-            warn!("Failed to update disk usage, err={}", e);
-        }
-    }
+    Ok(())
 }
@@ -0,0 +1,134 @@
+use crate::config::{CameraConfig, RenditionConfig};
+use std::path::Path;
+
+pub(crate) const MASTER_PLAYLIST_FILENAME: &str = "master.m3u8";
+
+/// Generates a HLS master playlist listing the stream as received from the camera and each of
+/// its transcoded renditions.
+///
+/// Returns `None` if the camera has no renditions.
+pub(crate) fn master_playlist(camera: &CameraConfig) -> Option<Result<String, String>> {
+    if camera.stream.renditions.is_empty() {
+        return None;
+    }
+
+    Some(camera.get_playlist().map(|playlist| {
+        let source_bandwidth = estimate_bandwidth(&camera.video_directory, &playlist);
+        render(source_bandwidth, &camera.stream.renditions)
+    }))
+}
+
+fn render(source_bandwidth: u64, renditions: &[RenditionConfig]) -> String {
+    let source = m3u8_rs::VariantStream {
+        uri: crate::ffmpeg::HLS_PLAYLIST_FILENAME.into(),
+        bandwidth: source_bandwidth,
+        ..Default::default()
+    };
+
+    let variants = renditions.iter().map(|rendition| m3u8_rs::VariantStream {
+        uri: format!(
+            "{}/{}",
+            rendition.name,
+            crate::ffmpeg::HLS_PLAYLIST_FILENAME
+        ),
+        bandwidth: rendition.bitrate as u64 * 1000,
+        resolution: Some(m3u8_rs::Resolution {
+            width: rendition.width.into(),
+            height: rendition.height.into(),
+        }),
+        ..Default::default()
+    });
+
+    let playlist = m3u8_rs::MasterPlaylist {
+        version: Some(3),
+        variants: std::iter::once(source).chain(variants).collect(),
+        ..Default::default()
+    };
+
+    let mut s = Vec::new();
+    playlist
+        .write_to(&mut s)
+        .expect("playlist should be written to memory");
+    String::from_utf8(s).expect("playlist should be valid UTF-8")
+}
+
+/// Estimates the bit rate of a stream from the size of the segments in its playlist.
+fn estimate_bandwidth(directory: &Path, playlist: &m3u8_rs::MediaPlaylist) -> u64 {
+    let duration: f32 = playlist.segments.iter().map(|s| s.duration).sum();
+    let size: u64 = playlist
+        .segments
+        .iter()
+        .filter_map(|s| std::fs::metadata(directory.join(&s.uri)).ok())
+        .map(|m| m.len())
+        .sum();
+
+    if duration > 0.0 {
+        (size as f64 * 8.0 / duration as f64) as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let playlist = render(
+            4_000_000,
+            &[
+                RenditionConfig {
+                    name: "medium".into(),
+                    width: 1280,
+                    height: 720,
+                    bitrate: 1500,
+                },
+                RenditionConfig {
+                    name: "low".into(),
+                    width: 640,
+                    height: 360,
+                    bitrate: 500,
+                },
+            ],
+        );
+
+        let playlist = match m3u8_rs::parse_playlist_res(playlist.as_bytes()).unwrap() {
+            m3u8_rs::Playlist::MasterPlaylist(playlist) => playlist,
+            m3u8_rs::Playlist::MediaPlaylist(_) => panic!("should be a master playlist"),
+        };
+
+        assert_eq!(playlist.variants.len(), 3);
+
+        assert_eq!(playlist.variants[0].uri, "stream.m3u8");
+        assert_eq!(playlist.variants[0].bandwidth, 4_000_000);
+        assert_eq!(playlist.variants[0].resolution, None);
+
+        assert_eq!(playlist.variants[1].uri, "medium/stream.m3u8");
+        assert_eq!(playlist.variants[1].bandwidth, 1_500_000);
+        assert_eq!(
+            playlist.variants[1].resolution,
+            Some(m3u8_rs::Resolution {
+                width: 1280,
+                height: 720
+            })
+        );
+
+        assert_eq!(playlist.variants[2].uri, "low/stream.m3u8");
+        assert_eq!(playlist.variants[2].bandwidth, 500_000);
+    }
+
+    #[test]
+    fn test_estimate_bandwidth() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("one.ts"), vec![0; 1000]).unwrap();
+        std::fs::write(dir.path().join("two.ts"), vec![0; 3000]).unwrap();
+
+        let playlist = m3u8_rs::parse_media_playlist_res(
+            b"#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2.0,\none.ts\n#EXTINF:2.0,\ntwo.ts\n",
+        )
+        .unwrap();
+
+        assert_eq!(estimate_bandwidth(dir.path(), &playlist), 8000);
+    }
+}
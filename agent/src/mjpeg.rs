@@ -0,0 +1,240 @@
+use axum::http::header;
+use bytes::{BufMut, Bytes};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+
+pub(crate) type SharedImageData = Arc<Mutex<Option<Bytes>>>;
+
+/// Parameters of a MJPEG stream client.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub(crate) struct MjpegParams {
+    /// Maximum frame rate in frames per second, defaults to the rate of the source.
+    fps: Option<u32>,
+}
+
+impl MjpegParams {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.fps == Some(0) {
+            return Err("fps must be at least 1".into());
+        }
+
+        Ok(())
+    }
+
+    /// Minimum interval between frames sent to the client, taking into account the server side
+    /// frame rate limit.
+    pub(crate) fn frame_interval(&self, max_fps: Option<u32>) -> Option<Duration> {
+        let fps = match (self.fps, max_fps) {
+            (Some(fps), Some(max_fps)) => fps.min(max_fps),
+            (fps, max_fps) => fps.or(max_fps)?,
+        };
+        Some(Duration::from_secs(1) / fps.max(1))
+    }
+}
+
+/// Limits the rate of frames in a stream by dropping any frame that arrives less than `interval`
+/// after the last frame that was passed on.
+pub(crate) fn throttle<S, T, E>(
+    stream: S,
+    interval: Option<Duration>,
+) -> impl Stream<Item = Result<T, E>>
+where
+    S: Stream<Item = Result<T, E>>,
+{
+    let mut last_frame: Option<Instant> = None;
+
+    stream.filter(move |frame| {
+        let now = Instant::now();
+
+        let pass = match (frame, interval, last_frame) {
+            (Ok(_), Some(interval), Some(last)) => now.duration_since(last) >= interval,
+            _ => true,
+        };
+        if pass && frame.is_ok() {
+            last_frame = Some(now);
+        }
+
+        futures::future::ready(pass)
+    })
+}
+
+/// Distributes JPEG frames to the latest frame store and to MJPEG stream clients.
+///
+/// When no MJPEG clients are connected the multipart encoding and broadcast is skipped and the
+/// latest frame is only updated every `idle_frame_interval`.
+pub(crate) struct MjpegBroadcaster {
+    frame_image: SharedImageData,
+    multipart_tx: broadcast::Sender<Bytes>,
+
+    idle_frame_interval: Duration,
+    last_frame_update: Option<Instant>,
+}
+
+impl MjpegBroadcaster {
+    pub(crate) fn new(frame_image: SharedImageData, idle_frame_interval: Duration) -> Self {
+        let (multipart_tx, _) = broadcast::channel(8);
+
+        Self {
+            frame_image,
+            multipart_tx,
+            idle_frame_interval,
+            last_frame_update: None,
+        }
+    }
+
+    pub(crate) fn sender(&self) -> broadcast::Sender<Bytes> {
+        self.multipart_tx.clone()
+    }
+
+    pub(crate) fn client_count(&self) -> usize {
+        self.multipart_tx.receiver_count()
+    }
+
+    /// Handles a new frame, returning true if it was broadcast to MJPEG clients.
+    pub(crate) fn handle_frame(&mut self, image: Bytes) -> bool {
+        let now = Instant::now();
+
+        if self.client_count() > 0 {
+            let _ = self.multipart_tx.send(encode_multipart_frame(&image));
+            self.update_frame(image, now);
+            true
+        } else {
+            let due = match self.last_frame_update {
+                Some(last) => now.duration_since(last) >= self.idle_frame_interval,
+                None => true,
+            };
+            if due {
+                self.update_frame(image, now);
+            }
+            false
+        }
+    }
+
+    fn update_frame(&mut self, image: Bytes, now: Instant) {
+        self.frame_image.lock().unwrap().replace(image);
+        self.last_frame_update = Some(now);
+    }
+}
+
+fn encode_multipart_frame(image: &Bytes) -> Bytes {
+    let mut body = bytes::BytesMut::new();
+    body.put_slice(b"--frame\r\n");
+    body.put_slice(format!("{}: image/jpeg\r\n", header::CONTENT_TYPE).as_bytes());
+    body.put_slice(format!("{}: {}\r\n", header::CONTENT_LENGTH, image.len()).as_bytes());
+    body.put_slice(b"\r\n");
+    body.put_slice(image);
+    body.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_no_clients_skips_broadcast() {
+        let frame_image = SharedImageData::default();
+        let mut broadcaster = MjpegBroadcaster::new(frame_image.clone(), Duration::from_secs(60));
+        assert_eq!(broadcaster.client_count(), 0);
+
+        // The first frame is always stored
+        assert!(!broadcaster.handle_frame(Bytes::from("one")));
+        assert_eq!(frame_image.lock().unwrap().as_ref().unwrap(), "one");
+
+        // Frames are only stored at the idle rate
+        assert!(!broadcaster.handle_frame(Bytes::from("two")));
+        assert_eq!(frame_image.lock().unwrap().as_ref().unwrap(), "one");
+    }
+
+    #[test]
+    fn test_broadcast_resumes_when_client_connects() {
+        let frame_image = SharedImageData::default();
+        let mut broadcaster = MjpegBroadcaster::new(frame_image.clone(), Duration::from_secs(60));
+
+        assert!(!broadcaster.handle_frame(Bytes::from("one")));
+
+        let mut rx = broadcaster.sender().subscribe();
+        assert_eq!(broadcaster.client_count(), 1);
+
+        assert!(broadcaster.handle_frame(Bytes::from("two")));
+        assert_eq!(frame_image.lock().unwrap().as_ref().unwrap(), "two");
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            Bytes::from("--frame\r\ncontent-type: image/jpeg\r\ncontent-length: 3\r\n\r\ntwo")
+        );
+
+        // Broadcast stops again once the client disconnects
+        drop(rx);
+        assert_eq!(broadcaster.client_count(), 0);
+        assert!(!broadcaster.handle_frame(Bytes::from("three")));
+        assert_eq!(frame_image.lock().unwrap().as_ref().unwrap(), "two");
+    }
+
+    #[test]
+    fn test_frame_interval() {
+        let params = MjpegParams::default();
+        assert_eq!(params.frame_interval(None), None);
+        assert_eq!(
+            params.frame_interval(Some(5)),
+            Some(Duration::from_millis(200))
+        );
+
+        let params = MjpegParams { fps: Some(2) };
+        assert!(params.validate().is_ok());
+        assert_eq!(
+            params.frame_interval(None),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(params.frame_interval(Some(1)), Some(Duration::from_secs(1)));
+        assert_eq!(
+            params.frame_interval(Some(10)),
+            Some(Duration::from_millis(500))
+        );
+
+        assert!(MjpegParams { fps: Some(0) }.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_throttle() {
+        let frame_image = SharedImageData::default();
+        let mut broadcaster = MjpegBroadcaster::new(frame_image, Duration::ZERO);
+
+        let params = MjpegParams { fps: Some(1) };
+        let stream = throttle(
+            tokio_stream::wrappers::BroadcastStream::new(broadcaster.sender().subscribe()),
+            params.frame_interval(None),
+        );
+
+        // Produce frames at roughly 100 fps for a little over two seconds
+        let producer = tokio::spawn(async move {
+            for i in 0..220 {
+                broadcaster.handle_frame(Bytes::from(format!("{i}")));
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let frames: Vec<_> = stream.collect().await;
+        producer.await.unwrap();
+
+        assert!(
+            (2..=3).contains(&frames.len()),
+            "received {} frames",
+            frames.len()
+        );
+        assert!(frames.iter().all(|frame| frame.is_ok()));
+    }
+
+    #[test]
+    fn test_no_clients_idle_frame_interval() {
+        let frame_image = SharedImageData::default();
+        let mut broadcaster = MjpegBroadcaster::new(frame_image.clone(), Duration::ZERO);
+
+        assert!(!broadcaster.handle_frame(Bytes::from("one")));
+        assert!(!broadcaster.handle_frame(Bytes::from("two")));
+        assert_eq!(frame_image.lock().unwrap().as_ref().unwrap(), "two");
+    }
+}
@@ -0,0 +1,215 @@
+use byte_unit::Byte;
+use chrono::{DateTime, FixedOffset};
+use satori_common::SegmentFormat;
+use std::{collections::HashSet, fs, path::Path};
+use tracing::{info, warn};
+
+/// Deletes the oldest segments in `video_directory` until its disk usage is no more than
+/// `max_disk_usage`.
+///
+/// Segments that are referenced by `playlist` are never deleted. Files that are not segments of
+/// `segment_format`, or whose names cannot be parsed as a segment timestamp, are ignored. This
+/// includes the initialisation section of fragmented MP4 segments.
+///
+/// Returns the number of segments deleted.
+pub(crate) fn prune_segments(
+    video_directory: &Path,
+    max_disk_usage: Byte,
+    playlist: &m3u8_rs::MediaPlaylist,
+    segment_format: SegmentFormat,
+) -> std::io::Result<usize> {
+    let max_disk_usage = max_disk_usage.get_bytes();
+    let mut disk_usage = crate::utils::get_size(video_directory)?.get_bytes();
+
+    if disk_usage <= max_disk_usage {
+        return Ok(0);
+    }
+
+    let referenced: HashSet<&str> = playlist.segments.iter().map(|s| s.uri.as_str()).collect();
+
+    let mut segments: Vec<(DateTime<FixedOffset>, String)> = fs::read_dir(video_directory)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|filename| {
+            DateTime::parse_from_str(&filename, segment_format.segment_filename_format())
+                .ok()
+                .map(|timestamp| (timestamp, filename))
+        })
+        .collect();
+
+    // Oldest segments first
+    segments.sort();
+
+    let mut deleted = 0;
+
+    for (_, filename) in segments {
+        if disk_usage <= max_disk_usage {
+            break;
+        }
+
+        if referenced.contains(filename.as_str()) {
+            continue;
+        }
+
+        let path = video_directory.join(&filename);
+        let size = path.metadata()?.len() as u128;
+
+        info!("Deleting segment {filename} to reduce disk usage");
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                disk_usage = disk_usage.saturating_sub(size);
+                deleted += 1;
+            }
+            Err(e) => {
+                warn!("Failed to delete segment {filename}, err={e}");
+            }
+        }
+    }
+
+    if disk_usage > max_disk_usage {
+        warn!("Disk usage is still above limit, all remaining segments are in the playlist");
+    }
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_file(dir: &Path, filename: &str, size: usize) {
+        fs::write(dir.join(filename), vec![0u8; size]).unwrap();
+    }
+
+    fn parse_playlist(playlist: &str) -> m3u8_rs::MediaPlaylist {
+        m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_prune_oldest_segments() {
+        let dir = tempfile::tempdir().unwrap();
+
+        write_file(dir.path(), "2022-12-30T18_10_00+0000.ts", 100);
+        write_file(dir.path(), "2022-12-30T18_10_10+0000.ts", 100);
+        write_file(dir.path(), "2022-12-30T18_10_20+0000.ts", 100);
+        write_file(dir.path(), "2022-12-30T18_10_30+0000.ts", 100);
+        write_file(dir.path(), "stream.m3u8", 50);
+
+        let playlist = parse_playlist(indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:10
+            #EXTINF:10.000000,
+            2022-12-30T18_10_20+0000.ts
+            #EXTINF:10.000000,
+            2022-12-30T18_10_30+0000.ts
+        "});
+
+        let deleted = prune_segments(
+            dir.path(),
+            Byte::from_bytes(300),
+            &playlist,
+            SegmentFormat::Mpegts,
+        )
+        .unwrap();
+        assert_eq!(deleted, 2);
+
+        let mut remaining: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![
+                "2022-12-30T18_10_20+0000.ts",
+                "2022-12-30T18_10_30+0000.ts",
+                "stream.m3u8",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prune_under_limit() {
+        let dir = tempfile::tempdir().unwrap();
+
+        write_file(dir.path(), "2022-12-30T18_10_00+0000.ts", 100);
+        write_file(dir.path(), "2022-12-30T18_10_10+0000.ts", 100);
+
+        let playlist = parse_playlist("#EXTM3U\n#EXT-X-TARGETDURATION:10\n");
+
+        let deleted = prune_segments(
+            dir.path(),
+            Byte::from_bytes(200),
+            &playlist,
+            SegmentFormat::Mpegts,
+        )
+        .unwrap();
+        assert_eq!(deleted, 0);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_prune_never_deletes_playlist_segments() {
+        let dir = tempfile::tempdir().unwrap();
+
+        write_file(dir.path(), "2022-12-30T18_10_00+0000.ts", 100);
+        write_file(dir.path(), "2022-12-30T18_10_10+0000.ts", 100);
+        write_file(dir.path(), "2022-12-30T18_10_20+0000.ts", 100);
+
+        let playlist = parse_playlist(indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:10
+            #EXTINF:10.000000,
+            2022-12-30T18_10_00+0000.ts
+            #EXTINF:10.000000,
+            2022-12-30T18_10_10+0000.ts
+        "});
+
+        let deleted = prune_segments(
+            dir.path(),
+            Byte::from_bytes(0),
+            &playlist,
+            SegmentFormat::Mpegts,
+        )
+        .unwrap();
+        assert_eq!(deleted, 1);
+        assert!(!dir.path().join("2022-12-30T18_10_20+0000.ts").exists());
+        assert!(dir.path().join("2022-12-30T18_10_00+0000.ts").exists());
+        assert!(dir.path().join("2022-12-30T18_10_10+0000.ts").exists());
+    }
+
+    #[test]
+    fn test_prune_fmp4_segments() {
+        let dir = tempfile::tempdir().unwrap();
+
+        write_file(dir.path(), "init.mp4", 10);
+        write_file(dir.path(), "2022-12-30T18_10_00+0000.m4s", 100);
+        write_file(dir.path(), "2022-12-30T18_10_10+0000.m4s", 100);
+        write_file(dir.path(), "2022-12-30T18_10_20+0000.m4s", 100);
+
+        let playlist = parse_playlist(indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:7
+            #EXT-X-TARGETDURATION:10
+            #EXT-X-MAP:URI=\"init.mp4\"
+            #EXTINF:10.000000,
+            2022-12-30T18_10_20+0000.m4s
+        "});
+
+        let deleted = prune_segments(
+            dir.path(),
+            Byte::from_bytes(0),
+            &playlist,
+            SegmentFormat::Fmp4,
+        )
+        .unwrap();
+        assert_eq!(deleted, 2);
+
+        // The initialisation section is needed by the remaining segments
+        assert!(dir.path().join("init.mp4").exists());
+        assert!(dir.path().join("2022-12-30T18_10_20+0000.m4s").exists());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::jpeg_frame_decoder::JpegFrameDecoder;
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    io,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{io::AsyncWriteExt, process::Command};
+use tokio_util::codec::FramedRead;
+
+const MAX_WIDTH: u32 = 7680;
+
+/// Parameters used to re-encode a JPEG frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub(crate) struct SnapshotParams {
+    /// Width of the image in pixels, the height is scaled to preserve the aspect ratio.
+    width: Option<u32>,
+
+    /// JPEG quality, from 1 (worst) to 100 (best).
+    quality: Option<u8>,
+}
+
+impl SnapshotParams {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if let Some(width) = self.width {
+            if !(2..=MAX_WIDTH).contains(&width) {
+                return Err(format!("width must be between 2 and {MAX_WIDTH}"));
+            }
+        }
+
+        if let Some(quality) = self.quality {
+            if !(1..=100).contains(&quality) {
+                return Err("quality must be between 1 and 100".into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// True if the frame should be returned as is.
+    pub(crate) fn is_original(&self) -> bool {
+        self.width.is_none() && self.quality.is_none()
+    }
+
+    fn ffmpeg_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(width) = self.width {
+            // Height of -2 keeps the aspect ratio while ensuring an even number of pixels
+            args.push("-vf".into());
+            args.push(format!("scale={width}:-2"));
+        }
+
+        if let Some(quality) = self.quality {
+            // ffmpeg uses a scale from 2 (best) to 31 (worst) for MJPEG
+            let qscale = 31 - ((quality as u32 - 1) * 29 / 99);
+            args.push("-q:v".into());
+            args.push(qscale.to_string());
+        }
+
+        args
+    }
+}
+
+/// Caches re-encoded frames, so that identical requests made within a short time of each other
+/// are only encoded once.
+#[derive(Clone)]
+pub(crate) struct SnapshotCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<SnapshotParams, (Instant, Bytes)>>>,
+}
+
+impl SnapshotCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+
+    fn get(&self, params: &SnapshotParams) -> Option<Bytes> {
+        let entries = self.entries.lock().unwrap();
+
+        entries
+            .get(params)
+            .filter(|(created, _)| created.elapsed() < self.ttl)
+            .map(|(_, image)| image.clone())
+    }
+
+    fn insert(&self, params: SnapshotParams, image: Bytes) {
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.retain(|_, (created, _)| created.elapsed() < self.ttl);
+        entries.insert(params, (Instant::now(), image));
+    }
+
+    /// Returns `image` re-encoded using `params`, from the cache if possible.
+    pub(crate) async fn get_or_encode(
+        &self,
+        image: Bytes,
+        params: SnapshotParams,
+    ) -> io::Result<Bytes> {
+        if let Some(image) = self.get(&params) {
+            return Ok(image);
+        }
+
+        let image = encode(image, &params).await?;
+        self.insert(params, image.clone());
+
+        Ok(image)
+    }
+}
+
+/// Re-encodes a JPEG image using ffmpeg.
+async fn encode(image: Bytes, params: &SnapshotParams) -> io::Result<Bytes> {
+    let mut ffmpeg_process = Command::new("ffmpeg")
+        .arg("-hide_banner")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-f")
+        .arg("image2pipe")
+        .arg("-c:v")
+        .arg("mjpeg")
+        .arg("-i")
+        .arg("pipe:0")
+        .args(params.ffmpeg_args())
+        .arg("-f")
+        .arg("image2pipe")
+        .arg("-c:v")
+        .arg("mjpeg")
+        .arg("pipe:1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdin = ffmpeg_process.stdin.take().unwrap();
+    let writer = tokio::spawn(async move {
+        stdin.write_all(&image).await?;
+        // Closing stdin signals the end of the input
+        drop(stdin);
+        Ok::<_, io::Error>(())
+    });
+
+    let stdout = ffmpeg_process.stdout.take().unwrap();
+    let frame = FramedRead::new(stdout, JpegFrameDecoder).next().await;
+
+    writer.await.map_err(io::Error::other)??;
+    ffmpeg_process.wait().await?;
+
+    frame.unwrap_or_else(|| {
+        Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "ffmpeg produced no image",
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{extract::Query, http::Uri};
+
+    fn parse(uri: &str) -> Result<SnapshotParams, String> {
+        Query::<SnapshotParams>::try_from_uri(&uri.parse::<Uri>().unwrap())
+            .map(|q| q.0)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("/jpeg").unwrap(), SnapshotParams::default());
+        assert!(parse("/jpeg").unwrap().is_original());
+
+        let params = parse("/jpeg?width=640&quality=70").unwrap();
+        assert_eq!(
+            params,
+            SnapshotParams {
+                width: Some(640),
+                quality: Some(70),
+            }
+        );
+        assert!(!params.is_original());
+        assert!(params.validate().is_ok());
+
+        assert!(parse("/jpeg?width=big").is_err());
+        assert!(parse("/jpeg?quality=-1").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(parse("/jpeg?quality=1").unwrap().validate().is_ok());
+        assert!(parse("/jpeg?quality=100").unwrap().validate().is_ok());
+        assert!(parse("/jpeg?quality=0").unwrap().validate().is_err());
+        assert!(parse("/jpeg?quality=101").unwrap().validate().is_err());
+
+        assert!(parse("/jpeg?width=0").unwrap().validate().is_err());
+        assert!(parse("/jpeg?width=100000").unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        assert!(SnapshotParams::default().ffmpeg_args().is_empty());
+
+        assert_eq!(
+            parse("/jpeg?width=640&quality=100").unwrap().ffmpeg_args(),
+            vec!["-vf", "scale=640:-2", "-q:v", "2"]
+        );
+        assert_eq!(
+            parse("/jpeg?quality=1").unwrap().ffmpeg_args(),
+            vec!["-q:v", "31"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let cache = SnapshotCache::new(Duration::from_millis(50));
+        let params = parse("/jpeg?width=640").unwrap();
+
+        cache.insert(params, Bytes::from("encoded"));
+
+        // Identical requests are served from the cache
+        assert_eq!(
+            cache
+                .get_or_encode(Bytes::from("original"), params)
+                .await
+                .unwrap(),
+            Bytes::from("encoded")
+        );
+        assert_eq!(cache.get(&parse("/jpeg?width=320").unwrap()), None);
+
+        // Entries expire
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(cache.get(&params), None);
+    }
+}
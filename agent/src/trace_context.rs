@@ -0,0 +1,22 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use tracing::Instrument;
+
+/// Middleware that handles each request in a span continuing the trace of the client, if the
+/// client propagated a trace context.
+pub(crate) async fn continue_trace(request: Request, next: Next) -> Response {
+    let span = tracing::info_span!(
+        "http_request",
+        method = %request.method(),
+        uri = %request.uri()
+    );
+
+    satori_common::set_trace_parent(
+        &span,
+        request
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| value.to_str().ok().map(|value| (name.as_str(), value))),
+    );
+
+    next.run(request).instrument(span).await
+}
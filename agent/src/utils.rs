@@ -1,5 +1,6 @@
 use byte_unit::Byte;
-use std::{fs, path::Path};
+use satori_common::hls::Playlist;
+use std::{fs, path::Path, time::Duration};
 
 pub(crate) fn get_size<P>(path: P) -> std::io::Result<Byte>
 where
@@ -19,3 +20,94 @@ where
 
     Ok(Byte::from_bytes(result))
 }
+
+/// Average duration of the segments in a playlist, `None` if the playlist has no segments.
+pub(crate) fn get_average_segment_duration(playlist: &m3u8_rs::MediaPlaylist) -> Option<Duration> {
+    if playlist.segments.is_empty() {
+        None
+    } else {
+        let total: f32 = playlist.segments.iter().map(|s| s.duration).sum();
+        Some(Duration::from_secs_f32(
+            total / playlist.segments.len() as f32,
+        ))
+    }
+}
+
+/// Number of gaps in a playlist, i.e. consecutive segments whose start times are more than
+/// `threshold` apart, such as when the camera stalled.
+pub(crate) fn count_segment_gaps(playlist: &Playlist, threshold: Duration) -> usize {
+    playlist
+        .segments
+        .windows(2)
+        .filter(|pair| {
+            (pair[1].start() - pair[0].start())
+                .to_std()
+                .is_ok_and(|delta| delta > threshold)
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_average_segment_duration() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:12
+            #EXTINF:10.000000,
+            2022-12-30T18_10_00+0000.ts
+            #EXTINF:10.500000,
+            2022-12-30T18_10_10+0000.ts
+            #EXTINF:9.500000,
+            2022-12-30T18_10_20+0000.ts
+            #EXTINF:12.000000,
+            2022-12-30T18_10_30+0000.ts
+        "};
+        let playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+
+        assert_eq!(
+            get_average_segment_duration(&playlist),
+            Some(Duration::from_millis(10500))
+        );
+    }
+
+    #[test]
+    fn test_count_segment_gaps() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:10
+            #EXTINF:10.000000,
+            2022-12-30T18_10_00+0000.ts
+            #EXTINF:10.000000,
+            2022-12-30T18_10_10+0000.ts
+            #EXTINF:10.000000,
+            2022-12-30T18_10_20+0000.ts
+            #EXT-X-DISCONTINUITY
+            #EXTINF:10.000000,
+            2022-12-30T18_15_00+0000.ts
+            #EXTINF:10.000000,
+            2022-12-30T18_15_15+0000.ts
+            #EXT-X-DISCONTINUITY
+            #EXTINF:10.000000,
+            2022-12-30T18_00_00+0000.ts
+        "};
+        let playlist =
+            Playlist::try_from(m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap())
+                .unwrap();
+
+        // Only the stall of several minutes, not the slightly late segment or the clock reset
+        assert_eq!(count_segment_gaps(&playlist, Duration::from_secs(20)), 1);
+        assert_eq!(count_segment_gaps(&playlist, Duration::from_secs(12)), 2);
+        assert_eq!(count_segment_gaps(&playlist, Duration::from_secs(600)), 0);
+    }
+
+    #[test]
+    fn test_average_segment_duration_empty() {
+        let playlist = m3u8_rs::MediaPlaylist::default();
+        assert_eq!(get_average_segment_duration(&playlist), None);
+    }
+}
@@ -0,0 +1,75 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// Middleware that rejects requests which do not present the bearer token.
+pub(crate) async fn require_token(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    if satori_common::check_bearer_token(authorization, &token) {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use satori_storage::StorageConfig;
+    use tower::ServiceExt;
+
+    async fn get_status(authorization: Option<&str>) -> StatusCode {
+        let storage = serde_json::from_str::<StorageConfig>(
+            r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+        )
+        .unwrap()
+        .create_provider();
+
+        let app = crate::presign::router(storage).layer(axum::middleware::from_fn_with_state(
+            Arc::from("secret"),
+            require_token,
+        ));
+
+        let mut request = Request::builder().uri("/presign/segment/camera1/1.ts");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_require_token() {
+        // The dummy storage provider does not support presigning, but the request got that far
+        assert_eq!(
+            get_status(Some("Bearer secret")).await,
+            StatusCode::NOT_IMPLEMENTED
+        );
+
+        assert_eq!(
+            get_status(Some("Bearer wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(get_status(None).await, StatusCode::UNAUTHORIZED);
+    }
+}
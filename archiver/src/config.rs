@@ -12,7 +12,97 @@ pub(crate) struct Config {
     #[serde_as(as = "DurationMilliSeconds<u64>")]
     pub(crate) interval: Duration,
 
+    /// Maximum number of tasks to process in parallel on each interval.
+    #[serde(default = "default_concurrency")]
+    pub(crate) concurrency: usize,
+
+    #[serde(default)]
+    pub(crate) retry: RetryConfig,
+
+    /// Maximum size of a segment in bytes, larger segments are not archived.
+    #[serde(default = "default_max_segment_size")]
+    pub(crate) max_segment_size: u64,
+
+    /// Bearer token presented when retrieving segments, e.g. from agents with an authenticated
+    /// HTTP API.
+    #[serde(default)]
+    pub(crate) camera_auth_token: Option<String>,
+
+    /// Maximum time to wait for in-flight HTTP requests to complete when exiting.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[serde(default = "default_shutdown_timeout")]
+    pub(crate) shutdown_timeout: Duration,
+
     pub(crate) mqtt: MqttConfig,
 
     pub(crate) storage: StorageConfig,
 }
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_max_segment_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_shutdown_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RetryConfig {
+    /// Delay before the first retry of a failed task, doubled for each subsequent failure.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub(crate) base: Duration,
+
+    /// Maximum delay between retries of a failed task.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub(crate) max: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Gets the delay before retrying a task that has failed `attempts` times.
+    ///
+    /// Half of the delay is random, so that tasks which failed together are not all retried at
+    /// the same time.
+    pub(crate) fn delay(&self, attempts: u32) -> Duration {
+        let delay = self
+            .base
+            .saturating_mul(2_u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max);
+
+        let half = delay / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = RetryConfig {
+            base: Duration::from_secs(10),
+            max: Duration::from_secs(60),
+        };
+
+        for (attempts, expected) in [(1, 10), (2, 20), (3, 40), (4, 60), (10, 60), (100, 60)] {
+            let expected = Duration::from_secs(expected);
+            let delay = retry.delay(attempts);
+            assert!(delay >= expected / 2, "{attempts}: {delay:?}");
+            assert!(delay <= expected, "{attempts}: {delay:?}");
+        }
+    }
+}
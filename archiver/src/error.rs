@@ -14,6 +14,15 @@ pub(crate) enum ArchiverError {
 
     #[error("URL manipulation error")]
     Url,
+
+    #[error("Segment is not MPEG-TS: {0}")]
+    InvalidSegment(&'static str),
+
+    #[error("Segment is {size} bytes, larger than the maximum of {max} bytes")]
+    SegmentTooLarge { size: u64, max: u64 },
+
+    #[error("{0} task(s) failed")]
+    TasksFailed(usize),
 }
 
 pub(crate) type ArchiverResult<T> = Result<T, ArchiverError>;
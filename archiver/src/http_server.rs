@@ -0,0 +1,104 @@
+use axum::Router;
+use std::time::Duration;
+use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+use tracing::{error, info, warn};
+
+/// HTTP server that completes in-flight requests when stopped.
+pub(crate) struct HttpServer {
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl HttpServer {
+    pub(crate) fn start(listener: TcpListener, app: Router) -> Self {
+        let (shutdown, shutdown_rx) = oneshot::channel();
+
+        let handle = tokio::spawn(async move {
+            let shutdown_signal = async {
+                let _ = shutdown_rx.await;
+            };
+
+            if let Err(err) = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal)
+                .await
+            {
+                error!("HTTP server failed: {err}");
+            }
+        });
+
+        Self { shutdown, handle }
+    }
+
+    /// Stops accepting new connections and waits for in-flight requests to complete.
+    ///
+    /// Connections that remain open after `timeout` (e.g. event streams) are abandoned, and are
+    /// closed when the runtime shuts down.
+    pub(crate) async fn stop(self, timeout: Duration) {
+        info!("Stopping HTTP server");
+        let _ = self.shutdown.send(());
+
+        let mut handle = self.handle;
+        if tokio::time::timeout(timeout, &mut handle).await.is_err() {
+            warn!("HTTP server did not stop within {timeout:?}, closing remaining connections");
+            handle.abort();
+            let _ = handle.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::routing::get;
+    use std::{net::SocketAddr, time::Instant};
+
+    async fn start_server(handler_duration: Duration) -> (HttpServer, SocketAddr) {
+        let app = Router::new().route(
+            "/slow",
+            get(move || async move {
+                tokio::time::sleep(handler_duration).await;
+                "done"
+            }),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        (HttpServer::start(listener, app), address)
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_request_completes() {
+        let (server, address) = start_server(Duration::from_millis(500)).await;
+
+        let request = tokio::spawn(async move {
+            reqwest::get(format!("http://{address}/slow"))
+                .await?
+                .text()
+                .await
+        });
+
+        // Stop the server while the request is being handled
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        server.stop(Duration::from_secs(5)).await;
+
+        assert_eq!(request.await.unwrap().unwrap(), "done");
+
+        // No new connections are accepted
+        assert!(reqwest::get(format!("http://{address}/slow"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stop_timeout() {
+        let (server, address) = start_server(Duration::from_secs(60)).await;
+
+        tokio::spawn(reqwest::get(format!("http://{address}/slow")));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let start = Instant::now();
+        server.stop(Duration::from_millis(200)).await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
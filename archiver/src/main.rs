@@ -1,17 +1,30 @@
+mod auth;
 mod config;
 mod error;
+mod http_server;
+mod presign;
 mod queue;
 mod task;
+mod task_events;
+#[cfg(test)]
+mod test_utils;
 
-use crate::config::Config;
+use crate::{config::Config, http_server::HttpServer};
 use clap::Parser;
 use metrics_exporter_prometheus::PrometheusBuilder;
-use satori_common::mqtt::MqttClient;
-use std::{net::SocketAddr, path::PathBuf};
-use tracing::info;
+use satori_common::{mqtt::MqttClient, LogFormat};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use tokio::net::TcpListener;
+use tracing::{error, info};
 
 const METRIC_QUEUE_LENGTH: &str = "satori_archiver_queue_length";
 const METRIC_PROCESSED_TASKS: &str = "satori_archiver_processed_tasks";
+const METRIC_UPLOADED_EVENTS: &str = "satori_archiver_uploaded_events";
+const METRIC_UPLOADED_SEGMENTS: &str = "satori_archiver_uploaded_segments";
+const METRIC_UPLOADED_BYTES: &str = "satori_archiver_uploaded_bytes";
+const METRIC_UPLOAD_FAILURES: &str = "satori_archiver_upload_failures";
+const METRIC_UPLOAD_DURATION: &str = "satori_archiver_upload_duration_seconds";
+const METRIC_TASK_ATTEMPTS: &str = "satori_archiver_task_attempts";
 
 /// Run the archiver.
 #[derive(Clone, Parser)]
@@ -24,18 +37,41 @@ pub(crate) struct Cli {
     /// Address to listen on for observability/metrics endpoints
     #[clap(long, env = "OBSERVABILITY_ADDRESS", default_value = "127.0.0.1:9090")]
     observability_address: SocketAddr,
+
+    /// Address to listen on for the HTTP server (disabled if not provided)
+    #[clap(long, env = "HTTP_SERVER_ADDRESS")]
+    http_server_address: Option<SocketAddr>,
+
+    /// Bearer token required by the HTTP server, which is unauthenticated if not provided
+    #[clap(long, env = "AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+
+    /// Exit with an error as soon as any task fails, instead of retrying it later
+    #[arg(long, env = "STRICT")]
+    strict: bool,
+
+    /// File to append the details of any panic to
+    #[arg(long, env = "CRASH_FILE", value_name = "FILE")]
+    crash_file: Option<PathBuf>,
+
+    /// Format of log output
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t)]
+    log_format: LogFormat,
 }
 
 struct Context {
     storage: satori_storage::Provider,
     http_client: reqwest::Client,
+    task_events: tokio::sync::broadcast::Sender<task_events::TaskEvent>,
+    max_segment_size: u64,
+    camera_auth_token: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), ()> {
-    tracing_subscriber::fmt::init();
-
     let cli = Cli::parse();
+    let _tracing = satori_common::init_tracing(env!("CARGO_PKG_NAME"), cli.log_format);
+    satori_common::install_panic_hook(cli.crash_file.clone());
     let config: Config = satori_common::load_config_file(&cli.config);
 
     let mut mqtt_client: MqttClient = config.mqtt.into();
@@ -43,6 +79,9 @@ async fn main() -> Result<(), ()> {
     let context = Context {
         storage: config.storage.create_provider(),
         http_client: reqwest::Client::new(),
+        task_events: tokio::sync::broadcast::channel(task_events::TASK_EVENT_BUFFER).0,
+        max_segment_size: config.max_segment_size,
+        camera_auth_token: config.camera_auth_token.clone(),
     };
 
     let mut queue = queue::ArchiveTaskQueue::load_or_new(&config.queue_file);
@@ -67,6 +106,71 @@ async fn main() -> Result<(), ()> {
         "Finished task count"
     );
 
+    metrics::describe_histogram!(
+        METRIC_TASK_ATTEMPTS,
+        metrics::Unit::Count,
+        "Number of attempts taken for tasks to succeed"
+    );
+
+    metrics::describe_counter!(
+        METRIC_UPLOADED_EVENTS,
+        metrics::Unit::Count,
+        "Events saved to storage"
+    );
+
+    metrics::describe_counter!(
+        METRIC_UPLOADED_SEGMENTS,
+        metrics::Unit::Count,
+        "Segments saved to storage"
+    );
+
+    metrics::describe_counter!(
+        METRIC_UPLOADED_BYTES,
+        metrics::Unit::Bytes,
+        "Size of segments saved to storage"
+    );
+
+    metrics::describe_counter!(
+        METRIC_UPLOAD_FAILURES,
+        metrics::Unit::Count,
+        "Failed attempts to save events or segments to storage"
+    );
+
+    metrics::describe_histogram!(
+        METRIC_UPLOAD_DURATION,
+        metrics::Unit::Seconds,
+        "Time taken to save events or segments to storage"
+    );
+
+    metrics::describe_counter!(
+        satori_storage::METRIC_RETRIES,
+        metrics::Unit::Count,
+        "Storage operations retried after a transient failure"
+    );
+
+    // Start HTTP server
+    let server = match cli.http_server_address {
+        Some(address) => {
+            let listener = TcpListener::bind(&address)
+                .await
+                .unwrap_or_else(|_| panic!("tcp listener should bind to {address}"));
+            let mut app = task_events::router(context.task_events.clone())
+                .merge(presign::router(context.storage.clone()));
+            if let Some(token) = &cli.auth_token {
+                app = app.layer(axum::middleware::from_fn_with_state(
+                    Arc::<str>::from(token.as_str()),
+                    auth::require_token,
+                ));
+            }
+
+            info!("Starting HTTP server on {address}");
+            Some(HttpServer::start(listener, app))
+        }
+        None => None,
+    };
+
+    let mut result = Ok(());
+
     loop {
         tokio::select! {
             _ = tokio::signal::ctrl_c() => {
@@ -79,7 +183,16 @@ async fn main() -> Result<(), ()> {
                 }
             }
             _ = queue_process_interval.tick() => {
-                queue.process_one(&context).await;
+                if let Err(err) = queue
+                    .process(&context, config.concurrency, &config.retry)
+                    .await
+                {
+                    if cli.strict {
+                        error!("Exiting, strict mode is enabled and {err}");
+                        result = Err(());
+                        break;
+                    }
+                }
             }
         }
     }
@@ -87,5 +200,10 @@ async fn main() -> Result<(), ()> {
     // Disconnect MQTT client
     mqtt_client.disconnect().await;
 
-    Ok(())
+    // Stop server
+    if let Some(server) = server {
+        server.stop(config.shutdown_timeout).await;
+    }
+
+    result
 }
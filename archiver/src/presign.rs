@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use satori_storage::{Provider, StorageError, StorageProvider};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::warn;
+
+/// Validity of a presigned URL when no expiry is requested, in seconds.
+const DEFAULT_EXPIRY: u64 = 3600;
+
+#[derive(Debug, Deserialize)]
+struct PresignParams {
+    /// Validity of the URL, in seconds.
+    expiry: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct PresignedUrl {
+    url: String,
+}
+
+/// HTTP endpoints for generating URLs from which archived data can be downloaded directly.
+pub(crate) fn router(storage: Provider) -> Router {
+    Router::new()
+        .route("/presign/segment/:camera/:filename", get(presign_segment))
+        .with_state(storage)
+}
+
+/// Generates a time limited URL for a segment.
+async fn presign_segment(
+    State(storage): State<Provider>,
+    Path((camera, filename)): Path<(String, String)>,
+    Query(params): Query<PresignParams>,
+) -> Result<Json<PresignedUrl>, (StatusCode, String)> {
+    let expiry = Duration::from_secs(params.expiry.unwrap_or(DEFAULT_EXPIRY));
+
+    storage
+        .presigned_segment_url(&camera, std::path::Path::new(&filename), expiry)
+        .await
+        .map(|url| Json(PresignedUrl { url }))
+        .map_err(|err| {
+            warn!("Failed to presign segment {filename} for camera \"{camera}\", error: {err}");
+
+            let status = match err {
+                StorageError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+                StorageError::InvalidName(_) => StatusCode::BAD_REQUEST,
+                StorageError::NotFound => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, err.to_string())
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use satori_storage::StorageConfig;
+    use tower::ServiceExt;
+
+    async fn get(uri: &str) -> StatusCode {
+        let storage = serde_json::from_str::<StorageConfig>(
+            r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+        )
+        .unwrap()
+        .create_provider();
+
+        router(storage)
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_presign_unsupported_backend() {
+        assert_eq!(
+            get("/presign/segment/camera1/1.ts?expiry=60").await,
+            StatusCode::NOT_IMPLEMENTED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_presign_invalid_camera_name() {
+        assert_eq!(
+            get("/presign/segment/camera%201/1.ts").await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -1,5 +1,14 @@
-use crate::{error::ArchiverResult, task::ArchiveTask, Context};
-use satori_common::{mqtt::PublishExt, ArchiveCommand, ArchiveSegmentsCommand, Event};
+use crate::{
+    config::RetryConfig,
+    error::{ArchiverError, ArchiverResult},
+    task::ArchiveTask,
+    Context,
+};
+use chrono::{DateTime, Utc};
+use satori_common::{
+    mqtt::PublishExt, ArchiveCommand, ArchiveSegmentsCommand, ErrorLogThrottle, Event,
+};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::VecDeque,
     fs::File,
@@ -7,11 +16,56 @@ use std::{
 };
 use tracing::{debug, error, info, warn};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QueuedTask {
+    #[serde(flatten)]
+    task: ArchiveTask,
+
+    /// Number of times this task has failed.
+    #[serde(default)]
+    attempts: u32,
+
+    /// Time after which the task may be attempted again, if it has previously failed.
+    #[serde(default)]
+    next_attempt_at: Option<DateTime<Utc>>,
+}
+
+impl QueuedTask {
+    fn new(task: ArchiveTask) -> Self {
+        Self {
+            task,
+            attempts: 0,
+            next_attempt_at: None,
+        }
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        match self.next_attempt_at {
+            Some(t) => t <= now,
+            None => true,
+        }
+    }
+
+    fn record_failure(&mut self, now: DateTime<Utc>, retry: &RetryConfig) {
+        self.attempts += 1;
+
+        let delay = retry.delay(self.attempts);
+        info!(
+            "Task has failed {} time(s), retrying in {:?}",
+            self.attempts, delay
+        );
+        self.next_attempt_at = Some(now + delay);
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct ArchiveTaskQueue {
-    queue: VecDeque<ArchiveTask>,
+    queue: VecDeque<QueuedTask>,
 
     backing_file_name: PathBuf,
+
+    /// Throttles logging of task failures, by camera (or event metadata).
+    failure_log_throttle: ErrorLogThrottle<String>,
 }
 
 impl ArchiveTaskQueue {
@@ -30,6 +84,7 @@ impl ArchiveTaskQueue {
                 let queue = Self {
                     queue: Default::default(),
                     backing_file_name: path.into(),
+                    failure_log_throttle: Default::default(),
                 };
                 queue.update_queue_length_metrics();
                 queue
@@ -43,6 +98,7 @@ impl ArchiveTaskQueue {
         let queue = Self {
             queue: serde_json::from_reader(file)?,
             backing_file_name: path.into(),
+            failure_log_throttle: Default::default(),
         };
         queue.update_queue_length_metrics();
         Ok(queue)
@@ -51,8 +107,11 @@ impl ArchiveTaskQueue {
     #[tracing::instrument(skip_all)]
     fn save(&self) -> ArchiverResult<()> {
         info!("Saving job queue to {}", self.backing_file_name.display());
-        let file = File::create(&self.backing_file_name)?;
-        Ok(serde_json::to_writer(file, &self.queue)?)
+        let data = serde_json::to_vec(&self.queue)?;
+        Ok(satori_common::write_file_atomic(
+            &self.backing_file_name,
+            &data,
+        )?)
     }
 
     #[tracing::instrument(skip_all)]
@@ -69,7 +128,7 @@ impl ArchiveTaskQueue {
         let event_queue_length = self
             .queue
             .iter()
-            .filter(|t| matches!(t, ArchiveTask::EventMetadata(_)))
+            .filter(|t| matches!(t.task, ArchiveTask::EventMetadata(_)))
             .count() as f64;
 
         metrics::gauge!(
@@ -81,7 +140,7 @@ impl ArchiveTaskQueue {
         let segment_queue_length = self
             .queue
             .iter()
-            .filter(|t| matches!(t, ArchiveTask::CameraSegment(_)))
+            .filter(|t| matches!(t.task, ArchiveTask::CameraSegment(_)))
             .count() as f64;
 
         metrics::gauge!(
@@ -116,7 +175,7 @@ impl ArchiveTaskQueue {
     #[tracing::instrument(skip_all)]
     fn handle_archive_event_metadata_message(&mut self, event: Event) {
         info!("Queueing archive event metadata command");
-        self.queue.push_back(ArchiveTask::EventMetadata(event));
+        self.push(ArchiveTask::EventMetadata(event));
 
         self.attempt_save();
         self.update_queue_length_metrics();
@@ -125,66 +184,206 @@ impl ArchiveTaskQueue {
     #[tracing::instrument(skip_all)]
     fn handle_archive_segments_message(&mut self, msg: ArchiveSegmentsCommand) {
         info!("Queueing archive video segments command");
+
+        // Camera names and segment filenames are used as storage paths
+        if let Err(err) = satori_storage::validate_camera_name(&msg.camera_name) {
+            error!("Not archiving segments, reason: {err}");
+            return;
+        }
+
         for segment in msg.segment_list {
+            if let Err(err) = satori_storage::validate_filename(&segment) {
+                error!("Not archiving segment, reason: {err}");
+                continue;
+            }
+
             debug!("Adding video segment to queue: {}", segment.display());
-            self.queue
-                .push_back(ArchiveTask::CameraSegment(crate::task::CameraSegment {
-                    camera_name: msg.camera_name.clone(),
-                    camera_url: msg.camera_url.clone(),
-                    filename: segment,
-                }));
+            self.push(ArchiveTask::CameraSegment(crate::task::CameraSegment {
+                camera_name: msg.camera_name.clone(),
+                camera_url: msg.camera_url.clone(),
+                url: msg.segment_urls.get(&segment).cloned(),
+                filename: segment,
+            }));
         }
 
         self.attempt_save();
         self.update_queue_length_metrics();
     }
 
+    /// Adds a task to the queue, unless the same object is already pending archival.
+    ///
+    /// A pending event is updated to the newer version (which may list more segments), keeping its
+    /// place in the queue and any retry backoff.
+    fn push(&mut self, task: ArchiveTask) {
+        match self
+            .queue
+            .iter_mut()
+            .find(|t| t.task.is_duplicate_of(&task))
+        {
+            Some(queued) => match task {
+                ArchiveTask::EventMetadata(_) => {
+                    debug!("Updating queued event");
+                    queued.task = task;
+                }
+                ArchiveTask::CameraSegment(_) => {
+                    debug!("Segment is already queued, skipping");
+                }
+            },
+            None => self.queue.push_back(QueuedTask::new(task)),
+        }
+    }
+
+    /// Processes up to `concurrency` tasks that are due to be attempted in parallel.
+    ///
+    /// Tasks that fail remain in the queue, in their original order, and are not attempted again
+    /// until their backoff (as described by `retry`) has elapsed.
+    ///
+    /// Returns an error if any task failed.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn process_one(&mut self, context: &Context) {
-        if let Some(task) = self.queue.front() {
-            let task_type = match &task {
-                ArchiveTask::EventMetadata(_) => "event",
-                ArchiveTask::CameraSegment(_) => "segment",
-            };
-
-            let result = task.run(context).await;
-
-            let task_result = match &result {
-                Ok(_) => "success",
-                Err(_) => "failure",
-            };
-
-            metrics::counter!(
-                crate::METRIC_PROCESSED_TASKS,
-                1,
-                "type" => task_type,
-                "result" => task_result
-            );
+    pub(crate) async fn process(
+        &mut self,
+        context: &Context,
+        concurrency: usize,
+        retry: &RetryConfig,
+    ) -> ArchiverResult<()> {
+        let now = Utc::now();
+
+        let batch: Vec<usize> = self
+            .queue
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| task.is_due(now))
+            .map(|(idx, _)| idx)
+            .take(concurrency)
+            .collect();
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let results = futures::future::join_all(
+            batch
+                .iter()
+                .map(|idx| Self::process_task(context, &self.queue[*idx].task)),
+        )
+        .await;
+
+        let now = Utc::now();
+
+        let mut succeeded = vec![false; self.queue.len()];
+        let mut num_failed = 0;
+        for (idx, result) in batch.into_iter().zip(results) {
+            let task = &self.queue[idx];
+            let source = task.task.log_source();
 
             match result {
                 Ok(()) => {
-                    info!("Successfully processed task: {:?}", task);
+                    succeeded[idx] = true;
+                    self.failure_log_throttle.reset(&source);
 
-                    // Remove the task from the queue
-                    self.queue.pop_front();
-                    self.attempt_save();
-                    self.update_queue_length_metrics();
+                    metrics::histogram!(
+                        crate::METRIC_TASK_ATTEMPTS,
+                        (task.attempts + 1) as f64,
+                        "type" => task.task.task_type()
+                    );
                 }
                 Err(err) => {
-                    error!("Failed to process task: {:?}, reason: {}", task, err);
+                    if let Some(suppressed) = self.failure_log_throttle.should_log(source) {
+                        error!(
+                            "Failed to process task: {:?}, reason: {} ({} similar failure(s) not logged)",
+                            task.task, err, suppressed
+                        );
+                    }
+
+                    self.queue[idx].record_failure(now, retry);
+                    num_failed += 1;
                 }
             }
         }
+
+        // Remove successful tasks from the queue
+        let mut succeeded = succeeded.into_iter();
+        self.queue
+            .retain(|_| !succeeded.next().expect("should have result for each task"));
+
+        self.attempt_save();
+        self.update_queue_length_metrics();
+
+        if num_failed > 0 {
+            Err(ArchiverError::TasksFailed(num_failed))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs a single task.
+    #[tracing::instrument(skip_all)]
+    async fn process_task(context: &Context, task: &ArchiveTask) -> ArchiverResult<()> {
+        let task_type = task.task_type();
+        let camera = match &task {
+            ArchiveTask::EventMetadata(_) => None,
+            ArchiveTask::CameraSegment(segment) => Some(segment.camera_name.clone()),
+        };
+
+        let result = task.run(context).await;
+
+        let task_result = match &result {
+            Ok(_) => "success",
+            Err(_) => "failure",
+        };
+
+        metrics::counter!(
+            crate::METRIC_PROCESSED_TASKS,
+            1,
+            "type" => task_type,
+            "result" => task_result
+        );
+
+        // Nothing is listening for task events if there are no receivers
+        let _ = context.task_events.send(crate::task_events::TaskEvent {
+            camera,
+            task_type,
+            result: task_result,
+        });
+
+        if result.is_ok() {
+            info!("Successfully processed task: {:?}", task);
+        }
+
+        result
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_utils::test_context;
     use rumqttc::{Publish, QoS};
-    use satori_common::{ArchiveCommand, ArchiveSegmentsCommand, Message};
+    use satori_common::{ArchiveCommand, ArchiveSegmentsCommand, EventMetadata, Message};
+    use satori_storage::StorageProvider;
     use url::Url;
 
+    fn test_event_task(id: &str) -> ArchiveTask {
+        ArchiveTask::EventMetadata(Event {
+            metadata: EventMetadata {
+                id: id.into(),
+                timestamp: chrono::Utc::now().into(),
+            },
+            start: chrono::Utc::now().into(),
+            end: chrono::Utc::now().into(),
+            reasons: Default::default(),
+            cameras: Default::default(),
+        })
+    }
+
+    fn test_unreachable_segment_task(filename: &str) -> ArchiveTask {
+        ArchiveTask::CameraSegment(crate::task::CameraSegment {
+            camera_name: "camera-1".into(),
+            camera_url: Url::parse("http://localhost:1/stream.m3u8").unwrap(),
+            filename: filename.into(),
+            url: None,
+        })
+    }
+
     #[test]
     fn test_load_bad_file_gives_empty_queue() {
         let queue =
@@ -201,6 +400,7 @@ mod test {
             camera_name: "camera-1".into(),
             camera_url: Url::parse("http://localhost:8080/stream.m3u8").unwrap(),
             segment_list: vec![],
+            segment_urls: Default::default(),
         }));
         let msg = Publish::new("", QoS::ExactlyOnce, serde_json::to_string(&msg).unwrap());
         queue.handle_mqtt_message(msg);
@@ -216,9 +416,362 @@ mod test {
             camera_name: "camera-1".into(),
             camera_url: Url::parse("http://localhost:8080/stream.m3u8").unwrap(),
             segment_list: vec!["one.ts".into(), "two.ts".into()],
+            segment_urls: Default::default(),
+        }));
+        let msg = Publish::new("", QoS::ExactlyOnce, serde_json::to_string(&msg).unwrap());
+        queue.handle_mqtt_message(msg);
+        assert_eq!(queue.queue.len(), 2);
+    }
+
+    #[test]
+    fn test_archive_segments_with_segment_urls() {
+        let mut queue = ArchiveTaskQueue::default();
+
+        let url = Url::parse("https://cdn.example.com/camera-1/two.ts").unwrap();
+        let msg = Message::ArchiveCommand(ArchiveCommand::Segments(ArchiveSegmentsCommand {
+            camera_name: "camera-1".into(),
+            camera_url: Url::parse("http://localhost:8080/stream.m3u8").unwrap(),
+            segment_list: vec!["one.ts".into(), "two.ts".into()],
+            segment_urls: [("two.ts".into(), url.clone())].into(),
+        }));
+        let msg = Publish::new("", QoS::ExactlyOnce, serde_json::to_string(&msg).unwrap());
+        queue.handle_mqtt_message(msg);
+
+        let urls: Vec<_> = queue
+            .queue
+            .iter()
+            .map(|t| match &t.task {
+                ArchiveTask::CameraSegment(segment) => segment.url.clone(),
+                _ => panic!("should be a segment task"),
+            })
+            .collect();
+        assert_eq!(urls, vec![None, Some(url)]);
+    }
+
+    #[test]
+    fn test_archive_unchanged_event_twice_queues_tasks_once() {
+        let mut queue = ArchiveTaskQueue::default();
+
+        let segments = Message::ArchiveCommand(ArchiveCommand::Segments(ArchiveSegmentsCommand {
+            camera_name: "camera-1".into(),
+            camera_url: Url::parse("http://localhost:8080/stream.m3u8").unwrap(),
+            segment_list: vec!["one.ts".into(), "two.ts".into()],
+            segment_urls: Default::default(),
+        }));
+        let event = match test_event_task("one") {
+            ArchiveTask::EventMetadata(event) => event,
+            _ => unreachable!(),
+        };
+        let event = Message::ArchiveCommand(ArchiveCommand::EventMetadata(event));
+
+        for _ in 0..2 {
+            for msg in [&segments, &event] {
+                let msg = Publish::new("", QoS::ExactlyOnce, serde_json::to_string(msg).unwrap());
+                queue.handle_mqtt_message(msg);
+            }
+        }
+
+        let filenames: Vec<_> = queue
+            .queue
+            .iter()
+            .filter_map(|t| match &t.task {
+                ArchiveTask::CameraSegment(segment) => Some(segment.filename.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            filenames,
+            vec![PathBuf::from("one.ts"), PathBuf::from("two.ts")]
+        );
+        assert_eq!(queue.queue.len(), 3);
+
+        // The same segment of another camera is a different task
+        let msg = Message::ArchiveCommand(ArchiveCommand::Segments(ArchiveSegmentsCommand {
+            camera_name: "camera-2".into(),
+            camera_url: Url::parse("http://localhost:8081/stream.m3u8").unwrap(),
+            segment_list: vec!["one.ts".into()],
+            segment_urls: Default::default(),
         }));
         let msg = Publish::new("", QoS::ExactlyOnce, serde_json::to_string(&msg).unwrap());
         queue.handle_mqtt_message(msg);
+        assert_eq!(queue.queue.len(), 4);
+    }
+
+    #[test]
+    fn test_queued_event_is_updated() {
+        let mut queue = ArchiveTaskQueue::default();
+
+        let event = match test_event_task("one") {
+            ArchiveTask::EventMetadata(event) => event,
+            _ => unreachable!(),
+        };
+        queue.push(ArchiveTask::EventMetadata(event.clone()));
+        queue.push(test_unreachable_segment_task("one.ts"));
+        queue.queue[0].attempts = 2;
+
+        let mut updated = event;
+        updated.cameras.push(satori_common::CameraSegments {
+            name: "camera-1".into(),
+            segment_list: vec!["one.ts".into()],
+        });
+        queue.push(ArchiveTask::EventMetadata(updated.clone()));
+
+        // The event keeps its place and retry state, with the newer contents
         assert_eq!(queue.queue.len(), 2);
+        assert_eq!(queue.queue[0].attempts, 2);
+        match &queue.queue[0].task {
+            ArchiveTask::EventMetadata(event) => assert_eq!(*event, updated),
+            _ => panic!("should be an event task"),
+        }
+    }
+
+    #[test]
+    fn test_archive_segments_invalid_paths() {
+        let mut queue = ArchiveTaskQueue::default();
+
+        let msg = Message::ArchiveCommand(ArchiveCommand::Segments(ArchiveSegmentsCommand {
+            camera_name: "../events".into(),
+            camera_url: Url::parse("http://localhost:8080/stream.m3u8").unwrap(),
+            segment_list: vec!["one.ts".into()],
+            segment_urls: Default::default(),
+        }));
+        let msg = Publish::new("", QoS::ExactlyOnce, serde_json::to_string(&msg).unwrap());
+        queue.handle_mqtt_message(msg);
+        assert!(queue.queue.is_empty());
+
+        let msg = Message::ArchiveCommand(ArchiveCommand::Segments(ArchiveSegmentsCommand {
+            camera_name: "camera1".into(),
+            camera_url: Url::parse("http://localhost:8080/stream.m3u8").unwrap(),
+            segment_list: vec!["one.ts".into(), "../two.ts".into()],
+            segment_urls: Default::default(),
+        }));
+        let msg = Publish::new("", QoS::ExactlyOnce, serde_json::to_string(&msg).unwrap());
+        queue.handle_mqtt_message(msg);
+        assert_eq!(queue.queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_process_runs_up_to_concurrency_tasks() {
+        let context = test_context();
+
+        let mut queue = ArchiveTaskQueue::default();
+        for id in ["one", "two", "three"] {
+            queue.push(test_event_task(id));
+        }
+
+        queue
+            .process(&context, 2, &RetryConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(queue.queue.len(), 1);
+        assert_eq!(context.storage.list_events().await.unwrap().len(), 2);
+
+        queue
+            .process(&context, 2, &RetryConfig::default())
+            .await
+            .unwrap();
+        assert!(queue.queue.is_empty());
+        assert_eq!(context.storage.list_events().await.unwrap().len(), 3);
+
+        // Processing an empty queue does nothing
+        queue
+            .process(&context, 2, &RetryConfig::default())
+            .await
+            .unwrap();
+        assert!(queue.queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_process_failed_tasks_remain_in_order() {
+        let context = test_context();
+
+        let mut queue = ArchiveTaskQueue::default();
+        queue.push(test_unreachable_segment_task("one.ts"));
+        queue.push(test_event_task("one"));
+        queue.push(test_unreachable_segment_task("two.ts"));
+        queue.push(test_event_task("two"));
+
+        assert!(matches!(
+            queue.process(&context, 3, &RetryConfig::default()).await,
+            Err(ArchiverError::TasksFailed(2))
+        ));
+
+        assert_eq!(queue.queue.len(), 3);
+        assert!(matches!(queue.queue[0].task, ArchiveTask::CameraSegment(_)));
+        assert!(matches!(queue.queue[1].task, ArchiveTask::CameraSegment(_)));
+        assert!(matches!(queue.queue[2].task, ArchiveTask::EventMetadata(_)));
+        assert_eq!(context.storage.list_events().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_process_failed_task_backoff() {
+        let context = test_context();
+        let retry = RetryConfig {
+            base: std::time::Duration::from_secs(60),
+            max: std::time::Duration::from_secs(600),
+        };
+
+        let mut queue = ArchiveTaskQueue::default();
+        queue.push(test_unreachable_segment_task("one.ts"));
+        queue.push(test_event_task("one"));
+
+        let start = Utc::now();
+        assert!(queue.process(&context, 1, &retry).await.is_err());
+
+        assert_eq!(queue.queue.len(), 2);
+        assert_eq!(queue.queue[0].attempts, 1);
+        let next_attempt_at = queue.queue[0].next_attempt_at.unwrap();
+        assert!(next_attempt_at >= start + std::time::Duration::from_secs(30));
+        assert!(next_attempt_at <= Utc::now() + std::time::Duration::from_secs(60));
+
+        // The failed task is not retried until its backoff has elapsed, allowing the following
+        // task to be processed
+        queue.process(&context, 1, &retry).await.unwrap();
+        assert_eq!(queue.queue.len(), 1);
+        assert_eq!(queue.queue[0].attempts, 1);
+
+        // Nothing is due
+        queue.process(&context, 1, &retry).await.unwrap();
+        assert_eq!(queue.queue[0].attempts, 1);
+
+        // Once the backoff has elapsed the task is retried
+        queue.queue[0].next_attempt_at = Some(Utc::now());
+        assert!(queue.process(&context, 1, &retry).await.is_err());
+        assert_eq!(queue.queue[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_process_repeated_failures_logged_once() {
+        let context = test_context();
+        let retry = RetryConfig {
+            base: std::time::Duration::ZERO,
+            max: std::time::Duration::ZERO,
+        };
+
+        let path = std::env::temp_dir().join("satori_archiver_test_repeated_failures.json");
+
+        let errors = satori_testing_utils::EventCounter::new(tracing::Level::ERROR);
+        let _guard = errors.set_default();
+
+        let mut queue = ArchiveTaskQueue {
+            backing_file_name: path.clone(),
+            ..Default::default()
+        };
+        for i in 0..5 {
+            queue.push(test_unreachable_segment_task(&format!("{i}.ts")));
+        }
+
+        for _ in 0..4 {
+            assert!(matches!(
+                queue.process(&context, 5, &retry).await,
+                Err(ArchiverError::TasksFailed(5))
+            ));
+        }
+
+        std::fs::remove_file(&path).unwrap();
+
+        // 20 failures of tasks for the same camera
+        assert_eq!(errors.count(), 1);
+    }
+
+    #[test]
+    fn test_save_load_retry_state() {
+        let path = std::env::temp_dir().join("satori_archiver_test_save_load_retry_state.json");
+
+        let mut queue = ArchiveTaskQueue {
+            backing_file_name: path.clone(),
+            ..Default::default()
+        };
+        queue.push(test_event_task("one"));
+        queue.push(test_unreachable_segment_task("one.ts"));
+
+        let next_attempt_at = Utc::now();
+        queue.queue[1].attempts = 3;
+        queue.queue[1].next_attempt_at = Some(next_attempt_at);
+        queue.save().unwrap();
+
+        let loaded = ArchiveTaskQueue::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.queue.len(), 2);
+        assert!(matches!(
+            loaded.queue[0].task,
+            ArchiveTask::EventMetadata(_)
+        ));
+        assert_eq!(loaded.queue[0].attempts, 0);
+        assert_eq!(loaded.queue[0].next_attempt_at, None);
+        assert!(matches!(
+            loaded.queue[1].task,
+            ArchiveTask::CameraSegment(_)
+        ));
+        assert_eq!(loaded.queue[1].attempts, 3);
+        assert_eq!(loaded.queue[1].next_attempt_at, Some(next_attempt_at));
+    }
+
+    #[test]
+    fn test_load_queue_without_retry_state() {
+        let path =
+            std::env::temp_dir().join("satori_archiver_test_load_queue_without_retry_state.json");
+
+        // Queue files written before retry state was added contain only the tasks
+        let tasks = vec![
+            test_event_task("one"),
+            test_unreachable_segment_task("one.ts"),
+        ];
+        std::fs::write(&path, serde_json::to_string(&tasks).unwrap()).unwrap();
+
+        let loaded = ArchiveTaskQueue::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.queue.len(), 2);
+        assert!(matches!(
+            loaded.queue[0].task,
+            ArchiveTask::EventMetadata(_)
+        ));
+        assert!(matches!(
+            loaded.queue[1].task,
+            ArchiveTask::CameraSegment(_)
+        ));
+        assert!(loaded.queue.iter().all(|t| t.attempts == 0));
+        assert!(loaded.queue.iter().all(|t| t.next_attempt_at.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_process_publishes_task_events_to_sse_clients() {
+        use axum::{body::Body, http::Request};
+        use futures::StreamExt;
+        use tower::ServiceExt;
+
+        let context = test_context();
+
+        let response = crate::task_events::router(context.task_events.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/events/stream")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let mut body = response.into_body().into_data_stream();
+
+        let mut queue = ArchiveTaskQueue::default();
+        queue.push(test_event_task("one"));
+        queue.push(test_unreachable_segment_task("one.ts"));
+        let _ = queue.process(&context, 1, &RetryConfig::default()).await;
+        let _ = queue.process(&context, 1, &RetryConfig::default()).await;
+
+        let event = body.next().await.unwrap().unwrap();
+        assert_eq!(
+            event,
+            "event: task\ndata: {\"type\":\"event\",\"result\":\"success\"}\n\n"
+        );
+
+        let event = body.next().await.unwrap().unwrap();
+        assert_eq!(
+            event,
+            "event: task\ndata: {\"camera\":\"camera-1\",\"type\":\"segment\",\"result\":\"failure\"}\n\n"
+        );
     }
 }
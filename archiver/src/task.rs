@@ -3,10 +3,13 @@ use crate::{
     Context,
 };
 use bytes::Bytes;
-use satori_common::Event;
+use satori_common::{Event, SegmentFormat};
 use satori_storage::StorageProvider;
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 use tracing::{debug, info};
 use url::Url;
 
@@ -17,6 +20,35 @@ pub(crate) enum ArchiveTask {
 }
 
 impl ArchiveTask {
+    /// Name of the kind of task, as used in metrics and task events.
+    pub(crate) fn task_type(&self) -> &'static str {
+        match self {
+            Self::EventMetadata(_) => "event",
+            Self::CameraSegment(_) => "segment",
+        }
+    }
+
+    /// Source of failures of the task, used to throttle logging when a source fails repeatedly,
+    /// e.g. a camera that is offline.
+    pub(crate) fn log_source(&self) -> String {
+        match self {
+            Self::EventMetadata(_) => "event".into(),
+            Self::CameraSegment(segment) => format!("camera {}", segment.camera_name),
+        }
+    }
+
+    /// Checks if this task archives the same object as `other`, i.e. the same event or the same
+    /// segment of the same camera.
+    pub(crate) fn is_duplicate_of(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::EventMetadata(a), Self::EventMetadata(b)) => a.metadata == b.metadata,
+            (Self::CameraSegment(a), Self::CameraSegment(b)) => {
+                a.camera_name == b.camera_name && a.filename == b.filename
+            }
+            _ => false,
+        }
+    }
+
     #[tracing::instrument(skip_all)]
     pub(crate) async fn run(&self, context: &Context) -> ArchiverResult<()> {
         match &self {
@@ -28,38 +60,196 @@ impl ArchiveTask {
     #[tracing::instrument(skip(context))]
     async fn run_event(&self, context: &Context, event: &Event) -> ArchiverResult<()> {
         info!("Saving event");
-        Ok(context.storage.put_event(event).await?)
+
+        let start = Instant::now();
+        let result = context.storage.put_event(event).await;
+        record_upload_duration("event", start.elapsed());
+
+        match result {
+            Ok(()) => {
+                metrics::counter!(crate::METRIC_UPLOADED_EVENTS, 1);
+                Ok(())
+            }
+            Err(err) => {
+                metrics::counter!(crate::METRIC_UPLOAD_FAILURES, 1, "type" => "event");
+                Err(err.into())
+            }
+        }
     }
 
     #[tracing::instrument(skip(context))]
     async fn run_segment(&self, context: &Context, segment: &CameraSegment) -> ArchiverResult<()> {
         info!("Saving segment");
+        satori_storage::validate_camera_name(&segment.camera_name)?;
+        satori_storage::validate_filename(&segment.filename)?;
+
         let data = segment.get(context).await?;
-        Ok(context
+        let size = data.len() as u64;
+
+        let start = Instant::now();
+        let result = context
             .storage
             .put_segment(&segment.camera_name, &segment.filename, data)
-            .await?)
+            .await;
+        record_upload_duration("segment", start.elapsed());
+
+        match result {
+            Ok(()) => {
+                metrics::counter!(
+                    crate::METRIC_UPLOADED_SEGMENTS,
+                    1,
+                    "camera" => segment.camera_name.clone()
+                );
+                metrics::counter!(
+                    crate::METRIC_UPLOADED_BYTES,
+                    size,
+                    "camera" => segment.camera_name.clone()
+                );
+                Ok(())
+            }
+            Err(err) => {
+                metrics::counter!(
+                    crate::METRIC_UPLOAD_FAILURES,
+                    1,
+                    "type" => "segment",
+                    "camera" => segment.camera_name.clone()
+                );
+                Err(err.into())
+            }
+        }
     }
 }
 
+fn record_upload_duration(task_type: &'static str, duration: Duration) {
+    metrics::histogram!(
+        crate::METRIC_UPLOAD_DURATION,
+        duration.as_secs_f64(),
+        "type" => task_type
+    );
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct CameraSegment {
     pub(crate) camera_name: String,
     pub(crate) camera_url: Url,
     pub(crate) filename: PathBuf,
+
+    /// URL of the segment, if it is not alongside the camera playlist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) url: Option<Url>,
 }
 
 impl CameraSegment {
     #[tracing::instrument(skip_all)]
     pub(crate) async fn get(&self, context: &Context) -> ArchiverResult<Bytes> {
-        let url = get_segment_url(self.camera_url.clone(), &self.filename)?;
+        let url = match &self.url {
+            Some(url) => url.clone(),
+            None => get_segment_url(self.camera_url.clone(), &self.filename)?,
+        };
         debug!("Segment URL: {url}");
 
-        let req = context.http_client.get(url).send().await?;
-        Ok(req.bytes().await?)
+        let mut request = context.http_client.get(url);
+        for (name, value) in satori_common::trace_context_headers() {
+            request = request.header(name, value);
+        }
+        if let Some(token) = &context.camera_auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        let req = request.send().await?;
+
+        // Avoid downloading segments that are known to be too large
+        if let Some(size) = req.content_length() {
+            check_segment_size(size, context.max_segment_size)?;
+        }
+
+        let data = req.bytes().await?;
+        validate_segment(&self.filename, &data, context.max_segment_size)?;
+
+        Ok(data)
     }
 }
 
+/// Size of an MPEG-TS packet in bytes.
+const TS_PACKET_SIZE: usize = 188;
+
+/// Byte at the start of every MPEG-TS packet.
+const TS_SYNC_BYTE: u8 = 0x47;
+
+fn check_segment_size(size: u64, max: u64) -> ArchiverResult<()> {
+    if size > max {
+        Err(ArchiverError::SegmentTooLarge { size, max })
+    } else {
+        Ok(())
+    }
+}
+
+/// Types of the box that fragmented MP4 segments and initialisation sections start with.
+const FMP4_FIRST_BOX_TYPES: [&[u8; 4]; 3] = [b"ftyp", b"styp", b"moof"];
+
+/// Checks that segment data is plausibly of the format given by its filename.
+fn validate_segment(filename: &Path, data: &[u8], max_size: u64) -> ArchiverResult<()> {
+    check_segment_size(data.len() as u64, max_size)?;
+
+    if data.is_empty() {
+        return Err(ArchiverError::InvalidSegment("empty"));
+    }
+
+    if SegmentFormat::from_path(filename) == Some(SegmentFormat::Fmp4)
+        || satori_common::is_init_section(filename)
+    {
+        validate_fmp4(data)
+    } else {
+        validate_mpegts(data)
+    }
+}
+
+/// Checks that data is a plausible MPEG-TS stream, i.e. a whole number of packets which each
+/// start with the sync byte.
+fn validate_mpegts(data: &[u8]) -> ArchiverResult<()> {
+    if !data.len().is_multiple_of(TS_PACKET_SIZE) {
+        return Err(ArchiverError::InvalidSegment(
+            "not a whole number of packets",
+        ));
+    }
+
+    if data
+        .chunks_exact(TS_PACKET_SIZE)
+        .any(|packet| packet[0] != TS_SYNC_BYTE)
+    {
+        return Err(ArchiverError::InvalidSegment("missing sync byte"));
+    }
+
+    Ok(())
+}
+
+/// Checks that data plausibly starts with an ISO-BMFF box of a type that begins a fragmented MP4
+/// segment or initialisation section.
+fn validate_fmp4(data: &[u8]) -> ArchiverResult<()> {
+    let Some((size, box_type)) = data.get(..8).map(|header| {
+        (
+            u32::from_be_bytes(header[..4].try_into().unwrap()),
+            &header[4..],
+        )
+    }) else {
+        return Err(ArchiverError::InvalidSegment("truncated box header"));
+    };
+
+    if !FMP4_FIRST_BOX_TYPES
+        .iter()
+        .any(|t| t.as_slice() == box_type)
+    {
+        return Err(ArchiverError::InvalidSegment("unexpected box type"));
+    }
+
+    // A size of 0 extends to the end of the data and 1 indicates a 64 bit size follows
+    if size > 1 && (size < 8 || size as usize > data.len()) {
+        return Err(ArchiverError::InvalidSegment("invalid box size"));
+    }
+
+    Ok(())
+}
+
 fn get_segment_url(hls_url: Url, segment_filename: &Path) -> ArchiverResult<Url> {
     let mut url = hls_url;
     url.path_segments_mut()
@@ -72,6 +262,7 @@ fn get_segment_url(hls_url: Url, segment_filename: &Path) -> ArchiverResult<Url>
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_utils::{serve, test_context};
 
     #[test]
     fn test_get_segment_url_1() {
@@ -92,4 +283,311 @@ mod test {
             Url::parse("http://localhost:8080/a_file.ts").unwrap()
         )
     }
+
+    #[test]
+    fn test_get_segment_url_trailing_slash() {
+        let hls_url = Url::parse("http://localhost:8080/camera/").unwrap();
+        let segment_filename: PathBuf = "a_file.ts".into();
+        assert_eq!(
+            get_segment_url(hls_url, &segment_filename).unwrap(),
+            Url::parse("http://localhost:8080/camera/a_file.ts").unwrap()
+        )
+    }
+
+    fn ts_packets(count: usize) -> Vec<u8> {
+        let mut packet = [0; TS_PACKET_SIZE];
+        packet[0] = TS_SYNC_BYTE;
+        packet.repeat(count)
+    }
+
+    #[test]
+    fn test_validate_segment() {
+        assert!(validate_segment(Path::new("valid.ts"), &ts_packets(5), 1024).is_ok());
+
+        assert!(matches!(
+            validate_segment(Path::new("valid.ts"), b"<html>Not Found</html>", 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+        assert!(matches!(
+            validate_segment(Path::new("valid.ts"), &[], 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+
+        // Truncated final packet
+        let data = ts_packets(2);
+        assert!(matches!(
+            validate_segment(Path::new("valid.ts"), &data[..300], 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+
+        // Missing sync byte in a later packet
+        let mut data = ts_packets(3);
+        data[TS_PACKET_SIZE * 2] = 0;
+        assert!(matches!(
+            validate_segment(Path::new("valid.ts"), &data, 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+
+        assert!(matches!(
+            validate_segment(Path::new("valid.ts"), &ts_packets(6), 1024),
+            Err(ArchiverError::SegmentTooLarge {
+                size: 1128,
+                max: 1024
+            })
+        ));
+    }
+
+    /// An ISO-BMFF box of the given type, containing `len` bytes.
+    fn mp4_box(box_type: &[u8; 4], len: usize) -> Vec<u8> {
+        let mut data = ((len + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(box_type);
+        data.resize(len + 8, 0);
+        data
+    }
+
+    #[test]
+    fn test_validate_fmp4_segment() {
+        let init = Path::new("init.mp4");
+        let segment = Path::new("2023-01-01T00_00_00+0000.m4s");
+
+        assert!(validate_segment(init, &mp4_box(b"ftyp", 16), 1024).is_ok());
+        assert!(validate_segment(segment, &mp4_box(b"moof", 16), 1024).is_ok());
+        assert!(validate_segment(segment, &mp4_box(b"styp", 16), 1024).is_ok());
+
+        // Only MPEG-TS segments need to be made of MPEG-TS packets
+        assert!(matches!(
+            validate_segment(Path::new("valid.ts"), &mp4_box(b"moof", 16), 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+        assert!(matches!(
+            validate_segment(segment, &ts_packets(2), 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+
+        assert!(matches!(
+            validate_segment(segment, b"<html>Not Found</html>", 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+        assert!(matches!(
+            validate_segment(init, b"ftyp", 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+
+        // Box larger than the data
+        let mut data = mp4_box(b"moof", 16);
+        data.truncate(12);
+        assert!(matches!(
+            validate_segment(segment, &data, 1024),
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fmp4_segment_task() {
+        use axum::{routing::get, Router};
+
+        let app = Router::new()
+            .route("/camera/init.mp4", get(|| async { mp4_box(b"ftyp", 16) }))
+            .route(
+                "/camera/2023-01-01T00_00_00+0000.m4s",
+                get(|| async { [mp4_box(b"moof", 16), mp4_box(b"mdat", 64)].concat() }),
+            );
+        let address = serve(app).await;
+        let context = test_context();
+
+        for filename in ["init.mp4", "2023-01-01T00_00_00+0000.m4s"] {
+            ArchiveTask::CameraSegment(CameraSegment {
+                camera_name: "camera".into(),
+                camera_url: Url::parse(&format!("http://{address}/camera/stream.m3u8")).unwrap(),
+                filename: filename.into(),
+                url: None,
+            })
+            .run(&context)
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            context.storage.list_segments("camera").await.unwrap(),
+            vec![
+                PathBuf::from("2023-01-01T00_00_00+0000.m4s"),
+                PathBuf::from("init.mp4")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_segment_task_uses_segment_url() {
+        use axum::{routing::get, Router};
+
+        let app = Router::new().route("/elsewhere/valid.ts", get(|| async { ts_packets(2) }));
+        let address = serve(app).await;
+
+        let context = test_context();
+
+        ArchiveTask::CameraSegment(CameraSegment {
+            camera_name: "camera".into(),
+            camera_url: Url::parse(&format!("http://{address}/camera/stream.m3u8")).unwrap(),
+            filename: "valid.ts".into(),
+            url: Some(Url::parse(&format!("http://{address}/elsewhere/valid.ts")).unwrap()),
+        })
+        .run(&context)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            context.storage.list_segments("camera").await.unwrap(),
+            vec![PathBuf::from("valid.ts")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_segment_task_rejects_invalid_data() {
+        use axum::{routing::get, Router};
+
+        let app = Router::new()
+            .route("/camera/valid.ts", get(|| async { ts_packets(2) }))
+            .route("/camera/garbage.ts", get(|| async { "garbage" }))
+            .route("/camera/large.ts", get(|| async { ts_packets(10) }));
+        let address = serve(app).await;
+
+        let context = test_context();
+
+        let task = |filename: &str| {
+            ArchiveTask::CameraSegment(CameraSegment {
+                camera_name: "camera".into(),
+                camera_url: Url::parse(&format!("http://{address}/camera/stream.m3u8")).unwrap(),
+                filename: filename.into(),
+                url: None,
+            })
+        };
+
+        task("valid.ts").run(&context).await.unwrap();
+        assert!(matches!(
+            task("garbage.ts").run(&context).await,
+            Err(ArchiverError::InvalidSegment(_))
+        ));
+        assert!(matches!(
+            task("large.ts").run(&context).await,
+            Err(ArchiverError::SegmentTooLarge { .. })
+        ));
+
+        // Only the valid segment is stored
+        assert_eq!(
+            context.storage.list_segments("camera").await.unwrap(),
+            vec![PathBuf::from("valid.ts")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_segment_request_propagates_trace_context() {
+        use axum::{http::HeaderMap, routing::get, Router};
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
+        use std::sync::{Arc, Mutex};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let tracer_provider = TracerProvider::builder().build();
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::registry()
+                .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test"))),
+        );
+
+        // Camera agent that records the trace context of requests
+        let traceparent = Arc::new(Mutex::new(None));
+        let app = {
+            let traceparent = traceparent.clone();
+            Router::new().route(
+                "/camera/a_file.ts",
+                get(move |headers: HeaderMap| async move {
+                    *traceparent.lock().unwrap() = headers
+                        .get("traceparent")
+                        .map(|v| v.to_str().unwrap().to_owned());
+                    ts_packets(2)
+                }),
+            )
+        };
+        let address = serve(app).await;
+
+        let context = test_context();
+
+        let segment = CameraSegment {
+            camera_name: "camera".into(),
+            camera_url: Url::parse(&format!("http://{address}/camera/stream.m3u8")).unwrap(),
+            filename: "a_file.ts".into(),
+            url: None,
+        };
+        assert_eq!(segment.get(&context).await.unwrap(), ts_packets(2));
+
+        let traceparent = traceparent.lock().unwrap().clone();
+        assert!(traceparent
+            .expect("traceparent should be sent")
+            .starts_with("00-"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_metrics_recorded() {
+        use axum::{routing::get, Router};
+        use metrics_exporter_prometheus::PrometheusBuilder;
+        use satori_common::EventMetadata;
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::set_boxed_recorder(Box::new(recorder)).unwrap();
+
+        let app = Router::new().route("/camera/valid.ts", get(|| async { ts_packets(2) }));
+        let address = serve(app).await;
+
+        let context = test_context();
+
+        ArchiveTask::EventMetadata(Event {
+            metadata: EventMetadata {
+                id: "metrics".into(),
+                timestamp: chrono::Utc::now().into(),
+            },
+            start: chrono::Utc::now().into(),
+            end: chrono::Utc::now().into(),
+            reasons: Default::default(),
+            cameras: Default::default(),
+        })
+        .run(&context)
+        .await
+        .unwrap();
+
+        // Camera name is unique to this test, as other tests may upload segments concurrently
+        ArchiveTask::CameraSegment(CameraSegment {
+            camera_name: "metrics-camera".into(),
+            camera_url: Url::parse(&format!("http://{address}/camera/stream.m3u8")).unwrap(),
+            filename: "valid.ts".into(),
+            url: None,
+        })
+        .run(&context)
+        .await
+        .unwrap();
+
+        let rendered = handle.render();
+        let find = |name: &str| {
+            rendered
+                .lines()
+                .find(|l| l.starts_with(name) && l.contains(r#"camera="metrics-camera""#))
+                .unwrap_or_else(|| panic!("{name} should be recorded"))
+                .to_owned()
+        };
+
+        assert!(find(crate::METRIC_UPLOADED_SEGMENTS).ends_with(" 1"));
+        assert!(find(crate::METRIC_UPLOADED_BYTES).ends_with(&format!(" {}", 2 * TS_PACKET_SIZE)));
+        assert!(!rendered.contains(&format!(
+            r#"{}{{type="segment",camera="metrics-camera"}}"#,
+            crate::METRIC_UPLOAD_FAILURES
+        )));
+
+        assert!(rendered
+            .lines()
+            .any(|l| l.starts_with(crate::METRIC_UPLOADED_EVENTS)));
+        assert!(rendered.lines().any(|l| {
+            l.starts_with(&format!("{}_count", crate::METRIC_UPLOAD_DURATION))
+                && l.contains(r#"type="segment""#)
+        }));
+    }
 }
@@ -0,0 +1,49 @@
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+/// Number of task events buffered for each client, beyond which events are dropped for clients
+/// that are not keeping up.
+pub(crate) const TASK_EVENT_BUFFER: usize = 64;
+
+/// Outcome of processing a single task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct TaskEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) camera: Option<String>,
+
+    #[serde(rename = "type")]
+    pub(crate) task_type: &'static str,
+
+    pub(crate) result: &'static str,
+}
+
+/// HTTP endpoints for observing task processing.
+pub(crate) fn router(task_events: broadcast::Sender<TaskEvent>) -> Router {
+    Router::new()
+        .route("/events/stream", get(stream))
+        .with_state(task_events)
+}
+
+/// Server-Sent Events stream of the outcome of each processed task.
+async fn stream(
+    State(task_events): State<broadcast::Sender<TaskEvent>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // Events that a client lagged behind on are skipped
+    let stream = BroadcastStream::new(task_events.subscribe()).filter_map(|event| async move {
+        event
+            .ok()
+            .and_then(|event| Event::default().event("task").json_data(event).ok())
+            .map(Ok)
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
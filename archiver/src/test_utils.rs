@@ -0,0 +1,27 @@
+use crate::Context;
+use axum::Router;
+use std::net::SocketAddr;
+
+/// Context with empty dummy storage.
+pub(crate) fn test_context() -> Context {
+    let storage: satori_storage::StorageConfig = serde_json::from_str(
+        r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+    )
+    .unwrap();
+
+    Context {
+        storage: storage.create_provider(),
+        http_client: reqwest::Client::new(),
+        task_events: tokio::sync::broadcast::channel(8).0,
+        max_segment_size: 1024,
+        camera_auth_token: None,
+    }
+}
+
+/// Serves an app on a random local port, returning the address it is served on.
+pub(crate) async fn serve(app: Router) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    address
+}
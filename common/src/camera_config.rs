@@ -1,17 +1,83 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::{serde_as, DurationMilliSeconds};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 use url::Url;
 
+#[serde_as]
 #[derive(Debug, Deserialize)]
 pub struct CamerasConfig {
     cameras: Vec<CameraConfig>,
+
+    /// Timeout of each request made to a camera, in milliseconds.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: Duration,
+
+    /// Number of times a failed request to a camera is retried.
+    #[serde(default = "default_request_retries")]
+    pub request_retries: u32,
+}
+
+fn default_request_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_request_retries() -> u32 {
+    2
+}
+
+/// Problems found in the configuration of a set of cameras.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid camera configuration: {}", .0.join("; "))]
+pub struct CameraConfigError(pub Vec<String>);
+
+/// Checks that camera names are not empty and are unique, returning a description of each
+/// problem found.
+pub fn check_camera_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = HashSet::new();
+
+    for name in names {
+        if name.trim().is_empty() {
+            problems.push("camera name must not be empty".to_owned());
+        } else if !seen.insert(name) {
+            problems.push(format!("camera name \"{name}\" is used more than once"));
+        }
+    }
+
+    problems
 }
 
 impl CamerasConfig {
-    pub fn into_map(self) -> HashMap<String, Url> {
+    /// Checks the configuration of all cameras, reporting every problem found.
+    pub fn validate(&self) -> Result<(), CameraConfigError> {
+        let mut problems = check_camera_names(self.names());
+
+        for camera in &self.cameras {
+            if let Err(err) = validate_playlist_url(&camera.url) {
+                problems.push(err);
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(CameraConfigError(problems))
+        }
+    }
+
+    /// Names of all configured cameras.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.cameras.iter().map(|c| c.name.as_str())
+    }
+
+    pub fn into_map(self) -> HashMap<String, CameraConfig> {
         let mut ret = HashMap::new();
         for c in self.cameras {
-            ret.insert(c.name, c.url);
+            ret.insert(c.name.clone(), c);
         }
         ret
     }
@@ -20,5 +86,203 @@ impl CamerasConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CameraConfig {
     name: String,
+
+    /// URL of the HLS playlist for this camera.
+    /// Segment URLs are derived from this by replacing the playlist filename.
+    #[serde(deserialize_with = "deserialize_playlist_url")]
     url: Url,
+
+    /// Format of the timestamp filenames of segments (the final path component of their URIs),
+    /// as a strftime pattern.
+    /// If the format does not include a UTC offset then timestamps are assumed to be UTC.
+    #[serde(default = "default_segment_filename_format")]
+    segment_filename_format: String,
+
+    /// Bearer token presented when requesting the playlist, e.g. for an agent with an
+    /// authenticated HTTP API.
+    #[serde(default, skip_serializing)]
+    auth_token: Option<String>,
+}
+
+fn default_segment_filename_format() -> String {
+    crate::SEGMENT_FILENAME_FORMAT.to_owned()
+}
+
+impl CameraConfig {
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn segment_filename_format(&self) -> &str {
+        &self.segment_filename_format
+    }
+
+    pub fn auth_token(&self) -> Option<&str> {
+        self.auth_token.as_deref()
+    }
+}
+
+fn deserialize_playlist_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Url, D::Error> {
+    let url = Url::deserialize(deserializer)?;
+    validate_playlist_url(&url).map_err(serde::de::Error::custom)?;
+    Ok(url)
+}
+
+fn validate_playlist_url(url: &Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "camera URL \"{url}\" must use the http or https scheme"
+        ));
+    }
+
+    match url.path_segments().and_then(|mut s| s.next_back()) {
+        Some(filename) if !filename.is_empty() => Ok(()),
+        _ => Err(format!(
+            "camera URL \"{url}\" must point to a HLS playlist, not a directory"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(url: &str) -> Result<CamerasConfig, toml::de::Error> {
+        toml::from_str(&format!(
+            r#"
+[[cameras]]
+name = "camera1"
+url = "{url}"
+"#
+        ))
+    }
+
+    #[test]
+    fn test_valid_url() {
+        let config = parse("http://localhost:8080/camera1/stream.m3u8").unwrap();
+        assert_eq!(
+            config.into_map().get("camera1").unwrap().url().as_str(),
+            "http://localhost:8080/camera1/stream.m3u8"
+        );
+    }
+
+    #[test]
+    fn test_segment_filename_format() {
+        let config = parse("http://localhost:8080/camera1/stream.m3u8").unwrap();
+        assert_eq!(
+            config
+                .into_map()
+                .get("camera1")
+                .unwrap()
+                .segment_filename_format(),
+            crate::SEGMENT_FILENAME_FORMAT
+        );
+
+        let config: CamerasConfig = toml::from_str(
+            r#"
+[[cameras]]
+name = "camera1"
+url = "http://localhost:8080/camera1/stream.m3u8"
+segment_filename_format = "%Y%m%d-%H%M%S.ts"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .into_map()
+                .get("camera1")
+                .unwrap()
+                .segment_filename_format(),
+            "%Y%m%d-%H%M%S.ts"
+        );
+    }
+
+    #[test]
+    fn test_request_defaults() {
+        let config = parse("http://localhost:8080/camera1/stream.m3u8").unwrap();
+        assert_eq!(config.request_timeout, Duration::from_secs(5));
+        assert_eq!(config.request_retries, 2);
+    }
+
+    #[test]
+    fn test_request_options() {
+        let config: CamerasConfig = toml::from_str(
+            r#"
+request_timeout = 1500
+request_retries = 0
+
+[[cameras]]
+name = "camera1"
+url = "http://localhost:8080/camera1/stream.m3u8"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.request_timeout, Duration::from_millis(1500));
+        assert_eq!(config.request_retries, 0);
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let config = parse("http://localhost:8080/camera1/stream.m3u8").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_all_problems() {
+        let mut config: CamerasConfig = toml::from_str(
+            r#"
+[[cameras]]
+name = "camera1"
+url = "http://localhost:8080/camera1/stream.m3u8"
+
+[[cameras]]
+name = "camera1"
+url = "http://localhost:8080/camera2/stream.m3u8"
+
+[[cameras]]
+name = ""
+url = "http://localhost:8080/camera3/stream.m3u8"
+"#,
+        )
+        .unwrap();
+
+        // A URL that would not have been accepted when deserializing
+        config.cameras[2].url = Url::parse("http://localhost:8080/camera3/").unwrap();
+
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err.0,
+            vec![
+                "camera name \"camera1\" is used more than once".to_owned(),
+                "camera name must not be empty".to_owned(),
+                "camera URL \"http://localhost:8080/camera3/\" must point to a HLS playlist, not a directory".to_owned(),
+            ]
+        );
+        assert!(err
+            .to_string()
+            .starts_with("Invalid camera configuration: "));
+    }
+
+    #[test]
+    fn test_check_camera_names() {
+        assert!(check_camera_names(["a", "b", "c"]).is_empty());
+        assert_eq!(check_camera_names(["a", "b", "a", "a"]).len(), 2);
+        assert_eq!(check_camera_names([" "]).len(), 1);
+    }
+
+    #[test]
+    fn test_malformed_url_rejected() {
+        assert!(parse("not a url").is_err());
+    }
+
+    #[test]
+    fn test_non_http_url_rejected() {
+        assert!(parse("file:///tmp/stream.m3u8").is_err());
+    }
+
+    #[test]
+    fn test_url_without_playlist_rejected() {
+        assert!(parse("http://localhost:8080/camera1/").is_err());
+        assert!(parse("http://localhost:8080").is_err());
+    }
 }
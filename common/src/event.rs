@@ -32,6 +32,7 @@ impl From<crate::Trigger> for Event {
             reasons: vec![EventReason {
                 timestamp: trigger.metadata.timestamp,
                 reason: trigger.reason,
+                trigger_id: None,
             }],
             metadata: trigger.metadata,
             cameras: trigger
@@ -97,6 +98,11 @@ pub struct EventReason {
 
     /// String description of the reason
     pub reason: String,
+
+    /// ID of the trigger that gave this reason, if it differs from the ID of the event, i.e. the
+    /// trigger was merged into an existing event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_id: Option<String>,
 }
 
 /// A collection of video segments for a specific camera.
@@ -132,7 +138,8 @@ mod test {
             e.reasons,
             vec![EventReason {
                 timestamp: expected_timestamp,
-                reason: "Something happened".to_string()
+                reason: "Something happened".to_string(),
+                trigger_id: None,
             }]
         );
     }
@@ -170,4 +177,37 @@ mod test {
 
         assert!(!e.should_expire(Duration::from_secs(600)));
     }
+
+    #[test]
+    fn test_offset_preserved() {
+        let offset = FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap();
+        let timestamp = DateTime::parse_from_rfc3339("2023-03-01T17:30:00+05:30").unwrap();
+
+        let t = crate::Trigger {
+            metadata: EventMetadata {
+                id: "trig1".into(),
+                timestamp,
+            },
+            reason: "Something happened".into(),
+            cameras: vec!["camera-1".into()],
+            pre: Duration::from_secs(90),
+            post: Duration::from_secs(120),
+        };
+        let e: Event = t.into();
+
+        assert_eq!(e.metadata.timestamp.offset(), &offset);
+        assert_eq!(e.start.offset(), &offset);
+        assert_eq!(e.end.offset(), &offset);
+        assert_eq!(e.reasons[0].timestamp.offset(), &offset);
+
+        let filename = e.metadata.get_filename();
+        assert_eq!(
+            filename,
+            PathBuf::from("2023-03-01T17:30:00+05:30_trig1.json")
+        );
+
+        let metadata = EventMetadata::from_filename(&filename).unwrap();
+        assert_eq!(metadata.timestamp.offset(), &offset);
+        assert_eq!(metadata.timestamp, timestamp);
+    }
 }
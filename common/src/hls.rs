@@ -0,0 +1,809 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use std::{path::PathBuf, time::Duration};
+use url::Url;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum PlaylistError {
+    #[error("Segment \"{0}\" uses a byte range (EXT-X-BYTERANGE), which is not supported")]
+    ByteRangeNotSupported(String),
+
+    #[error("Segment \"{0}\" does not have a valid timestamp filename")]
+    InvalidSegmentFilename(String),
+}
+
+pub struct Playlist {
+    pub segments: Vec<SegmentFile>,
+}
+
+impl Playlist {
+    /// Runs of continuous segments, i.e. segments separated by `#EXT-X-DISCONTINUITY`.
+    ///
+    /// Timestamps are only guaranteed to increase within a run, e.g. the clock of a camera may
+    /// have been reset when it rebooted.
+    pub fn runs(&self) -> impl Iterator<Item = &[SegmentFile]> {
+        self.segments
+            .chunk_by(|a, b| a.discontinuity_sequence == b.discontinuity_sequence)
+    }
+
+    /// Segments whose actual time interval overlaps the period from `start` to `end`.
+    ///
+    /// Each run of continuous segments is considered separately, so a jump in time at a
+    /// discontinuity does not affect which segments are selected from other runs.
+    pub fn between(
+        &self,
+        start: DateTime<FixedOffset>,
+        end: DateTime<FixedOffset>,
+    ) -> Vec<&SegmentFile> {
+        self.runs()
+            .flat_map(|run| run.iter().filter(|s| s.between(start, end)))
+            .collect()
+    }
+
+    /// Segments that cover the last `duration` of the playlist, i.e. the most recent segments
+    /// whose total duration is at least `duration`.
+    ///
+    /// Segment durations are used rather than timestamps, so the result is not affected by jumps
+    /// in time at discontinuities.
+    pub fn last(&self, duration: Duration) -> Vec<&SegmentFile> {
+        let mut total = Duration::ZERO;
+
+        let mut segments: Vec<_> = self
+            .segments
+            .iter()
+            .rev()
+            .take_while(|s| {
+                let needed = total < duration;
+                total += s.duration;
+                needed
+            })
+            .collect();
+
+        segments.reverse();
+        segments
+    }
+
+    /// The time covered by the playlist, from the earliest start to the latest end of any run
+    /// of continuous segments.
+    ///
+    /// The end of each run is its start plus the total duration of its segments.
+    pub fn time_range(&self) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+        self.runs()
+            .map(|run| {
+                let start = run[0].start;
+                let duration: Duration = run.iter().map(|s| s.duration).sum();
+                (start, start + chrono::Duration::from_std(duration).unwrap())
+            })
+            .reduce(|(a_start, a_end), (b_start, b_end)| (a_start.min(b_start), a_end.max(b_end)))
+    }
+}
+
+impl Playlist {
+    /// Creates a playlist from a HLS media playlist, whose segment filenames are timestamps in
+    /// the strftime format `segment_filename_format`.
+    pub fn parse(
+        playlist: m3u8_rs::MediaPlaylist,
+        segment_filename_format: &str,
+    ) -> Result<Self, PlaylistError> {
+        let mut discontinuity_sequence = playlist.discontinuity_sequence;
+        let mut init = None;
+
+        Ok(Self {
+            segments: playlist
+                .segments
+                .into_iter()
+                .map(|i| {
+                    if i.discontinuity {
+                        discontinuity_sequence += 1;
+                    }
+
+                    // An initialisation section applies to all following segments, until the next
+                    if let Some(map) = &i.map {
+                        init = Some(map.uri.clone());
+                    }
+
+                    let mut segment = SegmentFile::parse(i, segment_filename_format)?;
+                    segment.discontinuity_sequence = discontinuity_sequence;
+                    segment.init.clone_from(&init);
+                    Ok(segment)
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl TryFrom<m3u8_rs::MediaPlaylist> for Playlist {
+    type Error = PlaylistError;
+
+    fn try_from(playlist: m3u8_rs::MediaPlaylist) -> Result<Self, Self::Error> {
+        Self::parse(playlist, crate::SEGMENT_FILENAME_FORMAT)
+    }
+}
+
+#[derive(Debug)]
+pub struct SegmentFile {
+    /// URI of the segment as given in the playlist, either absolute or relative to the playlist.
+    pub uri: String,
+
+    /// Final path component of the URI, which is the timestamp of the segment.
+    pub filename: PathBuf,
+
+    /// Duration of the segment, as given by its `#EXTINF` tag.
+    pub duration: Duration,
+
+    /// Discontinuity sequence number of the segment, segments with the same number form a
+    /// continuous run.
+    pub discontinuity_sequence: u64,
+
+    /// URI of the initialisation section (`#EXT-X-MAP`) needed to decode the segment, if any,
+    /// e.g. for fragmented MP4 segments.
+    pub init: Option<String>,
+
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+}
+
+impl SegmentFile {
+    /// Time of the start of the segment, as given by its filename.
+    pub fn start(&self) -> DateTime<FixedOffset> {
+        self.start
+    }
+
+    /// Time of the end of the segment, i.e. the start plus the duration.
+    pub fn end(&self) -> DateTime<FixedOffset> {
+        self.end
+    }
+
+    /// URL of the segment, given the URL of the playlist it was listed in.
+    ///
+    /// An absolute URI is used as is, a relative URI is resolved against the playlist URL.
+    pub fn url(&self, playlist_url: &Url) -> Result<Url, url::ParseError> {
+        playlist_url.join(&self.uri)
+    }
+
+    /// Filename of the initialisation section needed to decode the segment, if any.
+    pub fn init_filename(&self) -> Option<PathBuf> {
+        self.init.as_deref().map(|uri| uri_filename(uri).into())
+    }
+
+    /// URL of the initialisation section needed to decode the segment, if any, resolved in the
+    /// same way as the segment URL.
+    pub fn init_url(&self, playlist_url: &Url) -> Option<Result<Url, url::ParseError>> {
+        self.init.as_deref().map(|uri| playlist_url.join(uri))
+    }
+
+    /// Checks if the segment overlaps the period from `start` to `end`.
+    ///
+    /// A segment covers the interval `[self.start, self.end)`, so a segment that ends exactly at
+    /// `start` is not included.
+    pub fn between(&self, start: DateTime<FixedOffset>, end: DateTime<FixedOffset>) -> bool {
+        self.start <= end && start < self.end
+    }
+}
+
+impl SegmentFile {
+    /// Creates a segment from a HLS media segment, whose filename is a timestamp in the strftime
+    /// format `filename_format`.
+    ///
+    /// If the format does not include a UTC offset then the timestamp is assumed to be UTC.
+    pub fn parse(
+        segment: m3u8_rs::MediaSegment,
+        filename_format: &str,
+    ) -> Result<Self, PlaylistError> {
+        // Segments are archived as discrete files, a segment that is only part of a file cannot
+        // be represented.
+        if segment.byte_range.is_some() {
+            return Err(PlaylistError::ByteRangeNotSupported(segment.uri));
+        }
+
+        let filename = uri_filename(&segment.uri);
+        let start = parse_segment_start(filename, filename_format)
+            .ok_or_else(|| PlaylistError::InvalidSegmentFilename(segment.uri.clone()))?;
+
+        let duration = Duration::from_secs_f32(segment.duration);
+        let end = start + chrono::Duration::from_std(duration).unwrap();
+
+        Ok(Self {
+            filename: filename.into(),
+            init: segment.map.map(|map| map.uri),
+            uri: segment.uri,
+            duration,
+            discontinuity_sequence: 0,
+            start,
+            end,
+        })
+    }
+}
+
+impl TryFrom<m3u8_rs::MediaSegment> for SegmentFile {
+    type Error = PlaylistError;
+
+    fn try_from(segment: m3u8_rs::MediaSegment) -> Result<Self, Self::Error> {
+        Self::parse(segment, crate::SEGMENT_FILENAME_FORMAT)
+    }
+}
+
+/// Final path component of a URI, excluding any query or fragment.
+fn uri_filename(uri: &str) -> &str {
+    let path = uri.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit('/').next().unwrap_or_default()
+}
+
+/// Parses the start time of a segment from its filename, a timestamp in the strftime format
+/// `format`.
+///
+/// If the format does not include a UTC offset then the timestamp is assumed to be UTC.
+pub fn parse_segment_start(filename: &str, format: &str) -> Option<DateTime<FixedOffset>> {
+    match DateTime::<FixedOffset>::parse_from_str(filename, format) {
+        Ok(start) => Some(start),
+        Err(err) if err.kind() == chrono::format::ParseErrorKind::NotEnough => {
+            NaiveDateTime::parse_from_str(filename, format)
+                .ok()
+                .map(|start| start.and_utc().fixed_offset())
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SegmentFormat;
+
+    fn get_test_file() -> SegmentFile {
+        SegmentFile {
+            uri: Default::default(),
+            filename: Default::default(),
+            duration: Duration::from_secs(60),
+            discontinuity_sequence: 0,
+            init: None,
+            start: chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 10, 0)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+            end: chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 11, 0)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_segment_file_between_1() {
+        let file = get_test_file();
+        assert!(file.between(
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 9, 30)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 10, 30)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+        ));
+    }
+
+    #[test]
+    fn test_segment_file_between_2() {
+        let file = get_test_file();
+        assert!(file.between(
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 10, 30)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 11, 30)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+        ));
+    }
+
+    #[test]
+    fn test_segment_file_between_not_1() {
+        let file = get_test_file();
+        assert!(!file.between(
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 12, 0)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 13, 0)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+        ));
+    }
+
+    #[test]
+    fn test_segment_file_between_not_2() {
+        let file = get_test_file();
+        assert!(!file.between(
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 8, 0)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2022, 12, 30)
+                .unwrap()
+                .and_hms_opt(18, 9, 0)
+                .unwrap()
+                .and_local_timezone(chrono::FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+        ));
+    }
+
+    fn get_test_playlist() -> Playlist {
+        m3u8_rs::MediaPlaylist {
+            segments: (0..10)
+                .map(|i| m3u8_rs::MediaSegment {
+                    uri: format!("2022-12-30T18_10_{:02}+0000.ts", i * 6),
+                    duration: 6.0,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_playlist_last() {
+        let playlist = get_test_playlist();
+
+        let segments = playlist.last(Duration::from_secs(30));
+        assert_eq!(
+            segments
+                .iter()
+                .map(|s| s.filename.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("2022-12-30T18_10_30+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_36+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_42+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_48+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_54+0000.ts"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playlist_last_longer_than_playlist() {
+        let playlist = get_test_playlist();
+        assert_eq!(playlist.last(Duration::from_secs(3600)).len(), 10);
+    }
+
+    #[test]
+    fn test_playlist_last_empty() {
+        let playlist = Playlist {
+            segments: Vec::new(),
+        };
+        assert!(playlist.last(Duration::from_secs(30)).is_empty());
+    }
+
+    fn time(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    fn get_variable_duration_playlist() -> Playlist {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:10
+            #EXTINF:2.0,
+            2022-12-30T18_10_00+0000.ts
+            #EXTINF:8.5,
+            2022-12-30T18_10_02+0000.ts
+            #EXTINF:1.5,
+            2022-12-30T18_10_10+0000.ts
+            #EXTINF:4.0,
+            2022-12-30T18_10_12+0000.ts
+        "};
+        m3u8_rs::parse_media_playlist_res(playlist.as_bytes())
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    fn filenames(segments: Vec<&SegmentFile>) -> Vec<PathBuf> {
+        segments.iter().map(|s| s.filename.clone()).collect()
+    }
+
+    #[test]
+    fn test_playlist_durations() {
+        let playlist = get_variable_duration_playlist();
+
+        assert_eq!(
+            playlist
+                .segments
+                .iter()
+                .map(|s| s.duration)
+                .collect::<Vec<_>>(),
+            vec![
+                Duration::from_secs_f32(2.0),
+                Duration::from_secs_f32(8.5),
+                Duration::from_secs_f32(1.5),
+                Duration::from_secs_f32(4.0),
+            ]
+        );
+
+        assert_eq!(
+            playlist.segments[1].start(),
+            time("2022-12-30T18:10:02+00:00")
+        );
+        assert_eq!(
+            playlist.segments[1].end(),
+            time("2022-12-30T18:10:10.5+00:00")
+        );
+    }
+
+    #[test]
+    fn test_playlist_time_range() {
+        let playlist = get_variable_duration_playlist();
+
+        assert_eq!(
+            playlist.time_range(),
+            Some((
+                time("2022-12-30T18:10:00+00:00"),
+                time("2022-12-30T18:10:16+00:00")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_playlist_time_range_empty() {
+        let playlist = Playlist {
+            segments: Vec::new(),
+        };
+        assert_eq!(playlist.time_range(), None);
+    }
+
+    #[test]
+    fn test_playlist_between_variable_durations() {
+        let playlist = get_variable_duration_playlist();
+
+        // The long segment extends past the start of the event
+        assert_eq!(
+            filenames(playlist.between(
+                time("2022-12-30T18:10:10.25+00:00"),
+                time("2022-12-30T18:10:11+00:00")
+            )),
+            vec![
+                PathBuf::from("2022-12-30T18_10_02+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_10+0000.ts"),
+            ]
+        );
+
+        // A segment that ends exactly at the start of the event is not included
+        assert_eq!(
+            filenames(playlist.between(
+                time("2022-12-30T18:10:02+00:00"),
+                time("2022-12-30T18:10:05+00:00")
+            )),
+            vec![PathBuf::from("2022-12-30T18_10_02+0000.ts")]
+        );
+
+        // An event entirely after the end of the playlist
+        assert!(playlist
+            .between(
+                time("2022-12-30T18:10:16+00:00"),
+                time("2022-12-30T18:10:20+00:00")
+            )
+            .is_empty());
+    }
+
+    /// A camera that rebooted, resetting its clock, after the first three segments.
+    fn get_discontinuity_playlist() -> Playlist {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:6
+            #EXT-X-DISCONTINUITY-SEQUENCE:4
+            #EXTINF:6.0,
+            2022-12-30T18_10_00+0000.ts
+            #EXTINF:6.0,
+            2022-12-30T18_10_06+0000.ts
+            #EXTINF:6.0,
+            2022-12-30T18_10_12+0000.ts
+            #EXT-X-DISCONTINUITY
+            #EXTINF:6.0,
+            2022-12-30T18_00_00+0000.ts
+            #EXTINF:6.0,
+            2022-12-30T18_00_06+0000.ts
+        "};
+        m3u8_rs::parse_media_playlist_res(playlist.as_bytes())
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_playlist_discontinuity_runs() {
+        let playlist = get_discontinuity_playlist();
+
+        assert_eq!(
+            playlist
+                .segments
+                .iter()
+                .map(|s| s.discontinuity_sequence)
+                .collect::<Vec<_>>(),
+            vec![4, 4, 4, 5, 5]
+        );
+        assert_eq!(
+            playlist.runs().map(|run| run.len()).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+    }
+
+    #[test]
+    fn test_playlist_discontinuity_between() {
+        let playlist = get_discontinuity_playlist();
+
+        // Segments after the discontinuity are found, despite time jumping backwards
+        assert_eq!(
+            filenames(playlist.between(
+                time("2022-12-30T18:00:04+00:00"),
+                time("2022-12-30T18:00:08+00:00")
+            )),
+            vec![
+                PathBuf::from("2022-12-30T18_00_00+0000.ts"),
+                PathBuf::from("2022-12-30T18_00_06+0000.ts"),
+            ]
+        );
+
+        // As are segments before it
+        assert_eq!(
+            filenames(playlist.between(
+                time("2022-12-30T18:10:07+00:00"),
+                time("2022-12-30T18:20:00+00:00")
+            )),
+            vec![
+                PathBuf::from("2022-12-30T18_10_06+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_12+0000.ts"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playlist_discontinuity_last() {
+        let playlist = get_discontinuity_playlist();
+
+        assert_eq!(
+            filenames(playlist.last(Duration::from_secs(10))),
+            vec![
+                PathBuf::from("2022-12-30T18_00_00+0000.ts"),
+                PathBuf::from("2022-12-30T18_00_06+0000.ts"),
+            ]
+        );
+        assert_eq!(
+            filenames(playlist.last(Duration::from_secs(13))),
+            vec![
+                PathBuf::from("2022-12-30T18_10_12+0000.ts"),
+                PathBuf::from("2022-12-30T18_00_00+0000.ts"),
+                PathBuf::from("2022-12-30T18_00_06+0000.ts"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playlist_discontinuity_time_range() {
+        let playlist = get_discontinuity_playlist();
+
+        assert_eq!(
+            playlist.time_range(),
+            Some((
+                time("2022-12-30T18:00:00+00:00"),
+                time("2022-12-30T18:10:18+00:00")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_playlist_custom_segment_filename_format() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:6
+            #EXTINF:6.0,
+            cam_20221230-181000+0100.ts
+            #EXTINF:6.0,
+            cam_20221230-181006+0100.ts
+        "};
+        let playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+
+        let playlist = Playlist::parse(playlist, "cam_%Y%m%d-%H%M%S%z.ts").unwrap();
+
+        assert_eq!(
+            playlist.segments[0].start(),
+            time("2022-12-30T18:10:00+01:00")
+        );
+        assert_eq!(
+            playlist.segments[1].start(),
+            time("2022-12-30T18:10:06+01:00")
+        );
+        assert_eq!(
+            playlist.time_range(),
+            Some((
+                time("2022-12-30T17:10:00+00:00"),
+                time("2022-12-30T17:10:12+00:00")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_playlist_custom_segment_filename_format_without_offset() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:6
+            #EXTINF:6.0,
+            2022-12-30_18-10-00.ts
+            #EXTINF:6.0,
+            2022-12-30_18-10-06.ts
+        "};
+        let playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+
+        let parsed = Playlist::parse(playlist.clone(), "%Y-%m-%d_%H-%M-%S.ts").unwrap();
+
+        // Times are assumed to be UTC
+        assert_eq!(
+            parsed.segments[0].start(),
+            time("2022-12-30T18:10:00+00:00")
+        );
+        assert_eq!(parsed.segments[1].end(), time("2022-12-30T18:10:12+00:00"));
+
+        // The default format does not match
+        assert_eq!(
+            Playlist::try_from(playlist).err(),
+            Some(PlaylistError::InvalidSegmentFilename(
+                "2022-12-30_18-10-00.ts".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_playlist_absolute_and_relative_uris() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:6
+            #EXTINF:6.0,
+            2022-12-30T18_10_00+0000.ts
+            #EXTINF:6.0,
+            https://cdn.example.com/camera1/2022-12-30T18_10_06+0000.ts?token=abc
+            #EXTINF:6.0,
+            /other/2022-12-30T18_10_12+0000.ts
+            #EXTINF:6.0,
+            segments/2022-12-30T18_10_18+0000.ts
+        "};
+        let playlist: Playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes())
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        assert_eq!(
+            filenames(playlist.segments.iter().collect()),
+            vec![
+                PathBuf::from("2022-12-30T18_10_00+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_06+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_12+0000.ts"),
+                PathBuf::from("2022-12-30T18_10_18+0000.ts"),
+            ]
+        );
+        assert_eq!(
+            playlist.segments[1].start(),
+            time("2022-12-30T18:10:06+00:00")
+        );
+
+        let playlist_url = Url::parse("http://camera:8080/hls/stream.m3u8").unwrap();
+        let urls: Vec<String> = playlist
+            .segments
+            .iter()
+            .map(|s| s.url(&playlist_url).unwrap().to_string())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "http://camera:8080/hls/2022-12-30T18_10_00+0000.ts",
+                "https://cdn.example.com/camera1/2022-12-30T18_10_06+0000.ts?token=abc",
+                "http://camera:8080/other/2022-12-30T18_10_12+0000.ts",
+                "http://camera:8080/hls/segments/2022-12-30T18_10_18+0000.ts",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playlist_byte_range_rejected() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:4
+            #EXT-X-TARGETDURATION:6
+            #EXTINF:6.0,
+            #EXT-X-BYTERANGE:75232@0
+            2022-12-30T18_10_00+0000.ts
+            #EXTINF:6.0,
+            #EXT-X-BYTERANGE:82112@75232
+            2022-12-30T18_10_00+0000.ts
+        "};
+        let playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+
+        assert_eq!(
+            Playlist::try_from(playlist).err(),
+            Some(PlaylistError::ByteRangeNotSupported(
+                "2022-12-30T18_10_00+0000.ts".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_playlist_invalid_segment_filename() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:3
+            #EXT-X-TARGETDURATION:6
+            #EXTINF:6.0,
+            segment0.ts
+        "};
+        let playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+
+        assert_eq!(
+            Playlist::try_from(playlist).err(),
+            Some(PlaylistError::InvalidSegmentFilename("segment0.ts".into()))
+        );
+    }
+
+    #[test]
+    fn test_playlist_fmp4_init_section() {
+        let playlist = indoc::indoc! {"
+            #EXTM3U
+            #EXT-X-VERSION:7
+            #EXT-X-TARGETDURATION:6
+            #EXT-X-MAP:URI=\"init.mp4\"
+            #EXTINF:6.0,
+            2022-12-30T18_10_00+0000.m4s
+            #EXTINF:6.0,
+            2022-12-30T18_10_06+0000.m4s
+        "};
+        let playlist = m3u8_rs::parse_media_playlist_res(playlist.as_bytes()).unwrap();
+        let playlist =
+            Playlist::parse(playlist, SegmentFormat::Fmp4.segment_filename_format()).unwrap();
+
+        assert_eq!(
+            filenames(playlist.segments.iter().collect()),
+            vec![
+                PathBuf::from("2022-12-30T18_10_00+0000.m4s"),
+                PathBuf::from("2022-12-30T18_10_06+0000.m4s"),
+            ]
+        );
+
+        // The initialisation section applies to every segment that follows it
+        for segment in &playlist.segments {
+            assert_eq!(segment.init_filename(), Some(PathBuf::from("init.mp4")));
+        }
+
+        let playlist_url = Url::parse("http://camera:8080/hls/stream.m3u8").unwrap();
+        assert_eq!(
+            playlist.segments[1]
+                .init_url(&playlist_url)
+                .unwrap()
+                .unwrap()
+                .as_str(),
+            "http://camera:8080/hls/init.mp4"
+        );
+    }
+}
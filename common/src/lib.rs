@@ -1,5 +1,7 @@
 pub mod camera_config;
 
+pub mod hls;
+
 mod event;
 pub use self::event::{CameraSegments, Event, EventMetadata, EventReason};
 
@@ -9,11 +11,21 @@ pub use self::message_schema::{ArchiveCommand, ArchiveSegmentsCommand, Message,
 pub mod mqtt;
 
 mod trigger;
-pub use self::trigger::{Trigger, TriggerTemplate};
+pub use self::trigger::{Trigger, TriggerError, TriggerTemplate};
+
+mod segment_format;
+pub use self::segment_format::{
+    init_section_filename, init_section_first_segment, is_init_section, is_segment_file,
+    SegmentFormat,
+};
 
 pub const SEGMENT_FILENAME_FORMAT: &str = "%Y-%m-%dT%H_%M_%S%z.ts";
 
 mod version;
 
 mod utils;
-pub use self::utils::{load_config_file, ThrottledErrorLogger};
+pub use self::utils::{
+    check_bearer_token, init_tracing, install_panic_hook, load_config_file, set_trace_parent,
+    trace_context_headers, write_file_atomic, ErrorLogThrottle, LogFormat, ThrottledErrorLogger,
+    TracingGuard,
+};
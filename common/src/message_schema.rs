@@ -1,7 +1,7 @@
 use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationSeconds};
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +32,17 @@ pub struct TriggerCommand {
     #[serde_as(as = "Option<DurationSeconds<u64>>")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub post: Option<Duration>,
+
+    /// Name of the trigger template to use, instead of selecting one by ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    /// Arbitrary structured data describing the trigger, used to render reason templates.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_segments: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,4 +57,9 @@ pub struct ArchiveSegmentsCommand {
     pub camera_name: String,
     pub camera_url: Url,
     pub segment_list: Vec<PathBuf>,
+
+    /// URLs of segments that are not alongside the camera playlist, e.g. because the playlist
+    /// lists them by absolute URL, keyed by filename.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub segment_urls: HashMap<PathBuf, Url>,
 }
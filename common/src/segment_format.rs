@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Filename format of fragmented MP4 segments, the equivalent of `SEGMENT_FILENAME_FORMAT`.
+const FMP4_SEGMENT_FILENAME_FORMAT: &str = "%Y-%m-%dT%H_%M_%S%z.m4s";
+
+/// Extension of the initialisation section (`#EXT-X-MAP`) of fragmented MP4 segments.
+const INIT_SEGMENT_EXTENSION: &str = "mp4";
+
+/// Container format of HLS segments, named as per the `hls_segment_type` option of ffmpeg.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentFormat {
+    /// MPEG-TS segments.
+    #[default]
+    Mpegts,
+
+    /// Fragmented MP4 segments, which require an initialisation section to be decoded.
+    Fmp4,
+}
+
+impl SegmentFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Mpegts => "ts",
+            Self::Fmp4 => "m4s",
+        }
+    }
+
+    /// Default strftime format of segment filenames of this format.
+    pub fn segment_filename_format(&self) -> &'static str {
+        match self {
+            Self::Mpegts => crate::SEGMENT_FILENAME_FORMAT,
+            Self::Fmp4 => FMP4_SEGMENT_FILENAME_FORMAT,
+        }
+    }
+
+    /// Format of a media segment, given its filename.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "ts" => Some(Self::Mpegts),
+            "m4s" => Some(Self::Fmp4),
+            _ => None,
+        }
+    }
+}
+
+/// Checks if a file is a segment, i.e. either a media segment or the initialisation section of
+/// fragmented MP4 segments.
+pub fn is_segment_file(path: &Path) -> bool {
+    SegmentFormat::from_path(path).is_some() || is_init_section(path)
+}
+
+/// Checks if a file is the initialisation section of fragmented MP4 segments.
+pub fn is_init_section(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(INIT_SEGMENT_EXTENSION)
+}
+
+/// Prefix of the filename of initialisation sections that are named after a segment.
+const INIT_SECTION_PREFIX: &str = "init_";
+
+/// Filename of an initialisation section, named after the first segment that uses it.
+pub fn init_section_filename(first_segment: &Path) -> PathBuf {
+    let stem = first_segment
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    PathBuf::from(format!(
+        "{INIT_SECTION_PREFIX}{stem}.{INIT_SEGMENT_EXTENSION}"
+    ))
+}
+
+/// Filename of the first segment that uses an initialisation section, if it is named after one.
+///
+/// This is the inverse of [`init_section_filename`] for segments of the given format.
+pub fn init_section_first_segment(init: &Path, format: SegmentFormat) -> Option<PathBuf> {
+    if !is_init_section(init) {
+        return None;
+    }
+    let stem = init
+        .file_stem()?
+        .to_str()?
+        .strip_prefix(INIT_SECTION_PREFIX)?;
+    Some(PathBuf::from(format!("{stem}.{}", format.extension())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            SegmentFormat::from_path(Path::new("2023-01-01T00_00_00+0000.ts")),
+            Some(SegmentFormat::Mpegts)
+        );
+        assert_eq!(
+            SegmentFormat::from_path(Path::new("2023-01-01T00_00_00+0000.m4s")),
+            Some(SegmentFormat::Fmp4)
+        );
+        assert_eq!(SegmentFormat::from_path(Path::new("init.mp4")), None);
+        assert_eq!(SegmentFormat::from_path(Path::new("stream.m3u8")), None);
+        assert_eq!(SegmentFormat::from_path(Path::new("ts")), None);
+    }
+
+    #[test]
+    fn test_is_segment_file() {
+        assert!(is_segment_file(Path::new("one.ts")));
+        assert!(is_segment_file(Path::new("one.m4s")));
+        assert!(is_segment_file(Path::new("init.mp4")));
+        assert!(!is_segment_file(Path::new("stream.m3u8")));
+        assert!(!is_segment_file(Path::new("preview.jpg")));
+    }
+
+    #[test]
+    fn test_init_section_filename() {
+        let segment = Path::new("2023-01-01T00_00_00+0000.m4s");
+        let init = init_section_filename(segment);
+        assert_eq!(init, Path::new("init_2023-01-01T00_00_00+0000.mp4"));
+        assert!(is_segment_file(&init));
+
+        assert_eq!(
+            init_section_first_segment(&init, SegmentFormat::Fmp4).as_deref(),
+            Some(segment)
+        );
+        assert_eq!(
+            init_section_first_segment(Path::new("init.mp4"), SegmentFormat::Fmp4),
+            None
+        );
+        assert_eq!(
+            init_section_first_segment(segment, SegmentFormat::Fmp4),
+            None
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        #[derive(Deserialize)]
+        struct Config {
+            format: SegmentFormat,
+        }
+
+        assert_eq!(
+            toml::from_str::<Config>("format = \"fmp4\"")
+                .unwrap()
+                .format,
+            SegmentFormat::Fmp4
+        );
+        assert_eq!(
+            toml::from_str::<Config>("format = \"mpegts\"")
+                .unwrap()
+                .format,
+            SegmentFormat::Mpegts
+        );
+    }
+}
@@ -2,7 +2,7 @@ use crate::{EventMetadata, TriggerCommand};
 use chrono::{DateTime, FixedOffset, Utc};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationSeconds};
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -24,44 +24,146 @@ pub struct Trigger {
     pub post: Duration,
 }
 
+/// Reasons a trigger command could not be turned into a trigger.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TriggerError {
+    #[error("No trigger template named \"{0}\"")]
+    UnknownTemplate(String),
+
+    #[error("Missing template variable(s): {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
+
+    #[error("No camera group named \"{0}\"")]
+    UnknownCameraGroup(String),
+}
+
 impl Trigger {
-    pub fn from_default_and_command(default: &TriggerTemplate, cmd: &TriggerCommand) -> Self {
-        Self {
+    pub fn from_default_and_command(
+        default: &TriggerTemplate,
+        cmd: &TriggerCommand,
+    ) -> Result<Self, TriggerError> {
+        Ok(Self {
             metadata: EventMetadata {
                 id: cmd.id.clone(),
                 timestamp: cmd.timestamp.unwrap_or_else(|| Utc::now().into()),
             },
-            reason: cmd.reason.clone().unwrap_or_else(|| default.reason.clone()),
-            cameras: cmd
-                .cameras
-                .clone()
-                .unwrap_or_else(|| default.cameras.clone()),
+            reason: default.render_reason(cmd)?,
+            cameras: if cmd.archive_segments.unwrap_or(default.archive_segments) {
+                match &cmd.cameras {
+                    Some(cameras) => cameras.clone(),
+                    None => default.select_cameras(cmd)?,
+                }
+            } else {
+                // Metadata only triggers do not reference any cameras, so no segments are archived
+                Vec::new()
+            },
             pre: cmd.pre.unwrap_or(default.pre),
             post: cmd.post.unwrap_or(default.post),
-        }
+        })
     }
 
+    /// Longest `pre` or `post` time that a trigger may have.
+    pub const MAX_PRE_POST: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    /// Start of the period of time covered by the trigger.
+    ///
+    /// `pre` is capped at [`Trigger::MAX_PRE_POST`], if the start time is still not representable
+    /// then the trigger timestamp is used.
     pub fn start_time(&self) -> DateTime<FixedOffset> {
-        self.metadata.timestamp - chrono::Duration::from_std(self.pre).unwrap()
+        self.metadata
+            .timestamp
+            .checked_sub_signed(capped_duration(self.pre))
+            .unwrap_or(self.metadata.timestamp)
     }
 
+    /// End of the period of time covered by the trigger, see [`Trigger::start_time`].
     pub fn end_time(&self) -> DateTime<FixedOffset> {
-        self.metadata.timestamp + chrono::Duration::from_std(self.post).unwrap()
+        self.metadata
+            .timestamp
+            .checked_add_signed(capped_duration(self.post))
+            .unwrap_or(self.metadata.timestamp)
     }
 }
 
+fn capped_duration(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration.min(Trigger::MAX_PRE_POST))
+        .expect("maximum pre/post time should be representable")
+}
+
 #[serde_as]
 #[derive(Debug, Deserialize)]
 pub struct TriggerTemplate {
+    /// Cameras used when the template does not select a camera group.
+    #[serde(default)]
     pub cameras: Vec<String>,
 
+    /// Named groups of cameras, one of which may be selected by `camera_group`.
+    #[serde(default)]
+    pub camera_groups: HashMap<String, Vec<String>>,
+
+    /// Template used to select the name of a group in `camera_groups`, substituted in the same
+    /// way as `reason_template`, e.g. `{zone}`.
+    #[serde(default)]
+    pub camera_group: Option<String>,
+
     pub reason: String,
 
+    /// Template used to generate the reason, `{placeholders}` are substituted with the trigger ID
+    /// (`{id}`), reason (`{reason}`) or any of the labels of the trigger command.
+    #[serde(default)]
+    pub reason_template: Option<String>,
+
     #[serde_as(as = "DurationSeconds<u64>")]
     pub pre: Duration,
 
     #[serde_as(as = "DurationSeconds<u64>")]
     pub post: Duration,
+
+    /// If segments from cameras should be archived, otherwise only the event metadata is archived.
+    #[serde(default = "default_archive_segments")]
+    pub archive_segments: bool,
+}
+
+impl TriggerTemplate {
+    fn reason<'a>(&'a self, cmd: &'a TriggerCommand) -> &'a str {
+        cmd.reason.as_ref().unwrap_or(&self.reason)
+    }
+
+    fn render(&self, template: &str, cmd: &TriggerCommand) -> Result<String, TriggerError> {
+        let mut vars: HashMap<&str, &str> = cmd
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        vars.insert("id", &cmd.id);
+        vars.insert("reason", self.reason(cmd));
+
+        crate::utils::render_template(template, &vars).map_err(TriggerError::MissingVariables)
+    }
+
+    fn render_reason(&self, cmd: &TriggerCommand) -> Result<String, TriggerError> {
+        match &self.reason_template {
+            Some(template) => self.render(template, cmd),
+            None => Ok(self.reason(cmd).to_owned()),
+        }
+    }
+
+    fn select_cameras(&self, cmd: &TriggerCommand) -> Result<Vec<String>, TriggerError> {
+        match &self.camera_group {
+            Some(template) => {
+                let group = self.render(template, cmd)?;
+                self.camera_groups
+                    .get(&group)
+                    .cloned()
+                    .ok_or(TriggerError::UnknownCameraGroup(group))
+            }
+            None => Ok(self.cameras.clone()),
+        }
+    }
+}
+
+fn default_archive_segments() -> bool {
+    true
 }
 
 #[cfg(test)]
@@ -131,6 +233,10 @@ mod test {
             reason: "Something happened".into(),
             pre: Duration::from_secs(60),
             post: Duration::from_secs(120),
+            reason_template: None,
+            archive_segments: true,
+            camera_groups: Default::default(),
+            camera_group: None,
         };
 
         let cmd = TriggerCommand {
@@ -140,9 +246,12 @@ mod test {
             reason: None,
             pre: None,
             post: None,
+            template: None,
+            labels: Default::default(),
+            archive_segments: None,
         };
 
-        let trigger = Trigger::from_default_and_command(&default, &cmd);
+        let trigger = Trigger::from_default_and_command(&default, &cmd).unwrap();
 
         assert_eq!(
             trigger,
@@ -169,6 +278,10 @@ mod test {
             reason: "Something happened".into(),
             pre: Duration::from_secs(60),
             post: Duration::from_secs(120),
+            reason_template: None,
+            archive_segments: true,
+            camera_groups: Default::default(),
+            camera_group: None,
         };
 
         let time = Utc.with_ymd_and_hms(2022, 11, 20, 5, 30, 0).unwrap().into();
@@ -180,9 +293,12 @@ mod test {
             reason: Some("Something else happened".into()),
             pre: Some(Duration::from_secs(30)),
             post: Some(Duration::from_secs(60)),
+            template: None,
+            labels: Default::default(),
+            archive_segments: None,
         };
 
-        let trigger = Trigger::from_default_and_command(&default, &cmd);
+        let trigger = Trigger::from_default_and_command(&default, &cmd).unwrap();
 
         assert_eq!(
             trigger,
@@ -199,6 +315,225 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_from_default_and_command_metadata_only_command() {
+        let default = TriggerTemplate {
+            cameras: vec!["camera-1".into(), "camera-2".into()],
+            reason: "Something happened".into(),
+            pre: Duration::from_secs(60),
+            post: Duration::from_secs(120),
+            reason_template: None,
+            archive_segments: true,
+            camera_groups: Default::default(),
+            camera_group: None,
+        };
+
+        let cmd = TriggerCommand {
+            id: "door sensor".into(),
+            cameras: Some(vec!["camera-2".into()]),
+            archive_segments: Some(false),
+            ..Default::default()
+        };
+
+        let trigger = Trigger::from_default_and_command(&default, &cmd).unwrap();
+
+        assert!(trigger.cameras.is_empty());
+    }
+
+    #[test]
+    fn test_from_default_and_command_metadata_only_template() {
+        let default: TriggerTemplate = toml::from_str(
+            r#"
+cameras = ["camera-1", "camera-2"]
+reason = "Something happened"
+pre = 60
+post = 120
+archive_segments = false
+"#,
+        )
+        .unwrap();
+
+        let cmd = TriggerCommand {
+            id: "door sensor".into(),
+            ..Default::default()
+        };
+        assert!(Trigger::from_default_and_command(&default, &cmd)
+            .unwrap()
+            .cameras
+            .is_empty());
+
+        // The command can override the template
+        let cmd = TriggerCommand {
+            id: "door sensor".into(),
+            archive_segments: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            Trigger::from_default_and_command(&default, &cmd)
+                .unwrap()
+                .cameras,
+            vec!["camera-1".to_string(), "camera-2".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_from_default_and_command_reason_template() {
+        let default = TriggerTemplate {
+            cameras: vec!["camera-1".into()],
+            reason: "Motion".into(),
+            reason_template: Some("{reason} in {zone} ({confidence}%) from {id}".into()),
+            pre: Duration::from_secs(60),
+            post: Duration::from_secs(120),
+            archive_segments: true,
+            camera_groups: Default::default(),
+            camera_group: None,
+        };
+
+        let cmd = TriggerCommand {
+            id: "camera-1-motion".into(),
+            labels: HashMap::from([
+                ("zone".into(), "garden".into()),
+                ("confidence".into(), "87".into()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            Trigger::from_default_and_command(&default, &cmd)
+                .unwrap()
+                .reason,
+            "Motion in garden (87%) from camera-1-motion"
+        );
+
+        // The reason placeholder uses the reason from the command, if provided
+        let cmd = TriggerCommand {
+            id: "camera-1-motion".into(),
+            reason: Some("Person".into()),
+            labels: HashMap::from([
+                ("zone".into(), "garden".into()),
+                ("confidence".into(), "87".into()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            Trigger::from_default_and_command(&default, &cmd)
+                .unwrap()
+                .reason,
+            "Person in garden (87%) from camera-1-motion"
+        );
+    }
+
+    #[test]
+    fn test_from_default_and_command_reason_template_missing_label() {
+        let default = TriggerTemplate {
+            cameras: vec!["camera-1".into()],
+            reason: "Motion".into(),
+            reason_template: Some("{reason} in {zone} ({confidence}%)".into()),
+            pre: Duration::from_secs(60),
+            post: Duration::from_secs(120),
+            archive_segments: true,
+            camera_groups: Default::default(),
+            camera_group: None,
+        };
+
+        let cmd = TriggerCommand {
+            id: "camera-1-motion".into(),
+            labels: HashMap::from([("zone".into(), "garden".into())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            Trigger::from_default_and_command(&default, &cmd),
+            Err(TriggerError::MissingVariables(vec!["confidence".into()]))
+        );
+    }
+
+    fn camera_group_template() -> TriggerTemplate {
+        toml::from_str(
+            r#"
+reason = "Motion"
+reason_template = "{reason} in {zone}"
+camera_group = "{zone}"
+pre = 60
+post = 120
+
+[camera_groups]
+garden = ["camera-1", "camera-2"]
+driveway = ["camera-3"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_default_and_command_camera_group() {
+        let default = camera_group_template();
+
+        let cmd = TriggerCommand {
+            id: "motion".into(),
+            labels: HashMap::from([("zone".into(), "garden".into())]),
+            ..Default::default()
+        };
+        let trigger = Trigger::from_default_and_command(&default, &cmd).unwrap();
+        assert_eq!(trigger.reason, "Motion in garden");
+        assert_eq!(
+            trigger.cameras,
+            vec!["camera-1".to_string(), "camera-2".to_string()]
+        );
+
+        // Cameras in the command take precedence over the group
+        let cmd = TriggerCommand {
+            id: "motion".into(),
+            cameras: Some(vec!["camera-4".into()]),
+            labels: HashMap::from([("zone".into(), "garden".into())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            Trigger::from_default_and_command(&default, &cmd)
+                .unwrap()
+                .cameras,
+            vec!["camera-4".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_from_default_and_command_camera_group_missing_variable() {
+        let cmd = TriggerCommand {
+            id: "motion".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Trigger::from_default_and_command(&camera_group_template(), &cmd),
+            Err(TriggerError::MissingVariables(vec!["zone".into()]))
+        );
+    }
+
+    #[test]
+    fn test_from_default_and_command_unknown_camera_group() {
+        let cmd = TriggerCommand {
+            id: "motion".into(),
+            labels: HashMap::from([("zone".into(), "roof".into())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            Trigger::from_default_and_command(&camera_group_template(), &cmd),
+            Err(TriggerError::UnknownCameraGroup("roof".into()))
+        );
+    }
+
+    #[test]
+    fn test_template_archive_segments_default() {
+        let template: TriggerTemplate = toml::from_str(
+            r#"
+cameras = ["camera-1"]
+reason = "Something happened"
+pre = 60
+post = 120
+"#,
+        )
+        .unwrap();
+
+        assert!(template.archive_segments);
+    }
+
     #[test]
     fn test_wall_clock_times() {
         let t = Trigger {
@@ -221,4 +556,37 @@ mod test {
             Utc.with_ymd_and_hms(2022, 11, 20, 5, 32, 30).unwrap(),
         );
     }
+
+    #[test]
+    fn test_start_end_time_capped() {
+        let timestamp = Utc.with_ymd_and_hms(2022, 11, 20, 5, 30, 0).unwrap();
+
+        let t = Trigger {
+            metadata: EventMetadata {
+                id: "trig1".into(),
+                timestamp: timestamp.into(),
+            },
+            reason: "Something happened".into(),
+            cameras: vec!["camera-1".into()],
+            pre: Duration::MAX,
+            post: Duration::from_secs(u64::MAX),
+        };
+
+        assert_eq!(t.start_time(), timestamp - Trigger::MAX_PRE_POST);
+        assert_eq!(t.end_time(), timestamp + Trigger::MAX_PRE_POST);
+
+        // Times that are not representable fall back to the trigger timestamp
+        let t = Trigger {
+            metadata: EventMetadata {
+                id: "trig1".into(),
+                timestamp: DateTime::<Utc>::MAX_UTC.into(),
+            },
+            ..t
+        };
+        assert_eq!(
+            t.start_time(),
+            DateTime::<Utc>::MAX_UTC - Trigger::MAX_PRE_POST
+        );
+        assert_eq!(t.end_time(), DateTime::<Utc>::MAX_UTC);
+    }
 }
@@ -0,0 +1,133 @@
+use std::{fs::File, io::Write, path::Path};
+
+/// Writes a file such that either the previous or new contents are observed, never a partially
+/// written file, and such that the new contents are durable once this returns.
+///
+/// The data is written to a uniquely named hidden temporary file alongside `path`, synced to
+/// disk, then renamed over `path`, so concurrent writers never share a temporary file. The
+/// temporary file is removed if writing fails.
+pub fn write_file_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut prefix = std::ffi::OsString::from(".");
+    prefix.push(path.file_name().unwrap_or_default());
+    prefix.push(".");
+
+    let mut file = tempfile::Builder::new()
+        .prefix(&prefix)
+        .suffix(".tmp")
+        .tempfile_in(dir)?;
+    file.write_all(data)?;
+    file.as_file().sync_all()?;
+
+    file.persist(path).map_err(|err| err.error)?;
+
+    // The rename is only durable once the directory containing the file is synced
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn test_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        write_file_atomic(&path, b"one").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"one");
+
+        write_file_atomic(&path, b"two").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"two");
+
+        // The temporary file is not left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_failure_leaves_file_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        write_file_atomic(&path, b"one").unwrap();
+
+        // Renaming over a directory fails
+        std::fs::create_dir(dir.path().join("dir")).unwrap();
+        assert!(write_file_atomic(&dir.path().join("dir"), b"two").is_err());
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"one");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_no_partial_write_observed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let a = vec![b'a'; 1024 * 1024];
+        let b = vec![b'b'; 1024 * 1024];
+        write_file_atomic(&path, &a).unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+
+        let writer = {
+            let path = path.clone();
+            let done = done.clone();
+            let a = a.clone();
+            let b = b.clone();
+            std::thread::spawn(move || {
+                for i in 0..50 {
+                    write_file_atomic(&path, if i % 2 == 0 { &b } else { &a }).unwrap();
+                }
+                done.store(true, Ordering::Relaxed);
+            })
+        };
+
+        // Every read sees one complete version of the file
+        while !done.load(Ordering::Relaxed) {
+            let data = std::fs::read(&path).unwrap();
+            assert!(data == a || data == b, "partial file observed");
+        }
+
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let writers: Vec<_> = (0..8u8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let data = vec![i; 64 * 1024];
+                    for _ in 0..20 {
+                        write_file_atomic(&path, &data).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        // The file holds the complete content of one writer and no temporary files remain
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), 64 * 1024);
+        assert!(data.iter().all(|b| *b == data[0]));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,35 @@
+/// Checks that the value of an HTTP `Authorization` header presents `token` as a bearer token.
+///
+/// The comparison takes the same time regardless of how much of the token matches, so that the
+/// token cannot be guessed by timing requests.
+pub fn check_bearer_token(authorization: Option<&str>, token: &str) -> bool {
+    let presented = match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+        Some(presented) => presented.trim().as_bytes(),
+        None => return false,
+    };
+
+    let expected = token.as_bytes();
+
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_bearer_token() {
+        assert!(check_bearer_token(Some("Bearer secret"), "secret"));
+        assert!(!check_bearer_token(Some("Bearer secreT"), "secret"));
+        assert!(!check_bearer_token(Some("Bearer secret2"), "secret"));
+        assert!(!check_bearer_token(Some("Bearer "), "secret"));
+        assert!(!check_bearer_token(Some("Basic secret"), "secret"));
+        assert!(!check_bearer_token(Some("secret"), "secret"));
+        assert!(!check_bearer_token(None, "secret"));
+    }
+}
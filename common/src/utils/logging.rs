@@ -0,0 +1,144 @@
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider, Resource};
+use tracing_subscriber::{
+    filter::LevelFilter, fmt::MakeWriter, layer::SubscriberExt, util::SubscriberInitExt, Layer,
+};
+
+/// Environment variable that enables exporting traces via OTLP when set.
+const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Format of log output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human readable text
+    #[default]
+    Text,
+
+    /// One JSON object per line, including the fields of the spans the event was logged in
+    Json,
+}
+
+/// Keeps trace export running, flushing any spans that are yet to be exported when dropped.
+#[must_use]
+pub struct TracingGuard {
+    tracer_provider: Option<TracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            if let Err(err) = tracer_provider.shutdown() {
+                eprintln!("Failed to flush traces: {err}");
+            }
+        }
+    }
+}
+
+/// Installs the global tracing subscriber, logging to stdout in a given format.
+///
+/// If `OTEL_EXPORTER_OTLP_ENDPOINT` is set then spans are also exported via OTLP, attributed to
+/// `service_name`, and trace context propagation is enabled.
+pub fn init_tracing(service_name: &'static str, format: LogFormat) -> TracingGuard {
+    let fmt_layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => json_layer(std::io::stdout).boxed(),
+    };
+
+    let tracer_provider = std::env::var_os(OTLP_ENDPOINT_ENV)
+        .is_some()
+        .then(|| otlp_tracer_provider(service_name))
+        .flatten();
+
+    let otel_layer = tracer_provider.as_ref().map(|tracer_provider| {
+        tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(service_name))
+    });
+
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otel_layer)
+        .with(LevelFilter::INFO)
+        .init();
+
+    TracingGuard { tracer_provider }
+}
+
+fn json_layer<S, W>(writer: W) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_writer(writer)
+}
+
+fn otlp_tracer_provider(service_name: &'static str) -> Option<TracerProvider> {
+    // The endpoint and other exporter options are read from the standard environment variables
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(err) => {
+            eprintln!("Failed to create OTLP exporter, traces will not be exported: {err}");
+            return None;
+        }
+    };
+
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Some(
+        TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_includes_span_fields() {
+        let buffer = Buffer::default();
+        let subscriber = {
+            let buffer = buffer.clone();
+            tracing_subscriber::registry().with(json_layer(move || buffer.clone()))
+        };
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("archive", camera = "camera1");
+            let _guard = span.enter();
+            tracing::info!(segment = "one.ts", "Archiving segment");
+        });
+
+        let output = buffer.0.lock().unwrap().clone();
+        let line: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(line["fields"]["message"], "Archiving segment");
+        assert_eq!(line["fields"]["segment"], "one.ts");
+        assert_eq!(line["span"]["name"], "archive");
+        assert_eq!(line["span"]["camera"], "camera1");
+        assert_eq!(line["spans"][0]["camera"], "camera1");
+    }
+}
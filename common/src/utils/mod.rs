@@ -1,4 +1,19 @@
+mod atomic_file;
+mod bearer_token;
 mod config_file;
+mod logging;
+mod panic_hook;
+mod template;
 mod throttled_error;
+mod trace_context;
 
-pub use self::{config_file::load_config_file, throttled_error::ThrottledErrorLogger};
+pub(crate) use self::template::render_template;
+pub use self::{
+    atomic_file::write_file_atomic,
+    bearer_token::check_bearer_token,
+    config_file::load_config_file,
+    logging::{init_tracing, LogFormat, TracingGuard},
+    panic_hook::install_panic_hook,
+    throttled_error::{ErrorLogThrottle, ThrottledErrorLogger},
+    trace_context::{set_trace_parent, trace_context_headers},
+};
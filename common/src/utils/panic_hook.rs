@@ -0,0 +1,89 @@
+use std::{
+    backtrace::Backtrace,
+    fs::OpenOptions,
+    io::Write,
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+};
+use tracing::error;
+
+/// Installs a panic hook that logs panics, with a backtrace, before running the default panic
+/// hook.
+///
+/// If a crash file is given then the panic is also appended to it, so that it is not lost along
+/// with the output of a restarted service.
+pub fn install_panic_hook(crash_file: Option<PathBuf>) {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        let report = crash_report(info);
+        error!("{report}");
+
+        if let Some(crash_file) = &crash_file {
+            if let Err(e) = append_to_file(crash_file, &report) {
+                error!(
+                    "Failed to write panic to crash file {}, err={}",
+                    crash_file.display(),
+                    e
+                );
+            }
+        }
+
+        default_hook(info);
+    }));
+}
+
+fn crash_report(info: &PanicHookInfo) -> String {
+    let thread = std::thread::current();
+    let thread = thread.name().unwrap_or("<unnamed>");
+
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(s) => *s,
+        None => match info.payload().downcast_ref::<String>() {
+            Some(s) => s.as_str(),
+            None => "<non-string panic payload>",
+        },
+    };
+
+    let location = match info.location() {
+        Some(location) => location.to_string(),
+        None => "<unknown>".to_string(),
+    };
+
+    format!(
+        "{} panic in thread '{thread}' at {location}: {message}\n{}",
+        chrono::Utc::now().to_rfc3339(),
+        Backtrace::force_capture()
+    )
+}
+
+fn append_to_file(path: &Path, report: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{report}")?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_panic_in_task_written_to_crash_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let crash_file = dir.path().join("crash.log");
+
+        install_panic_hook(Some(crash_file.clone()));
+
+        let result = tokio::spawn(async {
+            panic!("something went very wrong");
+        })
+        .await;
+        assert!(result.unwrap_err().is_panic());
+
+        let _ = std::panic::take_hook();
+
+        let report = std::fs::read_to_string(&crash_file).unwrap();
+        assert!(report.contains("something went very wrong"));
+        assert!(report.contains("panic_hook.rs"));
+    }
+}
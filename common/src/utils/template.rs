@@ -0,0 +1,80 @@
+use regex::Regex;
+use std::collections::HashMap;
+
+lazy_static::lazy_static! {
+    /// Matches a `{name}` placeholder, capturing the name.
+    static ref PLACEHOLDER: Regex =
+        Regex::new(r"\{([A-Za-z0-9_-]+)\}").expect("placeholder regex should be valid");
+}
+
+/// Renders a template, replacing `{name}` placeholders with the matching value from `vars`.
+///
+/// Fails with the names of any placeholders that have no matching value, rather than producing
+/// output with missing data.
+pub(crate) fn render_template(
+    template: &str,
+    vars: &HashMap<&str, &str>,
+) -> Result<String, Vec<String>> {
+    let mut missing = Vec::new();
+
+    let rendered = PLACEHOLDER.replace_all(template, |captures: &regex::Captures| {
+        match vars.get(&captures[1]) {
+            Some(value) => value.to_string(),
+            None => {
+                if !missing.iter().any(|m| m == &captures[1]) {
+                    missing.push(captures[1].to_string());
+                }
+                captures[0].to_string()
+            }
+        }
+    });
+
+    if missing.is_empty() {
+        Ok(rendered.into_owned())
+    } else {
+        Err(missing)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let vars = HashMap::from([("zone", "garden"), ("confidence", "87")]);
+        assert_eq!(
+            render_template("Motion in {zone} ({confidence}%)", &vars).unwrap(),
+            "Motion in garden (87%)"
+        );
+    }
+
+    #[test]
+    fn test_render_template_repeated_placeholder() {
+        let vars = HashMap::from([("zone", "garden")]);
+        assert_eq!(
+            render_template("{zone}, {zone}", &vars).unwrap(),
+            "garden, garden"
+        );
+    }
+
+    #[test]
+    fn test_render_template_missing_value() {
+        let vars = HashMap::from([("zone", "garden")]);
+        assert_eq!(
+            render_template(
+                "Motion in {zone} ({confidence}%) by {sensor}, {sensor}",
+                &vars
+            ),
+            Err(vec!["confidence".to_string(), "sensor".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_render_template_no_placeholders() {
+        assert_eq!(
+            render_template("Something happened", &HashMap::new()).unwrap(),
+            "Something happened"
+        );
+    }
+}
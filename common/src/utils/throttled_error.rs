@@ -1,3 +1,4 @@
+use std::{collections::HashMap, hash::Hash};
 use tokio::time::{Duration, Instant};
 use tracing::warn;
 
@@ -66,6 +67,72 @@ impl<E: PartialEq + std::fmt::Display> ThrottledErrorLogger<E> {
     }
 }
 
+struct ThrottledSource {
+    last_logged: Instant,
+    suppressed: usize,
+}
+
+/// Throttles logging of errors by their source (e.g. a camera), so that a persistently failing
+/// source is logged at most once per interval rather than on every failure.
+///
+/// Unlike [`ThrottledErrorLogger`] the errors from a source do not need to be identical, e.g. they
+/// may each refer to a different segment.
+pub struct ErrorLogThrottle<K> {
+    interval: Duration,
+    sources: HashMap<K, ThrottledSource>,
+}
+
+impl<K: Eq + Hash> Default for ErrorLogThrottle<K> {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+impl<K: Eq + Hash> ErrorLogThrottle<K> {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Records an error from a source.
+    ///
+    /// Returns the number of errors from the source that were suppressed since one was last
+    /// logged if this error should be logged, otherwise `None`.
+    pub fn should_log(&mut self, source: K) -> Option<usize> {
+        let now = Instant::now();
+
+        match self.sources.get_mut(&source) {
+            Some(s) if now.duration_since(s.last_logged) < self.interval => {
+                s.suppressed += 1;
+                None
+            }
+            Some(s) => {
+                let suppressed = s.suppressed;
+                s.last_logged = now;
+                s.suppressed = 0;
+                Some(suppressed)
+            }
+            None => {
+                self.sources.insert(
+                    source,
+                    ThrottledSource {
+                        last_logged: now,
+                        suppressed: 0,
+                    },
+                );
+                Some(0)
+            }
+        }
+    }
+
+    /// Forgets a source once it has recovered, so that its next error is logged immediately.
+    pub fn reset(&mut self, source: &K) {
+        self.sources.remove(source);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -112,4 +179,29 @@ mod test {
         tokio::time::sleep(Duration::from_millis(95)).await;
         assert_eq!(te.log("test b".to_string()).unwrap(), "test b");
     }
+
+    #[tokio::test]
+    async fn throttle_per_source() {
+        let mut throttle = ErrorLogThrottle::new(Duration::from_millis(100));
+        assert_eq!(throttle.should_log("a"), Some(0));
+        assert_eq!(throttle.should_log("a"), None);
+        assert_eq!(throttle.should_log("a"), None);
+
+        // Other sources are not affected
+        assert_eq!(throttle.should_log("b"), Some(0));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(throttle.should_log("a"), Some(2));
+        assert_eq!(throttle.should_log("a"), None);
+    }
+
+    #[tokio::test]
+    async fn throttle_reset() {
+        let mut throttle = ErrorLogThrottle::new(Duration::from_secs(60));
+        assert_eq!(throttle.should_log("a"), Some(0));
+        assert_eq!(throttle.should_log("a"), None);
+
+        throttle.reset(&"a");
+        assert_eq!(throttle.should_log("a"), Some(0));
+    }
 }
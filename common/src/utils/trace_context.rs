@@ -0,0 +1,70 @@
+use opentelemetry::global;
+use std::collections::HashMap;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Headers that propagate the trace context of the current span to another service in an HTTP
+/// request.
+///
+/// Empty unless traces are exported, see [`crate::init_tracing`].
+pub fn trace_context_headers() -> HashMap<String, String> {
+    let context = tracing::Span::current().context();
+
+    let mut headers = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut headers));
+    headers
+}
+
+/// Sets the parent of a span to the trace context propagated by another service in the headers
+/// of an HTTP request, if there is one.
+pub fn set_trace_parent<'a>(
+    span: &tracing::Span,
+    headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+) {
+    let headers: HashMap<String, String> = headers
+        .into_iter()
+        .map(|(name, value)| (name.to_lowercase(), value.to_owned()))
+        .collect();
+
+    let context = global::get_text_map_propagator(|propagator| propagator.extract(&headers));
+    span.set_parent(context);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_propagation() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let tracer_provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let client_span = tracing::info_span!("client");
+            let headers = client_span.in_scope(trace_context_headers);
+
+            let traceparent = headers
+                .get("traceparent")
+                .expect("traceparent should be set");
+            let trace_id = client_span.context().span().span_context().trace_id();
+            assert!(traceparent.starts_with(&format!("00-{trace_id}-")));
+
+            let server_span = tracing::info_span!("server");
+            set_trace_parent(&server_span, [("Traceparent", traceparent.as_str())]);
+            assert_eq!(
+                server_span.context().span().span_context().trace_id(),
+                trace_id
+            );
+        });
+    }
+
+    #[test]
+    fn test_no_context() {
+        assert!(trace_context_headers().is_empty());
+    }
+}
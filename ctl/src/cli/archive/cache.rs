@@ -0,0 +1,61 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Directory, private to the current user, in which files are kept between invocations.
+///
+/// This is `satori/<name>` in `$XDG_CACHE_HOME`, or in `~/.cache` if that is not set, and is
+/// created if it does not exist.
+pub(super) fn user_cache_dir(name: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let base = match std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::var_os("HOME")
+            .filter(|d| !d.is_empty())
+            .map(|home| PathBuf::from(home).join(".cache"))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "cannot determine cache directory, neither XDG_CACHE_HOME nor HOME is set",
+                )
+            })?,
+    };
+
+    create_private_dir(&base.join("satori").join(name))
+}
+
+/// Creates a directory, and any missing parents, that only the current user can access.
+fn create_private_dir(dir: &Path) -> io::Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+    builder.create(dir)?;
+    Ok(dir.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_create_private_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("satori").join("export");
+
+        assert_eq!(create_private_dir(&dir).unwrap(), dir);
+
+        for dir in [&dir, dir.parent().unwrap()] {
+            let mode = std::fs::metadata(dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        // Creating an existing directory succeeds
+        assert!(create_private_dir(&dir).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
 use clap::Parser;
 use satori_storage::{Provider, StorageProvider};
 use std::path::PathBuf;
@@ -12,7 +12,7 @@ pub(crate) struct DeleteEventCommand {
 }
 
 impl DeleteEventCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
         for path in &self.file {
             let event = storage.get_event(path).await.map_err(|err| {
                 error!("{}", err);
@@ -21,6 +21,12 @@ impl DeleteEventCommand {
                 error!("{}", err);
             })?;
         }
+
+        // Only reached if every event was deleted
+        if output == OutputMode::Json {
+            super::output::print_json(&self.file)?;
+        }
+
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
 use clap::Parser;
 use satori_storage::{Provider, StorageProvider};
 use std::path::PathBuf;
@@ -15,7 +15,7 @@ pub(crate) struct DeleteSegmentCommand {
 }
 
 impl DeleteSegmentCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
         for path in &self.file {
             storage
                 .delete_segment(&self.camera, path)
@@ -24,6 +24,12 @@ impl DeleteSegmentCommand {
                     error!("{}", err);
                 })?;
         }
+
+        // Only reached if every segment was deleted
+        if output == OutputMode::Json {
+            super::output::print_json(&self.file)?;
+        }
+
         Ok(())
     }
 }
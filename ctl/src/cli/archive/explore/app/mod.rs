@@ -4,20 +4,21 @@ use self::panels::{
     camera_list::CameraListPanel, event_list::EventListPanel, trigger_list::TriggerListPanel,
     PanelOperations,
 };
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
 use ratatui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame, Terminal,
 };
-use satori_storage::Provider;
+use satori_storage::{EventNote, Provider, StorageProvider};
 use std::{
     io,
     sync::{Arc, Mutex},
 };
+use tracing::error;
 
 fn border_style(active: bool) -> Style {
     if active {
@@ -49,25 +50,46 @@ pub(super) async fn run<B: Backend>(terminal: &mut Terminal<B>, mut app: App) ->
         terminal.draw(|f| ui(f, &mut app))?;
 
         if let Event::Key(key) = event::read()? {
-            let result = match key.code {
-                KeyCode::Char('q') => KeyEventResult::Quit,
-                KeyCode::Esc => KeyEventResult::Quit,
+            // All keys are used for text input while a note is being edited
+            if app.note_input.is_some() {
+                app.handle_note_input_keys(key).await;
+                continue;
+            }
 
-                KeyCode::Tab => {
-                    app.tab();
-                    KeyEventResult::Noop
-                }
+            // Keys controlling a search of the event list take precedence over other keybinds
+            let result = if app.event_list.wants_key(&key) {
+                app.event_list.handle_keys(key).await
+            } else {
+                match key.code {
+                    KeyCode::Char('q') => KeyEventResult::Quit,
+                    KeyCode::Esc => KeyEventResult::Quit,
+
+                    KeyCode::Tab => {
+                        app.tab();
+                        KeyEventResult::Noop
+                    }
+
+                    KeyCode::Char('e') => {
+                        app.edit_note();
+                        KeyEventResult::Noop
+                    }
 
-                _ => {
-                    if app.event_list.active() {
-                        app.event_list.handle_keys(key).await
-                    } else if app.trigger_list.active() {
-                        app.trigger_list.handle_keys(key).await
-                    } else if app.camera_list.active() {
-                        app.camera_list.handle_keys(key).await
-                    } else {
+                    KeyCode::Char('p') => {
+                        app.preview().await;
                         KeyEventResult::Noop
                     }
+
+                    _ => {
+                        if app.event_list.active() {
+                            app.event_list.handle_keys(key).await
+                        } else if app.trigger_list.active() {
+                            app.trigger_list.handle_keys(key).await
+                        } else if app.camera_list.active() {
+                            app.camera_list.handle_keys(key).await
+                        } else {
+                            KeyEventResult::Noop
+                        }
+                    }
                 }
             };
 
@@ -90,30 +112,125 @@ pub(super) async fn run<B: Backend>(terminal: &mut Terminal<B>, mut app: App) ->
 }
 
 type SharedEvent = Arc<Mutex<Option<satori_common::Event>>>;
+type SharedNote = Arc<Mutex<Option<EventNote>>>;
 
 pub(crate) struct App {
+    storage: Provider,
+
     event_list: EventListPanel,
     trigger_list: TriggerListPanel,
     camera_list: CameraListPanel,
 
     selected_event: SharedEvent,
+    selected_event_note: SharedNote,
+
+    /// Text of the note being edited, if any
+    note_input: Option<String>,
+
+    /// Outcome of the last operation that does not otherwise show its result, if any
+    status: Option<String>,
 }
 
 impl App {
     pub(super) async fn new(storage: Provider) -> App {
         let selected_event = SharedEvent::default();
+        let selected_event_note = SharedNote::default();
 
-        let mut event_list = EventListPanel::new(selected_event.clone(), storage.clone());
+        let mut event_list = EventListPanel::new(
+            selected_event.clone(),
+            selected_event_note.clone(),
+            storage.clone(),
+        );
         event_list.refresh_events().await;
 
         App {
+            storage: storage.clone(),
             event_list,
             trigger_list: TriggerListPanel::new(selected_event.clone()),
             camera_list: CameraListPanel::new(selected_event.clone(), storage),
             selected_event,
+            selected_event_note,
+            note_input: None,
+            status: None,
+        }
+    }
+
+    fn edit_note(&mut self) {
+        if self.selected_event.lock().unwrap().is_some() {
+            self.note_input = Some(
+                self.selected_event_note
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map(|note| note.text.clone())
+                    .unwrap_or_default(),
+            );
+        }
+    }
+
+    async fn handle_note_input_keys(&mut self, key: KeyEvent) {
+        let input = self.note_input.as_mut().expect("a note should be edited");
+
+        match key.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.note_input = None,
+            KeyCode::Enter => {
+                let note = EventNote::new(self.note_input.take().unwrap());
+                self.save_note(note).await;
+            }
+            _ => {}
+        }
+    }
+
+    async fn save_note(&mut self, note: EventNote) {
+        let filename = match &*self.selected_event.lock().unwrap() {
+            Some(event) => event.metadata.get_filename(),
+            None => return,
+        };
+
+        match self.storage.put_event_note(&filename, &note).await {
+            Ok(()) => {
+                *self.selected_event_note.lock().unwrap() = Some(note);
+            }
+            Err(err) => {
+                error!("Failed to save note: {}", err);
+            }
         }
     }
 
+    /// Opens the video of the selected event in the system video player.
+    ///
+    /// The camera selected in the camera list is used if the camera list is active, otherwise the
+    /// event must only have a single camera.
+    async fn preview(&mut self) {
+        let Some(event) = self.selected_event.lock().unwrap().clone() else {
+            self.status = Some("No event selected".into());
+            return;
+        };
+
+        let camera_name = if self.camera_list.active() {
+            self.camera_list
+                .state
+                .state()
+                .selected()
+                .map(|i| event.cameras[i].name.clone())
+        } else {
+            None
+        };
+
+        self.status = Some(
+            match super::preview::preview_event_video(self.storage.clone(), &event, camera_name)
+                .await
+            {
+                Ok(filename) => format!("Opened {}", filename.display()),
+                Err(err) => err,
+            },
+        );
+    }
+
     fn tab(&mut self) {
         if self.event_list.active() {
             self.event_list.set_active(false);
@@ -136,11 +253,15 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     panels::event_list::render(f, app, rects[0]);
     render_right_pane(f, app, rects[1]);
+
+    if app.note_input.is_some() {
+        render_note_input(f, app, f.size());
+    }
 }
 
 fn render_right_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
-    let event_info_pane_height = 6;
-    let app_info_pane_height = 7;
+    let event_info_pane_height = 7;
+    let app_info_pane_height = 13;
 
     let remaining_height =
         area.bottom() - area.top() - event_info_pane_height - app_info_pane_height;
@@ -187,6 +308,17 @@ fn render_event_info_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rec
                     Span::raw("End       : "),
                     Span::raw(event.end.to_string()),
                 ]),
+                Line::from(vec![
+                    Span::raw("Note      : "),
+                    Span::raw(
+                        app.selected_event_note
+                            .lock()
+                            .unwrap()
+                            .as_ref()
+                            .map(|note| note.text.clone())
+                            .unwrap_or_default(),
+                    ),
+                ]),
             ]
         }
     };
@@ -198,7 +330,7 @@ fn render_event_info_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rec
     f.render_widget(info_text, area);
 }
 
-fn render_app_info_pane<B: Backend>(f: &mut Frame<B>, _: &mut App, area: Rect) {
+fn render_app_info_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let title = Line::from(vec![
         Span::styled("satorictl", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(" "),
@@ -211,6 +343,23 @@ fn render_app_info_pane<B: Backend>(f: &mut Frame<B>, _: &mut App, area: Rect) {
         Line::from(vec![Span::raw("j/Down, k/Up : scroll list")]),
         Line::from(vec![Span::raw("Home, End    : jump to start/end of list")]),
         Line::from(vec![Span::raw("l/Enter      : select")]),
+        Line::from(vec![Span::raw(
+            "e            : edit note of selected event",
+        )]),
+        Line::from(vec![Span::raw("d            : delete selected event")]),
+        Line::from(vec![Span::raw(
+            "p            : play video of selected event/camera",
+        )]),
+        Line::from(vec![Span::raw(
+            "/            : search events by ID or reason",
+        )]),
+        Line::from(vec![Span::raw(
+            "n/N, Esc     : next/previous match, clear search",
+        )]),
+        Line::from(vec![Span::styled(
+            app.status.clone().unwrap_or_default(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )]),
     ];
 
     let info_text = Paragraph::new(text)
@@ -219,3 +368,42 @@ fn render_app_info_pane<B: Backend>(f: &mut Frame<B>, _: &mut App, area: Rect) {
 
     f.render_widget(info_text, area);
 }
+
+fn render_note_input<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    let rects = Layout::default()
+        .constraints(
+            [
+                Constraint::Percentage(40),
+                Constraint::Length(5),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    let rects = Layout::default()
+        .constraints(
+            [
+                Constraint::Percentage(20),
+                Constraint::Percentage(60),
+                Constraint::Percentage(20),
+            ]
+            .as_ref(),
+        )
+        .direction(Direction::Horizontal)
+        .split(rects[1]);
+
+    let text = format!("{}_", app.note_input.as_deref().unwrap_or_default());
+
+    let input = Paragraph::new(text)
+        .style(Style::default())
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border_style(true))
+                .title("Note (Enter: save, Esc: cancel)"),
+        );
+
+    f.render_widget(Clear, rects[1]);
+    f.render_widget(input, rects[1]);
+}
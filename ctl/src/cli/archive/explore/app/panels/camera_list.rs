@@ -112,8 +112,12 @@ impl CameraListPanel {
         reset_terminal();
 
         if let Some((event, camera_name)) = doot {
-            let output_filename =
-                workflows::generate_video_filename(&event, camera_name.clone()).unwrap();
+            let output_filename = workflows::generate_video_filename(
+                &event,
+                camera_name.clone(),
+                workflows::VideoFormat::Ts,
+            )
+            .unwrap();
             info!("Saving to {}", output_filename.display());
             let mut file = File::create(&output_filename).unwrap();
 
@@ -121,6 +125,7 @@ impl CameraListPanel {
                 self.storage.clone(),
                 &event.metadata.get_filename(),
                 camera_name,
+                None,
             )
             .await
             .unwrap();
@@ -1,27 +1,55 @@
 use super::{
-    super::{border_style, highlight_style, App, KeyEventResult, SharedEvent},
+    super::{border_style, highlight_style, App, KeyEventResult, SharedEvent, SharedNote},
     PanelOperations,
 };
 use crate::cli::archive::explore::table_scroll::TableScrollState;
 use async_trait::async_trait;
 use crossterm::event::{KeyCode, KeyEvent};
+use futures::StreamExt;
 use ratatui::{
     backend::Backend,
-    layout::{Constraint, Rect},
+    layout::{Constraint, Layout, Rect},
     style::{Modifier, Style},
-    widgets::{Block, Borders, Cell, Row, Table},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table},
     Frame,
 };
 use rayon::prelude::*;
 use satori_common::EventMetadata;
 use satori_storage::{Provider, StorageProvider};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tracing::error;
+
+/// Number of events loaded at a time.
+const PAGE_SIZE: usize = 100;
+
+/// Number of events retrieved concurrently when loading event reasons for searching.
+const SEARCH_CONCURRENCY: usize = 16;
+
+struct Search {
+    query: String,
+    /// True while the query is being typed.
+    editing: bool,
+}
 
 pub(crate) struct EventListPanel {
     active: bool,
     storage: Provider,
     state: TableScrollState,
     event_metadata_cache: Vec<EventMetadata>,
+    /// Reasons of loaded events, only retrieved once a search is started.
+    event_reasons_cache: HashMap<PathBuf, Vec<String>>,
+    /// Indices into `event_metadata_cache` of the events that are listed.
+    visible: Vec<usize>,
+    search: Option<Search>,
+    /// Filename of the event awaiting confirmation of its deletion.
+    pending_delete: Option<PathBuf>,
+    /// Filename to load the next page of events after, `None` once all events are loaded.
+    next_page: Option<PathBuf>,
     selected_event: SharedEvent,
+    selected_event_note: SharedNote,
 }
 
 #[async_trait]
@@ -37,18 +65,28 @@ impl PanelOperations for EventListPanel {
     fn update(&mut self) {}
 
     async fn handle_keys(&mut self, event: KeyEvent) -> KeyEventResult {
+        if let Some(filename) = self.pending_delete.take() {
+            return self.handle_delete_confirmation_keys(event, filename).await;
+        }
+
+        if self.search.as_ref().is_some_and(|s| s.editing) {
+            self.handle_search_input_keys(event);
+            return KeyEventResult::Noop;
+        }
+
         match event.code {
             KeyCode::Home => {
                 self.state.home();
                 KeyEventResult::Noop
             }
             KeyCode::End => {
+                while self.load_next_page().await {}
                 self.state.end();
                 KeyEventResult::Noop
             }
 
             KeyCode::Char('j') => {
-                self.state.down();
+                self.down().await;
                 KeyEventResult::Noop
             }
             KeyCode::Char('k') => {
@@ -61,7 +99,7 @@ impl PanelOperations for EventListPanel {
             }
 
             KeyCode::Down => {
-                self.state.down();
+                self.down().await;
                 KeyEventResult::Noop
             }
             KeyCode::Up => {
@@ -73,61 +111,328 @@ impl PanelOperations for EventListPanel {
                 KeyEventResult::UpdateData
             }
 
+            KeyCode::Char('d') => {
+                self.pending_delete = self.selected_filename();
+                KeyEventResult::Noop
+            }
+
+            KeyCode::Char('/') => {
+                self.start_search().await;
+                KeyEventResult::Noop
+            }
+            KeyCode::Char('n') => {
+                self.down().await;
+                self.select().await;
+                KeyEventResult::UpdateData
+            }
+            KeyCode::Char('N') => {
+                self.state.up();
+                self.select().await;
+                KeyEventResult::UpdateData
+            }
+            KeyCode::Esc => {
+                self.clear_search();
+                KeyEventResult::Noop
+            }
+
             _ => KeyEventResult::Noop,
         }
     }
 }
 
 impl EventListPanel {
-    pub(crate) fn new(selected_event: SharedEvent, storage: Provider) -> Self {
+    pub(crate) fn new(
+        selected_event: SharedEvent,
+        selected_event_note: SharedNote,
+        storage: Provider,
+    ) -> Self {
         Self {
             active: true,
             storage,
             state: Default::default(),
             event_metadata_cache: Default::default(),
+            event_reasons_cache: Default::default(),
+            visible: Default::default(),
+            search: None,
+            pending_delete: None,
+            next_page: None,
             selected_event,
+            selected_event_note,
         }
     }
 
     pub(crate) async fn refresh_events(&mut self) {
         self.state.clear_data();
         *self.selected_event.lock().unwrap() = None;
+        *self.selected_event_note.lock().unwrap() = None;
 
-        if let Ok(events) = self.storage.list_events().await {
-            self.event_metadata_cache = events
-                .par_iter()
-                .map(|p| EventMetadata::from_filename(p))
-                .filter_map(|i| i.ok())
-                .collect();
+        self.event_metadata_cache.clear();
+        self.event_reasons_cache.clear();
+        self.visible.clear();
+        self.next_page = None;
+        self.load_page(None).await;
+    }
 
-            // Sort by timestamp, newest first
-            self.event_metadata_cache
-                .sort_by(|a, b| b.timestamp.partial_cmp(&a.timestamp).unwrap());
+    /// Loads the next page of events, returning true if a page was loaded.
+    async fn load_next_page(&mut self) -> bool {
+        match self.next_page.take() {
+            Some(start_after) => self.load_page(Some(start_after)).await,
+            None => false,
+        }
+    }
 
-            self.state.set_data_length(self.event_metadata_cache.len());
+    /// Loads a page of events, appending them to the list in the order they are listed by the
+    /// storage provider (oldest first).
+    async fn load_page(&mut self, start_after: Option<PathBuf>) -> bool {
+        match self
+            .storage
+            .list_events_paginated(start_after.as_deref(), PAGE_SIZE)
+            .await
+        {
+            Ok(page) => {
+                self.event_metadata_cache.extend(
+                    page.items
+                        .par_iter()
+                        .map(|p| EventMetadata::from_filename(p))
+                        .filter_map(|i| i.ok())
+                        .collect::<Vec<_>>(),
+                );
+                self.next_page = page.next;
+
+                if self.search.is_some() {
+                    self.load_event_reasons().await;
+                }
+                self.update_visible();
+                true
+            }
+            Err(err) => {
+                error!("Failed to list events: {}", err);
+                // Allow the failed page to be retried
+                self.next_page = start_after;
+                false
+            }
         }
     }
 
+    /// Moves the selection down, loading the next page of events when moving past the last
+    /// loaded event.
+    async fn down(&mut self) {
+        let at_end = self
+            .state
+            .state()
+            .selected()
+            .is_some_and(|i| i + 1 >= self.visible.len());
+
+        if at_end && self.next_page.is_some() {
+            self.load_next_page().await;
+        }
+
+        self.state.down();
+    }
+
+    /// True if a key should be handled by this panel before any application wide keybinds,
+    /// which is the case for all keys used to control a search or confirm a deletion.
+    pub(crate) fn wants_key(&self, event: &KeyEvent) -> bool {
+        if self.pending_delete.is_some() {
+            return true;
+        }
+
+        match &self.search {
+            Some(search) if search.editing => true,
+            Some(_) => {
+                self.active
+                    && matches!(
+                        event.code,
+                        KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N')
+                    )
+            }
+            None => false,
+        }
+    }
+
+    fn handle_search_input_keys(&mut self, event: KeyEvent) {
+        let search = self.search.as_mut().expect("a search should be edited");
+
+        match event.code {
+            KeyCode::Char(c) => {
+                search.query.push(c);
+                self.search_changed();
+            }
+            KeyCode::Backspace => {
+                search.query.pop();
+                self.search_changed();
+            }
+            KeyCode::Enter => {
+                if search.query.is_empty() {
+                    self.clear_search();
+                } else {
+                    search.editing = false;
+                }
+            }
+            KeyCode::Esc => self.clear_search(),
+            _ => {}
+        }
+    }
+
+    async fn start_search(&mut self) {
+        match &mut self.search {
+            Some(search) => search.editing = true,
+            None => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    editing: true,
+                })
+            }
+        }
+
+        self.load_event_reasons().await;
+    }
+
+    fn clear_search(&mut self) {
+        if self.search.take().is_some() {
+            self.search_changed();
+        }
+    }
+
+    /// Retrieves the reasons of loaded events that have not already been retrieved.
+    async fn load_event_reasons(&mut self) {
+        let filenames: Vec<PathBuf> = self
+            .event_metadata_cache
+            .iter()
+            .map(|metadata| metadata.get_filename())
+            .filter(|filename| !self.event_reasons_cache.contains_key(filename))
+            .collect();
+
+        let storage = &self.storage;
+        let events: Vec<_> = futures::stream::iter(filenames)
+            .map(|filename| async move {
+                let event = storage.get_event(&filename).await;
+                (filename, event)
+            })
+            .buffer_unordered(SEARCH_CONCURRENCY)
+            .collect()
+            .await;
+
+        for (filename, event) in events {
+            match event {
+                Ok(event) => {
+                    self.event_reasons_cache.insert(
+                        filename,
+                        event.reasons.into_iter().map(|r| r.reason).collect(),
+                    );
+                }
+                Err(err) => {
+                    error!("Failed to get event {}: {}", filename.display(), err);
+                }
+            }
+        }
+    }
+
+    fn matches_search(&self, metadata: &EventMetadata, query: &str) -> bool {
+        metadata.id.to_lowercase().contains(query)
+            || self
+                .event_reasons_cache
+                .get(&metadata.get_filename())
+                .is_some_and(|reasons| reasons.iter().any(|r| r.to_lowercase().contains(query)))
+    }
+
+    /// Updates the listed events to those matching the search, keeping the current selection
+    /// where possible.
+    fn update_visible(&mut self) {
+        let query = self
+            .search
+            .as_ref()
+            .map(|search| search.query.to_lowercase())
+            .unwrap_or_default();
+
+        self.visible = self
+            .event_metadata_cache
+            .iter()
+            .enumerate()
+            .filter(|(_, metadata)| query.is_empty() || self.matches_search(metadata, &query))
+            .map(|(i, _)| i)
+            .collect();
+
+        self.state.set_data_length(self.visible.len());
+    }
+
+    /// Updates the listed events after the search changed, selecting the first match.
+    fn search_changed(&mut self) {
+        self.state.clear_data();
+        self.update_visible();
+    }
+
+    fn selected_filename(&mut self) -> Option<PathBuf> {
+        self.state
+            .state()
+            .selected()
+            .map(|i| self.event_metadata_cache[self.visible[i]].get_filename())
+    }
+
+    /// Deletes the event awaiting confirmation if the deletion is confirmed, otherwise cancels
+    /// the deletion.
+    async fn handle_delete_confirmation_keys(
+        &mut self,
+        event: KeyEvent,
+        filename: PathBuf,
+    ) -> KeyEventResult {
+        if event.code != KeyCode::Char('y') {
+            return KeyEventResult::Noop;
+        }
+
+        if let Err(err) = self.storage.delete_event_filename(&filename).await {
+            error!("Failed to delete event {}: {}", filename.display(), err);
+            return KeyEventResult::Noop;
+        }
+
+        self.refresh_events().await;
+        KeyEventResult::UpdateData
+    }
+
     async fn select(&mut self) {
-        if let Some(i) = self.state.state().selected() {
-            *self.selected_event.lock().unwrap() = Some(
-                self.storage
-                    .get_event(&self.event_metadata_cache[i].get_filename())
-                    .await
-                    .unwrap(),
-            );
+        if let Some(filename) = self.selected_filename() {
+            *self.selected_event.lock().unwrap() =
+                Some(self.storage.get_event(&filename).await.unwrap());
+
+            *self.selected_event_note.lock().unwrap() =
+                match self.storage.get_event_note(&filename).await {
+                    Ok(note) => note,
+                    Err(err) => {
+                        error!("Failed to get note: {}", err);
+                        None
+                    }
+                };
         }
     }
 }
 
 pub(crate) fn render<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+    if let Some(filename) = &app.event_list.pending_delete {
+        let rects = Layout::default()
+            .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+            .split(area);
+        render_delete_confirmation(f, filename, rects[1]);
+    }
+
+    let area = match &app.event_list.search {
+        Some(search) => {
+            let rects = Layout::default()
+                .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+                .split(area);
+            render_search_input(f, search, rects[1]);
+            rects[0]
+        }
+        None => area,
+    };
+
     let header_cells = ["Timestamp", "ID"].iter().map(|h| Cell::from(*h));
 
     let header = Row::new(header_cells)
         .style(Style::default().add_modifier(Modifier::UNDERLINED))
         .height(1);
 
-    let rows = app.event_list.event_metadata_cache.iter().map(|item| {
+    let rows = app.event_list.visible.iter().map(|&i| {
+        let item = &app.event_list.event_metadata_cache[i];
         Row::new(vec![
             Cell::from(item.timestamp.to_string()),
             Cell::from(item.id.clone()),
@@ -137,16 +442,144 @@ pub(crate) fn render<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
     let active = app.event_list.active();
 
+    let title = match app.event_list.search {
+        Some(_) => format!("Events ({} matches)", app.event_list.visible.len()),
+        None => "Events".to_string(),
+    };
+
     let table = Table::new(rows)
         .header(header)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(border_style(active))
-                .title("Events"),
+                .title(title),
         )
         .highlight_style(highlight_style(active))
         .widths(&[Constraint::Percentage(40), Constraint::Percentage(60)]);
 
     f.render_stateful_widget(table, area, app.event_list.state.state());
 }
+
+fn render_delete_confirmation<B: Backend>(f: &mut Frame<B>, filename: &Path, area: Rect) {
+    let text = format!("Delete event {}? (y/n)", filename.display());
+
+    let confirmation = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(border_style(true))
+            .title("Confirm"),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(confirmation, area);
+}
+
+fn render_search_input<B: Backend>(f: &mut Frame<B>, search: &Search, area: Rect) {
+    let (text, title) = if search.editing {
+        (
+            format!("{}_", search.query),
+            "Search (Enter: done, Esc: clear)",
+        )
+    } else {
+        (
+            search.query.clone(),
+            "Search (n/N: next/previous match, Esc: clear)",
+        )
+    };
+
+    let input = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(border_style(search.editing))
+            .title(title),
+    );
+
+    f.render_widget(input, area);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use satori_common::Event;
+    use satori_storage::StorageConfig;
+
+    async fn build_test_panel() -> EventListPanel {
+        let storage: StorageConfig = serde_json::from_str(
+            r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+        )
+        .unwrap();
+        let storage = storage.create_provider();
+
+        for id in ["test-1", "test-2"] {
+            storage
+                .put_event(&Event {
+                    metadata: EventMetadata {
+                        id: id.into(),
+                        timestamp: Utc::now().into(),
+                    },
+                    start: Utc::now().into(),
+                    end: Utc::now().into(),
+                    reasons: Default::default(),
+                    cameras: Default::default(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut panel = EventListPanel::new(SharedEvent::default(), SharedNote::default(), storage);
+        panel.refresh_events().await;
+        panel
+    }
+
+    async fn press(panel: &mut EventListPanel, code: KeyCode) -> KeyEventResult {
+        panel.handle_keys(KeyEvent::from(code)).await
+    }
+
+    #[tokio::test]
+    async fn test_delete_requires_confirmation() {
+        let mut panel = build_test_panel().await;
+        let filename = panel.selected_filename().unwrap();
+
+        assert!(matches!(
+            press(&mut panel, KeyCode::Char('d')).await,
+            KeyEventResult::Noop
+        ));
+        assert_eq!(panel.pending_delete, Some(filename.clone()));
+        assert!(panel.wants_key(&KeyEvent::from(KeyCode::Char('q'))));
+        assert_eq!(panel.storage.list_events().await.unwrap().len(), 2);
+
+        // Any key other than confirmation cancels the deletion
+        assert!(matches!(
+            press(&mut panel, KeyCode::Char('n')).await,
+            KeyEventResult::Noop
+        ));
+        assert_eq!(panel.pending_delete, None);
+        assert_eq!(panel.storage.list_events().await.unwrap().len(), 2);
+
+        press(&mut panel, KeyCode::Char('d')).await;
+        assert!(matches!(
+            press(&mut panel, KeyCode::Char('y')).await,
+            KeyEventResult::UpdateData
+        ));
+        assert_eq!(panel.pending_delete, None);
+
+        let events = panel.storage.list_events().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(!events.contains(&filename));
+
+        // The list is refreshed
+        assert_eq!(panel.event_metadata_cache.len(), 1);
+        assert_eq!(panel.visible, vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_nothing_selected() {
+        let mut panel = build_test_panel().await;
+        panel.state.clear_data();
+
+        press(&mut panel, KeyCode::Char('d')).await;
+        assert_eq!(panel.pending_delete, None);
+    }
+}
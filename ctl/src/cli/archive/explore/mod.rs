@@ -1,4 +1,5 @@
 mod app;
+mod preview;
 mod table_scroll;
 
 use super::CliResult;
@@ -0,0 +1,66 @@
+use satori_common::Event;
+use satori_storage::{workflows, Provider};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::process::Command;
+use tracing::info;
+
+/// Exports the video of an event to a file in the user's cache directory and opens it in the
+/// system video player.
+///
+/// Returns the filename of the exported video.
+pub(super) async fn preview_event_video(
+    storage: Provider,
+    event: &Event,
+    camera_name: Option<String>,
+) -> Result<PathBuf, String> {
+    let (_, file_content) = workflows::export_event_video(
+        storage,
+        &event.metadata.get_filename(),
+        camera_name.clone(),
+        None,
+    )
+    .await
+    .map_err(|err| format!("Failed to export video: {err}"))?;
+
+    let filename =
+        workflows::generate_video_filename(event, camera_name, workflows::VideoFormat::Ts)
+            .map_err(|err| format!("Failed to generate filename: {err}"))?;
+    let filename = crate::cli::archive::cache::user_cache_dir("preview")
+        .map_err(|err| format!("Failed to create cache directory: {err}"))?
+        .join(filename);
+
+    info!("Saving preview to {}", filename.display());
+    tokio::fs::write(&filename, file_content)
+        .await
+        .map_err(|err| format!("Failed to save video: {err}"))?;
+
+    open_file(&filename)?;
+
+    Ok(filename)
+}
+
+/// Opens a file with the default application for its type.
+fn open_file(path: &Path) -> Result<(), String> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    let mut child = Command::new(opener)
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Failed to start {opener}: {err}"))?;
+
+    // Reap the opener once it exits, without blocking the UI
+    tokio::spawn(async move {
+        let _ = child.wait().await;
+    });
+
+    Ok(())
+}
@@ -33,19 +33,19 @@ impl TableScrollState {
     }
 
     pub fn home(&mut self) {
-        if self.data_length.is_some() {
+        if self.data_length.is_some_and(|l| l > 0) {
             self.state.select(Some(0));
         }
     }
 
     pub fn end(&mut self) {
-        if let Some(data_length) = self.data_length {
+        if let Some(data_length) = self.data_length.filter(|l| *l > 0) {
             self.state.select(Some(data_length - 1));
         }
     }
 
     pub fn scroll(&mut self, step: Step, wrap: bool) {
-        if let Some(data_length) = self.data_length {
+        if let Some(data_length) = self.data_length.filter(|l| *l > 0) {
             self.state.select(Some(match self.state.selected() {
                 Some(i) => match step {
                     Step::Up(step) => {
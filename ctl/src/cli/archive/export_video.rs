@@ -1,53 +1,606 @@
-use super::CliResult;
-use clap::Parser;
-use satori_storage::{workflows, Provider};
-use std::{fs::File, io::Write, path::PathBuf};
-use tracing::{error, info};
+use super::{output::OutputMode, CliResult};
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset};
+use clap::{Parser, ValueEnum};
+use satori_common::{CameraSegments, Event, SegmentFormat};
+use satori_storage::{
+    workflows::{self, VideoFormat},
+    Provider,
+};
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{error, info, warn};
 
-/// Exports a video file for a given event.
+/// Exports a video file for a given event, or of a camera for a given period of time.
 #[derive(Debug, Clone, Parser)]
 pub(crate) struct ExportVideoSubcommand {
     /// Name of the camera who's video should be exported.
     ///
-    /// Can be omitted for events containing a single camera.
+    /// Can be omitted for events containing a single camera, required when exporting a period of
+    /// time.
     #[arg(short, long)]
     camera: Option<String>,
 
+    /// Start of the period of time to export, as an RFC 3339 timestamp, instead of an event.
+    #[arg(
+        long,
+        value_parser = DateTime::parse_from_rfc3339,
+        requires_all = ["until", "camera"],
+        conflicts_with = "event"
+    )]
+    since: Option<DateTime<FixedOffset>>,
+
+    /// End of the period of time to export, as an RFC 3339 timestamp.
+    #[arg(long, value_parser = DateTime::parse_from_rfc3339, requires = "since")]
+    until: Option<DateTime<FixedOffset>>,
+
+    /// Format of the timestamp filenames of the camera's segments, as a strftime pattern, when
+    /// exporting a period of time.
+    ///
+    /// This must match the `segment_filename_format` of the camera if one is configured,
+    /// defaults to the format used by the agent for the type of segment.
+    #[arg(long, requires = "since")]
+    segment_filename_format: Option<String>,
+
+    /// Container format of the video.
+    ///
+    /// Formats other than that of the stored segments are remuxed using ffmpeg.
+    #[arg(short, long, value_enum, default_value_t = ExportFormat::Mp4)]
+    format: ExportFormat,
+
     /// Name of the output video file.
     #[arg(short, long)]
     output: Option<PathBuf>,
 
+    /// Export a time-lapse, sampling one frame per this interval of the video (e.g. "10s").
+    ///
+    /// The sampled frames are played back at 25 frames per second, so the video is sped up by
+    /// a factor of 25 times the interval in seconds (e.g. 250 times for an interval of 10s).
+    /// The video is re-encoded, so this is much slower than a regular export.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    timelapse: Option<Duration>,
+
+    /// Reuse the segments retrieved by a previous, interrupted, export of the same event or
+    /// period of time.
+    ///
+    /// With this or `--keep-temp`, segments are saved in a directory in the user's cache
+    /// directory as they are retrieved, so that an interrupted export can be resumed. Otherwise
+    /// segments are only held in memory.
+    #[arg(long)]
+    resume: bool,
+
+    /// Keep the directory of retrieved segments after a successful export.
+    #[arg(long)]
+    keep_temp: bool,
+
     /// Filename of the event to export.
-    event: PathBuf,
+    #[arg(required_unless_present = "since")]
+    event: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// MP4, with the index at the start of the file for faster playback.
+    Mp4,
+
+    /// Matroska.
+    Mkv,
+
+    /// MPEG-TS, MPEG-TS segments are concatenated as they are stored.
+    Ts,
+}
+
+impl From<ExportFormat> for VideoFormat {
+    fn from(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Mp4 => VideoFormat::Mp4,
+            ExportFormat::Mkv => VideoFormat::Mkv,
+            ExportFormat::Ts => VideoFormat::Ts,
+        }
+    }
 }
 
 impl ExportVideoSubcommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
-        let (event, file_content) =
-            workflows::export_event_video(storage, &self.event, self.camera.clone())
-                .await
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        // Segments are only saved on disk when they may be reused
+        let cache_dir = if self.resume || self.keep_temp {
+            let name = match (&self.event, &self.camera, self.since, self.until) {
+                (Some(event), camera, _, _) => segment_cache_dir_name(event, camera.as_deref()),
+                (None, Some(camera), Some(since), Some(until)) => {
+                    range_segment_cache_dir_name(camera, since, until)
+                }
+                _ => unreachable!("either an event or a camera and period of time are required"),
+            };
+            let cache_dir = super::cache::user_cache_dir("export")
                 .map_err(|err| {
-                    error!("{}", err);
+                    error!("Failed to create cache directory: {}", err);
+                })?
+                .join(name);
+
+            if !self.resume && cache_dir.exists() {
+                std::fs::remove_dir_all(&cache_dir).map_err(|err| {
+                    error!("Failed to remove {}: {}", cache_dir.display(), err);
                 })?;
+            }
+
+            Some(cache_dir)
+        } else {
+            None
+        };
+
+        let (bar, callback) = super::progress::progress_bar("Retrieving segments");
+
+        let result = match (&self.event, &self.camera, self.since, self.until) {
+            (Some(event), _, _, _) => match &cache_dir {
+                Some(cache_dir) => {
+                    workflows::export_event_video_resumable(
+                        storage,
+                        event,
+                        self.camera.clone(),
+                        cache_dir,
+                        Some(callback),
+                    )
+                    .await
+                }
+                None => {
+                    workflows::export_event_video(
+                        storage,
+                        event,
+                        self.camera.clone(),
+                        Some(callback),
+                    )
+                    .await
+                }
+            }
+            .map(|(event, file_content)| (Retrieved::Event(event), file_content)),
+            (None, Some(camera), Some(since), Some(until)) => workflows::export_camera_video(
+                storage,
+                camera,
+                since,
+                until,
+                self.segment_filename_format.as_deref(),
+                cache_dir.as_deref(),
+                Some(callback),
+            )
+            .await
+            .map(|(camera, file_content)| (Retrieved::Camera(camera, since), file_content)),
+            _ => unreachable!("either an event or a camera and period of time are required"),
+        };
+        bar.finish_and_clear();
+
+        let (retrieved, file_content) = result.map_err(|err| {
+            error!("{}", err);
+            match &cache_dir {
+                Some(cache_dir) => info!(
+                    "Segments retrieved so far are kept in {}, use --resume to reuse them",
+                    cache_dir.display()
+                ),
+                None => info!("Use --resume to keep retrieved segments for a later export"),
+            }
+        })?;
 
         // Use the user provided output filename if one exists, otherwise generate one.
-        let output_filename = match &self.output {
-            Some(filename) => filename.clone(),
-            None => {
-                workflows::generate_video_filename(&event, self.camera.clone()).map_err(|err| {
+        let output_filename = match (&self.output, &retrieved) {
+            (Some(filename), _) => filename.clone(),
+            (None, Retrieved::Event(event)) => {
+                workflows::generate_video_filename(event, self.camera.clone(), self.format.into())
+                    .map_err(|err| {
                     error!("{}", err);
                 })?
             }
+            (None, Retrieved::Camera(camera, since)) => {
+                workflows::generate_camera_video_filename(&camera.name, *since, self.format.into())
+            }
+        };
+
+        let source = match &retrieved {
+            Retrieved::Event(event) => source_format(event, self.camera.as_deref()),
+            Retrieved::Camera(camera, _) => segment_list_format(&camera.segment_list),
         };
 
         info!("Saving video: {}", output_filename.display());
-        let mut file = File::create(&output_filename).map_err(|err| {
-            error!("{}", err);
-        })?;
-        file.write_all(&file_content).map_err(|err| {
-            error!("{}", err);
+        match self.format.into() {
+            VideoFormat::Ts if source == SegmentFormat::Mpegts && self.timelapse.is_none() => {
+                let mut file = File::create(&output_filename).map_err(|err| {
+                    error!("{}", err);
+                })?;
+                file.write_all(&file_content).map_err(|err| {
+                    error!("{}", err);
+                })?;
+            }
+            format => {
+                run_ffmpeg(
+                    file_content,
+                    ffmpeg_args(source, format, self.timelapse, &output_filename),
+                )
+                .await?;
+            }
+        }
+
+        if let Some(cache_dir) = cache_dir {
+            if self.keep_temp {
+                info!("Keeping retrieved segments in {}", cache_dir.display());
+            } else if let Err(err) = std::fs::remove_dir_all(&cache_dir) {
+                warn!("Failed to remove {}: {}", cache_dir.display(), err);
+            }
+        }
+
+        if output == OutputMode::Json {
+            super::output::print_json(&serde_json::json!({ "filename": output_filename }))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Source of the segments of an export.
+enum Retrieved {
+    Event(Event),
+    /// Segments of a camera for a period of time, starting at the given time.
+    Camera(CameraSegments, DateTime<FixedOffset>),
+}
+
+/// Name of the directory in which the segments of an export are saved, which is the same for
+/// every export of the same event and camera so that an interrupted export can be resumed.
+fn segment_cache_dir_name(event: &Path, camera: Option<&str>) -> PathBuf {
+    let event = event.file_stem().unwrap_or(event.as_os_str());
+    let mut name = OsString::from("event_");
+    name.push(event);
+    if let Some(camera) = camera {
+        name.push("_");
+        name.push(camera);
+    }
+    name.into()
+}
+
+/// Name of the directory in which the segments of an export of a period of time are saved, see
+/// [`segment_cache_dir_name`].
+fn range_segment_cache_dir_name(
+    camera: &str,
+    since: DateTime<FixedOffset>,
+    until: DateTime<FixedOffset>,
+) -> PathBuf {
+    format!("range_{camera}_{}_{}", since.timestamp(), until.timestamp()).into()
+}
+
+/// Output frame rate of time-lapse videos.
+const TIMELAPSE_FRAME_RATE: u32 = 25;
+
+/// Format of the segments of the exported camera, assumed to be MPEG-TS if it cannot be
+/// determined.
+fn source_format(event: &Event, camera: Option<&str>) -> SegmentFormat {
+    event
+        .cameras
+        .iter()
+        .find(|c| camera.is_none_or(|name| c.name == name))
+        .map(|c| segment_list_format(&c.segment_list))
+        .unwrap_or_default()
+}
+
+/// Format of a list of segments, assumed to be MPEG-TS if it cannot be determined.
+fn segment_list_format(segment_list: &[PathBuf]) -> SegmentFormat {
+    segment_list
+        .iter()
+        .find_map(|s| SegmentFormat::from_path(s))
+        .unwrap_or_default()
+}
+
+/// Runs ffmpeg, providing concatenated segments as input.
+async fn run_ffmpeg(data: Bytes, args: Vec<OsString>) -> CliResult {
+    let mut ffmpeg_process = Command::new("ffmpeg")
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            error!("Failed to start ffmpeg: {}", err);
         })?;
 
+    let mut stdin = ffmpeg_process.stdin.take().unwrap();
+    stdin.write_all(&data).await.map_err(|err| {
+        error!("Failed to write video to ffmpeg: {}", err);
+    })?;
+    // Closing stdin signals the end of the input
+    drop(stdin);
+
+    let status = ffmpeg_process.wait().await.map_err(|err| {
+        error!("{}", err);
+    })?;
+
+    if status.success() {
         Ok(())
+    } else {
+        error!("ffmpeg failed: {}", status);
+        Err(())
+    }
+}
+
+/// Filter that keeps one frame per `interval` and retimes the kept frames to be consecutive at
+/// the time-lapse frame rate.
+fn timelapse_filter(interval: Duration) -> String {
+    format!(
+        "select='isnan(prev_selected_t)+gte(t-prev_selected_t,{})',setpts=N/{TIMELAPSE_FRAME_RATE}/TB",
+        interval.as_secs_f64()
+    )
+}
+
+/// Arguments for ffmpeg to convert concatenated segments of the `source` format into the
+/// requested format.
+///
+/// Fragmented MP4 segments are expected to be preceded by their initialisation section, as they
+/// are in an event.
+///
+/// Without a time-lapse the video is only remuxed, otherwise it must be re-encoded.
+fn ffmpeg_args(
+    source: SegmentFormat,
+    format: VideoFormat,
+    timelapse: Option<Duration>,
+    output: &Path,
+) -> Vec<OsString> {
+    let input_format = match source {
+        SegmentFormat::Mpegts => "mpegts",
+        SegmentFormat::Fmp4 => "mp4",
+    };
+
+    let mut args: Vec<OsString> = [
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        // Always overwrite files
+        "-y",
+        // Generate timestamps, segments may not be continuous
+        "-fflags",
+        "+genpts",
+        "-f",
+        input_format,
+        "-i",
+        "pipe:0",
+    ]
+    .into_iter()
+    .map(OsString::from)
+    .collect();
+
+    match timelapse {
+        Some(interval) => {
+            args.extend(["-vf".into(), timelapse_filter(interval).into()]);
+            args.extend(["-r", &TIMELAPSE_FRAME_RATE.to_string(), "-an"].map(OsString::from));
+        }
+        None => {
+            // Keep audio if the camera recorded any, without failing if it did not
+            args.extend(["-map", "0:v", "-map", "0:a?", "-c", "copy"].map(OsString::from));
+        }
+    }
+
+    match format {
+        VideoFormat::Mp4 => {
+            args.extend(["-movflags", "+faststart", "-f", "mp4"].map(OsString::from));
+        }
+        VideoFormat::Mkv => {
+            args.extend(["-f", "matroska"].map(OsString::from));
+        }
+        VideoFormat::Ts => {
+            args.extend(["-f", "mpegts"].map(OsString::from));
+        }
+    }
+
+    args.push(output.into());
+    args
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ffmpeg_args_mp4() {
+        let args = ffmpeg_args(
+            SegmentFormat::Mpegts,
+            VideoFormat::Mp4,
+            None,
+            Path::new("out.mp4"),
+        );
+
+        assert_eq!(
+            args,
+            [
+                "-hide_banner",
+                "-loglevel",
+                "error",
+                "-y",
+                "-fflags",
+                "+genpts",
+                "-f",
+                "mpegts",
+                "-i",
+                "pipe:0",
+                "-map",
+                "0:v",
+                "-map",
+                "0:a?",
+                "-c",
+                "copy",
+                "-movflags",
+                "+faststart",
+                "-f",
+                "mp4",
+                "out.mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ffmpeg_args_mkv() {
+        let args = ffmpeg_args(
+            SegmentFormat::Mpegts,
+            VideoFormat::Mkv,
+            None,
+            Path::new("out.mkv"),
+        );
+
+        assert_eq!(args[args.len() - 3..], ["-f", "matroska", "out.mkv"]);
+        assert!(!args.contains(&OsString::from("+faststart")));
+    }
+
+    #[test]
+    fn test_segment_cache_dir_name() {
+        assert_eq!(
+            segment_cache_dir_name(
+                Path::new("events/2023-01-01T00:00:00+00:00_test.json"),
+                None,
+            ),
+            Path::new("event_2023-01-01T00:00:00+00:00_test")
+        );
+
+        // The same event and camera always gives the same directory
+        assert_eq!(
+            segment_cache_dir_name(Path::new("test.json"), Some("camera1")),
+            segment_cache_dir_name(Path::new("test.json"), Some("camera1"))
+        );
+        assert_ne!(
+            segment_cache_dir_name(Path::new("test.json"), Some("camera1")),
+            segment_cache_dir_name(Path::new("test.json"), Some("camera2"))
+        );
+    }
+
+    #[test]
+    fn test_range_segment_cache_dir_name() {
+        let since = DateTime::parse_from_rfc3339("2023-01-01T14:00:00+00:00").unwrap();
+        let until = DateTime::parse_from_rfc3339("2023-01-01T15:00:00+00:00").unwrap();
+
+        assert_eq!(
+            range_segment_cache_dir_name("camera1", since, until),
+            Path::new("range_camera1_1672581600_1672585200")
+        );
+        assert_ne!(
+            range_segment_cache_dir_name("camera1", since, until),
+            range_segment_cache_dir_name("camera1", since, since)
+        );
+    }
+
+    #[test]
+    fn test_range_arguments() {
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            export: ExportVideoSubcommand,
+        }
+
+        let cli = Cli::try_parse_from([
+            "test",
+            "--camera",
+            "camera1",
+            "--since",
+            "2023-01-01T14:00:00+00:00",
+            "--until",
+            "2023-01-01T15:00:00+00:00",
+        ])
+        .unwrap();
+        assert!(cli.export.event.is_none());
+        assert!(cli.export.since.is_some());
+
+        // A camera is required
+        assert!(Cli::try_parse_from([
+            "test",
+            "--since",
+            "2023-01-01T14:00:00+00:00",
+            "--until",
+            "2023-01-01T15:00:00+00:00",
+        ])
+        .is_err());
+
+        // Either an event or a period of time is required, not both
+        assert!(Cli::try_parse_from(["test", "--camera", "camera1"]).is_err());
+        assert!(Cli::try_parse_from([
+            "test",
+            "--camera",
+            "camera1",
+            "--since",
+            "2023-01-01T14:00:00+00:00",
+            "--until",
+            "2023-01-01T15:00:00+00:00",
+            "event.json",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_timelapse_filter() {
+        assert_eq!(
+            timelapse_filter(Duration::from_secs(10)),
+            "select='isnan(prev_selected_t)+gte(t-prev_selected_t,10)',setpts=N/25/TB"
+        );
+        assert_eq!(
+            timelapse_filter(Duration::from_millis(500)),
+            "select='isnan(prev_selected_t)+gte(t-prev_selected_t,0.5)',setpts=N/25/TB"
+        );
+    }
+
+    #[test]
+    fn test_ffmpeg_args_timelapse() {
+        let args = ffmpeg_args(
+            SegmentFormat::Mpegts,
+            VideoFormat::Ts,
+            Some(Duration::from_secs(10)),
+            Path::new("out.ts"),
+        );
+
+        assert_eq!(
+            args[10..],
+            [
+                "-vf",
+                "select='isnan(prev_selected_t)+gte(t-prev_selected_t,10)',setpts=N/25/TB",
+                "-r",
+                "25",
+                "-an",
+                "-f",
+                "mpegts",
+                "out.ts",
+            ]
+        );
+        assert!(!args.contains(&OsString::from("copy")));
+    }
+
+    #[test]
+    fn test_ffmpeg_args_fmp4_source() {
+        let args = ffmpeg_args(
+            SegmentFormat::Fmp4,
+            VideoFormat::Ts,
+            None,
+            Path::new("out.ts"),
+        );
+
+        assert_eq!(args[6..10], ["-f", "mp4", "-i", "pipe:0"]);
+        assert_eq!(args[args.len() - 3..], ["-f", "mpegts", "out.ts"]);
+    }
+
+    #[test]
+    fn test_source_format() {
+        let event: Event = serde_json::from_value(serde_json::json!({
+            "metadata": {"id": "test", "timestamp": "2022-12-30T18:08:00+00:00"},
+            "start": "2022-12-30T18:07:00+00:00",
+            "end": "2022-12-30T18:09:00+00:00",
+            "reasons": [],
+            "cameras": [
+                {"name": "camera1", "segment_list": ["1.ts", "2.ts"]},
+                {"name": "camera2", "segment_list": ["init.mp4", "1.m4s"]},
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            source_format(&event, Some("camera1")),
+            SegmentFormat::Mpegts
+        );
+        assert_eq!(source_format(&event, Some("camera2")), SegmentFormat::Fmp4);
+        assert_eq!(
+            source_format(&event, Some("camera3")),
+            SegmentFormat::Mpegts
+        );
     }
 }
@@ -1,4 +1,4 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
 use clap::Parser;
 use satori_storage::{Provider, StorageProvider};
 use std::path::PathBuf;
@@ -12,14 +12,21 @@ pub(crate) struct GetEventCommand {
 }
 
 impl GetEventCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
-        let event = storage.get_event(&self.file).await;
-        println!(
-            "{:#?}",
-            event.map_err(|err| {
-                error!("{}", err);
-            })?
-        );
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let event = storage.get_event(&self.file).await.map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        match output {
+            OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+            OutputMode::Text => {
+                println!("{:#?}", event);
+            }
+            OutputMode::Json => {
+                super::output::print_json(&event)?;
+            }
+        }
+
         Ok(())
     }
 }
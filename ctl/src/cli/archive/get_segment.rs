@@ -1,28 +1,329 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
+use chrono::{DateTime, FixedOffset};
 use clap::Parser;
-use satori_storage::{Provider, StorageProvider};
-use std::path::PathBuf;
-use tracing::error;
+use satori_storage::{workflows, Provider, StorageProvider};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::{error, info};
 
-/// Retrieve a specific video segment for a given camera.
+/// Retrieve a specific video segment, or all segments in a period of time, for a given camera.
 #[derive(Debug, Clone, Parser)]
 pub(crate) struct GetSegmentCommand {
     /// Name of the camera.
     camera: String,
 
     /// File to retrieve.
-    file: PathBuf,
+    #[arg(
+        required_unless_present = "range_since",
+        conflicts_with = "range_since"
+    )]
+    file: Option<PathBuf>,
+
+    /// Retrieve all segments covering the period of time starting at this RFC 3339 timestamp.
+    #[arg(long, value_parser = DateTime::parse_from_rfc3339, requires = "range_until")]
+    range_since: Option<DateTime<FixedOffset>>,
+
+    /// End of the period of time to retrieve segments for, as an RFC 3339 timestamp.
+    #[arg(long, value_parser = DateTime::parse_from_rfc3339, requires = "range_since")]
+    range_until: Option<DateTime<FixedOffset>>,
+
+    /// Format of the timestamp filenames of the camera's segments, as a strftime pattern, when
+    /// retrieving a period of time.
+    ///
+    /// This must match the `segment_filename_format` of the camera if one is configured,
+    /// defaults to the format used by the agent for the type of segment.
+    #[arg(long, requires = "range_since")]
+    segment_filename_format: Option<String>,
+
+    /// Directory to save retrieved segments in, using their original filenames.
+    ///
+    /// Without this a single segment is printed and a range of segments is written to stdout,
+    /// concatenated in the order they were recorded.
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
 }
 
 impl GetSegmentCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
-        let event = storage.get_segment(&self.camera, &self.file).await;
-        println!(
-            "{:?}",
-            event.map_err(|err| {
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let segments = match (&self.file, self.range_since, self.range_until) {
+            (Some(file), _, _) => {
+                if self.output_dir.is_none() {
+                    return print_segment(storage, &self.camera, file, output).await;
+                }
+                vec![file.clone()]
+            }
+            (None, Some(since), Some(until)) => workflows::list_segments_between(
+                &storage,
+                &self.camera,
+                since,
+                until,
+                self.segment_filename_format.as_deref(),
+            )
+            .await
+            .map_err(|err| {
                 error!("{}", err);
-            })?
-        );
+            })?,
+            _ => unreachable!("either a file or a period of time is required"),
+        };
+        info!("Retrieving {} segments", segments.len());
+
+        match &self.output_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir).map_err(|err| {
+                    error!("Failed to create {}: {}", dir.display(), err);
+                })?;
+
+                let filenames = save_segments(storage, &self.camera, &segments, dir).await?;
+
+                if output == OutputMode::Json {
+                    super::output::print_json(&filenames)?;
+                }
+            }
+            None => {
+                write_segments(storage, &self.camera, &segments, &mut std::io::stdout()).await?;
+            }
+        }
+
         Ok(())
     }
 }
+
+async fn print_segment(
+    storage: Provider,
+    camera: &str,
+    file: &Path,
+    output: OutputMode,
+) -> CliResult {
+    let segment = storage.get_segment(camera, file).await.map_err(|err| {
+        error!("{}", err);
+    })?;
+
+    match output {
+        OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+        OutputMode::Text => {
+            println!("{:?}", segment);
+        }
+        OutputMode::Json => {
+            // Printed as an array of bytes
+            super::output::print_json(&segment)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves each segment in `dir`, retrieving one segment at a time, returning the saved filenames.
+async fn save_segments(
+    storage: Provider,
+    camera: &str,
+    segments: &[PathBuf],
+    dir: &Path,
+) -> Result<Vec<PathBuf>, ()> {
+    let mut filenames = Vec::with_capacity(segments.len());
+
+    for segment in segments {
+        let data = storage.get_segment(camera, segment).await.map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        let filename = dir.join(segment.file_name().unwrap_or(segment.as_os_str()));
+        std::fs::write(&filename, data).map_err(|err| {
+            error!("Failed to write {}: {}", filename.display(), err);
+        })?;
+        info!("Saved segment: {}", filename.display());
+
+        filenames.push(filename);
+    }
+
+    Ok(filenames)
+}
+
+/// Writes each segment to `out`, retrieving one segment at a time.
+async fn write_segments(
+    storage: Provider,
+    camera: &str,
+    segments: &[PathBuf],
+    out: &mut impl Write,
+) -> CliResult {
+    for segment in segments {
+        let data = storage.get_segment(camera, segment).await.map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        out.write_all(&data).map_err(|err| {
+            error!("{}", err);
+        })?;
+    }
+
+    out.flush().map_err(|err| {
+        error!("{}", err);
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+    use satori_storage::StorageConfig;
+
+    async fn build_test_storage() -> Provider {
+        let storage: StorageConfig = serde_json::from_str(
+            r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+        )
+        .unwrap();
+        let storage = storage.create_provider();
+
+        for segment in [
+            "2023-01-01T14_00_00+0000.ts",
+            "2023-01-01T14_00_06+0000.ts",
+            "2023-01-01T14_00_12+0000.ts",
+            "2023-01-01T14_00_18+0000.ts",
+        ] {
+            storage
+                .put_segment("camera1", Path::new(segment), Bytes::from(segment))
+                .await
+                .unwrap();
+        }
+
+        storage
+    }
+
+    #[tokio::test]
+    async fn test_range_to_output_dir() {
+        let storage = build_test_storage().await;
+        let dir = tempfile::tempdir().unwrap();
+
+        GetSegmentCommand::try_parse_from([
+            "get-segment",
+            "camera1",
+            "--range-since",
+            "2023-01-01T14:00:07+00:00",
+            "--range-until",
+            "2023-01-01T14:00:13+00:00",
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .unwrap()
+        .execute(storage, OutputMode::Text)
+        .await
+        .unwrap();
+
+        let mut files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            ["2023-01-01T14_00_06+0000.ts", "2023-01-01T14_00_12+0000.ts"]
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("2023-01-01T14_00_06+0000.ts")).unwrap(),
+            b"2023-01-01T14_00_06+0000.ts"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_range_custom_segment_filename_format() {
+        let storage = build_test_storage().await;
+        for segment in ["20230101-140000.ts", "20230101-140006.ts"] {
+            storage
+                .put_segment("camera2", Path::new(segment), Bytes::from(segment))
+                .await
+                .unwrap();
+        }
+        let dir = tempfile::tempdir().unwrap();
+
+        GetSegmentCommand::try_parse_from([
+            "get-segment",
+            "camera2",
+            "--range-since",
+            "2023-01-01T14:00:07+00:00",
+            "--range-until",
+            "2023-01-01T14:00:08+00:00",
+            "--segment-filename-format",
+            "%Y%m%d-%H%M%S.ts",
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .unwrap()
+        .execute(storage, OutputMode::Text)
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(dir.path().join("20230101-140006.ts").exists());
+    }
+
+    #[tokio::test]
+    async fn test_single_segment_to_output_dir() {
+        let storage = build_test_storage().await;
+        let dir = tempfile::tempdir().unwrap();
+
+        GetSegmentCommand::try_parse_from([
+            "get-segment",
+            "camera1",
+            "2023-01-01T14_00_18+0000.ts",
+            "--output-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .unwrap()
+        .execute(storage, OutputMode::Text)
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(dir.path().join("2023-01-01T14_00_18+0000.ts").exists());
+    }
+
+    #[tokio::test]
+    async fn test_write_segments() {
+        let storage = build_test_storage().await;
+
+        let segments = workflows::list_segments_between(
+            &storage,
+            "camera1",
+            DateTime::parse_from_rfc3339("2023-01-01T14:00:00+00:00").unwrap(),
+            DateTime::parse_from_rfc3339("2023-01-01T14:00:06+00:00").unwrap(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_segments(storage, "camera1", &segments, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(
+            out,
+            b"2023-01-01T14_00_00+0000.ts2023-01-01T14_00_06+0000.ts"
+        );
+    }
+
+    #[test]
+    fn test_arguments() {
+        // Either a file or a period of time is required, not both
+        assert!(GetSegmentCommand::try_parse_from(["get-segment", "camera1"]).is_err());
+        assert!(GetSegmentCommand::try_parse_from([
+            "get-segment",
+            "camera1",
+            "1.ts",
+            "--range-since",
+            "2023-01-01T14:00:00+00:00",
+            "--range-until",
+            "2023-01-01T14:00:06+00:00",
+        ])
+        .is_err());
+
+        // Both ends of the period are required
+        assert!(GetSegmentCommand::try_parse_from([
+            "get-segment",
+            "camera1",
+            "--range-since",
+            "2023-01-01T14:00:00+00:00",
+        ])
+        .is_err());
+    }
+}
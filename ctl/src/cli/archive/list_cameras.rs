@@ -1,4 +1,4 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
 use clap::Parser;
 use satori_storage::{Provider, StorageProvider};
 use tracing::error;
@@ -8,12 +8,23 @@ use tracing::error;
 pub(crate) struct ListCamerasCommand {}
 
 impl ListCamerasCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
-        for camera in storage.list_cameras().await.map_err(|err| {
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let cameras = storage.list_cameras().await.map_err(|err| {
             error!("{}", err);
-        })? {
-            println!("{camera}");
+        })?;
+
+        match output {
+            OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+            OutputMode::Text => {
+                for camera in cameras {
+                    println!("{camera}");
+                }
+            }
+            OutputMode::Json => {
+                super::output::print_json(&cameras)?;
+            }
         }
+
         Ok(())
     }
 }
@@ -1,19 +1,75 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
+use chrono::{DateTime, FixedOffset};
 use clap::Parser;
-use satori_storage::{Provider, StorageProvider};
+use satori_storage::{
+    workflows::{self, EventFilter},
+    Provider, StorageProvider,
+};
 use tracing::error;
 
-/// List all event metadata files.
+/// List event metadata files.
 #[derive(Debug, Clone, Parser)]
-pub(crate) struct ListEventsCommand {}
+pub(crate) struct ListEventsCommand {
+    /// Only list events with a timestamp at or after this time (RFC 3339).
+    #[arg(long, value_parser = DateTime::parse_from_rfc3339)]
+    since: Option<DateTime<FixedOffset>>,
+
+    /// Only list events with a timestamp at or before this time (RFC 3339).
+    #[arg(long, value_parser = DateTime::parse_from_rfc3339)]
+    until: Option<DateTime<FixedOffset>>,
+
+    /// Only list events that reference this camera.
+    ///
+    /// This requires retrieving every event in the time range.
+    #[arg(long)]
+    camera: Option<String>,
+
+    /// List at most this many events, oldest first.
+    #[arg(long)]
+    limit: Option<usize>,
+}
 
 impl ListEventsCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
-        for event_file in storage.list_events().await.map_err(|err| {
-            error!("{}", err);
-        })? {
-            println!("{}", event_file.display());
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let filter = EventFilter {
+            since: self.since,
+            until: self.until,
+            camera: self.camera.clone(),
+            limit: self.limit,
+        };
+
+        let event_files = workflows::list_events_filtered(storage.clone(), &filter)
+            .await
+            .map_err(|err| {
+                error!("{}", err);
+            })?;
+
+        match output {
+            OutputMode::Text => {
+                for event_file in event_files {
+                    println!("{}", event_file.display());
+                }
+            }
+            OutputMode::Json => {
+                super::output::print_json(&event_files)?;
+            }
+            // Includes the timestamp, ID and cameras of each event, which requires retrieving
+            // every event
+            OutputMode::Csv => {
+                let mut events = Vec::new();
+                for event_file in event_files {
+                    let event = storage.get_event(&event_file).await.map_err(|err| {
+                        error!("{}", err);
+                    })?;
+                    events.push((event_file, event));
+                }
+
+                super::output::write_events_csv(std::io::stdout(), &events).map_err(|err| {
+                    error!("{}", err);
+                })?;
+            }
         }
+
         Ok(())
     }
 }
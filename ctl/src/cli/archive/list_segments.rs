@@ -1,6 +1,6 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
 use clap::Parser;
-use satori_storage::{Provider, StorageProvider};
+use satori_storage::{Provider, StorageProvider, DEFAULT_PAGE_SIZE};
 use tracing::error;
 
 /// List video segment files for a given camera.
@@ -11,12 +11,46 @@ pub(crate) struct ListSegmentsCommand {
 }
 
 impl ListSegmentsCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
-        for segment_file in storage.list_segments(&self.camera).await.map_err(|err| {
-            error!("{}", err);
-        })? {
-            println!("{}", segment_file.display());
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let mut segment_files = Vec::new();
+        let mut start_after = None;
+
+        loop {
+            let page = storage
+                .list_segments_paginated(&self.camera, start_after.as_deref(), DEFAULT_PAGE_SIZE)
+                .await
+                .map_err(|err| {
+                    error!("{}", err);
+                })?;
+
+            // Plain text can be printed as each page arrives
+            if output == OutputMode::Text {
+                for segment_file in &page.items {
+                    println!("{}", segment_file.display());
+                }
+            } else {
+                segment_files.extend(page.items);
+            }
+
+            match page.next {
+                Some(next) => start_after = Some(next),
+                None => break,
+            }
         }
+
+        match output {
+            OutputMode::Text => {}
+            OutputMode::Json => {
+                super::output::print_json(&segment_files)?;
+            }
+            OutputMode::Csv => {
+                super::output::write_segments_csv(std::io::stdout(), &self.camera, &segment_files)
+                    .map_err(|err| {
+                        error!("{}", err);
+                    })?;
+            }
+        }
+
         Ok(())
     }
 }
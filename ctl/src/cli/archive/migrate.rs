@@ -0,0 +1,33 @@
+use super::CliResult;
+use clap::Parser;
+use satori_storage::{workflows, Provider, StorageConfig};
+use std::path::PathBuf;
+use tracing::error;
+
+/// Copies all events and segments to another archive.
+///
+/// Objects are re-encrypted using the encryption configuration of the destination archive.
+/// Objects that already exist in the destination are skipped, so an interrupted migration can be
+/// completed by running it again.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct MigrateCommand {
+    /// Path to the storage configuration of the archive to copy to.
+    #[arg(long)]
+    dest: PathBuf,
+
+    /// Number of parallel jobs to run
+    #[arg(short, long, default_value_t = 8)]
+    jobs: usize,
+}
+
+impl MigrateCommand {
+    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
+        let dest: StorageConfig = satori_common::load_config_file(&self.dest);
+
+        workflows::migrate(storage, dest.create_provider(), self.jobs)
+            .await
+            .map_err(|err| {
+                error!("{}", err);
+            })
+    }
+}
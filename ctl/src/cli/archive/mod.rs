@@ -1,3 +1,4 @@
+mod cache;
 mod delete_event;
 mod delete_segment;
 mod explore;
@@ -7,14 +8,22 @@ mod get_segment;
 mod list_cameras;
 mod list_events;
 mod list_segments;
+mod migrate;
+mod output;
+mod progress;
 mod prune_events;
 mod prune_segments;
+mod reencrypt;
+mod stats;
+mod verify;
 
+use self::output::OutputMode;
 use super::{CliExecute, CliResult, CliResultWithValue};
 use async_trait::async_trait;
 use clap::{Parser, Subcommand};
 use satori_storage::StorageConfig;
 use std::path::PathBuf;
+use tracing::error;
 
 /// Interact with an archive target.
 #[derive(Debug, Clone, Parser)]
@@ -23,6 +32,10 @@ pub(crate) struct ArchiveCommand {
     #[arg(long)]
     storage: PathBuf,
 
+    /// Format to print the result of the command in.
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputMode,
+
     #[command(subcommand)]
     command: ArchiveSubcommand,
 }
@@ -30,20 +43,29 @@ pub(crate) struct ArchiveCommand {
 #[async_trait]
 impl CliExecute for ArchiveCommand {
     async fn execute(&self) -> CliResult {
+        if self.output == OutputMode::Csv && !self.command.supports_csv() {
+            error!("CSV output is not supported by this command");
+            return Err(());
+        }
+
         let storage_config: StorageConfig = satori_common::load_config_file(&self.storage);
         let storage = storage_config.create_provider();
 
         match &self.command {
-            ArchiveSubcommand::ListEvents(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::ListCameras(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::ListSegments(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::GetEvent(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::GetSegment(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::DeleteEvent(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::DeleteSegment(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::PruneEvents(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::PruneSegments(cmd) => cmd.execute(storage).await,
-            ArchiveSubcommand::ExportVideo(cmd) => cmd.execute(storage).await,
+            ArchiveSubcommand::ListEvents(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::ListCameras(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::ListSegments(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::GetEvent(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::GetSegment(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::DeleteEvent(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::DeleteSegment(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::Migrate(cmd) => cmd.execute(storage).await,
+            ArchiveSubcommand::PruneEvents(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::PruneSegments(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::Reencrypt(cmd) => cmd.execute(storage).await,
+            ArchiveSubcommand::Stats(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::Verify(cmd) => cmd.execute(storage, self.output).await,
+            ArchiveSubcommand::ExportVideo(cmd) => cmd.execute(storage, self.output).await,
             ArchiveSubcommand::Explore(cmd) => cmd.execute(storage).await,
         }
     }
@@ -58,8 +80,52 @@ pub(crate) enum ArchiveSubcommand {
     GetSegment(get_segment::GetSegmentCommand),
     DeleteEvent(delete_event::DeleteEventCommand),
     DeleteSegment(delete_segment::DeleteSegmentCommand),
+    Migrate(migrate::MigrateCommand),
     PruneEvents(prune_events::PruneEventsCommand),
     PruneSegments(prune_segments::PruneSegmentsCommand),
+    Reencrypt(reencrypt::ReencryptCommand),
+    Stats(stats::StatsCommand),
+    Verify(verify::VerifyCommand),
     ExportVideo(export_video::ExportVideoSubcommand),
     Explore(explore::ExploreCommand),
 }
+
+impl ArchiveSubcommand {
+    /// If the command can print its result as CSV.
+    fn supports_csv(&self) -> bool {
+        matches!(self, Self::ListEvents(_) | Self::ListSegments(_))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_csv_output_rejected_when_unsupported() {
+        let storage = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(storage.path(), "kind = \"dummy\"").unwrap();
+        let storage = storage.path().to_str().unwrap();
+
+        for (command, supported) in [
+            ("list-events", true),
+            ("list-cameras", false),
+            ("stats", false),
+        ] {
+            let cmd = ArchiveCommand::try_parse_from([
+                "archive",
+                "--storage",
+                storage,
+                "--output",
+                "csv",
+                command,
+            ])
+            .unwrap();
+
+            assert_eq!(cmd.command.supports_csv(), supported);
+            if !supported {
+                assert!(cmd.execute().await.is_err());
+            }
+        }
+    }
+}
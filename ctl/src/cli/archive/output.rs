@@ -0,0 +1,163 @@
+use super::CliResult;
+use clap::ValueEnum;
+use satori_common::Event;
+use serde::Serialize;
+use std::{io::Write, path::PathBuf};
+use tracing::error;
+
+/// Format used to print the result of any command.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputMode {
+    /// Human readable text.
+    #[default]
+    Text,
+
+    /// A single JSON value, lists are printed as arrays.
+    Json,
+
+    /// Comma separated values, with a header row, only supported by commands that list events or
+    /// segments.
+    Csv,
+}
+
+pub(super) fn write_json<W: Write, T: Serialize + ?Sized>(
+    mut writer: W,
+    value: &T,
+) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writeln!(writer)
+}
+
+pub(super) fn print_json<T: Serialize + ?Sized>(value: &T) -> CliResult {
+    write_json(std::io::stdout(), value).map_err(|err| {
+        error!("{}", err);
+    })
+}
+
+pub(super) fn write_events_csv<W: Write>(
+    writer: W,
+    events: &[(PathBuf, Event)],
+) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    writer.write_record(["filename", "timestamp", "id", "cameras"])?;
+
+    for (filename, event) in events {
+        let cameras: Vec<&str> = event.cameras.iter().map(|c| c.name.as_str()).collect();
+
+        writer.write_record([
+            filename.display().to_string(),
+            event.metadata.timestamp.to_rfc3339(),
+            event.metadata.id.clone(),
+            cameras.join(","),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub(super) fn write_segments_csv<W: Write>(
+    writer: W,
+    camera: &str,
+    segments: &[PathBuf],
+) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    writer.write_record(["camera", "filename"])?;
+
+    for filename in segments {
+        writer.write_record([camera.to_owned(), filename.display().to_string()])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+    use satori_common::{CameraSegments, EventMetadata};
+
+    #[test]
+    fn test_events_csv_round_trip() {
+        let timestamp = FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2023, 3, 1, 12, 0, 0)
+            .unwrap();
+
+        let event = Event {
+            metadata: EventMetadata {
+                id: "front door".into(),
+                timestamp,
+            },
+            reasons: Default::default(),
+            start: timestamp,
+            end: timestamp,
+            cameras: vec![
+                CameraSegments {
+                    name: "camera1".into(),
+                    segment_list: Default::default(),
+                },
+                CameraSegments {
+                    name: "camera2".into(),
+                    segment_list: Default::default(),
+                },
+            ],
+        };
+        let filename = event.metadata.get_filename();
+
+        let mut output = Vec::new();
+        write_events_csv(&mut output, &[(filename.clone(), event)]).unwrap();
+
+        let mut reader = csv::Reader::from_reader(output.as_slice());
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["filename", "timestamp", "id", "cameras"]
+        );
+
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0],
+            vec![
+                filename.to_str().unwrap(),
+                "2023-03-01T12:00:00+00:00",
+                "front door",
+                "camera1,camera2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_list() {
+        let segments = vec![PathBuf::from("one.ts"), PathBuf::from("two.ts")];
+
+        let mut output = Vec::new();
+        write_json(&mut output, &segments).unwrap();
+
+        let parsed: Vec<String> = serde_json::from_slice(&output).unwrap();
+        assert_eq!(parsed, vec!["one.ts", "two.ts"]);
+    }
+
+    #[test]
+    fn test_segments_csv_round_trip() {
+        let segments = vec![PathBuf::from("one.ts"), PathBuf::from("two,three.ts")];
+
+        let mut output = Vec::new();
+        write_segments_csv(&mut output, "camera1", &segments).unwrap();
+
+        let mut reader = csv::Reader::from_reader(output.as_slice());
+        assert_eq!(reader.headers().unwrap(), vec!["camera", "filename"]);
+
+        let records: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(
+            records,
+            vec![
+                csv::StringRecord::from(vec!["camera1", "one.ts"]),
+                csv::StringRecord::from(vec!["camera1", "two,three.ts"]),
+            ]
+        );
+    }
+}
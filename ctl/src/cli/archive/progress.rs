@@ -0,0 +1,22 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use satori_storage::workflows::{Progress, ProgressCallback};
+use std::sync::Arc;
+
+/// Creates a progress bar, along with a callback that updates it with the progress of a workflow.
+pub(super) fn progress_bar(message: &'static str) -> (ProgressBar, ProgressCallback) {
+    let bar = ProgressBar::new(0).with_message(message).with_style(
+        ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len} ({eta})")
+            .expect("progress bar template should be valid")
+            .progress_chars("=> "),
+    );
+
+    let callback: ProgressCallback = {
+        let bar = bar.clone();
+        Arc::new(move |progress: Progress| {
+            bar.set_length(progress.total as u64);
+            bar.set_position(progress.processed as u64);
+        })
+    };
+
+    (bar, callback)
+}
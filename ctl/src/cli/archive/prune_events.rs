@@ -1,25 +1,228 @@
-use super::CliResult;
+use super::{output::OutputMode, CliResult};
 use chrono::{Duration, Utc};
-use clap::Parser;
+use clap::{ArgGroup, Parser};
 use satori_storage::{workflows, Provider};
+use std::path::PathBuf;
 use tracing::error;
 
 /// Removes events matching specific rules.
 #[derive(Debug, Clone, Parser)]
+#[command(group(ArgGroup::new("rules").required(true).multiple(true)))]
 pub(crate) struct PruneEventsCommand {
     /// Number of days worth of events to keep
+    #[arg(long, group = "rules")]
+    days: Option<i64>,
+
+    /// Maximum number of events to keep, the oldest events are removed first.
+    ///
+    /// Applied after events older than --days have been removed.
+    #[arg(long, group = "rules")]
+    max: Option<usize>,
+
+    /// Number of most recent events to keep for each camera.
+    ///
+    /// An event is kept if it is within the most recent events of any of the cameras it
+    /// references.
+    #[arg(long, group = "rules")]
+    keep_last: Option<usize>,
+
+    /// Only list the events that would be removed by any of the rules, do not remove them.
     #[arg(long)]
-    days: i64,
+    dry_run: bool,
 }
 
 impl PruneEventsCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
-        let time =
-            Utc::now() - Duration::try_days(self.days).expect("days range should be within limits");
-        workflows::prune_events_older_than(storage, time.into())
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let pruned = self.prune(storage).await?;
+
+        if self.dry_run {
+            match output {
+                OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+                OutputMode::Text => {
+                    for filename in pruned {
+                        println!("{}", filename.display());
+                    }
+                }
+                OutputMode::Json => {
+                    super::output::print_json(&pruned)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies every rule, returning the filenames of the events that were removed, or would be
+    /// removed in a dry run.
+    async fn prune(&self, storage: Provider) -> Result<Vec<PathBuf>, ()> {
+        let mut pruned = Vec::new();
+
+        if self.days.is_some() || self.max.is_some() {
+            let time = self.days.map(|days| {
+                (Utc::now() - Duration::try_days(days).expect("days range should be within limits"))
+                    .into()
+            });
+            pruned.extend(
+                workflows::prune_events_bounded(storage.clone(), time, self.max, self.dry_run)
+                    .await
+                    .map_err(|err| {
+                        error!("{}", err);
+                    })?,
+            );
+        }
+
+        if let Some(keep_last) = self.keep_last {
+            pruned.extend(
+                workflows::prune_events_keep_last(storage, keep_last, self.dry_run)
+                    .await
+                    .map_err(|err| {
+                        error!("{}", err);
+                    })?,
+            );
+        }
+
+        // An event may be removed by several rules in a dry run
+        pruned.sort();
+        pruned.dedup();
+
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use satori_common::{CameraSegments, Event, EventMetadata};
+    use satori_storage::{StorageConfig, StorageProvider};
+
+    async fn build_test_storage() -> Provider {
+        let storage: StorageConfig = serde_json::from_str(
+            r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+        )
+        .unwrap();
+        let storage = storage.create_provider();
+
+        for (id, days) in [("test-1", 10), ("test-2", 5), ("test-3", 0)] {
+            let timestamp = (Utc::now() - Duration::try_days(days).unwrap()).into();
+            storage
+                .put_event(&Event {
+                    metadata: EventMetadata {
+                        id: id.into(),
+                        timestamp,
+                    },
+                    start: timestamp,
+                    end: timestamp,
+                    reasons: Default::default(),
+                    cameras: vec![CameraSegments {
+                        name: "camera1".into(),
+                        segment_list: Default::default(),
+                    }],
+                })
+                .await
+                .unwrap();
+        }
+
+        storage
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let storage = build_test_storage().await;
+
+        PruneEventsCommand::try_parse_from(["prune-events", "--days", "7"])
+            .unwrap()
+            .execute(storage.clone(), OutputMode::Text)
             .await
-            .map_err(|err| {
-                error!("{}", err);
-            })
+            .unwrap();
+
+        assert_eq!(storage.list_events().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_prune_max() {
+        let storage = build_test_storage().await;
+
+        PruneEventsCommand::try_parse_from(["prune-events", "--max", "1"])
+            .unwrap()
+            .execute(storage.clone(), OutputMode::Text)
+            .await
+            .unwrap();
+
+        let events = storage.list_events().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].to_str().unwrap().ends_with("_test-3.json"));
+    }
+
+    #[tokio::test]
+    async fn test_prune_days_and_max() {
+        let storage = build_test_storage().await;
+
+        PruneEventsCommand::try_parse_from(["prune-events", "--days", "7", "--max", "1"])
+            .unwrap()
+            .execute(storage.clone(), OutputMode::Text)
+            .await
+            .unwrap();
+
+        let events = storage.list_events().await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].to_str().unwrap().ends_with("_test-3.json"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let storage = build_test_storage().await;
+
+        for args in [
+            vec!["prune-events", "--dry-run", "--days", "7"],
+            vec!["prune-events", "--dry-run", "--keep-last", "1"],
+            vec!["prune-events", "--dry-run", "--max", "1"],
+            vec![
+                "prune-events",
+                "--dry-run",
+                "--days",
+                "1",
+                "--keep-last",
+                "1",
+            ],
+        ] {
+            PruneEventsCommand::try_parse_from(args)
+                .unwrap()
+                .execute(storage.clone(), OutputMode::Json)
+                .await
+                .unwrap();
+
+            assert_eq!(storage.list_events().await.unwrap().len(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_matches_prune() {
+        for args in [
+            vec!["prune-events", "--days", "7"],
+            vec!["prune-events", "--days", "7", "--keep-last", "1"],
+            vec!["prune-events", "--max", "2", "--keep-last", "1"],
+        ] {
+            let storage = build_test_storage().await;
+            let before = storage.list_events().await.unwrap();
+
+            let would_prune = PruneEventsCommand::try_parse_from(args.iter().chain(&["--dry-run"]))
+                .unwrap()
+                .prune(storage.clone())
+                .await
+                .unwrap();
+            assert_eq!(storage.list_events().await.unwrap(), before);
+
+            PruneEventsCommand::try_parse_from(&args)
+                .unwrap()
+                .prune(storage.clone())
+                .await
+                .unwrap();
+
+            let after = storage.list_events().await.unwrap();
+            let mut pruned: Vec<PathBuf> =
+                before.into_iter().filter(|e| !after.contains(e)).collect();
+            pruned.sort();
+            assert_eq!(would_prune, pruned, "{args:?}");
+        }
     }
 }
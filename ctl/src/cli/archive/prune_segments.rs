@@ -1,4 +1,4 @@
-use super::{CliResult, CliResultWithValue};
+use super::{output::OutputMode, CliResult, CliResultWithValue};
 use clap::{Parser, Subcommand};
 use satori_storage::{workflows, Provider};
 use std::path::PathBuf;
@@ -18,7 +18,11 @@ pub(crate) struct PruneSegmentsCommand {
 #[derive(Debug, Clone, Subcommand)]
 pub(crate) enum PruneSegmentsAction {
     /// Calculate segments that are not referenced by any event and delete them
-    Prune,
+    Prune {
+        /// Only list the segments that would be removed, do not remove them.
+        #[arg(long)]
+        dry_run: bool,
+    },
 
     /// Calculate segments that are not referenced by any event and produce a report detailing them
     Report {
@@ -30,17 +34,25 @@ pub(crate) enum PruneSegmentsAction {
     Delete {
         /// Filename of the report to load
         report: PathBuf,
+
+        /// Only list the segments that would be removed, do not remove them.
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
 impl PruneSegmentsCommand {
-    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
         match &self.command {
-            PruneSegmentsAction::Prune => {
+            PruneSegmentsAction::Prune { dry_run } => {
                 let unreferenced_segments =
                     calculate_unrefeferenced_segments(storage.clone(), self.jobs).await?;
 
-                delete_unreferenced_segments(storage, unreferenced_segments, self.jobs).await
+                if *dry_run {
+                    print_unreferenced_segments(&unreferenced_segments, output)
+                } else {
+                    delete_unreferenced_segments(storage, unreferenced_segments, self.jobs).await
+                }
             }
             PruneSegmentsAction::Report { report } => {
                 let unreferenced_segments =
@@ -50,13 +62,17 @@ impl PruneSegmentsCommand {
                     error!("{}", err);
                 })
             }
-            PruneSegmentsAction::Delete { report } => {
+            PruneSegmentsAction::Delete { report, dry_run } => {
                 let unreferenced_segments =
                     workflows::UnreferencedSegments::load(report).map_err(|err| {
                         error!("{}", err);
                     })?;
 
-                delete_unreferenced_segments(storage, unreferenced_segments, self.jobs).await
+                if *dry_run {
+                    print_unreferenced_segments(&unreferenced_segments, output)
+                } else {
+                    delete_unreferenced_segments(storage, unreferenced_segments, self.jobs).await
+                }
             }
         }
     }
@@ -66,11 +82,34 @@ async fn calculate_unrefeferenced_segments(
     storage: Provider,
     jobs: usize,
 ) -> CliResultWithValue<workflows::UnreferencedSegments> {
-    workflows::calculate_unreferenced_segments(storage, jobs)
+    let (bar, callback) = super::progress::progress_bar("Processing events");
+
+    let result = workflows::calculate_unreferenced_segments(storage, jobs, Some(callback))
         .await
         .map_err(|err| {
             error!("{}", err);
-        })
+        });
+
+    bar.finish_and_clear();
+    result
+}
+
+fn print_unreferenced_segments(
+    segments: &workflows::UnreferencedSegments,
+    output: OutputMode,
+) -> CliResult {
+    match output {
+        OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+        OutputMode::Text => {
+            for (camera, segments) in segments.cameras() {
+                for segment in segments {
+                    println!("{camera}/{}", segment.display());
+                }
+            }
+            Ok(())
+        }
+        OutputMode::Json => super::output::print_json(segments),
+    }
 }
 
 async fn delete_unreferenced_segments(
@@ -84,3 +123,87 @@ async fn delete_unreferenced_segments(
             error!("{}", err);
         })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+    use satori_storage::{StorageConfig, StorageProvider};
+    use std::path::Path;
+
+    async fn build_test_storage() -> Provider {
+        let storage: StorageConfig = serde_json::from_str(
+            r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+        )
+        .unwrap();
+        let storage = storage.create_provider();
+
+        for segment in ["1.ts", "2.ts"] {
+            storage
+                .put_segment("camera1", Path::new(segment), Bytes::from("segment"))
+                .await
+                .unwrap();
+        }
+
+        storage
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let storage = build_test_storage().await;
+
+        PruneSegmentsCommand::try_parse_from(["prune-segments", "prune"])
+            .unwrap()
+            .execute(storage.clone(), OutputMode::Text)
+            .await
+            .unwrap();
+
+        assert!(storage.list_cameras().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prune_dry_run() {
+        let storage = build_test_storage().await;
+
+        for output in [OutputMode::Text, OutputMode::Json] {
+            PruneSegmentsCommand::try_parse_from(["prune-segments", "prune", "--dry-run"])
+                .unwrap()
+                .execute(storage.clone(), output)
+                .await
+                .unwrap();
+
+            assert_eq!(storage.list_segments("camera1").await.unwrap().len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_dry_run() {
+        let storage = build_test_storage().await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let report = dir.path().join("report.toml");
+
+        PruneSegmentsCommand::try_parse_from([
+            "prune-segments",
+            "report",
+            report.to_str().unwrap(),
+        ])
+        .unwrap()
+        .execute(storage.clone(), OutputMode::Text)
+        .await
+        .unwrap();
+
+        PruneSegmentsCommand::try_parse_from([
+            "prune-segments",
+            "delete",
+            "--dry-run",
+            report.to_str().unwrap(),
+        ])
+        .unwrap()
+        .execute(storage.clone(), OutputMode::Text)
+        .await
+        .unwrap();
+
+        assert_eq!(storage.list_segments("camera1").await.unwrap().len(), 2);
+    }
+}
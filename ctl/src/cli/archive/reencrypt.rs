@@ -0,0 +1,57 @@
+use super::CliResult;
+use clap::Parser;
+use satori_storage::{workflows, EncryptionConfig, Provider};
+use std::path::PathBuf;
+use tracing::error;
+
+/// Re-encrypts all events and segments, replacing the old encryption keys with new ones.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct ReencryptCommand {
+    /// Path to the encryption configuration the archive is currently encrypted with.
+    #[arg(long)]
+    old_key: PathBuf,
+
+    /// Path to the encryption configuration to re-encrypt the archive with.
+    #[arg(long)]
+    new_key: PathBuf,
+
+    /// Number of parallel jobs to run
+    #[arg(short, long, default_value_t = 8)]
+    jobs: usize,
+
+    /// File in which to record progress, allowing an interrupted run to be resumed.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Skip items recorded as complete in the checkpoint file, rather than starting over.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+}
+
+impl ReencryptCommand {
+    pub(super) async fn execute(&self, storage: Provider) -> CliResult {
+        let old_key: EncryptionConfig = satori_common::load_config_file(&self.old_key);
+        let new_key: EncryptionConfig = satori_common::load_config_file(&self.new_key);
+
+        let checkpoint = self
+            .checkpoint
+            .as_ref()
+            .map(|path| {
+                if self.resume {
+                    workflows::Checkpoint::resume(path)
+                } else {
+                    workflows::Checkpoint::new(path)
+                }
+            })
+            .transpose()
+            .map_err(|err| {
+                error!("{}", err);
+            })?;
+
+        workflows::reencrypt_archive(storage, old_key, new_key, self.jobs, checkpoint)
+            .await
+            .map_err(|err| {
+                error!("{}", err);
+            })
+    }
+}
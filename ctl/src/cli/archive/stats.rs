@@ -0,0 +1,107 @@
+use super::{output::OutputMode, CliResult};
+use clap::Parser;
+use satori_storage::{workflows, ObjectStats, Provider, StorageProvider};
+use tracing::error;
+
+/// Report statistics about the contents of the archive.
+///
+/// By default the number and stored size of events, of the segments of each camera and of the
+/// blobs holding the content of content addressed segments are reported.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct StatsCommand {
+    /// Report a histogram of segment sizes per camera and flag cameras whose recent segments are
+    /// anomalously small.
+    #[arg(long)]
+    segment_sizes: bool,
+
+    /// Number of most recent segments of each camera to compare against all of its segments.
+    #[arg(long, default_value_t = 10)]
+    recent: usize,
+
+    /// Flag a camera if the median size of its recent segments is less than this fraction of the
+    /// median size of all its segments.
+    #[arg(long, default_value_t = 0.5)]
+    threshold: f64,
+}
+
+impl StatsCommand {
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        if self.segment_sizes {
+            self.report_segment_sizes(storage, output).await
+        } else {
+            report_usage(storage, output).await
+        }
+    }
+
+    async fn report_segment_sizes(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let thresholds = workflows::SegmentSizeThresholds {
+            recent: self.recent,
+            min_ratio: self.threshold,
+        };
+
+        let reports = workflows::segment_size_stats(storage, thresholds)
+            .await
+            .map_err(|err| {
+                error!("{}", err);
+            })?;
+
+        match output {
+            OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+            OutputMode::Text => {
+                for report in reports {
+                    println!(
+                        "{}: {} segments, median size {} bytes, recent median size {} bytes{}",
+                        report.camera,
+                        report.count,
+                        report.median_size,
+                        report.recent_median_size,
+                        if report.anomalous { " (ANOMALOUS)" } else { "" },
+                    );
+                    for bucket in report.histogram {
+                        println!("  <= {:>12} bytes: {}", bucket.max_size, bucket.count);
+                    }
+                }
+            }
+            OutputMode::Json => {
+                super::output::print_json(&reports)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn report_usage(storage: Provider, output: OutputMode) -> CliResult {
+    let stats = storage.storage_stats().await.map_err(|err| {
+        error!("{}", err);
+    })?;
+
+    match output {
+        OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+        OutputMode::Text => {
+            fn print_row(name: &str, stats: &ObjectStats) {
+                println!("{:<24} {:>10} {:>16}", name, stats.count, stats.bytes);
+            }
+
+            println!("{:<24} {:>10} {:>16}", "", "objects", "bytes");
+            for (camera, camera_stats) in &stats.cameras {
+                print_row(camera, camera_stats);
+            }
+            print_row("(all segments)", &stats.segments());
+            print_row("(blobs)", &stats.blobs);
+            print_row("(events)", &stats.events);
+            print_row("(total)", &stats.total());
+        }
+        OutputMode::Json => {
+            super::output::print_json(&serde_json::json!({
+                "events": stats.events,
+                "cameras": stats.cameras,
+                "segments": stats.segments(),
+                "blobs": stats.blobs,
+                "total": stats.total(),
+            }))?;
+        }
+    }
+
+    Ok(())
+}
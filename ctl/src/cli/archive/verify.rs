@@ -0,0 +1,67 @@
+use super::{output::OutputMode, CliResult};
+use clap::Parser;
+use satori_storage::{workflows, Provider};
+use tracing::error;
+
+/// Check that every segment referenced by an event exists in the archive.
+///
+/// Exits with a non-zero status if any problems are found.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct VerifyCommand {
+    /// Number of parallel jobs used to verify events
+    #[arg(short, long, default_value_t = 8)]
+    jobs: usize,
+
+    /// Retrieve and decrypt every referenced segment, rather than only checking it exists
+    #[arg(long)]
+    deep: bool,
+}
+
+impl VerifyCommand {
+    pub(super) async fn execute(&self, storage: Provider, output: OutputMode) -> CliResult {
+        let report = workflows::verify_archive(storage, self.jobs, self.deep)
+            .await
+            .map_err(|err| {
+                error!("{}", err);
+            })?;
+
+        match output {
+            OutputMode::Csv => unreachable!("CSV output is rejected for this command"),
+            OutputMode::Text => {
+                println!("Verified {} events", report.events);
+
+                for filename in &report.unreadable_events {
+                    println!("Unreadable event: {}", filename.display());
+                }
+                for (camera, segments) in &report.missing_segments {
+                    println!(
+                        "Camera \"{camera}\" has {} missing segment(s):",
+                        segments.len()
+                    );
+                    for segment in segments {
+                        println!("  {}", segment.display());
+                    }
+                }
+                for (camera, segments) in &report.corrupt_segments {
+                    println!(
+                        "Camera \"{camera}\" has {} corrupt segment(s):",
+                        segments.len()
+                    );
+                    for segment in segments {
+                        println!("  {}", segment.display());
+                    }
+                }
+            }
+            OutputMode::Json => {
+                super::output::print_json(&report)?;
+            }
+        }
+
+        if report.is_ok() {
+            Ok(())
+        } else {
+            error!("Archive verification found problems");
+            Err(())
+        }
+    }
+}
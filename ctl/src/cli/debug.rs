@@ -6,7 +6,7 @@ use satori_common::{
     ArchiveCommand, ArchiveSegmentsCommand, Event, EventMetadata, Message, Trigger,
 };
 use std::{path::PathBuf, time::Duration};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 use url::Url;
 
 /// Debugging operations.
@@ -76,8 +76,23 @@ impl CliExecute for DebugCommand {
                         camera_name: cmd.camera.clone(),
                         camera_url: cmd.url.clone(),
                         segment_list: cmd.filename.clone(),
+                        segment_urls: Default::default(),
                     }));
 
+                let mut client = mqtt_client.client();
+                let topic = mqtt_client.topic();
+                client.publish_json(topic, &message).await;
+                mqtt_client.poll_until_message_is_sent().await;
+            }
+            DebugSubcommand::SendArchiveCommand(cmd) => {
+                let command: ArchiveCommand = std::fs::File::open(&cmd.file)
+                    .map_err(|err| err.to_string())
+                    .and_then(|file| serde_json::from_reader(file).map_err(|err| err.to_string()))
+                    .map_err(|err| {
+                        error!("Failed to read archive command, reason: {}", err);
+                    })?;
+                let message = Message::ArchiveCommand(command);
+
                 let mut client = mqtt_client.client();
                 let topic = mqtt_client.topic();
                 client.publish_json(topic, &message).await;
@@ -96,6 +111,7 @@ pub(crate) enum DebugSubcommand {
     DumpMessages,
     ArchiveEvent(DebugArchiveEventCommand),
     ArchiveSegments(DebugArchiveSegmentsCommand),
+    SendArchiveCommand(DebugSendArchiveCommandCommand),
 }
 
 /// Send a dummy event to listening archivers.
@@ -120,3 +136,12 @@ pub(crate) struct DebugArchiveSegmentsCommand {
     /// Filenames of segments to archive.
     filename: Vec<PathBuf>,
 }
+
+/// Send an arbitrary archive command to listening archivers.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct DebugSendArchiveCommandCommand {
+    /// JSON file containing the archive command to send.
+    ///
+    /// e.g. {"kind": "event_metadata", "data": { ... }}
+    file: PathBuf,
+}
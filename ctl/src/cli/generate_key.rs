@@ -0,0 +1,236 @@
+use super::{CliExecute, CliResult};
+use async_trait::async_trait;
+use clap::{Parser, ValueEnum};
+use satori_storage::{Aes256Gcm, Hpke, KeyEncoding};
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+use tracing::{error, info};
+
+/// Generate a new encryption key for use in a storage configuration.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct GenerateKeyCommand {
+    /// Kind of key to generate.
+    #[arg(long, value_enum, default_value_t = KeyKind::Aes256Gcm)]
+    kind: KeyKind,
+
+    /// Encoding of the key bytes, only applies to AES-256-GCM keys.
+    #[arg(long, value_enum, default_value_t = KeyFormat::Array)]
+    encoding: KeyFormat,
+
+    /// Format in which the key is output.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Toml)]
+    format: OutputFormat,
+
+    /// File to write the key to, instead of printing it.
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Overwrite the output file if it already exists.
+    #[arg(long, requires = "output")]
+    force: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum KeyKind {
+    /// Asymmetric encryption, the private key is only needed to retrieve data.
+    Hpke,
+
+    /// Symmetric encryption with a pre-shared key.
+    #[value(name = "aes256_gcm")]
+    Aes256Gcm,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum KeyFormat {
+    /// Array of byte values.
+    Array,
+
+    /// Base64 string.
+    Base64,
+
+    /// Hex string.
+    Hex,
+}
+
+impl From<KeyFormat> for KeyEncoding {
+    fn from(format: KeyFormat) -> Self {
+        match format {
+            KeyFormat::Array => KeyEncoding::Array,
+            KeyFormat::Base64 => KeyEncoding::Base64,
+            KeyFormat::Hex => KeyEncoding::Hex,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// TOML, as used in a storage configuration.
+    Toml,
+
+    /// JSON object.
+    Json,
+
+    /// Environment variable assignments, e.g. for a .env file.
+    Env,
+}
+
+/// Prefix of the names of environment variables when output in the env format.
+const ENV_PREFIX: &str = "SATORI_ENCRYPTION_";
+
+impl GenerateKeyCommand {
+    fn render(&self) -> Result<String, String> {
+        let toml = match self.kind {
+            KeyKind::Hpke => Hpke::generate().to_toml(),
+            KeyKind::Aes256Gcm => Aes256Gcm::generate().to_toml(self.encoding.into()),
+        };
+
+        match self.format {
+            OutputFormat::Toml => Ok(toml),
+            OutputFormat::Json => {
+                let table: toml::Table = toml::from_str(&toml).map_err(|err| err.to_string())?;
+                serde_json::to_string_pretty(&table)
+                    .map(|json| json + "\n")
+                    .map_err(|err| err.to_string())
+            }
+            OutputFormat::Env => {
+                let table: toml::Table = toml::from_str(&toml).map_err(|err| err.to_string())?;
+                table
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let value = match value {
+                            toml::Value::String(s) => s,
+                            value => {
+                                serde_json::to_string(&value).map_err(|err| err.to_string())?
+                            }
+                        };
+                        Ok(format!(
+                            "{ENV_PREFIX}{}=\"{}\"\n",
+                            name.to_uppercase(),
+                            value.replace('\\', "\\\\").replace('"', "\\\"")
+                        ))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn write(&self, path: &std::path::Path, data: &str) -> std::io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true);
+
+        // Never clobber an existing key unless asked to, it may be the only way to read an archive
+        if self.force {
+            options.create(true).truncate(true);
+        } else {
+            options.create_new(true);
+        }
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        options.open(path)?.write_all(data.as_bytes())
+    }
+}
+
+#[async_trait]
+impl CliExecute for GenerateKeyCommand {
+    async fn execute(&self) -> CliResult {
+        let key = self.render().map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        match &self.output {
+            Some(path) => {
+                self.write(path, &key).map_err(|err| {
+                    error!("Failed to write key to {}, reason: {}", path.display(), err);
+                })?;
+                info!("Key written to {}", path.display());
+            }
+            None => print!("{key}"),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use satori_storage::EncryptionKey;
+
+    fn command(kind: KeyKind, format: OutputFormat, output: PathBuf) -> GenerateKeyCommand {
+        GenerateKeyCommand {
+            kind,
+            encoding: KeyFormat::Base64,
+            format,
+            output: Some(output),
+            force: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_to_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for kind in [KeyKind::Hpke, KeyKind::Aes256Gcm] {
+            let path = dir.path().join(format!("{kind:?}.toml"));
+            command(kind, OutputFormat::Toml, path.clone())
+                .execute()
+                .await
+                .unwrap();
+
+            let key: EncryptionKey = toml::from_str(&std::fs::read_to_string(&path).unwrap())
+                .expect("generated TOML key should deserialize");
+            assert_eq!(
+                matches!(key, EncryptionKey::Hpke(_)),
+                matches!(kind, KeyKind::Hpke)
+            );
+
+            let path = dir.path().join(format!("{kind:?}.json"));
+            command(kind, OutputFormat::Json, path.clone())
+                .execute()
+                .await
+                .unwrap();
+
+            let key: EncryptionKey = serde_json::from_str(&std::fs::read_to_string(&path).unwrap())
+                .expect("generated JSON key should deserialize");
+            assert_eq!(
+                matches!(key, EncryptionKey::Hpke(_)),
+                matches!(kind, KeyKind::Hpke)
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_env() {
+        let cmd = GenerateKeyCommand {
+            kind: KeyKind::Aes256Gcm,
+            encoding: KeyFormat::Hex,
+            format: OutputFormat::Env,
+            output: None,
+            force: false,
+        };
+
+        let env = cmd.render().unwrap();
+        let lines: Vec<_> = env.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.contains(&"SATORI_ENCRYPTION_KIND=\"aes256_gcm\""));
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("SATORI_ENCRYPTION_KEY=\"")));
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key.toml");
+        std::fs::write(&path, "existing key").unwrap();
+
+        let mut cmd = command(KeyKind::Aes256Gcm, OutputFormat::Toml, path.clone());
+        assert!(cmd.execute().await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "existing key");
+
+        cmd.force = true;
+        cmd.execute().await.unwrap();
+        let _: EncryptionKey = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    }
+}
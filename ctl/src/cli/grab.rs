@@ -0,0 +1,219 @@
+use super::{CliExecute, CliResult, CliResultWithValue};
+use async_trait::async_trait;
+use bytes::Bytes;
+use clap::Parser;
+use futures::StreamExt;
+use satori_common::{
+    hls::{Playlist, PlaylistError, SegmentFile},
+    SegmentFormat,
+};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{error, info};
+use url::Url;
+
+/// Export the most recent footage retained by a live agent, without going through the archive.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct GrabCommand {
+    /// URL of the agent camera's HLS endpoint to grab footage from (e.g. "http://agent/camera/hls/").
+    #[arg(long)]
+    agent: Url,
+
+    /// Duration of footage to grab, counting back from the most recent segment (e.g. "10m").
+    #[arg(long, value_parser = humantime::parse_duration)]
+    last: Duration,
+
+    /// Name of the output video file.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Number of segments to download in parallel.
+    #[arg(short, long, default_value_t = 4)]
+    jobs: usize,
+}
+
+#[async_trait]
+impl CliExecute for GrabCommand {
+    async fn execute(&self) -> CliResult {
+        let playlist_url = playlist_url(&self.agent).map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        let http_client = reqwest::Client::new();
+
+        info!("Getting playlist: {}", playlist_url);
+        let playlist = http_client
+            .get(playlist_url.clone())
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|err| {
+                error!("{}", err);
+            })?
+            .bytes()
+            .await
+            .map_err(|err| {
+                error!("{}", err);
+            })?;
+        let playlist = m3u8_rs::parse_media_playlist_res(&playlist).map_err(|err| {
+            error!("Failed to parse playlist, reason: {}", err);
+        })?;
+        let playlist = parse_playlist(playlist).map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        let segments = playlist.last(self.last);
+        if segments.is_empty() {
+            error!("Agent has no segments");
+            return Err(());
+        }
+        info!("Grabbing {} segment(s)", segments.len());
+
+        let mut file = File::create(&self.output).map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        let urls = segment_urls(&playlist_url, &segments).map_err(|err| {
+            error!("{}", err);
+        })?;
+
+        // Segments are downloaded in parallel, but are still written in playlist order
+        let mut segment_data = futures::stream::iter(urls)
+            .map(|url| get_segment(&http_client, url))
+            .buffered(self.jobs.max(1));
+
+        while let Some(data) = segment_data.next().await {
+            file.write_all(&data?).map_err(|err| {
+                error!("{}", err);
+            })?;
+        }
+
+        info!("Saved video: {}", self.output.display());
+
+        Ok(())
+    }
+}
+
+/// Parses a playlist, with segment filenames in the default format for the type of segment the
+/// agent produces.
+fn parse_playlist(playlist: m3u8_rs::MediaPlaylist) -> Result<Playlist, PlaylistError> {
+    let format = playlist
+        .segments
+        .first()
+        .and_then(|s| SegmentFormat::from_path(Path::new(&s.uri)))
+        .unwrap_or_default();
+    Playlist::parse(playlist, format.segment_filename_format())
+}
+
+/// URLs of segments, each preceded by the initialisation section it needs to be decoded (if any)
+/// whenever that differs from the one of the previous segment.
+fn segment_urls(
+    playlist_url: &Url,
+    segments: &[&SegmentFile],
+) -> Result<Vec<Url>, url::ParseError> {
+    let mut urls = Vec::new();
+    let mut current_init = None;
+
+    for segment in segments {
+        if let Some(init_url) = segment.init_url(playlist_url).transpose()? {
+            if current_init.as_ref() != Some(&init_url) {
+                urls.push(init_url.clone());
+                current_init = Some(init_url);
+            }
+        }
+        urls.push(segment.url(playlist_url)?);
+    }
+
+    Ok(urls)
+}
+
+/// URL of the playlist of an agent camera's HLS endpoint.
+///
+/// The endpoint is a directory, so is treated as such whether or not it has a trailing slash.
+fn playlist_url(agent: &Url) -> Result<Url, url::ParseError> {
+    let mut agent = agent.clone();
+    if !agent.path().ends_with('/') {
+        agent.set_path(&format!("{}/", agent.path()));
+    }
+    agent.join("stream.m3u8")
+}
+
+async fn get_segment(http_client: &reqwest::Client, url: Url) -> CliResultWithValue<Bytes> {
+    info!("Getting segment: {}", url);
+    http_client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|err| {
+            error!("{}", err);
+        })?
+        .bytes()
+        .await
+        .map_err(|err| {
+            error!("{}", err);
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_segment_urls_init_sections() {
+        let playlist = parse_playlist(
+            m3u8_rs::parse_media_playlist_res(
+                indoc::indoc! {"
+                #EXTM3U
+                #EXT-X-VERSION:7
+                #EXT-X-TARGETDURATION:10
+                #EXT-X-MEDIA-SEQUENCE:0
+                #EXT-X-MAP:URI=\"init.mp4\"
+                #EXTINF:10.0,
+                2022-12-30T18_10_00+0000.m4s
+                #EXTINF:10.0,
+                2022-12-30T18_10_10+0000.m4s
+                #EXT-X-DISCONTINUITY
+                #EXT-X-MAP:URI=\"init2.mp4\"
+                #EXTINF:10.0,
+                2022-12-30T18_10_20+0000.m4s
+                "}
+                .as_bytes(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let segments: Vec<_> = playlist.segments.iter().collect();
+
+        let playlist_url = Url::parse("http://agent/camera1/hls/stream.m3u8").unwrap();
+
+        assert_eq!(
+            segment_urls(&playlist_url, &segments)
+                .unwrap()
+                .iter()
+                .map(Url::as_str)
+                .collect::<Vec<_>>(),
+            [
+                "http://agent/camera1/hls/init.mp4",
+                "http://agent/camera1/hls/2022-12-30T18_10_00+0000.m4s",
+                "http://agent/camera1/hls/2022-12-30T18_10_10+0000.m4s",
+                "http://agent/camera1/hls/init2.mp4",
+                "http://agent/camera1/hls/2022-12-30T18_10_20+0000.m4s",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playlist_url() {
+        for agent in ["http://agent/camera1/hls/", "http://agent/camera1/hls"] {
+            assert_eq!(
+                playlist_url(&Url::parse(agent).unwrap()).unwrap(),
+                Url::parse("http://agent/camera1/hls/stream.m3u8").unwrap()
+            );
+        }
+    }
+}
@@ -1,9 +1,12 @@
 mod archive;
 mod debug;
+mod generate_key;
+mod grab;
 mod trigger;
 
 use async_trait::async_trait;
 use clap::{Parser, Subcommand};
+use satori_common::LogFormat;
 
 pub(crate) type CliResultWithValue<T> = Result<T, ()>;
 pub(crate) type CliResult = CliResultWithValue<()>;
@@ -17,6 +20,10 @@ pub(crate) trait CliExecute {
 #[derive(Debug, Clone, Parser)]
 #[command(author, version = satori_common::version!(), about, long_about = None)]
 pub(crate) struct Cli {
+    /// Format of log output
+    #[arg(long, global = true, env = "LOG_FORMAT", value_enum, default_value_t)]
+    pub(crate) log_format: LogFormat,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -33,6 +40,8 @@ pub(crate) enum Command {
     Trigger(trigger::TriggerCommand),
     Archive(archive::ArchiveCommand),
     Debug(debug::DebugCommand),
+    GenerateKey(generate_key::GenerateKeyCommand),
+    Grab(grab::GrabCommand),
 }
 
 #[async_trait]
@@ -42,6 +51,8 @@ impl CliExecute for Command {
             Command::Trigger(cmd) => cmd.execute().await,
             Command::Archive(cmd) => cmd.execute().await,
             Command::Debug(cmd) => cmd.execute().await,
+            Command::GenerateKey(cmd) => cmd.execute().await,
+            Command::Grab(cmd) => cmd.execute().await,
         }
     }
 }
@@ -37,6 +37,19 @@ pub(crate) struct TriggerCommand {
     /// Time into the future.
     #[arg(long)]
     post: Option<u64>,
+
+    /// Name of the trigger template to use, instead of the one matching the ID.
+    #[arg(long)]
+    template: Option<String>,
+
+    /// Labels describing the trigger, used when rendering reason templates (in the form
+    /// "key=value").
+    #[arg(long = "label", value_parser = parse_label)]
+    labels: Vec<(String, String)>,
+
+    /// Only archive the event metadata, do not archive any segments.
+    #[arg(long)]
+    no_segments: bool,
 }
 
 #[async_trait]
@@ -52,6 +65,9 @@ impl CliExecute for TriggerCommand {
             reason: self.reason.clone(),
             pre: self.pre.map(Duration::from_secs),
             post: self.post.map(Duration::from_secs),
+            template: self.template.clone(),
+            labels: self.labels.iter().cloned().collect(),
+            archive_segments: self.no_segments.then_some(false),
         };
         let message = Message::TriggerCommand(trigger);
 
@@ -65,3 +81,9 @@ impl CliExecute for TriggerCommand {
         Ok(())
     }
 }
+
+fn parse_label(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("label \"{s}\" should be in the form \"key=value\""))
+}
@@ -5,8 +5,7 @@ use clap::Parser;
 
 #[tokio::main]
 async fn main() -> CliResult {
-    tracing_subscriber::fmt::init();
-
     let args = Cli::parse();
+    let _tracing = satori_common::init_tracing(env!("CARGO_PKG_NAME"), args.log_format);
     args.execute().await
 }
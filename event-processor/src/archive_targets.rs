@@ -0,0 +1,264 @@
+use crate::error::{EventProcessorError, EventProcessorResult};
+use satori_common::Event;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// An archiver, identified by the MQTT topic it receives archive commands on, that archives the
+/// video of a subset of cameras.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ArchiveTarget {
+    /// Topic that archive commands for the cameras are sent to.
+    topic: String,
+
+    /// Names of the cameras archived by this target.
+    cameras: Vec<String>,
+}
+
+/// Routes archive commands for each camera to the archivers that should store its video.
+///
+/// Cameras that are not part of any target are archived via the default topic, i.e. the topic of
+/// the MQTT client.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct ArchiveTargets(Vec<ArchiveTarget>);
+
+impl ArchiveTargets {
+    /// Checks that every target has a topic and only refers to known cameras.
+    pub(crate) fn validate<'a>(
+        &self,
+        known_cameras: impl IntoIterator<Item = &'a str>,
+    ) -> EventProcessorResult<()> {
+        let known_cameras: HashSet<&str> = known_cameras.into_iter().collect();
+        let mut problems = Vec::new();
+
+        for target in &self.0 {
+            if target.topic.trim().is_empty() {
+                problems.push("archive target topic must not be empty".to_owned());
+            }
+            if target.cameras.is_empty() {
+                problems.push(format!(
+                    "archive target \"{}\" has no cameras",
+                    target.topic
+                ));
+            }
+            for camera in &target.cameras {
+                if !known_cameras.contains(camera.as_str()) {
+                    problems.push(format!(
+                        "archive target \"{}\" refers to unknown camera \"{camera}\"",
+                        target.topic
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(EventProcessorError::InvalidArchiveTargets(problems))
+        }
+    }
+
+    /// Topics that archive commands for a camera are sent to.
+    pub(crate) fn camera_topics<'a>(
+        &'a self,
+        camera: &str,
+        default_topic: &'a str,
+    ) -> Vec<&'a str> {
+        let mut topics: Vec<&str> = Vec::new();
+        for target in self
+            .0
+            .iter()
+            .filter(|t| t.cameras.iter().any(|c| c == camera))
+        {
+            if !topics.contains(&target.topic.as_str()) {
+                topics.push(&target.topic);
+            }
+        }
+
+        if topics.is_empty() {
+            topics.push(default_topic);
+        }
+        topics
+    }
+
+    /// Splits an event into the event sent to each topic, each containing only the cameras
+    /// archived via that topic.
+    ///
+    /// Topics that archive none of the cameras of the event are omitted, events without any
+    /// cameras (i.e. metadata only events) are sent to the default topic.
+    pub(crate) fn split_event<'a>(
+        &'a self,
+        event: &Event,
+        default_topic: &'a str,
+    ) -> Vec<(&'a str, Event)> {
+        if event.cameras.is_empty() {
+            return vec![(default_topic, event.clone())];
+        }
+
+        let mut events: Vec<(&str, Event)> = Vec::new();
+
+        for camera in &event.cameras {
+            for topic in self.camera_topics(&camera.name, default_topic) {
+                let idx = match events.iter().position(|(t, _)| *t == topic) {
+                    Some(idx) => idx,
+                    None => {
+                        events.push((
+                            topic,
+                            Event {
+                                cameras: Vec::new(),
+                                ..event.clone()
+                            },
+                        ));
+                        events.len() - 1
+                    }
+                };
+                events[idx].1.cameras.push(camera.clone());
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use satori_common::{CameraSegments, EventMetadata};
+    use std::path::PathBuf;
+
+    fn targets() -> ArchiveTargets {
+        serde_json::from_value(serde_json::json!([
+            {"topic": "satori/archive/a", "cameras": ["camera1", "camera2"]},
+            {"topic": "satori/archive/b", "cameras": ["camera2", "camera3"]},
+        ]))
+        .unwrap()
+    }
+
+    fn event() -> Event {
+        let now = chrono::DateTime::parse_from_rfc3339("2023-01-01T14:00:00+00:00").unwrap();
+        Event {
+            metadata: EventMetadata {
+                id: "test".into(),
+                timestamp: now,
+            },
+            start: now,
+            end: now,
+            reasons: Vec::new(),
+            cameras: ["camera1", "camera2", "camera4"]
+                .into_iter()
+                .map(|name| CameraSegments {
+                    name: name.into(),
+                    segment_list: vec![PathBuf::from(format!("{name}.ts"))],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_no_targets_uses_default_topic() {
+        let targets = ArchiveTargets::default();
+
+        assert_eq!(targets.camera_topics("camera1", "satori"), ["satori"]);
+
+        let events = targets.split_event(&event(), "satori");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, "satori");
+        assert_eq!(events[0].1, event());
+    }
+
+    #[test]
+    fn test_camera_topics() {
+        let targets = targets();
+
+        // Only archived via target A, never via target B or the default topic
+        assert_eq!(
+            targets.camera_topics("camera1", "satori"),
+            ["satori/archive/a"]
+        );
+
+        // Part of both targets
+        assert_eq!(
+            targets.camera_topics("camera2", "satori"),
+            ["satori/archive/a", "satori/archive/b"]
+        );
+
+        // Not part of any target
+        assert_eq!(targets.camera_topics("camera4", "satori"), ["satori"]);
+    }
+
+    #[test]
+    fn test_split_event() {
+        let targets = targets();
+        let events = targets.split_event(&event(), "satori");
+
+        let cameras: Vec<(&str, Vec<&str>)> = events
+            .iter()
+            .map(|(topic, event)| {
+                (
+                    *topic,
+                    event.cameras.iter().map(|c| c.name.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            cameras,
+            [
+                ("satori/archive/a", vec!["camera1", "camera2"]),
+                ("satori/archive/b", vec!["camera2"]),
+                ("satori", vec!["camera4"]),
+            ]
+        );
+
+        // Everything other than the cameras is unchanged
+        for (_, split) in events {
+            assert_eq!(
+                Event {
+                    cameras: Vec::new(),
+                    ..split
+                },
+                Event {
+                    cameras: Vec::new(),
+                    ..event()
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_event_without_cameras() {
+        let event = Event {
+            cameras: Vec::new(),
+            ..event()
+        };
+
+        for targets in [ArchiveTargets::default(), targets()] {
+            assert_eq!(
+                targets.split_event(&event, "satori"),
+                [("satori", event.clone())]
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(targets()
+            .validate(["camera1", "camera2", "camera3"])
+            .is_ok());
+
+        match targets().validate(["camera1", "camera2"]) {
+            Err(EventProcessorError::InvalidArchiveTargets(problems)) => {
+                assert_eq!(
+                    problems,
+                    ["archive target \"satori/archive/b\" refers to unknown camera \"camera3\""]
+                );
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        let targets: ArchiveTargets = serde_json::from_value(serde_json::json!([
+            {"topic": " ", "cameras": []},
+        ]))
+        .unwrap();
+        assert!(targets.validate(["camera1"]).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{config::chrono_duration, error::EventProcessorResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ArchivedSegment {
+    camera: String,
+    segment: PathBuf,
+    timestamp: DateTime<Utc>,
+}
+
+/// Segments that have recently been sent for archiving.
+///
+/// Used to avoid archiving a segment again when it is rediscovered, e.g. after a failure to
+/// persist the event set meant that the segment was never recorded in the event.
+#[derive(Default)]
+pub(crate) struct ArchivedSegments {
+    /// Time each segment was archived at, keyed by camera and segment.
+    segments: HashMap<(String, PathBuf), DateTime<Utc>>,
+
+    window: chrono::Duration,
+    backing_file_name: Option<PathBuf>,
+}
+
+impl ArchivedSegments {
+    /// Loads the archived segments from disk, starting with none if they cannot be read.
+    ///
+    /// Fails only if the window cannot be represented.
+    #[tracing::instrument]
+    pub(crate) fn load_or_new(path: &Path, window: Duration) -> EventProcessorResult<Self> {
+        let mut s = Self {
+            segments: match Self::load(path) {
+                Ok(v) => v,
+                Err(err) => {
+                    warn!(
+                        "Failed to read archived segments file {}, reason: {}",
+                        path.display(),
+                        err
+                    );
+                    Default::default()
+                }
+            },
+            window: chrono_duration("archive_deduplication.window", window)?,
+            backing_file_name: Some(path.into()),
+        };
+        s.prune_expired(Utc::now());
+        Ok(s)
+    }
+
+    #[tracing::instrument]
+    fn load(path: &Path) -> EventProcessorResult<HashMap<(String, PathBuf), DateTime<Utc>>> {
+        let file = File::open(path)?;
+        let segments: Vec<ArchivedSegment> = serde_json::from_reader(&file)?;
+        Ok(segments
+            .into_iter()
+            .map(|s| ((s.camera, s.segment), s.timestamp))
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn save(&self, path: &Path) -> EventProcessorResult<()> {
+        let segments: Vec<ArchivedSegment> = self
+            .segments
+            .iter()
+            .map(|((camera, segment), timestamp)| ArchivedSegment {
+                camera: camera.clone(),
+                segment: segment.clone(),
+                timestamp: *timestamp,
+            })
+            .collect();
+        let data = serde_json::to_vec(&segments)?;
+        Ok(satori_common::write_file_atomic(path, &data)?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn attempt_save(&self) {
+        if let Some(path) = &self.backing_file_name {
+            if let Err(err) = self.save(path) {
+                error!(
+                    "Could not persist archived segments file {}, reason: {}. Segments may be archived again upon restart.",
+                    path.display(), err
+                );
+            }
+        }
+    }
+
+    /// Checks if a segment has been archived within the deduplication window.
+    pub(crate) fn contains(&self, camera: &str, segment: &Path) -> bool {
+        self.contains_at(camera, segment, Utc::now())
+    }
+
+    fn contains_at(&self, camera: &str, segment: &Path, now: DateTime<Utc>) -> bool {
+        self.segments
+            .get(&(camera.to_owned(), segment.to_owned()))
+            .is_some_and(|timestamp| !self.is_expired(*timestamp, now))
+    }
+
+    /// Records that segments have been sent for archiving.
+    pub(crate) fn insert(&mut self, camera: &str, segments: &[PathBuf]) {
+        self.insert_at(camera, segments, Utc::now());
+    }
+
+    fn insert_at(&mut self, camera: &str, segments: &[PathBuf], now: DateTime<Utc>) {
+        // Nothing is recorded when deduplication is disabled
+        if self.window.is_zero() {
+            return;
+        }
+
+        self.prune_expired(now);
+
+        for segment in segments {
+            self.segments
+                .insert((camera.to_owned(), segment.clone()), now);
+        }
+    }
+
+    fn is_expired(&self, timestamp: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        now - timestamp >= self.window
+    }
+
+    fn prune_expired(&mut self, now: DateTime<Utc>) {
+        let before = self.segments.len();
+
+        let window = self.window;
+        self.segments
+            .retain(|_, timestamp| now - *timestamp < window);
+
+        let pruned = before - self.segments.len();
+        if pruned > 0 {
+            info!(
+                "Forgot {} archived segment(s) outside of the window",
+                pruned
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disabled_by_default() {
+        let mut segments = ArchivedSegments::default();
+
+        segments.insert("camera1", &["one.ts".into()]);
+        assert!(!segments.contains("camera1", Path::new("one.ts")));
+    }
+
+    #[test]
+    fn test_keyed_by_camera_and_segment() {
+        let mut segments = ArchivedSegments {
+            window: chrono::Duration::seconds(60),
+            ..Default::default()
+        };
+
+        segments.insert("camera1", &["one.ts".into(), "two.ts".into()]);
+
+        assert!(segments.contains("camera1", Path::new("one.ts")));
+        assert!(segments.contains("camera1", Path::new("two.ts")));
+        assert!(!segments.contains("camera1", Path::new("three.ts")));
+        assert!(!segments.contains("camera2", Path::new("one.ts")));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut segments = ArchivedSegments {
+            window: chrono::Duration::seconds(60),
+            ..Default::default()
+        };
+
+        let now = Utc::now();
+        segments.insert_at("camera1", &["one.ts".into()], now);
+
+        assert!(segments.contains_at(
+            "camera1",
+            Path::new("one.ts"),
+            now + chrono::Duration::seconds(59)
+        ));
+        assert!(!segments.contains_at(
+            "camera1",
+            Path::new("one.ts"),
+            now + chrono::Duration::seconds(60)
+        ));
+
+        // Expired segments are forgotten when new segments are recorded
+        segments.insert_at(
+            "camera1",
+            &["two.ts".into()],
+            now + chrono::Duration::seconds(61),
+        );
+        assert_eq!(segments.segments.len(), 1);
+        assert!(segments
+            .segments
+            .contains_key(&("camera1".into(), "two.ts".into())));
+    }
+
+    #[test]
+    fn test_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archived_segments.json");
+
+        let mut segments = ArchivedSegments::load_or_new(&path, Duration::from_secs(60)).unwrap();
+        segments.insert("camera1", &["one.ts".into()]);
+        segments.attempt_save();
+
+        let segments = ArchivedSegments::load_or_new(&path, Duration::from_secs(60)).unwrap();
+        assert!(segments.contains("camera1", Path::new("one.ts")));
+
+        // Segments outside of the window are not loaded
+        let segments = ArchivedSegments::load_or_new(&path, Duration::ZERO).unwrap();
+        assert!(segments.segments.is_empty());
+    }
+
+    #[test]
+    fn test_window_out_of_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archived_segments.json");
+
+        assert!(matches!(
+            ArchivedSegments::load_or_new(&path, Duration::from_secs(u64::MAX)),
+            Err(crate::error::EventProcessorError::DurationOutOfRange(_))
+        ));
+    }
+}
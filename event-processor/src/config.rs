@@ -1,5 +1,10 @@
+use crate::{
+    archive_targets::ArchiveTargets,
+    error::{EventProcessorError, EventProcessorResult},
+};
 use satori_common::{
-    camera_config::CamerasConfig, mqtt::MqttConfig, Trigger, TriggerCommand, TriggerTemplate,
+    camera_config::CamerasConfig, mqtt::MqttConfig, Trigger, TriggerCommand, TriggerError,
+    TriggerTemplate,
 };
 use serde::Deserialize;
 use serde_with::{serde_as, DurationSeconds};
@@ -17,6 +22,32 @@ pub(crate) struct Config {
     #[serde_as(as = "DurationSeconds<u64>")]
     pub(crate) event_ttl: Duration,
 
+    /// Maximum duration of an event, repeated triggers will not extend an event beyond this.
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    #[serde(default)]
+    pub(crate) max_event_duration: Option<Duration>,
+
+    /// Triggers whose time range is within this duration of an existing event are merged into
+    /// it, even if they have a different ID. Disabled when not set.
+    #[serde_as(as = "Option<DurationSeconds<u64>>")]
+    #[serde(default)]
+    pub(crate) merge_window: Option<Duration>,
+
+    /// Repeated triggers with the same ID within this duration of an accepted trigger are dropped.
+    /// Disabled when zero.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[serde(default)]
+    pub(crate) trigger_debounce: Duration,
+
+    /// Avoids archiving segments again when they are rediscovered shortly after being archived.
+    #[serde(default)]
+    pub(crate) archive_deduplication: Option<ArchiveDeduplicationConfig>,
+
+    /// Archivers that only archive a subset of cameras, all other cameras are archived via the
+    /// MQTT topic.
+    #[serde(default)]
+    pub(crate) archive_targets: ArchiveTargets,
+
     pub(crate) mqtt: MqttConfig,
 
     #[serde(flatten)]
@@ -25,6 +56,26 @@ pub(crate) struct Config {
     pub(crate) triggers: TriggersConfig,
 }
 
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub(crate) struct ArchiveDeduplicationConfig {
+    /// File used to persist the segments that have recently been archived
+    pub(crate) file: PathBuf,
+
+    /// Duration for which an archived segment will not be archived again
+    #[serde_as(as = "DurationSeconds<u64>")]
+    pub(crate) window: Duration,
+}
+
+/// Converts a configured duration for use with timestamps, failing if it cannot be represented.
+pub(crate) fn chrono_duration(
+    name: &str,
+    duration: Duration,
+) -> EventProcessorResult<chrono::Duration> {
+    chrono::Duration::from_std(duration)
+        .map_err(|_| EventProcessorError::DurationOutOfRange(name.to_owned()))
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct TriggersConfig {
     /// Trigger configs that are used when a trigger with a specific ID are issued
@@ -33,23 +84,40 @@ pub(crate) struct TriggersConfig {
 
     /// Trigger defaults that are used when no matching template is found
     pub(crate) fallback: TriggerTemplate,
+
+    /// Convert trigger timestamps to UTC, rather than keeping the offset they were provided with
+    #[serde(default)]
+    pub(crate) force_utc: bool,
 }
 
 impl TriggersConfig {
     #[tracing::instrument(skip(self))]
-    pub(crate) fn create_trigger(&self, cmd: &TriggerCommand) -> Trigger {
-        let template = match self.templates.get(&cmd.id) {
-            Some(t) => {
-                info!("Found predefined template for ID \"{}\"", cmd.id);
-                t
-            }
-            None => {
-                info!("No template matches ID \"{}\", using fallback", cmd.id);
-                &self.fallback
-            }
+    pub(crate) fn create_trigger(&self, cmd: &TriggerCommand) -> Result<Trigger, TriggerError> {
+        let template = match &cmd.template {
+            // A template requested by name must exist, falling back would hide a misconfiguration
+            Some(name) => self
+                .templates
+                .get(name)
+                .ok_or_else(|| TriggerError::UnknownTemplate(name.clone()))?,
+            None => match self.templates.get(&cmd.id) {
+                Some(t) => {
+                    info!("Found predefined template for ID \"{}\"", cmd.id);
+                    t
+                }
+                None => {
+                    info!("No template matches ID \"{}\", using fallback", cmd.id);
+                    &self.fallback
+                }
+            },
         };
 
-        Trigger::from_default_and_command(template, cmd)
+        let mut trigger = Trigger::from_default_and_command(template, cmd)?;
+
+        if self.force_utc {
+            trigger.metadata.timestamp = trigger.metadata.timestamp.to_utc().fixed_offset();
+        }
+
+        Ok(trigger)
     }
 }
 
@@ -68,7 +136,12 @@ mod test {
                 reason: "Something happened".into(),
                 pre: Duration::from_secs(60),
                 post: Duration::from_secs(120),
+                reason_template: None,
+                archive_segments: true,
+                camera_groups: Default::default(),
+                camera_group: None,
             },
+            force_utc: false,
         };
 
         let time = Utc.with_ymd_and_hms(2022, 11, 20, 5, 30, 0).unwrap().into();
@@ -80,6 +153,9 @@ mod test {
             reason: Some("reason".into()),
             pre: None,
             post: None,
+            template: None,
+            labels: Default::default(),
+            archive_segments: None,
         };
 
         assert_eq!(
@@ -93,7 +169,7 @@ mod test {
                 pre: Duration::from_secs(60),
                 post: Duration::from_secs(120),
             },
-            config.create_trigger(&cmd)
+            config.create_trigger(&cmd).unwrap()
         );
     }
 
@@ -108,6 +184,10 @@ mod test {
                         reason: "Something happened - 1".into(),
                         pre: Duration::from_secs(60),
                         post: Duration::from_secs(30),
+                        reason_template: None,
+                        archive_segments: true,
+                        camera_groups: Default::default(),
+                        camera_group: None,
                     },
                 ),
                 (
@@ -117,6 +197,10 @@ mod test {
                         reason: "Something happened - 2".into(),
                         pre: Duration::from_secs(60),
                         post: Duration::from_secs(60),
+                        reason_template: None,
+                        archive_segments: true,
+                        camera_groups: Default::default(),
+                        camera_group: None,
                     },
                 ),
             ]),
@@ -125,7 +209,12 @@ mod test {
                 reason: "Something happened".into(),
                 pre: Duration::from_secs(60),
                 post: Duration::from_secs(120),
+                reason_template: None,
+                archive_segments: true,
+                camera_groups: Default::default(),
+                camera_group: None,
             },
+            force_utc: false,
         };
 
         let time = Utc.with_ymd_and_hms(2022, 11, 20, 5, 30, 0).unwrap().into();
@@ -137,6 +226,9 @@ mod test {
             reason: Some("reason".into()),
             pre: None,
             post: None,
+            template: None,
+            labels: Default::default(),
+            archive_segments: None,
         };
 
         assert_eq!(
@@ -150,7 +242,7 @@ mod test {
                 pre: Duration::from_secs(60),
                 post: Duration::from_secs(120),
             },
-            config.create_trigger(&cmd)
+            config.create_trigger(&cmd).unwrap()
         );
     }
 
@@ -165,6 +257,10 @@ mod test {
                         reason: "Something happened - 1".into(),
                         pre: Duration::from_secs(60),
                         post: Duration::from_secs(30),
+                        reason_template: None,
+                        archive_segments: true,
+                        camera_groups: Default::default(),
+                        camera_group: None,
                     },
                 ),
                 (
@@ -174,6 +270,10 @@ mod test {
                         reason: "Something happened - 2".into(),
                         pre: Duration::from_secs(60),
                         post: Duration::from_secs(60),
+                        reason_template: None,
+                        archive_segments: true,
+                        camera_groups: Default::default(),
+                        camera_group: None,
                     },
                 ),
             ]),
@@ -182,7 +282,12 @@ mod test {
                 reason: "Something happened".into(),
                 pre: Duration::from_secs(60),
                 post: Duration::from_secs(120),
+                reason_template: None,
+                archive_segments: true,
+                camera_groups: Default::default(),
+                camera_group: None,
             },
+            force_utc: false,
         };
 
         let time = Utc.with_ymd_and_hms(2022, 11, 20, 5, 30, 0).unwrap().into();
@@ -194,6 +299,9 @@ mod test {
             reason: Some("reason".into()),
             pre: None,
             post: None,
+            template: None,
+            labels: Default::default(),
+            archive_segments: None,
         };
 
         assert_eq!(
@@ -207,7 +315,103 @@ mod test {
                 pre: Duration::from_secs(60),
                 post: Duration::from_secs(30),
             },
-            config.create_trigger(&cmd)
+            config.create_trigger(&cmd).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_trigger_config_named_template() {
+        let config: TriggersConfig = toml::from_str(
+            r#"
+[templates.motion]
+cameras = ["camera-2"]
+reason = "Motion"
+reason_template = "{reason} in {zone}"
+pre = 30
+post = 30
+
+[fallback]
+cameras = ["camera-1"]
+reason = "Something happened"
+pre = 60
+post = 120
+"#,
+        )
+        .unwrap();
+
+        let cmd = TriggerCommand {
+            id: "garden sensor".into(),
+            template: Some("motion".into()),
+            labels: HashMap::from([("zone".into(), "garden".into())]),
+            ..Default::default()
+        };
+        let trigger = config.create_trigger(&cmd).unwrap();
+        assert_eq!(trigger.reason, "Motion in garden");
+        assert_eq!(trigger.cameras, vec!["camera-2".to_string()]);
+
+        // Variables required by the template must be provided
+        let cmd = TriggerCommand {
+            id: "garden sensor".into(),
+            template: Some("motion".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.create_trigger(&cmd),
+            Err(TriggerError::MissingVariables(vec!["zone".into()]))
+        );
+
+        // A named template must exist, the fallback is not used
+        let cmd = TriggerCommand {
+            id: "garden sensor".into(),
+            template: Some("person".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.create_trigger(&cmd),
+            Err(TriggerError::UnknownTemplate("person".into()))
+        );
+    }
+
+    #[test]
+    fn test_trigger_config_timestamp_offset() {
+        let mut config = TriggersConfig {
+            templates: Default::default(),
+            fallback: TriggerTemplate {
+                cameras: vec!["camera-1".into()],
+                reason: "Something happened".into(),
+                pre: Duration::from_secs(60),
+                post: Duration::from_secs(120),
+                reason_template: None,
+                archive_segments: true,
+                camera_groups: Default::default(),
+                camera_group: None,
+            },
+            force_utc: false,
+        };
+
+        let time = chrono::DateTime::parse_from_rfc3339("2022-11-20T11:00:00+05:30").unwrap();
+
+        let cmd = TriggerCommand {
+            id: "thing".into(),
+            timestamp: Some(time),
+            ..Default::default()
+        };
+
+        // The offset of the timestamp is preserved
+        let trigger = config.create_trigger(&cmd).unwrap();
+        assert_eq!(trigger.metadata.timestamp, time);
+        assert_eq!(
+            trigger.metadata.timestamp.to_rfc3339(),
+            "2022-11-20T11:00:00+05:30"
+        );
+
+        // The timestamp is converted to UTC if requested
+        config.force_utc = true;
+        let trigger = config.create_trigger(&cmd).unwrap();
+        assert_eq!(trigger.metadata.timestamp, time);
+        assert_eq!(
+            trigger.metadata.timestamp.to_rfc3339(),
+            "2022-11-20T05:30:00+00:00"
         );
     }
 }
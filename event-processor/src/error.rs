@@ -12,8 +12,17 @@ pub(crate) enum EventProcessorError {
     #[error("Playlist parse error")]
     PlaylistParseError,
 
+    #[error("Playlist error: {0}")]
+    PlaylistError(#[from] satori_common::hls::PlaylistError),
+
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
+
+    #[error("Duration \"{0}\" is out of range")]
+    DurationOutOfRange(String),
+
+    #[error("Invalid archive target configuration: {}", .0.join("; "))]
+    InvalidArchiveTargets(Vec<String>),
 }
 
 pub(crate) type EventProcessorResult<T> = Result<T, EventProcessorError>;
@@ -1,27 +1,62 @@
-use crate::{error::EventProcessorResult, hls_client::HlsClient, segments::Playlist};
+use crate::{
+    archive_targets::ArchiveTargets, archived_segments::ArchivedSegments, config::chrono_duration,
+    error::EventProcessorResult, hls_client::HlsClient,
+};
+use chrono::Utc;
 use satori_common::{
+    hls::SegmentFile,
     mqtt::{AsyncClientExt, MqttClient},
-    ArchiveCommand, ArchiveSegmentsCommand, CameraSegments, Event, EventReason, Message, Trigger,
+    ArchiveCommand, ArchiveSegmentsCommand, CameraSegments, ErrorLogThrottle, Event, EventReason,
+    Message, Trigger,
 };
 use std::{
+    collections::{HashMap, HashSet},
     fs::File,
     path::{Path, PathBuf},
     time::Duration,
 };
 use tracing::{error, info, warn};
+use url::Url;
 
 #[derive(Default)]
 pub(crate) struct EventSet {
     events: Vec<Event>,
 
     event_ttl: Duration,
+    max_event_duration: Option<chrono::Duration>,
+    merge_window: Option<chrono::Duration>,
     backing_file_name: PathBuf,
+
+    archived_segments: ArchivedSegments,
+
+    /// Routes archive commands for each camera to the archivers that store its video.
+    archive_targets: ArchiveTargets,
+
+    /// Throttles logging of failures to get the segments of a camera, by camera.
+    camera_error_log_throttle: ErrorLogThrottle<String>,
 }
 
 impl EventSet {
-    #[tracing::instrument]
-    pub(crate) fn load_or_new(path: &Path, event_ttl: Duration) -> Self {
-        Self {
+    /// Loads the event set from disk, starting with an empty set if it cannot be read.
+    ///
+    /// Fails only if a configured duration cannot be represented.
+    #[tracing::instrument(skip(archived_segments, archive_targets))]
+    pub(crate) fn load_or_new(
+        path: &Path,
+        event_ttl: Duration,
+        max_event_duration: Option<Duration>,
+        merge_window: Option<Duration>,
+        archived_segments: ArchivedSegments,
+        archive_targets: ArchiveTargets,
+    ) -> EventProcessorResult<Self> {
+        let max_event_duration = max_event_duration
+            .map(|d| chrono_duration("max_event_duration", d))
+            .transpose()?;
+        let merge_window = merge_window
+            .map(|d| chrono_duration("merge_window", d))
+            .transpose()?;
+
+        Ok(Self {
             // Try and load active events from disk
             events: match Self::load(path) {
                 Ok(v) => v,
@@ -36,8 +71,13 @@ impl EventSet {
                 }
             },
             event_ttl,
+            max_event_duration,
+            merge_window,
             backing_file_name: path.into(),
-        }
+            archived_segments,
+            archive_targets,
+            camera_error_log_throttle: Default::default(),
+        })
     }
 
     #[tracing::instrument]
@@ -48,8 +88,11 @@ impl EventSet {
 
     #[tracing::instrument(skip_all)]
     fn save(&self) -> EventProcessorResult<()> {
-        let file = File::create(&self.backing_file_name)?;
-        Ok(serde_json::to_writer(&file, &self.events)?)
+        let data = serde_json::to_vec(&self.events)?;
+        Ok(satori_common::write_file_atomic(
+            &self.backing_file_name,
+            &data,
+        )?)
     }
 
     #[tracing::instrument(skip_all)]
@@ -62,6 +105,11 @@ impl EventSet {
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn events(&self) -> &[Event] {
+        &self.events
+    }
+
     #[tracing::instrument(skip(self))]
     pub(crate) fn trigger(&mut self, trigger: &Trigger) {
         metrics::counter!(
@@ -70,20 +118,39 @@ impl EventSet {
             "id" => trigger.metadata.id.clone()
         );
 
-        match self
+        let same_id = self
             .events
-            .iter_mut()
-            .find(|e| e.metadata.id == trigger.metadata.id)
-        {
-            Some(e) => {
-                // If there is an event with the same ID then update it
-                info!("Updating existing event matching trigger");
-                update_event(e, trigger);
+            .iter()
+            .position(|e| e.metadata.id == trigger.metadata.id);
+
+        // Otherwise look for an event that is close enough in time to merge into
+        // Times that cannot be represented are beyond any trigger, so are unbounded.
+        let nearby = || {
+            let window = self.merge_window?;
+            self.events.iter().position(|e| {
+                e.start
+                    .checked_sub_signed(window)
+                    .is_none_or(|start| start <= trigger.end_time())
+                    && e.end
+                        .checked_add_signed(window)
+                        .is_none_or(|end| trigger.start_time() <= end)
+            })
+        };
+
+        match same_id.or_else(nearby) {
+            Some(idx) => {
+                // If there is a matching event then update it
+                info!(
+                    "Updating existing event {} matching trigger",
+                    self.events[idx].metadata.id
+                );
+                update_event(&mut self.events[idx], trigger, self.max_event_duration);
             }
             None => {
                 // Otherwise add a new event
                 info!("Adding new event for trigger");
-                self.events.push(trigger.clone().into());
+                self.events
+                    .push(new_event(trigger, self.max_event_duration));
             }
         }
 
@@ -97,20 +164,63 @@ impl EventSet {
             return;
         }
 
+        // Retrieve the playlist of every camera in any event, once and concurrently
+        let cameras: HashSet<String> = self
+            .events
+            .iter()
+            .flat_map(|e| e.cameras.iter().map(|c| c.name.clone()))
+            .collect();
+        let playlists = camera_client.get_playlists(cameras).await;
+
         for event in &mut self.events {
             info!("Processing event: {:?}", event.metadata);
 
             for camera in &mut event.cameras {
                 info!("Processing camera: {}", camera.name);
 
-                // Retrieve playlist
-                let playlist: Playlist = match camera_client.get_playlist(&camera.name).await {
-                    Ok(response) => response.into(),
+                let camera_url = match camera_client.get_camera_url(&camera.name) {
+                    Ok(url) => url,
                     Err(err) => {
-                        error!(
-                            "Failed to get segments for {}, reason: {}",
-                            camera.name, err
-                        );
+                        if let Some(suppressed) = self
+                            .camera_error_log_throttle
+                            .should_log(camera.name.clone())
+                        {
+                            error!(
+                                "Skipping camera {}, reason: {} ({} similar error(s) not logged)",
+                                camera.name, err, suppressed
+                            );
+                        }
+                        continue;
+                    }
+                };
+
+                let playlist = match playlists.get(&camera.name) {
+                    Some(Ok(playlist)) => {
+                        self.camera_error_log_throttle.reset(&camera.name);
+                        playlist
+                    }
+                    Some(Err(err)) => {
+                        if let Some(suppressed) = self
+                            .camera_error_log_throttle
+                            .should_log(camera.name.clone())
+                        {
+                            error!(
+                                "Failed to get segments for {}, reason: {} ({} similar error(s) not logged)",
+                                camera.name, err, suppressed
+                            );
+                        }
+                        continue;
+                    }
+                    None => {
+                        if let Some(suppressed) = self
+                            .camera_error_log_throttle
+                            .should_log(camera.name.clone())
+                        {
+                            error!(
+                                "No playlist was retrieved for {} ({} similar error(s) not logged)",
+                                camera.name, suppressed
+                            );
+                        }
                         continue;
                     }
                 };
@@ -118,52 +228,59 @@ impl EventSet {
                 // Filter segments that are in event time frame
                 let segments = playlist.between(event.start, event.end);
 
-                // Compute set of new segments (that are not already recorded in event)
-                let mut new_segments: Vec<PathBuf> = segments
-                    .into_iter()
-                    .filter_map(|s| {
-                        if camera.segment_list.contains(&s.filename) {
-                            None
-                        } else {
-                            Some(s.filename.clone())
-                        }
-                    })
-                    .collect();
+                let (mut new_segments, segments_to_archive) = select_new_segments(
+                    camera,
+                    with_init_sections(&segments).into_iter(),
+                    &self.archived_segments,
+                );
                 info!(
-                    "Found {} new segment(s) for {}",
+                    "Found {} new segment(s) for {}, {} to archive",
                     new_segments.len(),
-                    camera.name
+                    camera.name,
+                    segments_to_archive.len()
                 );
 
-                if !new_segments.is_empty() {
-                    // Send archive command for segments
-                    mqtt_client
-                        .client()
-                        .publish_json(
-                            mqtt_client.topic(),
-                            &Message::ArchiveCommand(ArchiveCommand::Segments(
-                                ArchiveSegmentsCommand {
-                                    camera_name: camera.name.clone(),
-                                    camera_url: camera_client.get_camera_url(&camera.name).unwrap(),
-                                    segment_list: new_segments.clone(),
-                                },
-                            )),
-                        )
-                        .await;
+                if !segments_to_archive.is_empty() {
+                    // Send archive command for segments to each archiver of the camera
+                    let msg =
+                        Message::ArchiveCommand(ArchiveCommand::Segments(ArchiveSegmentsCommand {
+                            camera_name: camera.name.clone(),
+                            segment_urls: segment_urls(
+                                &camera_url,
+                                &segments,
+                                &segments_to_archive,
+                            ),
+                            camera_url,
+                            segment_list: segments_to_archive.clone(),
+                        }));
+                    for topic in self
+                        .archive_targets
+                        .camera_topics(&camera.name, mqtt_client.topic())
+                    {
+                        mqtt_client.client().publish_json(topic, &msg).await;
+                    }
+
+                    // Persist immediately, so that the segments are not archived again even if
+                    // the event set cannot be saved
+                    self.archived_segments
+                        .insert(&camera.name, &segments_to_archive);
+                    self.archived_segments.attempt_save();
                 }
 
                 // Update segment list in event
                 camera.segment_list.append(&mut new_segments);
             }
 
-            // Send archive command for event
-            mqtt_client
-                .client()
-                .publish_json(
-                    mqtt_client.topic(),
-                    &Message::ArchiveCommand(ArchiveCommand::EventMetadata(event.clone())),
-                )
-                .await;
+            // Send archive command for event, with only the cameras each archiver stores
+            for (topic, event) in self.archive_targets.split_event(event, mqtt_client.topic()) {
+                mqtt_client
+                    .client()
+                    .publish_json(
+                        topic,
+                        &Message::ArchiveCommand(ArchiveCommand::EventMetadata(event)),
+                    )
+                    .await;
+            }
         }
 
         // Now remove any events that have outlived the TTL
@@ -189,6 +306,13 @@ impl EventSet {
                         1,
                         "id" => event.metadata.id.clone()
                     );
+
+                    // Time since the first trigger of the event
+                    let lifetime = Utc::now().signed_duration_since(event.metadata.timestamp);
+                    metrics::histogram!(
+                        crate::METRIC_EVENT_LIFETIME,
+                        lifetime.to_std().unwrap_or_default().as_secs_f64()
+                    );
                     None
                 } else {
                     Some(event.clone())
@@ -200,28 +324,150 @@ impl EventSet {
     }
 }
 
-fn update_event(event: &mut Event, other: &Trigger) {
-    if event.metadata.id != other.metadata.id {
-        panic!("Event IDs should match");
+/// Selects the segments that are not yet recorded in an event, and the subset of those that have
+/// not recently been sent for archiving.
+fn select_new_segments(
+    camera: &CameraSegments,
+    segments: impl Iterator<Item = PathBuf>,
+    archived_segments: &ArchivedSegments,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    // Compute set of new segments (that are not already recorded in event)
+    let new_segments: Vec<PathBuf> = segments
+        .filter(|s| !camera.segment_list.contains(s))
+        .collect();
+
+    let segments_to_archive = new_segments
+        .iter()
+        .filter(|s| !archived_segments.contains(&camera.name, s))
+        .cloned()
+        .collect();
+
+    (new_segments, segments_to_archive)
+}
+
+/// Filename under which the initialisation section needed to decode each segment (e.g. for
+/// fragmented MP4 segments) is archived, if it needs one.
+///
+/// The initialisation section at a given URI is replaced when the stream is restarted or its
+/// parameters change, so it is named after the first segment that uses it, i.e. the first segment
+/// with the same initialisation section URI in the same continuous run of segments. This keeps
+/// initialisation sections archived for earlier events from being overwritten.
+fn init_section_filenames(segments: &[&SegmentFile]) -> Vec<Option<PathBuf>> {
+    let mut first_segments: HashMap<(&str, u64), &Path> = HashMap::new();
+
+    segments
+        .iter()
+        .map(|segment| {
+            let uri = segment.init.as_deref()?;
+            let first = first_segments
+                .entry((uri, segment.discontinuity_sequence))
+                .or_insert(&segment.filename);
+            Some(satori_common::init_section_filename(first))
+        })
+        .collect()
+}
+
+/// Filenames of segments, each preceded by the initialisation section it needs to be decoded
+/// (e.g. for fragmented MP4 segments), so that the segments of an event can be concatenated.
+fn with_init_sections(segments: &[&SegmentFile]) -> Vec<PathBuf> {
+    let mut filenames = Vec::new();
+
+    for (segment, init) in segments.iter().zip(init_section_filenames(segments)) {
+        if let Some(init) = init {
+            if !filenames.contains(&init) {
+                filenames.push(init);
+            }
+        }
+        filenames.push(segment.filename.clone());
+    }
+
+    filenames
+}
+
+/// URLs of the segments to archive that are not alongside the camera playlist, or are archived
+/// under a different filename (i.e. initialisation sections), which the archiver would otherwise
+/// not be able to retrieve.
+fn segment_urls(
+    camera_url: &Url,
+    segments: &[&SegmentFile],
+    to_archive: &[PathBuf],
+) -> HashMap<PathBuf, Url> {
+    segments
+        .iter()
+        .zip(init_section_filenames(segments))
+        .flat_map(|(s, init_filename)| {
+            let init = s
+                .init
+                .as_deref()
+                .zip(init_filename)
+                .zip(s.init_url(camera_url))
+                .map(|((uri, filename), url)| (uri, filename, url));
+            let segment = (s.uri.as_str(), s.filename.clone(), s.url(camera_url));
+            init.into_iter().chain(std::iter::once(segment))
+        })
+        .filter(|(uri, filename, _)| filename != Path::new(uri) && to_archive.contains(filename))
+        .filter_map(|(uri, filename, url)| match url {
+            Ok(url) => Some((filename, url)),
+            Err(err) => {
+                warn!("Invalid segment URI {uri}, reason: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Creates an event from a trigger, limited to the maximum event duration.
+///
+/// When limited, time before the trigger is kept in preference to time after it, the event always
+/// includes the trigger timestamp.
+fn new_event(trigger: &Trigger, max_duration: Option<chrono::Duration>) -> Event {
+    let mut event: Event = trigger.clone().into();
+
+    if let Some(max_duration) = max_duration {
+        if event.end - event.start > max_duration {
+            warn!("Event would exceed maximum duration, limiting start and end time");
+            if let Some(earliest) = trigger.metadata.timestamp.checked_sub_signed(max_duration) {
+                event.start = std::cmp::max(event.start, earliest);
+            }
+            event.end = event.start + max_duration;
+        }
     }
 
+    event
+}
+
+fn update_event(event: &mut Event, other: &Trigger, max_duration: Option<chrono::Duration>) {
     // Update reason list.
+    // The trigger ID is recorded when it differs from the event, i.e. when merging by time.
     event.reasons.push(EventReason {
         timestamp: other.metadata.timestamp,
         reason: other.reason.clone(),
+        trigger_id: (event.metadata.id != other.metadata.id).then(|| other.metadata.id.clone()),
     });
 
     // Update start time.
     // Set new start time if it is earlier than the event's current start time.
-    let other_start = other.start_time();
+    let mut other_start = other.start_time();
     if other_start < event.start {
+        if let Some(max_duration) = max_duration {
+            if event.end - other_start > max_duration {
+                warn!("Event would exceed maximum duration, limiting start time");
+                other_start = std::cmp::min(event.start, event.end - max_duration);
+            }
+        }
         event.start = other_start;
     }
 
     // Update end time.
     // Set new end time if it is later than the event's current end time.
-    let other_end = other.end_time();
+    let mut other_end = other.end_time();
     if other_end > event.end {
+        if let Some(max_duration) = max_duration {
+            if other_end - event.start > max_duration {
+                warn!("Event would exceed maximum duration, limiting end time");
+                other_end = std::cmp::max(event.end, event.start + max_duration);
+            }
+        }
         event.end = other_end;
     }
 
@@ -240,18 +486,137 @@ fn update_event(event: &mut Event, other: &Trigger) {
 #[cfg(test)]
 mod test {
     use super::*;
-    use chrono::Utc;
-    use satori_common::EventMetadata;
+    use crate::error::EventProcessorError;
+    use satori_common::{EventMetadata, TriggerCommand, TriggerTemplate};
 
     #[test]
     fn test_load_bad_file_gives_empty_event_set() {
         let es = EventSet::load_or_new(
             &std::env::temp_dir().join("not_a_real_file.json"),
             Duration::default(),
-        );
+            None,
+            None,
+            ArchivedSegments::default(),
+            ArchiveTargets::default(),
+        )
+        .unwrap();
         assert!(es.events.is_empty());
     }
 
+    #[test]
+    fn test_interrupted_save_keeps_previous_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.json");
+
+        let mut es = EventSet {
+            event_ttl: Duration::from_secs(60),
+            backing_file_name: path.clone(),
+            ..Default::default()
+        };
+        es.trigger(&Trigger {
+            metadata: EventMetadata {
+                id: "test".into(),
+                timestamp: Utc::now().into(),
+            },
+            reason: "".into(),
+            cameras: vec!["camera1".into()],
+            pre: Duration::from_secs(1),
+            post: Duration::from_secs(2),
+        });
+        es.save().unwrap();
+
+        // Simulate a crash part way through writing the next state
+        let data = serde_json::to_vec(&es.events).unwrap();
+        std::fs::write(
+            dir.path().join(".events.json.a1B2c3.tmp"),
+            &data[..data.len() / 2],
+        )
+        .unwrap();
+
+        let loaded = EventSet::load_or_new(
+            &path,
+            Duration::from_secs(60),
+            None,
+            None,
+            ArchivedSegments::default(),
+            ArchiveTargets::default(),
+        )
+        .unwrap();
+        assert_eq!(loaded.events, es.events);
+
+        // The next save is unaffected by the partially written file
+        loaded.save().unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert_eq!(
+            EventSet::load_or_new(
+                &path,
+                Duration::from_secs(60),
+                None,
+                None,
+                ArchivedSegments::default(),
+                ArchiveTargets::default(),
+            )
+            .unwrap()
+            .events,
+            es.events
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_repeated_camera_failures_logged_once() {
+        let cameras = toml::from_str(indoc::indoc! {r#"
+            request_retries = 0
+
+            [[cameras]]
+            name = "camera1"
+            url = "http://localhost:1/stream.m3u8"
+        "#})
+        .unwrap();
+        let camera_client = HlsClient::new(cameras);
+        let mqtt_client: MqttClient =
+            toml::from_str::<satori_common::mqtt::MqttConfig>(indoc::indoc! {r#"
+                broker = "localhost"
+                port = 1
+                client_id = "test"
+                username = ""
+                password = ""
+                topic = "satori"
+            "#})
+            .unwrap()
+            .into();
+
+        let path = std::env::temp_dir().join("satori_event_processor_test_repeated_failures.json");
+        let mut es = EventSet {
+            event_ttl: Duration::from_secs(60),
+            backing_file_name: path.clone(),
+            ..Default::default()
+        };
+        for id in ["trigger1", "trigger2", "trigger3"] {
+            es.trigger(&Trigger {
+                metadata: EventMetadata {
+                    id: id.into(),
+                    timestamp: Utc::now().into(),
+                },
+                reason: "".into(),
+                cameras: vec!["camera1".into(), "camera2".into()],
+                pre: Duration::from_secs(1),
+                post: Duration::from_secs(2),
+            });
+        }
+
+        let errors = satori_testing_utils::EventCounter::new(tracing::Level::ERROR);
+        let _guard = errors.set_default();
+
+        for _ in 0..4 {
+            es.process(&camera_client, &mqtt_client).await;
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        // 12 failures for each of an unreachable and an unknown camera
+        assert_eq!(es.events.len(), 3);
+        assert_eq!(errors.count(), 2);
+    }
+
     #[test]
     fn test_trigger_1() {
         let mut es = EventSet::default();
@@ -317,6 +682,43 @@ mod test {
         assert!(es.events.is_empty());
     }
 
+    #[test]
+    fn test_expired_event_lifetime_recorded() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+        // Metrics are recorded per thread so that other tests do not interfere
+        DebuggingRecorder::per_thread().install().unwrap();
+
+        let mut es = EventSet::default();
+
+        es.trigger(&Trigger {
+            metadata: EventMetadata {
+                id: "trigger1".into(),
+                timestamp: (Utc::now() - chrono::Duration::seconds(10)).into(),
+            },
+            reason: "".into(),
+            cameras: Vec::default(),
+            pre: Duration::from_secs(1),
+            post: Duration::from_secs(2),
+        });
+        es.prune_expired_events();
+        assert!(es.events.is_empty());
+
+        let lifetimes: Vec<f64> = Snapshotter::current_thread_snapshot()
+            .unwrap()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, ..)| key.key().name() == crate::METRIC_EVENT_LIFETIME)
+            .flat_map(|(.., value)| match value {
+                DebugValue::Histogram(values) => values.into_iter().map(|v| v.0).collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+
+        assert_eq!(lifetimes.len(), 1);
+        assert!(lifetimes[0] >= 10.0);
+    }
+
     #[test]
     fn test_trigger_2() {
         let mut es = EventSet::default();
@@ -458,6 +860,85 @@ mod test {
         assert!(es.events.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_trigger_metadata_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut es = EventSet {
+            event_ttl: Duration::from_secs(60),
+            backing_file_name: dir.path().join("events.json"),
+            ..Default::default()
+        };
+
+        let config = crate::config::TriggersConfig {
+            templates: Default::default(),
+            fallback: TriggerTemplate {
+                cameras: vec!["camera-1".into(), "camera-2".into()],
+                reason: "Something happened".into(),
+                pre: Duration::from_secs(60),
+                post: Duration::from_secs(120),
+                reason_template: None,
+                archive_segments: true,
+                camera_groups: Default::default(),
+                camera_group: None,
+            },
+            force_utc: false,
+        };
+
+        let trigger = config
+            .create_trigger(&TriggerCommand {
+                id: "trigger1".into(),
+                archive_segments: Some(false),
+                ..Default::default()
+            })
+            .unwrap();
+        es.trigger(&trigger);
+
+        // The event should exist, but reference no cameras and therefore have no segments to archive
+        assert_eq!(es.events.len(), 1);
+        assert_eq!(es.events[0].metadata.id, "trigger1");
+        assert!(es.events[0].cameras.is_empty());
+
+        // The event itself is still archived
+        let broker = satori_testing_utils::MqttCapture::start().await;
+        let mut mqtt_client: MqttClient =
+            toml::from_str::<satori_common::mqtt::MqttConfig>(&format!(
+                r#"
+                broker = "127.0.0.1"
+                port = {}
+                client_id = "test"
+                username = ""
+                password = ""
+                topic = "satori"
+                "#,
+                broker.port()
+            ))
+            .unwrap()
+            .into();
+        let camera_client = HlsClient::new(toml::from_str("cameras = []").unwrap());
+
+        es.process(&camera_client, &mqtt_client).await;
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while broker.published().is_empty() {
+                mqtt_client.poll().await;
+            }
+        })
+        .await
+        .expect("archive command should be published");
+
+        let published = broker.published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "satori");
+        match satori_common::mqtt::PublishExt::try_payload_from_json::<Message>(&published[0])
+            .unwrap()
+        {
+            Message::ArchiveCommand(ArchiveCommand::EventMetadata(event)) => {
+                assert_eq!(event, es.events[0]);
+            }
+            other => panic!("unexpected message: {other:?}"),
+        }
+    }
+
     #[test]
     fn test_update_event_same_trigger() {
         let trigger = Trigger {
@@ -474,16 +955,18 @@ mod test {
         let mut event: Event = trigger.clone().into();
         let mut expected = event.clone();
 
-        update_event(&mut event, &trigger);
+        update_event(&mut event, &trigger, None);
 
         expected.reasons = vec![
             EventReason {
                 timestamp: trigger.metadata.timestamp,
                 reason: "Something happened".into(),
+                trigger_id: None,
             },
             EventReason {
                 timestamp: trigger.metadata.timestamp,
                 reason: "Something happened".into(),
+                trigger_id: None,
             },
         ];
 
@@ -513,14 +996,16 @@ mod test {
             EventReason {
                 timestamp: trigger.metadata.timestamp,
                 reason: "Something happened".into(),
+                trigger_id: None,
             },
             EventReason {
                 timestamp: trigger.metadata.timestamp,
                 reason: "Something happened".into(),
+                trigger_id: None,
             },
         ];
 
-        update_event(&mut event, &trigger);
+        update_event(&mut event, &trigger, None);
 
         assert_eq!(event, expected);
     }
@@ -548,14 +1033,16 @@ mod test {
             EventReason {
                 timestamp: trigger.metadata.timestamp,
                 reason: "Something happened".into(),
+                trigger_id: None,
             },
             EventReason {
                 timestamp: trigger.metadata.timestamp,
                 reason: "Something happened".into(),
+                trigger_id: None,
             },
         ];
 
-        update_event(&mut event, &trigger);
+        update_event(&mut event, &trigger, None);
 
         assert_eq!(event, expected);
     }
@@ -588,14 +1075,16 @@ mod test {
             EventReason {
                 timestamp: reason_1_timestamp,
                 reason: "Something happened".into(),
+                trigger_id: None,
             },
             EventReason {
                 timestamp: reason_2_timestamp,
                 reason: "Something else happened".into(),
+                trigger_id: None,
             },
         ];
 
-        update_event(&mut event, &trigger);
+        update_event(&mut event, &trigger, None);
 
         assert_eq!(event, expected);
     }
@@ -617,7 +1106,7 @@ mod test {
 
         trigger.cameras = vec!["camera-1".into(), "camera-2".into()];
 
-        update_event(&mut event, &trigger);
+        update_event(&mut event, &trigger, None);
 
         assert_eq!(
             event
@@ -646,7 +1135,7 @@ mod test {
 
         trigger.cameras = vec!["camera-2".into()];
 
-        update_event(&mut event, &trigger);
+        update_event(&mut event, &trigger, None);
 
         assert_eq!(
             event
@@ -657,4 +1146,505 @@ mod test {
             vec!["camera-1".to_string(), "camera-2".to_string()]
         );
     }
+
+    fn trigger_at(id: &str, timestamp: chrono::DateTime<Utc>) -> Trigger {
+        Trigger {
+            metadata: EventMetadata {
+                id: id.into(),
+                timestamp: timestamp.into(),
+            },
+            reason: format!("{id} happened"),
+            cameras: vec![format!("{id}-camera")],
+            pre: Duration::from_secs(10),
+            post: Duration::from_secs(20),
+        }
+    }
+
+    #[test]
+    fn test_trigger_overlapping_merged() {
+        let mut es = EventSet {
+            merge_window: Some(chrono::Duration::zero()),
+            ..Default::default()
+        };
+
+        let time = Utc::now();
+        es.trigger(&trigger_at("motion1", time));
+        es.trigger(&trigger_at("motion2", time + chrono::Duration::seconds(1)));
+
+        assert_eq!(es.events.len(), 1);
+        let event = &es.events[0];
+        assert_eq!(event.metadata.id, "motion1");
+        assert_eq!(event.start, time - chrono::Duration::seconds(10));
+        assert_eq!(event.end, time + chrono::Duration::seconds(21));
+        assert_eq!(
+            event
+                .cameras
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["motion1-camera", "motion2-camera"]
+        );
+        assert_eq!(
+            event.reasons,
+            vec![
+                EventReason {
+                    timestamp: time.into(),
+                    reason: "motion1 happened".into(),
+                    trigger_id: None,
+                },
+                EventReason {
+                    timestamp: (time + chrono::Duration::seconds(1)).into(),
+                    reason: "motion2 happened".into(),
+                    trigger_id: Some("motion2".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trigger_non_overlapping_not_merged() {
+        let mut es = EventSet {
+            merge_window: Some(chrono::Duration::zero()),
+            ..Default::default()
+        };
+
+        // First event ends 20s after, second starts 10s before, so there is a 1s gap
+        let time = Utc::now();
+        es.trigger(&trigger_at("motion1", time));
+        es.trigger(&trigger_at("motion2", time + chrono::Duration::seconds(31)));
+
+        assert_eq!(es.events.len(), 2);
+        assert_eq!(es.events[0].metadata.id, "motion1");
+        assert_eq!(es.events[1].metadata.id, "motion2");
+    }
+
+    #[test]
+    fn test_trigger_within_merge_window_merged() {
+        let mut es = EventSet {
+            merge_window: chrono::Duration::try_seconds(5),
+            ..Default::default()
+        };
+
+        let time = Utc::now();
+        es.trigger(&trigger_at("motion1", time));
+        es.trigger(&trigger_at("motion2", time + chrono::Duration::seconds(35)));
+        es.trigger(&trigger_at(
+            "motion3",
+            time + chrono::Duration::seconds(100),
+        ));
+
+        assert_eq!(es.events.len(), 2);
+        assert_eq!(es.events[0].metadata.id, "motion1");
+        assert_eq!(es.events[0].end, time + chrono::Duration::seconds(55));
+        assert_eq!(es.events[0].reasons.len(), 2);
+        assert_eq!(es.events[1].metadata.id, "motion3");
+    }
+
+    #[test]
+    fn test_trigger_within_enormous_merge_window_merged() {
+        let mut es = EventSet {
+            merge_window: Some(chrono::Duration::MAX),
+            ..Default::default()
+        };
+
+        let time = Utc::now();
+        es.trigger(&trigger_at("motion1", time));
+        es.trigger(&trigger_at(
+            "motion2",
+            time + chrono::Duration::try_days(365).unwrap(),
+        ));
+
+        assert_eq!(es.events.len(), 1);
+    }
+
+    #[test]
+    fn test_load_merge_window_out_of_range() {
+        assert!(matches!(
+            EventSet::load_or_new(
+                &std::env::temp_dir().join("not_a_real_file.json"),
+                Duration::default(),
+                None,
+                Some(Duration::from_secs(u64::MAX)),
+                ArchivedSegments::default(),
+                ArchiveTargets::default(),
+            ),
+            Err(EventProcessorError::DurationOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_trigger_overlapping_not_merged_by_default() {
+        let mut es = EventSet::default();
+
+        let time = Utc::now();
+        es.trigger(&trigger_at("motion1", time));
+        es.trigger(&trigger_at("motion2", time + chrono::Duration::seconds(1)));
+
+        assert_eq!(es.events.len(), 2);
+    }
+
+    #[test]
+    fn test_trigger_repeated_limited_by_max_event_duration() {
+        let mut es = EventSet {
+            max_event_duration: chrono::Duration::try_seconds(300),
+            ..Default::default()
+        };
+
+        let start_time = Utc::now();
+
+        // Repeatedly trigger the same ID over a period far longer than the maximum event duration
+        for i in 0..100 {
+            es.trigger(&Trigger {
+                metadata: EventMetadata {
+                    id: "trigger1".into(),
+                    timestamp: (start_time + chrono::Duration::try_seconds(i * 10).unwrap()).into(),
+                },
+                reason: "".into(),
+                cameras: Vec::default(),
+                pre: Duration::from_secs(30),
+                post: Duration::from_secs(60),
+            });
+
+            assert_eq!(es.events.len(), 1);
+            let event = &es.events[0];
+            assert!(event.end - event.start <= chrono::Duration::try_seconds(300).unwrap());
+        }
+
+        let event = &es.events[0];
+        assert_eq!(
+            event.start,
+            start_time - chrono::Duration::try_seconds(30).unwrap()
+        );
+        assert_eq!(
+            event.end,
+            start_time + chrono::Duration::try_seconds(270).unwrap()
+        );
+        assert_eq!(event.reasons.len(), 100);
+    }
+
+    #[test]
+    fn test_trigger_new_event_limited_by_max_event_duration() {
+        let mut es = EventSet {
+            max_event_duration: chrono::Duration::try_seconds(300),
+            ..Default::default()
+        };
+
+        let time = Utc::now();
+
+        // Post time is limited, keeping all of the pre time
+        es.trigger(&Trigger {
+            pre: Duration::from_secs(100),
+            post: Trigger::MAX_PRE_POST,
+            ..trigger_at("trigger1", time)
+        });
+
+        // Pre time is limited, keeping the trigger timestamp
+        es.trigger(&Trigger {
+            pre: Trigger::MAX_PRE_POST,
+            post: Trigger::MAX_PRE_POST,
+            ..trigger_at("trigger2", time)
+        });
+
+        assert_eq!(es.events.len(), 2);
+        assert_eq!(es.events[0].start, time - chrono::Duration::seconds(100));
+        assert_eq!(es.events[0].end, time + chrono::Duration::seconds(200));
+        assert_eq!(es.events[1].start, time - chrono::Duration::seconds(300));
+        assert_eq!(es.events[1].end, time);
+    }
+
+    #[test]
+    fn test_load_max_event_duration_out_of_range() {
+        assert!(matches!(
+            EventSet::load_or_new(
+                &std::env::temp_dir().join("not_a_real_file.json"),
+                Duration::default(),
+                Some(Duration::from_secs(u64::MAX)),
+                None,
+                ArchivedSegments::default(),
+                ArchiveTargets::default(),
+            ),
+            Err(EventProcessorError::DurationOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_update_event_start_time_limited_by_max_event_duration() {
+        let mut trigger = Trigger {
+            metadata: EventMetadata {
+                id: "event1".into(),
+                timestamp: Utc::now().into(),
+            },
+            reason: "Something happened".into(),
+            pre: Duration::from_secs(30),
+            post: Duration::from_secs(60),
+            cameras: Vec::new(),
+        };
+
+        let mut event: Event = trigger.clone().into();
+
+        trigger.pre = Duration::from_secs(600);
+        update_event(&mut event, &trigger, chrono::Duration::try_seconds(120));
+
+        assert_eq!(
+            event.start,
+            trigger.metadata.timestamp - chrono::Duration::try_seconds(60).unwrap()
+        );
+        assert_eq!(
+            event.end,
+            trigger.metadata.timestamp + chrono::Duration::try_seconds(60).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_update_event_within_max_event_duration() {
+        let mut trigger = Trigger {
+            metadata: EventMetadata {
+                id: "event1".into(),
+                timestamp: Utc::now().into(),
+            },
+            reason: "Something happened".into(),
+            pre: Duration::from_secs(30),
+            post: Duration::from_secs(60),
+            cameras: Vec::new(),
+        };
+
+        let mut event: Event = trigger.clone().into();
+
+        trigger.post = Duration::from_secs(120);
+        update_event(&mut event, &trigger, chrono::Duration::try_seconds(600));
+
+        assert_eq!(
+            event.end,
+            trigger.metadata.timestamp + chrono::Duration::try_seconds(120).unwrap()
+        );
+    }
+
+    fn fmp4_playlist() -> satori_common::hls::Playlist {
+        let playlist = m3u8_rs::parse_media_playlist_res(
+            indoc::indoc! {"
+                #EXTM3U
+                #EXT-X-VERSION:7
+                #EXT-X-TARGETDURATION:6
+                #EXT-X-MAP:URI=\"https://cdn.example.com/camera1/init.mp4\"
+                #EXTINF:6.0,
+                2022-12-30T18_10_00+0000.m4s
+                #EXTINF:6.0,
+                2022-12-30T18_10_06+0000.m4s
+            "}
+            .as_bytes(),
+        )
+        .unwrap();
+
+        satori_common::hls::Playlist::parse(
+            playlist,
+            satori_common::SegmentFormat::Fmp4.segment_filename_format(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_with_init_sections() {
+        let playlist = fmp4_playlist();
+        let segments: Vec<_> = playlist.segments.iter().collect();
+
+        // The initialisation section is listed once, before the segments that need it
+        assert_eq!(
+            with_init_sections(&segments),
+            vec![
+                PathBuf::from("init_2022-12-30T18_10_00+0000.mp4"),
+                PathBuf::from("2022-12-30T18_10_00+0000.m4s"),
+                PathBuf::from("2022-12-30T18_10_06+0000.m4s"),
+            ]
+        );
+
+        // Already recorded in the event, so only the new segment is selected
+        let camera = CameraSegments {
+            name: "camera1".into(),
+            segment_list: vec![
+                "init_2022-12-30T18_10_00+0000.mp4".into(),
+                "2022-12-30T18_10_00+0000.m4s".into(),
+            ],
+        };
+        let (new_segments, _) = select_new_segments(
+            &camera,
+            with_init_sections(&segments).into_iter(),
+            &ArchivedSegments::default(),
+        );
+        assert_eq!(
+            new_segments,
+            vec![PathBuf::from("2022-12-30T18_10_06+0000.m4s")]
+        );
+    }
+
+    #[test]
+    fn test_segment_urls_init_section() {
+        let playlist = fmp4_playlist();
+        let segments: Vec<_> = playlist.segments.iter().collect();
+        let camera_url = Url::parse("http://camera:8080/hls/stream.m3u8").unwrap();
+
+        assert_eq!(
+            segment_urls(&camera_url, &segments, &with_init_sections(&segments)),
+            HashMap::from([(
+                PathBuf::from("init_2022-12-30T18_10_00+0000.mp4"),
+                Url::parse("https://cdn.example.com/camera1/init.mp4").unwrap()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_init_section_named_per_run() {
+        // The stream is restarted, replacing the initialisation section at the same URI
+        let playlist = m3u8_rs::parse_media_playlist_res(
+            indoc::indoc! {"
+                #EXTM3U
+                #EXT-X-VERSION:7
+                #EXT-X-TARGETDURATION:6
+                #EXT-X-MAP:URI=\"init.mp4\"
+                #EXTINF:6.0,
+                2022-12-30T18_10_00+0000.m4s
+                #EXTINF:6.0,
+                2022-12-30T18_10_06+0000.m4s
+                #EXT-X-DISCONTINUITY
+                #EXT-X-MAP:URI=\"init.mp4\"
+                #EXTINF:6.0,
+                2022-12-30T18_11_00+0000.m4s
+            "}
+            .as_bytes(),
+        )
+        .unwrap();
+        let playlist = satori_common::hls::Playlist::parse(
+            playlist,
+            satori_common::SegmentFormat::Fmp4.segment_filename_format(),
+        )
+        .unwrap();
+        let segments: Vec<_> = playlist.segments.iter().collect();
+
+        assert_eq!(
+            with_init_sections(&segments),
+            vec![
+                PathBuf::from("init_2022-12-30T18_10_00+0000.mp4"),
+                PathBuf::from("2022-12-30T18_10_00+0000.m4s"),
+                PathBuf::from("2022-12-30T18_10_06+0000.m4s"),
+                PathBuf::from("init_2022-12-30T18_11_00+0000.mp4"),
+                PathBuf::from("2022-12-30T18_11_00+0000.m4s"),
+            ]
+        );
+
+        // Both are retrieved from the URI of the initialisation section
+        let camera_url = Url::parse("http://camera:8080/hls/stream.m3u8").unwrap();
+        let urls = segment_urls(&camera_url, &segments, &with_init_sections(&segments));
+        assert_eq!(urls.len(), 2);
+        assert!(urls
+            .values()
+            .all(|url| url.as_str() == "http://camera:8080/hls/init.mp4"));
+    }
+
+    #[test]
+    fn test_segment_urls_mixed_uris() {
+        let playlist: satori_common::hls::Playlist = m3u8_rs::parse_media_playlist_res(
+            indoc::indoc! {"
+                #EXTM3U
+                #EXT-X-VERSION:3
+                #EXT-X-TARGETDURATION:6
+                #EXTINF:6.0,
+                2022-12-30T18_10_00+0000.ts
+                #EXTINF:6.0,
+                https://cdn.example.com/camera1/2022-12-30T18_10_06+0000.ts
+                #EXTINF:6.0,
+                segments/2022-12-30T18_10_12+0000.ts
+                #EXTINF:6.0,
+                https://cdn.example.com/camera1/2022-12-30T18_10_18+0000.ts
+            "}
+            .as_bytes(),
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let segments: Vec<_> = playlist.segments.iter().collect();
+
+        let camera_url = Url::parse("http://camera:8080/hls/stream.m3u8").unwrap();
+
+        // The final segment is not being archived
+        let to_archive = vec![
+            PathBuf::from("2022-12-30T18_10_00+0000.ts"),
+            PathBuf::from("2022-12-30T18_10_06+0000.ts"),
+            PathBuf::from("2022-12-30T18_10_12+0000.ts"),
+        ];
+
+        // Segments alongside the playlist need no URL
+        assert_eq!(
+            segment_urls(&camera_url, &segments, &to_archive),
+            HashMap::from([
+                (
+                    PathBuf::from("2022-12-30T18_10_06+0000.ts"),
+                    Url::parse("https://cdn.example.com/camera1/2022-12-30T18_10_06+0000.ts")
+                        .unwrap()
+                ),
+                (
+                    PathBuf::from("2022-12-30T18_10_12+0000.ts"),
+                    Url::parse("http://camera:8080/hls/segments/2022-12-30T18_10_12+0000.ts")
+                        .unwrap()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_select_new_segments() {
+        let camera = CameraSegments {
+            name: "camera1".into(),
+            segment_list: vec!["one.ts".into()],
+        };
+
+        let (new_segments, segments_to_archive) = select_new_segments(
+            &camera,
+            ["one.ts".into(), "two.ts".into()].into_iter(),
+            &ArchivedSegments::default(),
+        );
+        assert_eq!(new_segments, vec![PathBuf::from("two.ts")]);
+        assert_eq!(segments_to_archive, vec![PathBuf::from("two.ts")]);
+    }
+
+    #[test]
+    fn test_select_new_segments_rediscovered_within_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archived_segments.json");
+
+        let segments = || ["one.ts".into(), "two.ts".into()].into_iter();
+
+        let mut archived_segments =
+            ArchivedSegments::load_or_new(&path, Duration::from_secs(60)).unwrap();
+        let camera = CameraSegments {
+            name: "camera1".into(),
+            segment_list: Vec::new(),
+        };
+
+        let (_, segments_to_archive) = select_new_segments(&camera, segments(), &archived_segments);
+        assert_eq!(segments_to_archive.len(), 2);
+        archived_segments.insert(&camera.name, &segments_to_archive);
+        archived_segments.attempt_save();
+
+        // The updated segment list was lost (e.g. the event set could not be saved), so the same
+        // segments are rediscovered after a restart
+        let archived_segments =
+            ArchivedSegments::load_or_new(&path, Duration::from_secs(60)).unwrap();
+        let (new_segments, segments_to_archive) =
+            select_new_segments(&camera, segments(), &archived_segments);
+
+        // They are still recorded in the event, but not archived again
+        assert_eq!(
+            new_segments,
+            vec![PathBuf::from("one.ts"), PathBuf::from("two.ts")]
+        );
+        assert!(segments_to_archive.is_empty());
+
+        // Segments of other cameras are unaffected
+        let other_camera = CameraSegments {
+            name: "camera2".into(),
+            segment_list: Vec::new(),
+        };
+        let (_, segments_to_archive) =
+            select_new_segments(&other_camera, segments(), &archived_segments);
+        assert_eq!(segments_to_archive.len(), 2);
+    }
 }
@@ -1,43 +1,135 @@
 use crate::error::{EventProcessorError, EventProcessorResult};
-use satori_common::camera_config::CamerasConfig;
-use std::collections::HashMap;
-use tracing::error;
+use satori_common::{
+    camera_config::{CameraConfig, CamerasConfig},
+    hls::Playlist,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::task::JoinSet;
+use tracing::{error, warn};
 use url::Url;
 
+/// Delay between attempts to get a playlist.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Maximum number of playlists that are requested at the same time.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+#[derive(Clone)]
 pub(crate) struct HlsClient {
     http_client: reqwest::Client,
-    camera_urls: HashMap<String, Url>,
+    cameras: Arc<HashMap<String, CameraConfig>>,
+    retries: u32,
 }
 
 impl HlsClient {
     pub(crate) fn new(cameras: CamerasConfig) -> Self {
         let http_client = reqwest::ClientBuilder::new()
             .danger_accept_invalid_certs(true)
+            .timeout(cameras.request_timeout)
             .build()
             .unwrap();
 
         Self {
             http_client,
-            camera_urls: cameras.into_map(),
+            retries: cameras.request_retries,
+            cameras: Arc::new(cameras.into_map()),
         }
     }
 
     #[tracing::instrument(skip(self))]
     pub(crate) fn get_camera_url(&self, camera: &str) -> EventProcessorResult<Url> {
-        self.camera_urls
+        Ok(self.get_camera(camera)?.url().clone())
+    }
+
+    fn get_camera(&self, camera: &str) -> EventProcessorResult<&CameraConfig> {
+        self.cameras
             .get(camera)
             .ok_or_else(|| EventProcessorError::NoSuchCamera(camera.into()))
-            .cloned()
     }
 
     #[tracing::instrument(skip(self))]
-    pub(crate) async fn get_playlist(
+    pub(crate) async fn get_playlist(&self, camera: &str) -> EventProcessorResult<Playlist> {
+        let config = self.get_camera(camera)?;
+        let url = config.url().clone();
+
+        let mut attempt = 0;
+        let body = loop {
+            match self.fetch(url.clone(), config.auth_token()).await {
+                Ok(body) => break body,
+                Err(err) if attempt < self.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Failed to get playlist for {camera} (retry {attempt} of {}), reason: {err}",
+                        self.retries
+                    );
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                Err(err) => {
+                    metrics::counter!(crate::METRIC_PLAYLIST_FAILURES, 1, "camera" => camera.to_owned());
+                    return Err(err.into());
+                }
+            }
+        };
+
+        Ok(Playlist::parse(
+            parse_playlist(body)?,
+            config.segment_filename_format(),
+        )?)
+    }
+
+    /// Gets the playlists of several cameras concurrently.
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn get_playlists(
+        &self,
+        cameras: HashSet<String>,
+    ) -> HashMap<String, EventProcessorResult<Playlist>> {
+        let mut playlists = HashMap::new();
+        let mut tasks = JoinSet::new();
+
+        for camera in cameras {
+            if tasks.len() >= MAX_CONCURRENT_REQUESTS {
+                if let Some(Ok((camera, playlist))) = tasks.join_next().await {
+                    playlists.insert(camera, playlist);
+                }
+            }
+
+            let client = self.clone();
+            tasks.spawn(async move {
+                let playlist = client.get_playlist(&camera).await;
+                (camera, playlist)
+            });
+        }
+
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok((camera, playlist)) => {
+                    playlists.insert(camera, playlist);
+                }
+                Err(err) => error!("Playlist request task failed, reason: {err}"),
+            }
+        }
+
+        playlists
+    }
+
+    async fn fetch(
         &self,
-        camera: &str,
-    ) -> EventProcessorResult<m3u8_rs::MediaPlaylist> {
-        let url = self.get_camera_url(camera)?;
-        let body = self.http_client.get(url).send().await?.bytes().await?;
-        parse_playlist(body)
+        url: Url,
+        auth_token: Option<&str>,
+    ) -> Result<bytes::Bytes, reqwest::Error> {
+        let mut request = self.http_client.get(url);
+        for (name, value) in satori_common::trace_context_headers() {
+            request = request.header(name, value);
+        }
+        if let Some(token) = auth_token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await?.bytes().await
     }
 }
 
@@ -59,3 +151,99 @@ fn parse_playlist(data: bytes::Bytes) -> EventProcessorResult<m3u8_rs::MediaPlay
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_playlists_requested_concurrently() {
+        use axum::{routing::get, Router};
+
+        const DELAY: Duration = Duration::from_millis(500);
+
+        let mut config = String::new();
+        for camera in ["camera1", "camera2", "camera3"] {
+            // Each camera is slow to respond
+            let app = Router::new().route(
+                "/stream.m3u8",
+                get(|| async {
+                    tokio::time::sleep(DELAY).await;
+                    indoc::indoc! {"
+                        #EXTM3U
+                        #EXT-X-VERSION:3
+                        #EXT-X-TARGETDURATION:6
+                        #EXTINF:6.0,
+                        2022-12-30T18_10_00+0000.ts
+                    "}
+                }),
+            );
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            config.push_str(&format!(
+                "[[cameras]]\nname = \"{camera}\"\nurl = \"http://{address}/stream.m3u8\"\n"
+            ));
+        }
+        let client = HlsClient::new(toml::from_str(&config).unwrap());
+
+        let start = Instant::now();
+        let playlists = client
+            .get_playlists(HashSet::from([
+                "camera1".to_string(),
+                "camera2".to_string(),
+                "camera3".to_string(),
+                "camera4".to_string(),
+            ]))
+            .await;
+        let elapsed = start.elapsed();
+
+        assert_eq!(playlists.len(), 4);
+        for camera in ["camera1", "camera2", "camera3"] {
+            assert_eq!(playlists[camera].as_ref().unwrap().segments.len(), 1);
+        }
+        assert!(matches!(
+            playlists["camera4"],
+            Err(EventProcessorError::NoSuchCamera(_))
+        ));
+
+        // All cameras were requested at the same time, rather than one after another
+        assert!(elapsed >= DELAY);
+        assert!(elapsed < DELAY * 2, "took {elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn test_unresponsive_camera_times_out() {
+        // Connections are accepted by the OS but never responded to
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let cameras: CamerasConfig = toml::from_str(&format!(
+            r#"
+request_timeout = 100
+request_retries = 1
+
+[[cameras]]
+name = "camera1"
+url = "http://{address}/stream.m3u8"
+"#
+        ))
+        .unwrap();
+        let client = HlsClient::new(cameras);
+
+        let start = Instant::now();
+        let result = tokio::time::timeout(Duration::from_secs(5), client.get_playlist("camera1"))
+            .await
+            .expect("request should time out rather than hang");
+
+        match result {
+            Err(EventProcessorError::NetworkError(err)) => assert!(err.is_timeout()),
+            _ => panic!("request should fail with a timeout"),
+        }
+
+        // Both attempts timed out
+        assert!(start.elapsed() >= Duration::from_millis(200) + RETRY_DELAY);
+    }
+}
@@ -1,22 +1,32 @@
+mod archive_targets;
+mod archived_segments;
 mod config;
 mod error;
 mod event_set;
 mod hls_client;
-mod segments;
+mod trigger_debounce;
 
 use crate::{
+    archived_segments::ArchivedSegments,
     config::{Config, TriggersConfig},
     event_set::EventSet,
+    trigger_debounce::TriggerDebounce,
 };
 use clap::Parser;
 use metrics_exporter_prometheus::PrometheusBuilder;
-use satori_common::mqtt::{MqttClient, PublishExt};
-use std::{net::SocketAddr, path::PathBuf};
-use tracing::{debug, error, info};
+use satori_common::{
+    mqtt::{MqttClient, PublishExt},
+    LogFormat, Trigger, TriggerCommand,
+};
+use std::{collections::HashSet, net::SocketAddr, path::PathBuf};
+use tracing::{debug, error, info, warn};
 
 const METRIC_TRIGGERS: &str = "satori_eventprocessor_triggers";
+const METRIC_DEBOUNCED_TRIGGERS: &str = "satori_eventprocessor_debounced_triggers";
 const METRIC_ACTIVE_EVENTS: &str = "satori_eventprocessor_active_events";
 const METRIC_EXPIRED_EVENTS: &str = "satori_eventprocessor_expired_events";
+const METRIC_PLAYLIST_FAILURES: &str = "satori_eventprocessor_playlist_failures";
+const METRIC_EVENT_LIFETIME: &str = "satori_eventprocessor_event_lifetime_seconds";
 
 /// Run the event processor.
 #[derive(Clone, Parser)]
@@ -29,23 +39,69 @@ pub(crate) struct Cli {
     /// Address to listen on for observability/metrics endpoints
     #[clap(long, env = "OBSERVABILITY_ADDRESS", default_value = "127.0.0.1:9090")]
     observability_address: SocketAddr,
+
+    /// File to append the details of any panic to
+    #[arg(long, env = "CRASH_FILE", value_name = "FILE")]
+    crash_file: Option<PathBuf>,
+
+    /// Format of log output
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t)]
+    log_format: LogFormat,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), ()> {
-    tracing_subscriber::fmt::init();
-
     let cli = Cli::parse();
+    let _tracing = satori_common::init_tracing(env!("CARGO_PKG_NAME"), cli.log_format);
+    satori_common::install_panic_hook(cli.crash_file.clone());
     let config: Config = satori_common::load_config_file(&cli.config);
+    if let Err(err) = config.cameras.validate() {
+        error!("{err}");
+        return Err(());
+    }
+    if let Err(err) = config.archive_targets.validate(config.cameras.names()) {
+        error!("{err}");
+        return Err(());
+    }
 
     // Set up and connect MQTT client
     let mut mqtt_client: MqttClient = config.mqtt.into();
 
+    // Names of cameras that triggers may refer to
+    let known_cameras: HashSet<String> = config.cameras.names().map(Into::into).collect();
+
     // Set up camera stream client
     let camera_client = self::hls_client::HlsClient::new(config.cameras);
 
+    // Load existing or create new record of recently archived segments
+    let archived_segments = match &config.archive_deduplication {
+        Some(dedup) => match ArchivedSegments::load_or_new(&dedup.file, dedup.window) {
+            Ok(segments) => segments,
+            Err(err) => {
+                error!("{err}");
+                return Err(());
+            }
+        },
+        None => ArchivedSegments::default(),
+    };
+
     // Load existing or create new event state
-    let mut events = EventSet::load_or_new(&config.event_file, config.event_ttl);
+    let mut events = match EventSet::load_or_new(
+        &config.event_file,
+        config.event_ttl,
+        config.max_event_duration,
+        config.merge_window,
+        archived_segments,
+        config.archive_targets.clone(),
+    ) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("{err}");
+            return Err(());
+        }
+    };
+
+    let mut trigger_debounce = TriggerDebounce::new(config.trigger_debounce);
 
     // Set up metrics server
     let builder = PrometheusBuilder::new();
@@ -56,6 +112,12 @@ async fn main() -> Result<(), ()> {
 
     metrics::describe_counter!(METRIC_TRIGGERS, metrics::Unit::Count, "Trigger count");
 
+    metrics::describe_counter!(
+        METRIC_DEBOUNCED_TRIGGERS,
+        metrics::Unit::Count,
+        "Repeated trigger count that were dropped"
+    );
+
     metrics::describe_gauge!(
         METRIC_ACTIVE_EVENTS,
         metrics::Unit::Count,
@@ -68,6 +130,18 @@ async fn main() -> Result<(), ()> {
         "Processed events count"
     );
 
+    metrics::describe_histogram!(
+        METRIC_EVENT_LIFETIME,
+        metrics::Unit::Seconds,
+        "Time from the first trigger of an event to it being expired"
+    );
+
+    metrics::describe_counter!(
+        METRIC_PLAYLIST_FAILURES,
+        metrics::Unit::Count,
+        "Number of times a camera playlist could not be retrieved"
+    );
+
     // Run event loop
     let mut process_interval = tokio::time::interval(config.interval);
     loop {
@@ -78,7 +152,7 @@ async fn main() -> Result<(), ()> {
             }
             msg = mqtt_client.poll() => {
                 if let Some(msg) = msg {
-                    if handle_mqtt_message(msg, &mut events, &config.triggers) {
+                    if handle_mqtt_message(msg, &mut events, &mut trigger_debounce, &config.triggers, &known_cameras) {
                         // Immediately process events
                         events.process(&camera_client, &mqtt_client).await;
                     }
@@ -101,7 +175,9 @@ async fn main() -> Result<(), ()> {
 fn handle_mqtt_message(
     msg: rumqttc::Publish,
     events: &mut EventSet,
+    trigger_debounce: &mut TriggerDebounce,
     trigger_config: &TriggersConfig,
+    known_cameras: &HashSet<String>,
 ) -> bool {
     let msg = msg.try_payload_from_json::<satori_common::Message>();
     if let Err(err) = msg {
@@ -111,10 +187,306 @@ fn handle_mqtt_message(
 
     if let satori_common::Message::TriggerCommand(cmd) = msg.unwrap() {
         debug!("Trigger command: {:?}", cmd);
-        let trigger = trigger_config.create_trigger(&cmd);
+
+        let trigger = match trigger_config.create_trigger(&cmd) {
+            Ok(trigger) => trigger,
+            Err(err) => {
+                warn!("Rejected trigger with ID \"{}\": {}", cmd.id, err);
+                return false;
+            }
+        };
+        let problems = validate_trigger(&cmd, &trigger, known_cameras);
+        if !problems.is_empty() {
+            warn!(
+                "Rejected trigger with ID \"{}\": {}",
+                trigger.metadata.id,
+                problems.join("; ")
+            );
+            return false;
+        }
+
+        // Only triggers that would reach the event set start a debounce window
+        if !trigger_debounce.accept(&cmd.id) {
+            return false;
+        }
+
         events.trigger(&trigger);
         true
     } else {
         false
     }
 }
+
+/// Checks that a trigger would create a useful event, returning a description of each problem
+/// found.
+///
+/// Rejected triggers must not change any state, so this is checked before debouncing.
+fn validate_trigger(
+    cmd: &TriggerCommand,
+    trigger: &Trigger,
+    known_cameras: &HashSet<String>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if trigger.pre.is_zero() && trigger.post.is_zero() {
+        problems.push("pre and post must not both be zero".to_owned());
+    }
+
+    for (name, duration) in [("pre", trigger.pre), ("post", trigger.post)] {
+        if duration > Trigger::MAX_PRE_POST {
+            problems.push(format!(
+                "{name} must not be longer than {}s",
+                Trigger::MAX_PRE_POST.as_secs()
+            ));
+        }
+    }
+
+    // Metadata only triggers legitimately have no cameras, but explicitly asking for none is a
+    // mistake
+    if cmd.cameras.as_ref().is_some_and(Vec::is_empty) {
+        problems.push("camera list must not be empty".to_owned());
+    }
+
+    for camera in &trigger.cameras {
+        if !known_cameras.contains(camera) {
+            problems.push(format!("camera \"{camera}\" is not configured"));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rumqttc::{Publish, QoS};
+    use satori_common::{Message, TriggerTemplate};
+    use std::time::Duration;
+
+    fn trigger_config() -> TriggersConfig {
+        TriggersConfig {
+            templates: Default::default(),
+            fallback: TriggerTemplate {
+                cameras: vec!["camera-1".into()],
+                reason: "Something happened".into(),
+                pre: Duration::from_secs(60),
+                post: Duration::from_secs(120),
+                reason_template: None,
+                archive_segments: true,
+                camera_groups: Default::default(),
+                camera_group: None,
+            },
+            force_utc: false,
+        }
+    }
+
+    fn known_cameras() -> HashSet<String> {
+        HashSet::from(["camera-1".into(), "camera-2".into()])
+    }
+
+    fn trigger_message(id: &str) -> Publish {
+        command_message(TriggerCommand {
+            id: id.into(),
+            ..Default::default()
+        })
+    }
+
+    fn command_message(cmd: TriggerCommand) -> Publish {
+        let msg = Message::TriggerCommand(cmd);
+        Publish::new("", QoS::ExactlyOnce, serde_json::to_string(&msg).unwrap())
+    }
+
+    #[test]
+    fn test_repeated_triggers_debounced() {
+        let mut events = EventSet::default();
+        let mut debounce = TriggerDebounce::new(Duration::from_secs(60));
+        let config = trigger_config();
+
+        let handled = (0..10)
+            .filter(|_| {
+                handle_mqtt_message(
+                    trigger_message("trigger1"),
+                    &mut events,
+                    &mut debounce,
+                    &config,
+                    &known_cameras(),
+                )
+            })
+            .count();
+        assert_eq!(handled, 1);
+
+        // Only the first trigger reached the event set
+        assert_eq!(events.events().len(), 1);
+        assert_eq!(events.events()[0].reasons.len(), 1);
+
+        // Other trigger IDs are unaffected
+        assert!(handle_mqtt_message(
+            trigger_message("trigger2"),
+            &mut events,
+            &mut debounce,
+            &config,
+            &known_cameras(),
+        ));
+        assert_eq!(events.events().len(), 2);
+    }
+
+    #[test]
+    fn test_triggers_not_debounced_when_disabled() {
+        let mut events = EventSet::default();
+        let mut debounce = TriggerDebounce::default();
+        let config = trigger_config();
+
+        for _ in 0..10 {
+            assert!(handle_mqtt_message(
+                trigger_message("trigger1"),
+                &mut events,
+                &mut debounce,
+                &config,
+                &known_cameras(),
+            ));
+        }
+
+        assert_eq!(events.events()[0].reasons.len(), 10);
+    }
+
+    #[test]
+    fn test_rejected_trigger_not_debounced() {
+        let mut events = EventSet::default();
+        let mut debounce = TriggerDebounce::new(Duration::from_secs(60));
+        let config = trigger_config();
+
+        assert!(!handle_mqtt_message(
+            command_message(TriggerCommand {
+                id: "trigger1".into(),
+                cameras: Some(vec!["camera-3".into()]),
+                ..Default::default()
+            }),
+            &mut events,
+            &mut debounce,
+            &config,
+            &known_cameras(),
+        ));
+        assert!(events.events().is_empty());
+
+        // A valid trigger with the same ID is not dropped by the rejected one
+        assert!(handle_mqtt_message(
+            trigger_message("trigger1"),
+            &mut events,
+            &mut debounce,
+            &config,
+            &known_cameras(),
+        ));
+        assert_eq!(events.events().len(), 1);
+    }
+
+    fn handle_command(cmd: TriggerCommand, events: &mut EventSet) -> bool {
+        handle_mqtt_message(
+            command_message(cmd),
+            events,
+            &mut TriggerDebounce::default(),
+            &trigger_config(),
+            &known_cameras(),
+        )
+    }
+
+    #[test]
+    fn test_trigger_accepted() {
+        let mut events = EventSet::default();
+
+        assert!(handle_command(
+            TriggerCommand {
+                id: "trigger1".into(),
+                cameras: Some(vec!["camera-1".into(), "camera-2".into()]),
+                ..Default::default()
+            },
+            &mut events,
+        ));
+        assert_eq!(events.events().len(), 1);
+    }
+
+    #[test]
+    fn test_trigger_unknown_camera_rejected() {
+        let mut events = EventSet::default();
+
+        assert!(!handle_command(
+            TriggerCommand {
+                id: "trigger1".into(),
+                cameras: Some(vec!["camera-1".into(), "camera-3".into()]),
+                ..Default::default()
+            },
+            &mut events,
+        ));
+        assert!(events.events().is_empty());
+    }
+
+    #[test]
+    fn test_trigger_no_cameras_rejected() {
+        let mut events = EventSet::default();
+
+        assert!(!handle_command(
+            TriggerCommand {
+                id: "trigger1".into(),
+                cameras: Some(vec![]),
+                ..Default::default()
+            },
+            &mut events,
+        ));
+        assert!(events.events().is_empty());
+    }
+
+    #[test]
+    fn test_trigger_zero_duration_rejected() {
+        let mut events = EventSet::default();
+
+        assert!(!handle_command(
+            TriggerCommand {
+                id: "trigger1".into(),
+                pre: Some(Duration::ZERO),
+                post: Some(Duration::ZERO),
+                ..Default::default()
+            },
+            &mut events,
+        ));
+        assert!(events.events().is_empty());
+    }
+
+    #[test]
+    fn test_trigger_enormous_duration_rejected() {
+        let mut events = EventSet::default();
+        let mut debounce = TriggerDebounce::new(Duration::from_secs(60));
+        let config = trigger_config();
+
+        for (pre, post) in [
+            (Some(Duration::from_secs(u64::MAX)), None),
+            (None, Some(Trigger::MAX_PRE_POST + Duration::from_secs(1))),
+        ] {
+            assert!(!handle_mqtt_message(
+                command_message(TriggerCommand {
+                    id: "trigger1".into(),
+                    pre,
+                    post,
+                    ..Default::default()
+                }),
+                &mut events,
+                &mut debounce,
+                &config,
+                &known_cameras(),
+            ));
+        }
+        assert!(events.events().is_empty());
+
+        // The rejected triggers did not start a debounce window for their ID
+        assert!(handle_mqtt_message(
+            command_message(TriggerCommand {
+                id: "trigger1".into(),
+                pre: Some(Trigger::MAX_PRE_POST),
+                ..Default::default()
+            }),
+            &mut events,
+            &mut debounce,
+            &config,
+            &known_cameras(),
+        ));
+        assert_eq!(events.events().len(), 1);
+    }
+}
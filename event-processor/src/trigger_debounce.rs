@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+/// Trigger IDs that have recently been acted upon.
+///
+/// Used to collapse a burst of triggers with the same ID (e.g. from a flaky sensor) into a single
+/// trigger.
+#[derive(Default)]
+pub(crate) struct TriggerDebounce {
+    window: Duration,
+    accepted: HashMap<String, Instant>,
+}
+
+impl TriggerDebounce {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            accepted: Default::default(),
+        }
+    }
+
+    /// Checks if a trigger with a given ID should be acted upon, i.e. no trigger with the same ID
+    /// has been accepted within the debounce window.
+    pub(crate) fn accept(&mut self, id: &str) -> bool {
+        self.accept_at(id, Instant::now())
+    }
+
+    fn accept_at(&mut self, id: &str, now: Instant) -> bool {
+        // Every trigger is accepted when debouncing is disabled
+        if self.window.is_zero() {
+            return true;
+        }
+
+        self.accepted
+            .retain(|_, accepted| now.duration_since(*accepted) < self.window);
+
+        if self.accepted.contains_key(id) {
+            info!("Dropping repeated trigger with ID \"{}\"", id);
+            metrics::counter!(crate::METRIC_DEBOUNCED_TRIGGERS, 1, "id" => id.to_owned());
+            false
+        } else {
+            self.accepted.insert(id.to_owned(), now);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disabled_by_default() {
+        let mut debounce = TriggerDebounce::default();
+
+        assert!(debounce.accept("trigger1"));
+        assert!(debounce.accept("trigger1"));
+    }
+
+    #[test]
+    fn test_keyed_by_id() {
+        let mut debounce = TriggerDebounce::new(Duration::from_secs(1));
+
+        assert!(debounce.accept("trigger1"));
+        assert!(debounce.accept("trigger2"));
+        assert!(!debounce.accept("trigger1"));
+        assert!(!debounce.accept("trigger2"));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut debounce = TriggerDebounce::new(Duration::from_secs(1));
+
+        let now = Instant::now();
+        assert!(debounce.accept_at("trigger1", now));
+        assert!(!debounce.accept_at("trigger1", now + Duration::from_millis(999)));
+
+        // The window starts from the last accepted trigger, so a constant stream of triggers is
+        // reduced to one trigger per window
+        assert!(debounce.accept_at("trigger1", now + Duration::from_secs(1)));
+        assert!(!debounce.accept_at("trigger1", now + Duration::from_millis(1500)));
+
+        // IDs outside of the window are forgotten
+        debounce.accept_at("trigger2", now + Duration::from_secs(5));
+        assert_eq!(debounce.accepted.len(), 1);
+    }
+}
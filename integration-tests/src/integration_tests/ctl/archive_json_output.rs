@@ -0,0 +1,100 @@
+use satori_common::{CameraSegments, Event, EventMetadata};
+use std::path::Path;
+use tempfile::{NamedTempFile, TempDir};
+
+fn create_archive(path: &Path) -> Vec<Event> {
+    let events: Vec<Event> = ["2023-01-01T00:00:00Z", "2023-01-02T00:00:00Z"]
+        .into_iter()
+        .enumerate()
+        .map(|(i, timestamp)| {
+            let timestamp = chrono::DateTime::parse_from_rfc3339(timestamp).unwrap();
+            Event {
+                metadata: EventMetadata {
+                    id: format!("event-{i}"),
+                    timestamp,
+                },
+                start: timestamp,
+                end: timestamp,
+                reasons: Default::default(),
+                cameras: vec![CameraSegments {
+                    name: "camera1".into(),
+                    segment_list: vec!["one.ts".into()],
+                }],
+            }
+        })
+        .collect();
+
+    std::fs::create_dir_all(path.join("events")).unwrap();
+    for event in &events {
+        std::fs::write(
+            path.join("events").join(event.metadata.get_filename()),
+            serde_json::to_vec(event).unwrap(),
+        )
+        .unwrap();
+    }
+
+    std::fs::create_dir_all(path.join("segments/camera1")).unwrap();
+    std::fs::write(path.join("segments/camera1/one.ts"), "segment").unwrap();
+
+    events
+}
+
+async fn run_json(storage_config: &Path, args: &[&str]) -> serde_json::Value {
+    let mut cmd_args = vec![
+        "archive".to_string(),
+        "--storage".to_string(),
+        storage_config.display().to_string(),
+        "--output".to_string(),
+        "json".to_string(),
+    ];
+    cmd_args.extend(args.iter().map(|a| a.to_string()));
+
+    let mut satorictl =
+        satori_testing_utils::CargoBinaryRunner::new("satorictl".to_string(), cmd_args, vec![]);
+    satorictl.wait().await;
+
+    serde_json::from_str(&satorictl.stdout()).unwrap()
+}
+
+#[tokio::test]
+#[ignore]
+async fn archive_json_output() {
+    let archive_dir = TempDir::new().unwrap();
+    let events = create_archive(archive_dir.path());
+
+    let storage_config = NamedTempFile::new().unwrap();
+    std::fs::write(
+        storage_config.path(),
+        format!(
+            "kind = \"local\"\npath = \"{}\"",
+            archive_dir.path().display()
+        ),
+    )
+    .unwrap();
+
+    let filenames: Vec<String> = events
+        .iter()
+        .map(|e| e.metadata.get_filename().display().to_string())
+        .collect();
+
+    assert_eq!(
+        run_json(storage_config.path(), &["list-events"]).await,
+        serde_json::json!(filenames)
+    );
+
+    assert_eq!(
+        run_json(storage_config.path(), &["list-cameras"]).await,
+        serde_json::json!(["camera1"])
+    );
+
+    assert_eq!(
+        run_json(storage_config.path(), &["list-segments", "camera1"]).await,
+        serde_json::json!(["one.ts"])
+    );
+
+    let event: Event = serde_json::from_value(
+        run_json(storage_config.path(), &["get-event", &filenames[1]]).await,
+    )
+    .unwrap();
+    assert_eq!(event, events[1]);
+}
@@ -0,0 +1,158 @@
+use satori_common::mqtt::PublishExt;
+use satori_testing_utils::{MinioDriver, MosquittoDriver, TestMqttClient};
+use std::{io::Write, time::Duration};
+use tempfile::NamedTempFile;
+
+const MQTT_TOPIC: &str = "satori";
+
+#[tokio::test]
+#[ignore]
+async fn debug_send_archive_command() {
+    let minio = MinioDriver::default();
+    minio.wait_for_ready().await;
+    minio.set_credential_env_vars();
+    let s3_bucket = minio.create_bucket("satori").await;
+
+    let mosquitto = MosquittoDriver::default();
+
+    let mut mqtt_client = TestMqttClient::new(mosquitto.port()).await;
+    mqtt_client
+        .client()
+        .subscribe(MQTT_TOPIC, rumqttc::QoS::ExactlyOnce)
+        .await
+        .unwrap();
+
+    let archiver_queue_file = NamedTempFile::new().unwrap();
+
+    let archiver_config_file = {
+        let contents = format!(
+            indoc::indoc!(
+                r#"
+                queue_file = "{}"
+                interval = 10  # miliseconds
+
+                [storage]
+                kind = "s3"
+                bucket = "satori"
+                region = ""
+                endpoint = "{}"
+
+                [mqtt]
+                broker = "localhost"
+                port = {}
+                client_id = "satori-archiver-s3"
+                username = "test"
+                password = ""
+                topic = "satori"
+                "#
+            ),
+            archiver_queue_file.path().display(),
+            minio.endpoint(),
+            mosquitto.port(),
+        );
+
+        let file = NamedTempFile::new().unwrap();
+        file.as_file().write_all(contents.as_bytes()).unwrap();
+        file
+    };
+
+    let satori_archiver = satori_testing_utils::CargoBinaryRunner::new(
+        "satori-archiver".to_string(),
+        vec![
+            "--config".to_string(),
+            archiver_config_file.path().display().to_string(),
+            "--observability-address".to_string(),
+            "127.0.0.1:9091".to_string(),
+        ],
+        vec![
+            ("AWS_ACCESS_KEY_ID".to_string(), "minioadmin".to_string()),
+            (
+                "AWS_SECRET_ACCESS_KEY".to_string(),
+                "minioadmin".to_string(),
+            ),
+        ],
+    );
+
+    // Wait for the archiver to start
+    satori_testing_utils::wait_for_url("http://localhost:9091", Duration::from_secs(600))
+        .await
+        .expect("archiver should be running");
+
+    let ctl_mqtt_config_file = {
+        let contents = format!(
+            indoc::indoc!(
+                r#"
+                broker = "localhost"
+                port = {}
+                client_id = "satorictl"
+                username = "test"
+                password = ""
+                topic = "satori"
+                "#
+            ),
+            mosquitto.port(),
+        );
+
+        let file = NamedTempFile::new().unwrap();
+        file.as_file().write_all(contents.as_bytes()).unwrap();
+        file
+    };
+
+    let archive_command = r#"{"kind":"event_metadata","data":{"metadata":{"id":"crafted","timestamp":"2023-01-01T00:02:15Z"},"reasons":[{"timestamp":"2023-01-01T00:02:15Z","reason":"test"}],"start":"2023-01-01T00:01:25Z","end":"2023-01-01T00:02:45Z","cameras":[{"name":"camera1","segment_list":["2023-01-01T00_01_24+0000.ts"]}]}}"#;
+
+    let archive_command_file = {
+        let file = NamedTempFile::new().unwrap();
+        file.as_file()
+            .write_all(archive_command.as_bytes())
+            .unwrap();
+        file
+    };
+
+    // Send crafted archive command with satorictl
+    satori_testing_utils::CargoBinaryRunner::new(
+        "satorictl".to_string(),
+        vec![
+            "debug".to_string(),
+            "--mqtt".to_string(),
+            ctl_mqtt_config_file.path().display().to_string(),
+            "send-archive-command".to_string(),
+            archive_command_file.path().display().to_string(),
+        ],
+        vec![],
+    )
+    .wait()
+    .await;
+
+    // Event metadata archive command should be sent
+    assert_eq!(
+        mqtt_client
+            .wait_for_message(Duration::from_secs(5))
+            .await
+            .unwrap()
+            .try_payload_str()
+            .unwrap(),
+        format!(r#"{{"kind":"archive_command","data":{archive_command}}}"#),
+    );
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // Check the event is stored in S3
+    let s3_event = s3_bucket
+        .get_object("events/2023-01-01T00:02:15+00:00_crafted.json")
+        .await
+        .unwrap();
+    let s3_event: satori_common::Event = serde_json::from_slice(s3_event.as_slice()).unwrap();
+    assert_eq!(
+        s3_event,
+        serde_json::from_str::<satori_common::ArchiveCommand>(archive_command)
+            .map(|c| match c {
+                satori_common::ArchiveCommand::EventMetadata(event) => event,
+                _ => panic!("archive command should be event metadata"),
+            })
+            .unwrap()
+    );
+
+    mqtt_client.stop().await;
+
+    satori_archiver.stop();
+}
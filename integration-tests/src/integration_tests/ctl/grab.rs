@@ -0,0 +1,192 @@
+use satori_common::SegmentFormat;
+use satori_testing_utils::{DummyHlsServer, DummyStreamParams};
+use std::time::Duration;
+use tempfile::NamedTempFile;
+
+#[tokio::test]
+#[ignore]
+async fn grab() {
+    let stream_1 = DummyHlsServer::new(
+        "stream 1".to_string(),
+        DummyStreamParams::new("2023-01-01T00:00:00Z", Duration::from_secs(6), 100).into(),
+    )
+    .await;
+
+    let agent_url = stream_1
+        .stream_address()
+        .trim_end_matches("stream.m3u8")
+        .to_string();
+
+    let output_file = NamedTempFile::new().unwrap();
+
+    // Grab the last minute of footage with satorictl
+    satori_testing_utils::CargoBinaryRunner::new(
+        "satorictl".to_string(),
+        vec![
+            "grab".to_string(),
+            "--agent".to_string(),
+            agent_url,
+            "--last".to_string(),
+            "1m".to_string(),
+            "--output".to_string(),
+            output_file.path().display().to_string(),
+        ],
+        vec![],
+    )
+    .wait()
+    .await;
+
+    // The clip should contain the last ten segments (60 seconds) of the stream, in order
+    let clip = std::fs::read_to_string(output_file.path()).unwrap();
+    let expected: String = (90..100)
+        .map(|i| {
+            let timestamp = chrono::DateTime::parse_from_rfc3339("2023-01-01T00:00:00Z").unwrap()
+                + chrono::Duration::try_seconds(i * 6).unwrap();
+            format!(
+                "Dummy MPEG-TS segment for dummy HLS stream \"stream 1\"\n{}\n",
+                timestamp.format(satori_common::SEGMENT_FILENAME_FORMAT)
+            )
+        })
+        .collect();
+    assert_eq!(clip, expected);
+}
+
+#[tokio::test]
+#[ignore]
+async fn grab_parallel() {
+    let stream_1 = DummyHlsServer::new(
+        "stream 1".to_string(),
+        DummyStreamParams::new("2023-01-01T00:00:00Z", Duration::from_secs(6), 100).into(),
+    )
+    .await;
+
+    let agent_url = stream_1
+        .stream_address()
+        .trim_end_matches("stream.m3u8")
+        .to_string();
+
+    let output_file = NamedTempFile::new().unwrap();
+
+    // Grab the last five minutes of footage with satorictl, downloading many segments at once
+    satori_testing_utils::CargoBinaryRunner::new(
+        "satorictl".to_string(),
+        vec![
+            "grab".to_string(),
+            "--agent".to_string(),
+            agent_url,
+            "--last".to_string(),
+            "5m".to_string(),
+            "--jobs".to_string(),
+            "16".to_string(),
+            "--output".to_string(),
+            output_file.path().display().to_string(),
+        ],
+        vec![],
+    )
+    .wait()
+    .await;
+
+    // The clip should contain the last fifty segments (300 seconds) of the stream, in order
+    let clip = std::fs::read_to_string(output_file.path()).unwrap();
+    let expected: String = (50..100)
+        .map(|i| {
+            let timestamp = chrono::DateTime::parse_from_rfc3339("2023-01-01T00:00:00Z").unwrap()
+                + chrono::Duration::try_seconds(i * 6).unwrap();
+            format!(
+                "Dummy MPEG-TS segment for dummy HLS stream \"stream 1\"\n{}\n",
+                timestamp.format(satori_common::SEGMENT_FILENAME_FORMAT)
+            )
+        })
+        .collect();
+    assert_eq!(clip, expected);
+}
+
+#[tokio::test]
+#[ignore]
+async fn grab_without_trailing_slash() {
+    let stream_1 = DummyHlsServer::new(
+        "stream 1".to_string(),
+        DummyStreamParams::new("2023-01-01T00:00:00Z", Duration::from_secs(6), 100).into(),
+    )
+    .await;
+
+    // The agent URL is given without the trailing slash
+    let agent_url = stream_1
+        .stream_address()
+        .trim_end_matches("/stream.m3u8")
+        .to_string();
+
+    let output_file = NamedTempFile::new().unwrap();
+
+    satori_testing_utils::CargoBinaryRunner::new(
+        "satorictl".to_string(),
+        vec![
+            "grab".to_string(),
+            "--agent".to_string(),
+            agent_url,
+            "--last".to_string(),
+            "1m".to_string(),
+            "--output".to_string(),
+            output_file.path().display().to_string(),
+        ],
+        vec![],
+    )
+    .wait()
+    .await;
+
+    // The clip should still contain the last ten segments (60 seconds) of the stream
+    let clip = std::fs::read_to_string(output_file.path()).unwrap();
+    assert_eq!(clip.matches("Dummy MPEG-TS segment").count(), 10);
+}
+
+#[tokio::test]
+#[ignore]
+async fn grab_fmp4() {
+    let stream_1 = DummyHlsServer::new(
+        "stream 1".to_string(),
+        DummyStreamParams::new("2023-01-01T00:00:00Z", Duration::from_secs(6), 100)
+            .with_segment_format(SegmentFormat::Fmp4)
+            .into(),
+    )
+    .await;
+
+    let agent_url = stream_1
+        .stream_address()
+        .trim_end_matches("stream.m3u8")
+        .to_string();
+
+    let output_file = NamedTempFile::new().unwrap();
+
+    satori_testing_utils::CargoBinaryRunner::new(
+        "satorictl".to_string(),
+        vec![
+            "grab".to_string(),
+            "--agent".to_string(),
+            agent_url,
+            "--last".to_string(),
+            "1m".to_string(),
+            "--output".to_string(),
+            output_file.path().display().to_string(),
+        ],
+        vec![],
+    )
+    .wait()
+    .await;
+
+    // The clip should contain the initialisation section once, followed by the last ten segments
+    // (60 seconds) of the stream, in order
+    let clip = std::fs::read_to_string(output_file.path()).unwrap();
+    let segments: String = (90..100)
+        .map(|i| {
+            let timestamp = chrono::DateTime::parse_from_rfc3339("2023-01-01T00:00:00Z").unwrap()
+                + chrono::Duration::try_seconds(i * 6).unwrap();
+            format!(
+                "Dummy fMP4 segment for dummy HLS stream \"stream 1\"\n{}\n",
+                timestamp.format(SegmentFormat::Fmp4.segment_filename_format())
+            )
+        })
+        .collect();
+    let expected =
+        format!("Dummy fMP4 initialisation section for dummy HLS stream \"stream 1\"\n{segments}");
+    assert_eq!(clip, expected);
+}
@@ -1,2 +1,5 @@
+mod archive_json_output;
 mod debug_archive_segments;
+mod debug_send_archive_command;
+mod grab;
 mod trigger;
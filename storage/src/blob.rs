@@ -0,0 +1,103 @@
+use crate::StorageResult;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// Time since a blob was last written or reused by a segment before it may be deleted when no
+/// segment refers to it.
+///
+/// This covers the time between a segment reusing a blob and its pointer being written, during
+/// which the blob is not yet referenced.
+pub(crate) const BLOB_GRACE_PERIOD: chrono::Duration = chrono::Duration::hours(1);
+
+/// Age after which a reused blob is rewritten by providers that cannot update the modification
+/// time of a blob without rewriting it, must be less than [`BLOB_GRACE_PERIOD`].
+pub(crate) const BLOB_REFRESH_INTERVAL: chrono::Duration = chrono::Duration::minutes(30);
+
+/// Hash used to address the content of a segment.
+///
+/// When segments are encrypted the hash is keyed with a key derived from the segment encryption
+/// key (see [`crate::encryption::KeyOperations::hash_key`]), otherwise the names of blobs would
+/// allow anyone to confirm that a blob holds known content.
+pub(crate) fn blob_hash(key: Option<[u8; 32]>, data: &[u8]) -> String {
+    match key {
+        Some(key) => blake3::keyed_hash(&key, data),
+        None => blake3::hash(data),
+    }
+    .to_hex()
+    .to_string()
+}
+
+/// Stored in place of a segment when segments are content addressed, referring to the blob that
+/// holds the content of the segment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SegmentPointer {
+    pub(crate) blob: String,
+}
+
+impl SegmentPointer {
+    pub(crate) fn new(blob: String) -> Self {
+        Self { blob }
+    }
+
+    pub(crate) fn to_bytes(&self) -> StorageResult<Bytes> {
+        Ok(serde_json::to_vec(self)?.into())
+    }
+
+    /// Parses a pointer from the (decrypted) content of a stored segment.
+    ///
+    /// Returns `None` if the segment holds video data rather than a pointer.
+    pub(crate) fn from_bytes(data: &[u8]) -> Option<Self> {
+        // Video segments never start with a JSON object, so avoid attempting to parse them
+        if data.first() != Some(&b'{') {
+            return None;
+        }
+
+        serde_json::from_slice(data).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_blob_hash() {
+        assert_eq!(blob_hash(None, b"segment"), blob_hash(None, b"segment"));
+        assert_ne!(
+            blob_hash(None, b"segment"),
+            blob_hash(None, b"other segment")
+        );
+        assert_eq!(blob_hash(None, b"segment").len(), 64);
+    }
+
+    #[test]
+    fn test_blob_hash_keyed() {
+        let key = |n| Some([n; 32]);
+
+        assert_eq!(blob_hash(key(1), b"segment"), blob_hash(key(1), b"segment"));
+        assert_ne!(
+            blob_hash(key(1), b"segment"),
+            blob_hash(key(1), b"other segment")
+        );
+
+        // The content cannot be confirmed without the key
+        assert_ne!(blob_hash(key(1), b"segment"), blob_hash(None, b"segment"));
+        assert_ne!(blob_hash(key(1), b"segment"), blob_hash(key(2), b"segment"));
+    }
+
+    #[test]
+    fn test_pointer_round_trip() {
+        let pointer = SegmentPointer::new(blob_hash(None, b"segment"));
+        let data = pointer.to_bytes().unwrap();
+
+        assert_eq!(SegmentPointer::from_bytes(&data), Some(pointer));
+    }
+
+    #[test]
+    fn test_pointer_from_video() {
+        assert_eq!(SegmentPointer::from_bytes(b""), None);
+        assert_eq!(SegmentPointer::from_bytes(&[0x47, 0x40, 0x00, 0x10]), None);
+        assert_eq!(SegmentPointer::from_bytes(b"{\"other\":\"value\"}"), None);
+    }
+}
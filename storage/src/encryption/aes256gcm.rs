@@ -0,0 +1,279 @@
+use super::KeyOperations;
+use crate::{StorageError, StorageResult};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Key, Nonce,
+};
+use base64::Engine;
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Symmetric encryption using AES-256-GCM with a pre-shared key.
+///
+/// The key may be given as an array of bytes, a base64 string or a hex string.
+#[derive(Clone, Deserialize)]
+pub struct Aes256Gcm {
+    #[serde(deserialize_with = "deserialize_key")]
+    key: [u8; 32],
+}
+
+/// Representation of the bytes of a key in a configuration file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEncoding {
+    #[default]
+    Array,
+    Base64,
+    Hex,
+}
+
+impl std::fmt::Debug for Aes256Gcm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AES-256-GCM")
+    }
+}
+
+impl Aes256Gcm {
+    /// Generates a new random key.
+    pub fn generate() -> Self {
+        Self {
+            key: aes_gcm::Aes256Gcm::generate_key(OsRng).into(),
+        }
+    }
+
+    /// Renders the key as TOML, suitable for use as an encryption key in a storage configuration.
+    pub fn to_toml(&self, encoding: KeyEncoding) -> String {
+        let key = match encoding {
+            KeyEncoding::Array => format!("{:?}", self.key),
+            KeyEncoding::Base64 => format!(
+                "\"{}\"",
+                base64::engine::general_purpose::STANDARD.encode(self.key)
+            ),
+            KeyEncoding::Hex => format!("\"{}\"", hex::encode(self.key)),
+        };
+
+        format!("kind = \"aes256_gcm\"\nkey = {key}\n")
+    }
+
+    fn cipher(&self) -> aes_gcm::Aes256Gcm {
+        aes_gcm::Aes256Gcm::new(Key::<aes_gcm::Aes256Gcm>::from_slice(&self.key))
+    }
+}
+
+fn deserialize_key<'de, D>(deserializer: D) -> Result<[u8; 32], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum KeyRepr {
+        Array(Vec<u8>),
+        Encoded(String),
+    }
+
+    let bytes = match KeyRepr::deserialize(deserializer)? {
+        KeyRepr::Array(bytes) => bytes,
+        KeyRepr::Encoded(s) => match hex::decode(&s) {
+            Ok(bytes) => bytes,
+            Err(_) => base64::engine::general_purpose::STANDARD
+                .decode(&s)
+                .map_err(|_| Error::custom("key is neither valid hex nor base64"))?,
+        },
+    };
+
+    let len = bytes.len();
+    bytes
+        .try_into()
+        .map_err(|_| Error::custom(StorageError::KeyLengthError(32, len)))
+}
+
+impl KeyOperations for Aes256Gcm {
+    fn encrypt(&self, id: Bytes, data: Bytes) -> StorageResult<Bytes> {
+        let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                &nonce,
+                aes_gcm::aead::Payload {
+                    msg: &data,
+                    aad: &id,
+                },
+            )
+            .map_err(|_| StorageError::AesGcmError)?;
+
+        let payload = Payload {
+            nonce: Bytes::copy_from_slice(&nonce),
+            ciphertext: ciphertext.into(),
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        ciborium::into_writer(&payload, &mut data)?;
+
+        Ok(data.into())
+    }
+
+    fn decrypt(&self, id: Bytes, data: Bytes) -> StorageResult<Bytes> {
+        let payload: Payload = ciborium::from_reader(&*data)?;
+
+        if payload.nonce.len() != 12 {
+            return Err(StorageError::AesGcmError);
+        }
+
+        let data = self
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(&payload.nonce),
+                aes_gcm::aead::Payload {
+                    msg: &payload.ciphertext,
+                    aad: &id,
+                },
+            )
+            .map_err(|_| StorageError::AesGcmError)?;
+
+        Ok(data.into())
+    }
+
+    fn hash_key(&self) -> Option<[u8; 32]> {
+        Some(blake3::derive_key(super::HASH_KEY_CONTEXT, &self.key))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Payload {
+    nonce: Bytes,
+    ciphertext: Bytes,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encryption::test::encryption_test;
+
+    #[test]
+    fn deserialize() {
+        let repr = "
+key = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+]
+        ";
+
+        let key: Aes256Gcm = toml::from_str(repr).unwrap();
+
+        assert_eq!(
+            key.key.as_slice(),
+            hex::decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn deserialize_encodings() {
+        let expected =
+            hex::decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")
+                .unwrap();
+
+        let repr = "
+key = \"000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\"
+        ";
+        let key: Aes256Gcm = toml::from_str(repr).unwrap();
+        assert_eq!(key.key.as_slice(), expected);
+
+        let repr = "
+key = \"AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=\"
+        ";
+        let key: Aes256Gcm = toml::from_str(repr).unwrap();
+        assert_eq!(key.key.as_slice(), expected);
+    }
+
+    #[test]
+    fn deserialize_bad_encoding() {
+        let repr = "
+key = \"not a key\"
+        ";
+
+        assert!(toml::from_str::<Aes256Gcm>(repr).is_err());
+    }
+
+    #[test]
+    fn deserialize_encoded_wrong_length() {
+        let repr = "
+key = \"00010203\"
+        ";
+
+        let result = toml::from_str::<Aes256Gcm>(repr);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Encryption key length incorrect, expected 32, got 4"));
+    }
+
+    #[test]
+    fn to_toml_round_trip() {
+        let key = Aes256Gcm::generate();
+
+        for encoding in [KeyEncoding::Array, KeyEncoding::Base64, KeyEncoding::Hex] {
+            let repr = key.to_toml(encoding);
+
+            let parsed: crate::EncryptionKey = toml::from_str(&repr).unwrap();
+            match parsed {
+                crate::EncryptionKey::Aes256Gcm(parsed) => assert_eq!(parsed.key, key.key),
+                _ => panic!("wrong key kind for {encoding:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn generate_unique() {
+        assert_ne!(Aes256Gcm::generate().key, Aes256Gcm::generate().key);
+    }
+
+    #[test]
+    fn deserialize_wrong_length() {
+        let repr = "
+key = [0, 1, 2, 3]
+        ";
+
+        assert!(toml::from_str::<Aes256Gcm>(repr).is_err());
+    }
+
+    fn key() -> (Aes256Gcm, Aes256Gcm) {
+        let key = Aes256Gcm { key: [42; 32] };
+        (key.clone(), key)
+    }
+
+    encryption_test!(basic_round_trip, key);
+
+    #[test]
+    fn key_mismatch() {
+        let id = Bytes::from("test");
+        let plaintext = Bytes::from("hello world");
+
+        let ciphertext = Aes256Gcm { key: [42; 32] }
+            .encrypt(id.clone(), plaintext)
+            .unwrap();
+
+        let result = Aes256Gcm { key: [24; 32] }.decrypt(id, ciphertext);
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "AES-GCM error: Failed to seal or open ciphertext"
+        );
+    }
+
+    #[test]
+    fn id_mismatch() {
+        let (key, _) = key();
+
+        let ciphertext = key
+            .encrypt(Bytes::from("test"), Bytes::from("hello world"))
+            .unwrap();
+
+        let result = key.decrypt(Bytes::from("not test"), ciphertext);
+
+        assert!(result.is_err());
+    }
+}
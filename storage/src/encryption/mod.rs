@@ -1,4 +1,8 @@
+mod aes256gcm;
+pub use self::aes256gcm::{Aes256Gcm, KeyEncoding};
+
 mod hpke;
+pub use self::hpke::Hpke;
 
 #[cfg(test)]
 mod test;
@@ -16,8 +20,15 @@ pub struct EncryptionConfig {
 pub(crate) trait KeyOperations {
     fn encrypt(&self, id: Bytes, data: Bytes) -> StorageResult<Bytes>;
     fn decrypt(&self, id: Bytes, data: Bytes) -> StorageResult<Bytes>;
+
+    /// Key used to hash content that is encrypted with this key, such that the hash does not
+    /// reveal the content to anyone who does not have the key.
+    fn hash_key(&self) -> Option<[u8; 32]>;
 }
 
+/// Context used to derive hash keys from encryption keys.
+const HASH_KEY_CONTEXT: &str = "satori 2025-01-01 content hash key";
+
 impl KeyOperations for Option<EncryptionKey> {
     fn encrypt(&self, id: Bytes, data: Bytes) -> StorageResult<Bytes> {
         match &self {
@@ -32,24 +43,38 @@ impl KeyOperations for Option<EncryptionKey> {
             None => Ok(data),
         }
     }
+
+    fn hash_key(&self) -> Option<[u8; 32]> {
+        self.as_ref().and_then(KeyOperations::hash_key)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum EncryptionKey {
     Hpke(hpke::Hpke),
+    Aes256Gcm(aes256gcm::Aes256Gcm),
 }
 
 impl KeyOperations for EncryptionKey {
     fn encrypt(&self, id: Bytes, data: Bytes) -> StorageResult<Bytes> {
         match &self {
             Self::Hpke(k) => k.encrypt(id, data),
+            Self::Aes256Gcm(k) => k.encrypt(id, data),
         }
     }
 
     fn decrypt(&self, id: Bytes, data: Bytes) -> StorageResult<Bytes> {
         match &self {
             Self::Hpke(k) => k.decrypt(id, data),
+            Self::Aes256Gcm(k) => k.decrypt(id, data),
+        }
+    }
+
+    fn hash_key(&self) -> Option<[u8; 32]> {
+        match &self {
+            Self::Hpke(k) => k.hash_key(),
+            Self::Aes256Gcm(k) => k.hash_key(),
         }
     }
 }
@@ -74,4 +99,7 @@ pub(crate) mod info {
             .to_owned()
             .into()
     }
+    pub(crate) fn blob_info_from_hash(hash: &str) -> Bytes {
+        format!("blob {hash}").as_bytes().to_owned().into()
+    }
 }
@@ -24,9 +24,15 @@ pub enum StorageError {
     #[error("S3 storage failure code {0}")]
     S3Failure(u16),
 
+    #[error("S3 object \"{0}\" has no valid last modified time")]
+    S3InvalidLastModified(String),
+
     #[error("Camera with name \"{0}\" was not found")]
     NoSuchCamera(String),
 
+    #[error("Name is not valid for use in storage: \"{0}\"")]
+    InvalidName(String),
+
     #[error("A camera was not specified, but is required to be")]
     CameraMustBeSpecified,
 
@@ -38,6 +44,9 @@ pub enum StorageError {
     #[error("A requested item was not found")]
     NotFound,
 
+    #[error("Operation is not supported: {0}")]
+    Unsupported(&'static str),
+
     #[error("A key that is required to perform an en/decrption operation is not provided")]
     KeyMissing,
 
@@ -49,6 +58,9 @@ pub enum StorageError {
 
     #[error("HPKE error: {0}")]
     HpkeError(#[from] hpke::HpkeError),
+
+    #[error("AES-GCM error: Failed to seal or open ciphertext")]
+    AesGcmError,
 }
 
 pub type StorageResult<T> = Result<T, StorageError>;
@@ -1,19 +1,37 @@
+mod blob;
+
 mod encryption;
-pub use self::encryption::{EncryptionConfig, EncryptionKey};
+pub use self::encryption::{Aes256Gcm, EncryptionConfig, EncryptionKey, Hpke, KeyEncoding};
 
 pub mod error;
 pub use self::error::{StorageError, StorageResult};
 
+mod names;
+pub use self::names::{validate_camera_name, validate_filename};
+
+mod note;
+pub use self::note::EventNote;
+
 mod providers;
 pub use self::providers::Provider;
 
+mod stats;
+pub use self::stats::{ObjectStats, StorageStats};
+
+mod retry;
+pub use self::retry::{RetryConfig, METRIC_RETRIES};
+
 pub mod workflows;
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use satori_common::Event;
-use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
@@ -33,14 +51,87 @@ impl StorageConfig {
     }
 }
 
+/// Details of a stored event or video segment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ObjectMetadata {
+    pub filename: PathBuf,
+    /// Size of the stored object in bytes, including any encryption overhead.
+    pub size: u64,
+}
+
+/// A page of filenames from a listing, in lexicographical order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Page {
+    pub items: Vec<PathBuf>,
+    /// Filename to list the next page after, `None` if this is the last page.
+    pub next: Option<PathBuf>,
+}
+
+/// Number of items per page used when paging through a complete listing.
+pub const DEFAULT_PAGE_SIZE: usize = 1000;
+
+impl Page {
+    /// Takes a page from a complete, sorted listing.
+    pub(crate) fn from_sorted(
+        items: Vec<PathBuf>,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> Self {
+        let mut items: Vec<PathBuf> = items
+            .into_iter()
+            .filter(|i| start_after.is_none_or(|s| i.as_path() > s))
+            .take(limit.saturating_add(1))
+            .collect();
+
+        let next = if items.len() > limit {
+            items.truncate(limit);
+            items.last().cloned()
+        } else {
+            None
+        };
+
+        Self { items, next }
+    }
+}
+
 #[async_trait]
 pub trait StorageProvider {
+    /// Stores an event, replacing any existing event with the same filename.
+    ///
+    /// Like all other writes, this never leaves a partially written object: the local provider
+    /// writes to a temporary file and renames it over the target, and object stores only make an
+    /// object visible once it has been completely uploaded.
     async fn put_event(&self, event: &Event) -> StorageResult<()>;
     async fn list_events(&self) -> StorageResult<Vec<PathBuf>>;
+    async fn list_events_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>>;
+
+    /// Lists at most `limit` event filenames that sort after `start_after`.
+    ///
+    /// By default every event is listed then a page taken from the result, providers that can
+    /// list a range of objects may override this.
+    async fn list_events_paginated(
+        &self,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> StorageResult<Page> {
+        Ok(Page::from_sorted(
+            self.list_events().await?,
+            start_after,
+            limit,
+        ))
+    }
     async fn get_event(&self, filename: &Path) -> StorageResult<Event>;
     async fn delete_event(&self, event: &Event) -> StorageResult<()>;
     async fn delete_event_filename(&self, filename: &Path) -> StorageResult<()>;
 
+    /// Stores a note for the event with a given filename, replacing any existing note.
+    ///
+    /// Notes are stored separately from the event, so are unaffected by the event being stored
+    /// again.
+    async fn put_event_note(&self, filename: &Path, note: &EventNote) -> StorageResult<()>;
+    /// Retrieves the note for the event with a given filename, if it has one.
+    async fn get_event_note(&self, filename: &Path) -> StorageResult<Option<EventNote>>;
+
     async fn list_cameras(&self) -> StorageResult<Vec<String>>;
 
     async fn put_segment(
@@ -50,6 +141,129 @@ pub trait StorageProvider {
         data: Bytes,
     ) -> StorageResult<()>;
     async fn list_segments(&self, camera_name: &str) -> StorageResult<Vec<PathBuf>>;
+
+    /// Lists at most `limit` segment filenames for a camera that sort after `start_after`.
+    ///
+    /// By default every segment is listed then a page taken from the result, providers that can
+    /// list a range of objects may override this.
+    async fn list_segments_paginated(
+        &self,
+        camera_name: &str,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> StorageResult<Page> {
+        Ok(Page::from_sorted(
+            self.list_segments(camera_name).await?,
+            start_after,
+            limit,
+        ))
+    }
+    async fn list_segments_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>>;
+    /// Lists segments along with the size of the object stored for each.
+    ///
+    /// Unlike [`StorageProvider::list_segments_with_meta`] this is the size of the pointer, not
+    /// of the content, for content addressed segments. By default segments are assumed to never
+    /// be content addressed.
+    async fn list_segment_objects_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        self.list_segments_with_meta(camera_name).await
+    }
     async fn get_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes>;
     async fn delete_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<()>;
+
+    /// Deletes several segments for a single camera.
+    ///
+    /// Returns the filename and error of each segment that could not be deleted.
+    /// By default segments are deleted one at a time, providers that support deleting multiple
+    /// objects in a single request may override this.
+    async fn delete_segments_batch(
+        &self,
+        camera_name: &str,
+        filenames: &[PathBuf],
+    ) -> Vec<(PathBuf, StorageError)> {
+        let mut errors = Vec::new();
+        for filename in filenames {
+            if let Err(err) = self.delete_segment(camera_name, filename).await {
+                errors.push((filename.clone(), err));
+            }
+        }
+        errors
+    }
+
+    /// Lists the hashes of all blobs holding the content of content addressed segments.
+    async fn list_blobs(&self) -> StorageResult<Vec<String>>;
+    /// Lists all blobs along with their stored size, the filename of each is the hash of the blob.
+    async fn list_blobs_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>>;
+    /// Retrieves the hash of the blob that a segment refers to, if the segment is content
+    /// addressed.
+    async fn get_segment_blob(
+        &self,
+        camera_name: &str,
+        filename: &Path,
+    ) -> StorageResult<Option<String>>;
+    async fn get_blob(&self, hash: &str) -> StorageResult<Bytes>;
+    /// Retrieves when a blob was last written or reused by a segment.
+    async fn get_blob_modified(&self, hash: &str) -> StorageResult<DateTime<Utc>>;
+    /// Stores a blob, replacing any existing blob with the same hash.
+    async fn put_blob(&self, hash: &str, data: Bytes) -> StorageResult<()>;
+    async fn delete_blob(&self, hash: &str) -> StorageResult<()>;
+
+    /// Generates a URL from which an event can be downloaded directly for a limited time.
+    ///
+    /// This bypasses the encryption applied by Satori, so is only possible when events are not
+    /// encrypted and the backend is able to sign URLs.
+    async fn presigned_event_url(
+        &self,
+        filename: &Path,
+        expiry: Duration,
+    ) -> StorageResult<String> {
+        let _ = (filename, expiry);
+        Err(StorageError::Unsupported(
+            "presigned URLs are not supported by this storage backend",
+        ))
+    }
+
+    /// Generates a URL from which a segment can be downloaded directly for a limited time.
+    ///
+    /// This bypasses the encryption applied by Satori, so is only possible when segments are not
+    /// encrypted and the backend is able to sign URLs.
+    async fn presigned_segment_url(
+        &self,
+        camera_name: &str,
+        filename: &Path,
+        expiry: Duration,
+    ) -> StorageResult<String> {
+        let _ = (camera_name, filename, expiry);
+        Err(StorageError::Unsupported(
+            "presigned URLs are not supported by this storage backend",
+        ))
+    }
+
+    /// Calculates the number and stored size of events, of the segments of each camera and of
+    /// blobs.
+    ///
+    /// Sizes are those of the stored objects, so include any encryption overhead. When segments
+    /// are content addressed, only the pointers are counted against each camera and the content
+    /// is counted once per blob, regardless of how many segments share it.
+    async fn storage_stats(&self) -> StorageResult<StorageStats> {
+        let mut stats = StorageStats {
+            events: ObjectStats::from_objects(&self.list_events_with_meta().await?),
+            blobs: ObjectStats::from_objects(&self.list_blobs_with_meta().await?),
+            ..Default::default()
+        };
+
+        for camera in self.list_cameras().await? {
+            let segments = self.list_segment_objects_with_meta(&camera).await?;
+            stats
+                .cameras
+                .insert(camera, ObjectStats::from_objects(&segments));
+        }
+
+        Ok(stats)
+    }
 }
@@ -0,0 +1,80 @@
+use crate::{StorageError, StorageResult};
+use std::path::{Component, Path};
+
+/// Checks that a camera name only contains characters that are safe to use as a component of a
+/// storage path (ASCII alphanumerics, `_` and `-`).
+pub fn validate_camera_name(name: &str) -> StorageResult<()> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidName(name.to_owned()))
+    }
+}
+
+/// Checks that a filename is a single plain path component, i.e. cannot refer to a file outside
+/// of the directory (or prefix) it is used in.
+pub fn validate_filename(filename: &Path) -> StorageResult<()> {
+    let mut components = filename.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(StorageError::InvalidName(filename.display().to_string())),
+    }
+}
+
+/// Checks both the camera name and filename of a segment.
+pub(crate) fn validate_segment_name(camera_name: &str, filename: &Path) -> StorageResult<()> {
+    validate_camera_name(camera_name)?;
+    validate_filename(filename)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_camera_name() {
+        for name in ["camera1", "front-door", "Back_Yard"] {
+            assert!(validate_camera_name(name).is_ok(), "{name}");
+        }
+
+        for name in [
+            "",
+            ".",
+            "..",
+            "../events",
+            "camera/1",
+            "camera\\1",
+            "camera 1",
+            "caméra",
+        ] {
+            assert!(
+                matches!(
+                    validate_camera_name(name),
+                    Err(StorageError::InvalidName(_))
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_filename() {
+        for filename in ["a_file.ts", "2023-03-01T12_00_00+0000_event 1.json"] {
+            assert!(validate_filename(Path::new(filename)).is_ok(), "{filename}");
+        }
+
+        for filename in ["", ".", "..", "../a_file.ts", "dir/a_file.ts", "/a_file.ts"] {
+            assert!(
+                matches!(
+                    validate_filename(Path::new(filename)),
+                    Err(StorageError::InvalidName(_))
+                ),
+                "{filename}"
+            );
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const NOTE_SUFFIX: &str = ".note.json";
+
+/// Free text annotation of an event, stored separately from the event itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventNote {
+    pub text: String,
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+impl EventNote {
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            timestamp: chrono::Utc::now().into(),
+        }
+    }
+}
+
+/// Filename of the note for the event with a given filename.
+pub(crate) fn note_filename(event_filename: &Path) -> PathBuf {
+    format!("{}{NOTE_SUFFIX}", event_filename.display()).into()
+}
+
+pub(crate) fn is_note_filename(filename: &Path) -> bool {
+    filename.to_string_lossy().ends_with(NOTE_SUFFIX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_note_filename() {
+        let filename = note_filename(Path::new("2023-03-01T12:00:00+00:00_test-1.json"));
+
+        assert_eq!(
+            filename,
+            PathBuf::from("2023-03-01T12:00:00+00:00_test-1.json.note.json")
+        );
+        assert!(is_note_filename(&filename));
+        assert!(!is_note_filename(Path::new(
+            "2023-03-01T12:00:00+00:00_test-1.json"
+        )));
+    }
+}
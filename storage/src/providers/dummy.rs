@@ -1,6 +1,7 @@
-use crate::{StorageError, StorageProvider, StorageResult};
+use crate::{EventNote, ObjectMetadata, StorageError, StorageProvider, StorageResult};
 use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use satori_common::Event;
 use serde::Deserialize;
 use std::{
@@ -12,6 +13,8 @@ use std::{
 #[derive(Debug, Default, Deserialize)]
 struct State {
     events: HashMap<PathBuf, Event>,
+    #[serde(default)]
+    notes: HashMap<PathBuf, EventNote>,
     segments: HashMap<String, HashMap<PathBuf, Bytes>>,
 }
 
@@ -59,6 +62,26 @@ impl StorageProvider for DummyStorage {
         Ok(events)
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn list_events_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        let mut events: Vec<ObjectMetadata> = self
+            .state
+            .lock()
+            .unwrap()
+            .events
+            .iter()
+            .map(|(filename, event)| {
+                // The size an unencrypted event would be stored as
+                Ok(ObjectMetadata {
+                    filename: filename.to_owned(),
+                    size: serde_json::to_vec_pretty(event)?.len() as u64,
+                })
+            })
+            .collect::<StorageResult<_>>()?;
+        events.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(events)
+    }
+
     #[tracing::instrument(skip(self))]
     async fn get_event(&self, filename: &Path) -> StorageResult<Event> {
         self.state
@@ -78,10 +101,27 @@ impl StorageProvider for DummyStorage {
 
     #[tracing::instrument(skip(self))]
     async fn delete_event_filename(&self, filename: &Path) -> StorageResult<()> {
-        self.state.lock().unwrap().events.remove(filename);
+        let mut state = self.state.lock().unwrap();
+        state.events.remove(filename);
+        state.notes.remove(filename);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn put_event_note(&self, filename: &Path, note: &EventNote) -> StorageResult<()> {
+        self.state
+            .lock()
+            .unwrap()
+            .notes
+            .insert(filename.into(), note.to_owned());
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn get_event_note(&self, filename: &Path) -> StorageResult<Option<EventNote>> {
+        Ok(self.state.lock().unwrap().notes.get(filename).cloned())
+    }
+
     #[tracing::instrument(skip(self))]
     async fn list_cameras(&self) -> StorageResult<Vec<String>> {
         let mut cameras: Vec<String> = self
@@ -136,6 +176,28 @@ impl StorageProvider for DummyStorage {
         Ok(segments)
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn list_segments_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        let mut segments: Vec<ObjectMetadata> = self
+            .state
+            .lock()
+            .unwrap()
+            .segments
+            .get(camera_name)
+            .ok_or(StorageError::NotFound)?
+            .iter()
+            .map(|(filename, data)| ObjectMetadata {
+                filename: filename.to_owned(),
+                size: data.len() as u64,
+            })
+            .collect();
+        segments.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(segments)
+    }
+
     #[tracing::instrument(skip(self))]
     async fn get_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes> {
         Ok(self
@@ -163,6 +225,47 @@ impl StorageProvider for DummyStorage {
         }
         Ok(())
     }
+
+    // Segments are never content addressed, so there are never any blobs
+
+    #[tracing::instrument(skip(self))]
+    async fn list_blobs(&self) -> StorageResult<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_blobs_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        Ok(Vec::new())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_segment_blob(
+        &self,
+        _camera_name: &str,
+        _filename: &Path,
+    ) -> StorageResult<Option<String>> {
+        Ok(None)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_blob(&self, _hash: &str) -> StorageResult<Bytes> {
+        Err(StorageError::NotFound)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_blob_modified(&self, _hash: &str) -> StorageResult<DateTime<Utc>> {
+        Err(StorageError::NotFound)
+    }
+
+    #[tracing::instrument(skip(self, _data))]
+    async fn put_blob(&self, _hash: &str, _data: Bytes) -> StorageResult<()> {
+        Err(StorageError::NotFound)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_blob(&self, _hash: &str) -> StorageResult<()> {
+        Err(StorageError::NotFound)
+    }
 }
 
 #[cfg(test)]
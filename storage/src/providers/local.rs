@@ -1,11 +1,16 @@
-use crate::{encryption::KeyOperations, EncryptionConfig, StorageProvider, StorageResult};
+use crate::{
+    blob::{self, SegmentPointer},
+    encryption::KeyOperations,
+    note, EncryptionConfig, EventNote, ObjectMetadata, StorageProvider, StorageResult,
+};
 use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use satori_common::Event;
 use serde::Deserialize;
 use std::{
     fs::File,
-    io::{Read, Write},
+    io::Read,
     path::{Path, PathBuf},
 };
 use tracing::warn;
@@ -15,24 +20,36 @@ pub struct LocalConfig {
     path: PathBuf,
     #[serde(default)]
     encryption: EncryptionConfig,
+    /// Store the content of segments in blobs addressed by their hash, such that identical
+    /// segments are only stored once.
+    ///
+    /// With segment encryption the hash is keyed by the encryption key, so blob names do not
+    /// reveal the content to anyone without the key. For HPKE this is the public key.
+    #[serde(default)]
+    content_addressed: bool,
 }
 
 #[derive(Clone)]
 pub struct LocalStorage {
     event_directory: PathBuf,
     segment_directory: PathBuf,
+    blob_directory: PathBuf,
     encryption: EncryptionConfig,
+    content_addressed: bool,
 }
 
 impl LocalStorage {
     pub fn new(config: LocalConfig) -> Self {
         let event_directory = config.path.join("events");
         let segment_directory = config.path.join("segments");
+        let blob_directory = config.path.join("blobs");
 
         let storage = Self {
             event_directory,
             segment_directory,
+            blob_directory,
             encryption: config.encryption,
+            content_addressed: config.content_addressed,
         };
 
         storage.make_directories();
@@ -40,9 +57,17 @@ impl LocalStorage {
         storage
     }
 
+    pub(crate) fn with_encryption(&self, encryption: EncryptionConfig) -> Self {
+        Self {
+            encryption,
+            ..self.clone()
+        }
+    }
+
     fn make_directories(&self) {
         std::fs::create_dir_all(&self.event_directory).unwrap();
         std::fs::create_dir_all(&self.segment_directory).unwrap();
+        std::fs::create_dir_all(&self.blob_directory).unwrap();
     }
 
     fn get_event_filename(&self, event: &Event) -> PathBuf {
@@ -56,6 +81,24 @@ impl LocalStorage {
     fn get_segment_filename(&self, camera_name: &str, filename: &Path) -> PathBuf {
         self.get_segment_directory(camera_name).join(filename)
     }
+
+    fn get_blob_filename(&self, hash: &str) -> PathBuf {
+        self.blob_directory.join(hash)
+    }
+
+    /// Reads and decrypts a segment, without following any pointer to a blob.
+    fn read_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes> {
+        let info =
+            crate::encryption::info::segment_info_from_camera_and_filename(camera_name, filename);
+
+        let filename = self.get_segment_filename(camera_name, filename);
+
+        let mut file = File::open(filename)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        self.encryption.segment.decrypt(info, data.into())
+    }
 }
 
 #[async_trait]
@@ -66,20 +109,35 @@ impl StorageProvider for LocalStorage {
             crate::encryption::info::event_info_from_filename(&event.metadata.get_filename());
 
         let filename = self.get_event_filename(event);
-        let mut file = File::create(filename)?;
 
         let data = serde_json::to_vec_pretty(&event)?;
 
         let data = self.encryption.event.encrypt(info, data.into())?;
 
-        file.write_all(&data)?;
+        satori_common::write_file_atomic(&filename, &data)?;
 
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
     async fn list_events(&self) -> StorageResult<Vec<PathBuf>> {
-        list_dir(&self.event_directory, "json")
+        let mut events = list_dir(&self.event_directory, |p| {
+            p.extension() == Some(std::ffi::OsStr::new("json"))
+        })?;
+        events.retain(|f| !note::is_note_filename(f));
+        Ok(events)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_events_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        self.list_events()
+            .await?
+            .into_iter()
+            .map(|filename| {
+                let size = std::fs::metadata(self.event_directory.join(&filename))?.len();
+                Ok(ObjectMetadata { filename, size })
+            })
+            .collect()
     }
 
     #[tracing::instrument(skip(self))]
@@ -99,17 +157,53 @@ impl StorageProvider for LocalStorage {
 
     #[tracing::instrument(skip(self))]
     async fn delete_event(&self, event: &Event) -> StorageResult<()> {
-        let filename = self.get_event_filename(event);
-        self.delete_event_filename(&filename).await
+        self.delete_event_filename(&event.metadata.get_filename())
+            .await
     }
 
     #[tracing::instrument(skip(self))]
     async fn delete_event_filename(&self, filename: &Path) -> StorageResult<()> {
-        let filename = self.event_directory.join(filename);
-        std::fs::remove_file(filename)?;
+        std::fs::remove_file(self.event_directory.join(filename))?;
+
+        // Delete the note of the event too, if it has one
+        match std::fs::remove_file(self.event_directory.join(note::note_filename(filename))) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(skip(self, note))]
+    async fn put_event_note(&self, filename: &Path, note: &EventNote) -> StorageResult<()> {
+        let note_filename = note::note_filename(filename);
+        let info = crate::encryption::info::event_info_from_filename(&note_filename);
+
+        let data = serde_json::to_vec_pretty(&note)?;
+        let data = self.encryption.event.encrypt(info, data.into())?;
+
+        satori_common::write_file_atomic(&self.event_directory.join(note_filename), &data)?;
+
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn get_event_note(&self, filename: &Path) -> StorageResult<Option<EventNote>> {
+        let note_filename = note::note_filename(filename);
+        let info = crate::encryption::info::event_info_from_filename(&note_filename);
+
+        let mut file = match File::open(self.event_directory.join(note_filename)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let data = self.encryption.event.decrypt(info, data.into())?;
+
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
     #[tracing::instrument(skip(self))]
     async fn list_cameras(&self) -> StorageResult<Vec<String>> {
         list_dir_dirs(&self.segment_directory)
@@ -128,11 +222,28 @@ impl StorageProvider for LocalStorage {
         let dir = self.get_segment_directory(camera_name);
         std::fs::create_dir_all(&dir)?;
 
+        let data = if self.content_addressed {
+            let hash = blob::blob_hash(self.encryption.segment.hash_key(), &data);
+
+            // Identical content has already been stored, only the pointer needs to be written.
+            // The blob is touched so that it is not pruned before the pointer is written.
+            match touch(&self.get_blob_filename(&hash)) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    self.put_blob(&hash, data).await?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+
+            SegmentPointer::new(hash).to_bytes()?
+        } else {
+            data
+        };
+
         let filename = dir.join(filename);
-        let mut file = File::create(filename)?;
 
         let data = self.encryption.segment.encrypt(info, data)?;
-        file.write_all(&data)?;
+        satori_common::write_file_atomic(&filename, &data)?;
 
         Ok(())
     }
@@ -140,23 +251,54 @@ impl StorageProvider for LocalStorage {
     #[tracing::instrument(skip(self))]
     async fn list_segments(&self, camera_name: &str) -> StorageResult<Vec<PathBuf>> {
         let dir = self.get_segment_directory(camera_name);
-        list_dir(&dir, "ts")
+        list_dir(&dir, satori_common::is_segment_file)
     }
 
     #[tracing::instrument(skip(self))]
-    async fn get_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes> {
-        let info =
-            crate::encryption::info::segment_info_from_camera_and_filename(camera_name, filename);
+    async fn list_segments_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        let mut segments = self.list_segment_objects_with_meta(camera_name).await?;
+
+        // Report the size of the content rather than that of the pointer
+        if self.content_addressed {
+            for segment in &mut segments {
+                if let Some(pointer) =
+                    SegmentPointer::from_bytes(&self.read_segment(camera_name, &segment.filename)?)
+                {
+                    segment.size = std::fs::metadata(self.get_blob_filename(&pointer.blob))?.len();
+                }
+            }
+        }
 
-        let filename = self.get_segment_filename(camera_name, filename);
+        Ok(segments)
+    }
 
-        let mut file = File::open(filename)?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+    #[tracing::instrument(skip(self))]
+    async fn list_segment_objects_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        let dir = self.get_segment_directory(camera_name);
 
-        let data = self.encryption.segment.decrypt(info, data.into())?;
+        list_dir(&dir, satori_common::is_segment_file)?
+            .into_iter()
+            .map(|filename| {
+                let size = std::fs::metadata(dir.join(&filename))?.len();
+                Ok(ObjectMetadata { filename, size })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes> {
+        let data = self.read_segment(camera_name, filename)?;
 
-        Ok(data)
+        match SegmentPointer::from_bytes(&data) {
+            Some(pointer) => self.get_blob(&pointer.blob).await,
+            None => Ok(data),
+        }
     }
 
     #[tracing::instrument(skip(self))]
@@ -178,15 +320,88 @@ impl StorageProvider for LocalStorage {
 
         Ok(())
     }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_blobs(&self) -> StorageResult<Vec<String>> {
+        let mut blobs: Vec<String> = std::fs::read_dir(&self.blob_directory)?
+            .filter_map(|p| p.ok())
+            .map(|p| p.path())
+            .filter(|p| p.is_file())
+            .filter_map(|p| p.file_name().and_then(|f| f.to_str()).map(|f| f.to_owned()))
+            // Skip temporary files left by an interrupted write
+            .filter(|f| !f.starts_with('.'))
+            .collect();
+        blobs.sort();
+        Ok(blobs)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_blobs_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        self.list_blobs()
+            .await?
+            .into_iter()
+            .map(|hash| {
+                let size = std::fs::metadata(self.get_blob_filename(&hash))?.len();
+                Ok(ObjectMetadata {
+                    filename: hash.into(),
+                    size,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_segment_blob(
+        &self,
+        camera_name: &str,
+        filename: &Path,
+    ) -> StorageResult<Option<String>> {
+        let data = self.read_segment(camera_name, filename)?;
+        Ok(SegmentPointer::from_bytes(&data).map(|p| p.blob))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_blob(&self, hash: &str) -> StorageResult<Bytes> {
+        let info = crate::encryption::info::blob_info_from_hash(hash);
+
+        let mut file = File::open(self.get_blob_filename(hash))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        self.encryption.segment.decrypt(info, data.into())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_blob_modified(&self, hash: &str) -> StorageResult<DateTime<Utc>> {
+        Ok(std::fs::metadata(self.get_blob_filename(hash))?
+            .modified()?
+            .into())
+    }
+
+    #[tracing::instrument(skip(self, data))]
+    async fn put_blob(&self, hash: &str, data: Bytes) -> StorageResult<()> {
+        let info = crate::encryption::info::blob_info_from_hash(hash);
+
+        let data = self.encryption.segment.encrypt(info, data)?;
+        satori_common::write_file_atomic(&self.get_blob_filename(hash), &data)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_blob(&self, hash: &str) -> StorageResult<()> {
+        std::fs::remove_file(self.get_blob_filename(hash))?;
+        Ok(())
+    }
 }
 
-#[tracing::instrument]
-fn list_dir(dir: &Path, ext: &str) -> StorageResult<Vec<PathBuf>> {
+#[tracing::instrument(skip(filter))]
+fn list_dir(dir: &Path, filter: impl Fn(&Path) -> bool) -> StorageResult<Vec<PathBuf>> {
     let mut contents: Vec<PathBuf> = std::fs::read_dir(dir)?
         .filter_map(|p| match p.as_ref() {
             Ok(p) => {
                 let md = p.path();
-                if md.is_file() && md.extension() == Some(std::ffi::OsStr::new(ext)) {
+                if md.is_file() && filter(&md) {
                     Some(md.file_name().unwrap().into())
                 } else {
                     None
@@ -199,6 +414,14 @@ fn list_dir(dir: &Path, ext: &str) -> StorageResult<Vec<PathBuf>> {
     Ok(contents)
 }
 
+/// Sets the modification time of an existing file to now, without changing its content.
+fn touch(path: &Path) -> std::io::Result<()> {
+    File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(std::time::SystemTime::now())
+}
+
 #[tracing::instrument]
 fn list_dir_dirs(dir: &Path) -> StorageResult<Vec<String>> {
     let mut contents: Vec<String> = std::fs::read_dir(dir)?
@@ -208,7 +431,7 @@ fn list_dir_dirs(dir: &Path) -> StorageResult<Vec<String>> {
                 if md.is_dir() {
                     Some(
                         md.components()
-                            .last()
+                            .next_back()
                             .unwrap()
                             .as_os_str()
                             .to_str()
@@ -245,6 +468,7 @@ mod test {
                     let provider = crate::StorageConfig::Local(LocalConfig {
                         path: temp_dir.path().to_owned(),
                         encryption: EncryptionConfig::default(),
+                        content_addressed: false,
                     })
                     .create_provider();
 
@@ -299,6 +523,67 @@ MC4CAQAwBQYDK2VuBCIEILhAcPMmERCi9QmBwH26wXzVo/6e5Lqw9lvA+8hf//xJ
 ",
                         )
                         .unwrap(),
+                        content_addressed: false,
+                    })
+                    .create_provider();
+
+                    crate::providers::test::$test(provider).await;
+                }
+            };
+        }
+
+        crate::providers::test::all_storage_tests!(test);
+    }
+
+    mod encryption_aes256_gcm {
+        use super::*;
+
+        macro_rules! test {
+            ( $test:ident ) => {
+                #[tokio::test]
+                async fn $test() {
+                    let temp_dir = tempfile::Builder::new()
+                        .prefix("satori_local_storage_test")
+                        .tempdir()
+                        .unwrap();
+
+                    let provider = crate::StorageConfig::Local(LocalConfig {
+                        path: temp_dir.path().to_owned(),
+                        encryption: toml::from_str(
+                            crate::providers::test::AES256_GCM_ENCRYPTION_CONFIG,
+                        )
+                        .unwrap(),
+                        content_addressed: false,
+                    })
+                    .create_provider();
+
+                    crate::providers::test::$test(provider).await;
+                }
+            };
+        }
+
+        crate::providers::test::all_storage_tests!(test);
+    }
+
+    mod content_addressed {
+        use super::*;
+
+        macro_rules! test {
+            ( $test:ident ) => {
+                #[tokio::test]
+                async fn $test() {
+                    let temp_dir = tempfile::Builder::new()
+                        .prefix("satori_local_storage_test")
+                        .tempdir()
+                        .unwrap();
+
+                    let provider = crate::StorageConfig::Local(LocalConfig {
+                        path: temp_dir.path().to_owned(),
+                        encryption: toml::from_str(
+                            crate::providers::test::AES256_GCM_ENCRYPTION_CONFIG,
+                        )
+                        .unwrap(),
+                        content_addressed: true,
                     })
                     .create_provider();
 
@@ -308,5 +593,312 @@ MC4CAQAwBQYDK2VuBCIEILhAcPMmERCi9QmBwH26wXzVo/6e5Lqw9lvA+8hf//xJ
         }
 
         crate::providers::test::all_storage_tests!(test);
+
+        #[tokio::test]
+        async fn test_identical_segments_stored_once() {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("satori_local_storage_test")
+                .tempdir()
+                .unwrap();
+
+            let encryption: EncryptionConfig =
+                toml::from_str(crate::providers::test::AES256_GCM_ENCRYPTION_CONFIG).unwrap();
+            let hash_key = encryption.segment.hash_key();
+
+            let provider = crate::StorageConfig::Local(LocalConfig {
+                path: temp_dir.path().to_owned(),
+                encryption,
+                content_addressed: true,
+            })
+            .create_provider();
+
+            let data = Bytes::from("segment");
+
+            provider
+                .put_segment("camera1", Path::new("1.ts"), data.clone())
+                .await
+                .unwrap();
+
+            // The blob is named by a hash keyed by the encryption key
+            let blobs = provider.list_blobs().await.unwrap();
+            assert_eq!(blobs, vec![crate::blob::blob_hash(hash_key, &data)]);
+            assert_ne!(blobs, vec![crate::blob::blob_hash(None, &data)]);
+
+            let blob_filename = temp_dir.path().join("blobs").join(&blobs[0]);
+            let blob_data = std::fs::read(&blob_filename).unwrap();
+            let blob_modified = provider.get_blob_modified(&blobs[0]).await.unwrap();
+
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+            provider
+                .put_segment("camera1", Path::new("2.ts"), data.clone())
+                .await
+                .unwrap();
+            provider
+                .put_segment("camera2", Path::new("1.ts"), data.clone())
+                .await
+                .unwrap();
+
+            // The blob was not written again (its content would differ due to encryption), but
+            // was marked as recently used
+            assert_eq!(provider.list_blobs().await.unwrap(), blobs);
+            assert_eq!(std::fs::read(&blob_filename).unwrap(), blob_data);
+            assert!(provider.get_blob_modified(&blobs[0]).await.unwrap() > blob_modified);
+
+            for (camera, filename) in [
+                ("camera1", "1.ts"),
+                ("camera1", "2.ts"),
+                ("camera2", "1.ts"),
+            ] {
+                assert_eq!(
+                    provider
+                        .get_segment(camera, Path::new(filename))
+                        .await
+                        .unwrap(),
+                    data
+                );
+                assert_eq!(
+                    provider
+                        .get_segment_blob(camera, Path::new(filename))
+                        .await
+                        .unwrap()
+                        .as_ref(),
+                    Some(&blobs[0])
+                );
+            }
+
+            // Different content is stored in a new blob
+            provider
+                .put_segment("camera1", Path::new("3.ts"), Bytes::from("other segment"))
+                .await
+                .unwrap();
+            assert_eq!(provider.list_blobs().await.unwrap().len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_storage_stats_shared_blob() {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("satori_local_storage_test")
+                .tempdir()
+                .unwrap();
+
+            let provider = crate::StorageConfig::Local(LocalConfig {
+                path: temp_dir.path().to_owned(),
+                encryption: EncryptionConfig::default(),
+                content_addressed: true,
+            })
+            .create_provider();
+
+            let data = Bytes::from(vec![0; 1000]);
+            for filename in ["1.ts", "2.ts"] {
+                provider
+                    .put_segment("camera1", Path::new(filename), data.clone())
+                    .await
+                    .unwrap();
+            }
+
+            let stats = provider.storage_stats().await.unwrap();
+
+            // Each segment is only a pointer to the blob
+            let pointer_size = std::fs::metadata(temp_dir.path().join("segments/camera1/1.ts"))
+                .unwrap()
+                .len();
+            assert!(pointer_size < 1000);
+            assert_eq!(
+                stats.cameras["camera1"],
+                crate::ObjectStats {
+                    count: 2,
+                    bytes: 2 * pointer_size,
+                }
+            );
+
+            // The shared content is counted once
+            assert_eq!(
+                stats.blobs,
+                crate::ObjectStats {
+                    count: 1,
+                    bytes: 1000,
+                }
+            );
+            assert_eq!(
+                stats.total(),
+                crate::ObjectStats {
+                    count: 3,
+                    bytes: 2 * pointer_size + 1000,
+                }
+            );
+        }
+
+        #[tokio::test]
+        async fn test_reads_segments_stored_without_content_addressing() {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("satori_local_storage_test")
+                .tempdir()
+                .unwrap();
+
+            let config = |content_addressed| LocalConfig {
+                path: temp_dir.path().to_owned(),
+                encryption: EncryptionConfig::default(),
+                content_addressed,
+            };
+
+            let provider = crate::StorageConfig::Local(config(false)).create_provider();
+            provider
+                .put_segment("camera1", Path::new("1.ts"), Bytes::from("segment 1"))
+                .await
+                .unwrap();
+
+            let provider = crate::StorageConfig::Local(config(true)).create_provider();
+            provider
+                .put_segment("camera1", Path::new("2.ts"), Bytes::from("segment 2"))
+                .await
+                .unwrap();
+
+            assert_eq!(
+                provider
+                    .get_segment("camera1", Path::new("1.ts"))
+                    .await
+                    .unwrap(),
+                Bytes::from("segment 1")
+            );
+            assert_eq!(
+                provider
+                    .get_segment_blob("camera1", Path::new("1.ts"))
+                    .await
+                    .unwrap(),
+                None
+            );
+
+            // Pointers are followed even once content addressing is disabled
+            let provider = crate::StorageConfig::Local(config(false)).create_provider();
+            assert_eq!(
+                provider
+                    .get_segment("camera1", Path::new("2.ts"))
+                    .await
+                    .unwrap(),
+                Bytes::from("segment 2")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_segments_batch_reports_errors() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("satori_local_storage_test")
+            .tempdir()
+            .unwrap();
+
+        let provider = crate::StorageConfig::Local(LocalConfig {
+            path: temp_dir.path().to_owned(),
+            encryption: EncryptionConfig::default(),
+            content_addressed: false,
+        })
+        .create_provider();
+
+        for filename in ["1.ts", "2.ts"] {
+            provider
+                .put_segment("camera1", Path::new(filename), Bytes::default())
+                .await
+                .unwrap();
+        }
+
+        let errors = provider
+            .delete_segments_batch(
+                "camera1",
+                &[
+                    PathBuf::from("1.ts"),
+                    PathBuf::from("missing.ts"),
+                    PathBuf::from("2.ts"),
+                ],
+            )
+            .await;
+
+        // Only the missing segment fails, all others are still deleted
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, PathBuf::from("missing.ts"));
+        assert!(matches!(errors[0].1, crate::StorageError::IOError(_)));
+
+        assert!(provider.list_cameras().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_write_leaves_previous_contents() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("satori_local_storage_test")
+            .tempdir()
+            .unwrap();
+
+        let provider = crate::StorageConfig::Local(LocalConfig {
+            path: temp_dir.path().to_owned(),
+            encryption: EncryptionConfig::default(),
+            content_addressed: true,
+        })
+        .create_provider();
+
+        provider
+            .put_segment("camera1", Path::new("1.ts"), Bytes::from("segment"))
+            .await
+            .unwrap();
+        let blob = provider.list_blobs().await.unwrap().remove(0);
+
+        // Simulate writes that were interrupted part way through
+        let segment_dir = temp_dir.path().join("segments/camera1");
+        std::fs::write(segment_dir.join(".1.ts.a1B2c3.tmp"), "seg").unwrap();
+        std::fs::write(segment_dir.join(".2.ts.d4E5f6.tmp"), "seg").unwrap();
+        std::fs::write(
+            temp_dir
+                .path()
+                .join("blobs")
+                .join(format!(".{blob}.g7H8i9.tmp")),
+            "seg",
+        )
+        .unwrap();
+
+        assert_eq!(
+            provider.list_segments("camera1").await.unwrap(),
+            vec![PathBuf::from("1.ts")]
+        );
+        assert_eq!(provider.list_blobs().await.unwrap(), vec![blob]);
+        assert_eq!(
+            provider
+                .get_segment("camera1", Path::new("1.ts"))
+                .await
+                .unwrap(),
+            Bytes::from("segment")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_presigned_url_unsupported() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("satori_local_storage_test")
+            .tempdir()
+            .unwrap();
+
+        let provider = crate::StorageConfig::Local(LocalConfig {
+            path: temp_dir.path().to_owned(),
+            encryption: EncryptionConfig::default(),
+            content_addressed: false,
+        })
+        .create_provider();
+
+        provider
+            .put_segment("camera1", Path::new("1.ts"), Bytes::default())
+            .await
+            .unwrap();
+
+        let expiry = std::time::Duration::from_secs(60);
+        assert!(matches!(
+            provider
+                .presigned_segment_url("camera1", Path::new("1.ts"), expiry)
+                .await,
+            Err(crate::StorageError::Unsupported(_))
+        ));
+        assert!(matches!(
+            provider
+                .presigned_event_url(Path::new("event.json"), expiry)
+                .await,
+            Err(crate::StorageError::Unsupported(_))
+        ));
     }
 }
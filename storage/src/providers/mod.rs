@@ -5,22 +5,47 @@ pub mod s3_object;
 #[cfg(test)]
 mod test;
 
-use super::{StorageProvider, StorageResult};
+use super::{
+    names::{validate_camera_name, validate_filename, validate_segment_name},
+    EncryptionConfig, EventNote, ObjectMetadata, Page, StorageError, StorageProvider,
+    StorageResult,
+};
 use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use satori_common::Event;
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Provider {
     Dummy(dummy::DummyStorage),
     Local(local::LocalStorage),
     S3(s3_object::S3Storage),
 }
 
+impl Provider {
+    /// Returns a copy of this provider that reads and writes using a different encryption
+    /// configuration.
+    ///
+    /// The dummy provider does not support encryption, so is returned unchanged.
+    pub fn with_encryption(&self, encryption: EncryptionConfig) -> Self {
+        match self {
+            Self::Dummy(p) => Self::Dummy(p.clone()),
+            Self::Local(p) => Self::Local(p.with_encryption(encryption)),
+            Self::S3(p) => Self::S3(p.with_encryption(encryption)),
+        }
+    }
+}
+
 #[async_trait]
 impl StorageProvider for Provider {
     async fn put_event(&self, event: &Event) -> StorageResult<()> {
+        validate_filename(&event.metadata.get_filename())?;
+
         match self {
             Self::Dummy(p) => p.put_event(event).await,
             Self::Local(p) => p.put_event(event).await,
@@ -36,7 +61,29 @@ impl StorageProvider for Provider {
         }
     }
 
+    async fn list_events_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        match self {
+            Self::Dummy(p) => p.list_events_with_meta().await,
+            Self::Local(p) => p.list_events_with_meta().await,
+            Self::S3(p) => p.list_events_with_meta().await,
+        }
+    }
+
+    async fn list_events_paginated(
+        &self,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> StorageResult<Page> {
+        match self {
+            Self::Dummy(p) => p.list_events_paginated(start_after, limit).await,
+            Self::Local(p) => p.list_events_paginated(start_after, limit).await,
+            Self::S3(p) => p.list_events_paginated(start_after, limit).await,
+        }
+    }
+
     async fn get_event(&self, filename: &Path) -> StorageResult<Event> {
+        validate_filename(filename)?;
+
         match self {
             Self::Dummy(p) => p.get_event(filename).await,
             Self::Local(p) => p.get_event(filename).await,
@@ -45,6 +92,8 @@ impl StorageProvider for Provider {
     }
 
     async fn delete_event(&self, event: &Event) -> StorageResult<()> {
+        validate_filename(&event.metadata.get_filename())?;
+
         match self {
             Self::Dummy(p) => p.delete_event(event).await,
             Self::Local(p) => p.delete_event(event).await,
@@ -53,6 +102,8 @@ impl StorageProvider for Provider {
     }
 
     async fn delete_event_filename(&self, filename: &Path) -> StorageResult<()> {
+        validate_filename(filename)?;
+
         match self {
             Self::Dummy(p) => p.delete_event_filename(filename).await,
             Self::Local(p) => p.delete_event_filename(filename).await,
@@ -60,6 +111,26 @@ impl StorageProvider for Provider {
         }
     }
 
+    async fn put_event_note(&self, filename: &Path, note: &EventNote) -> StorageResult<()> {
+        validate_filename(filename)?;
+
+        match self {
+            Self::Dummy(p) => p.put_event_note(filename, note).await,
+            Self::Local(p) => p.put_event_note(filename, note).await,
+            Self::S3(p) => p.put_event_note(filename, note).await,
+        }
+    }
+
+    async fn get_event_note(&self, filename: &Path) -> StorageResult<Option<EventNote>> {
+        validate_filename(filename)?;
+
+        match self {
+            Self::Dummy(p) => p.get_event_note(filename).await,
+            Self::Local(p) => p.get_event_note(filename).await,
+            Self::S3(p) => p.get_event_note(filename).await,
+        }
+    }
+
     async fn list_cameras(&self) -> StorageResult<Vec<String>> {
         match self {
             Self::Dummy(p) => p.list_cameras().await,
@@ -74,6 +145,8 @@ impl StorageProvider for Provider {
         filename: &Path,
         data: Bytes,
     ) -> StorageResult<()> {
+        validate_segment_name(camera_name, filename)?;
+
         match self {
             Self::Dummy(p) => p.put_segment(camera_name, filename, data).await,
             Self::Local(p) => p.put_segment(camera_name, filename, data).await,
@@ -82,6 +155,8 @@ impl StorageProvider for Provider {
     }
 
     async fn list_segments(&self, camera_name: &str) -> StorageResult<Vec<PathBuf>> {
+        validate_camera_name(camera_name)?;
+
         match self {
             Self::Dummy(p) => p.list_segments(camera_name).await,
             Self::Local(p) => p.list_segments(camera_name).await,
@@ -89,7 +164,59 @@ impl StorageProvider for Provider {
         }
     }
 
+    async fn list_segments_paginated(
+        &self,
+        camera_name: &str,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> StorageResult<Page> {
+        validate_camera_name(camera_name)?;
+
+        match self {
+            Self::Dummy(p) => {
+                p.list_segments_paginated(camera_name, start_after, limit)
+                    .await
+            }
+            Self::Local(p) => {
+                p.list_segments_paginated(camera_name, start_after, limit)
+                    .await
+            }
+            Self::S3(p) => {
+                p.list_segments_paginated(camera_name, start_after, limit)
+                    .await
+            }
+        }
+    }
+
+    async fn list_segments_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        validate_camera_name(camera_name)?;
+
+        match self {
+            Self::Dummy(p) => p.list_segments_with_meta(camera_name).await,
+            Self::Local(p) => p.list_segments_with_meta(camera_name).await,
+            Self::S3(p) => p.list_segments_with_meta(camera_name).await,
+        }
+    }
+
+    async fn list_segment_objects_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        validate_camera_name(camera_name)?;
+
+        match self {
+            Self::Dummy(p) => p.list_segment_objects_with_meta(camera_name).await,
+            Self::Local(p) => p.list_segment_objects_with_meta(camera_name).await,
+            Self::S3(p) => p.list_segment_objects_with_meta(camera_name).await,
+        }
+    }
+
     async fn get_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes> {
+        validate_segment_name(camera_name, filename)?;
+
         match self {
             Self::Dummy(p) => p.get_segment(camera_name, filename).await,
             Self::Local(p) => p.get_segment(camera_name, filename).await,
@@ -98,10 +225,125 @@ impl StorageProvider for Provider {
     }
 
     async fn delete_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<()> {
+        validate_segment_name(camera_name, filename)?;
+
         match self {
             Self::Dummy(p) => p.delete_segment(camera_name, filename).await,
             Self::Local(p) => p.delete_segment(camera_name, filename).await,
             Self::S3(p) => p.delete_segment(camera_name, filename).await,
         }
     }
+
+    async fn delete_segments_batch(
+        &self,
+        camera_name: &str,
+        filenames: &[PathBuf],
+    ) -> Vec<(PathBuf, StorageError)> {
+        let mut valid = Vec::new();
+        let mut errors = Vec::new();
+        for filename in filenames {
+            match validate_segment_name(camera_name, filename) {
+                Ok(()) => valid.push(filename.clone()),
+                Err(err) => errors.push((filename.clone(), err)),
+            }
+        }
+
+        errors.extend(match self {
+            Self::Dummy(p) => p.delete_segments_batch(camera_name, &valid).await,
+            Self::Local(p) => p.delete_segments_batch(camera_name, &valid).await,
+            Self::S3(p) => p.delete_segments_batch(camera_name, &valid).await,
+        });
+        errors
+    }
+
+    async fn list_blobs(&self) -> StorageResult<Vec<String>> {
+        match self {
+            Self::Dummy(p) => p.list_blobs().await,
+            Self::Local(p) => p.list_blobs().await,
+            Self::S3(p) => p.list_blobs().await,
+        }
+    }
+
+    async fn list_blobs_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        match self {
+            Self::Dummy(p) => p.list_blobs_with_meta().await,
+            Self::Local(p) => p.list_blobs_with_meta().await,
+            Self::S3(p) => p.list_blobs_with_meta().await,
+        }
+    }
+
+    async fn get_segment_blob(
+        &self,
+        camera_name: &str,
+        filename: &Path,
+    ) -> StorageResult<Option<String>> {
+        validate_segment_name(camera_name, filename)?;
+
+        match self {
+            Self::Dummy(p) => p.get_segment_blob(camera_name, filename).await,
+            Self::Local(p) => p.get_segment_blob(camera_name, filename).await,
+            Self::S3(p) => p.get_segment_blob(camera_name, filename).await,
+        }
+    }
+
+    async fn get_blob(&self, hash: &str) -> StorageResult<Bytes> {
+        match self {
+            Self::Dummy(p) => p.get_blob(hash).await,
+            Self::Local(p) => p.get_blob(hash).await,
+            Self::S3(p) => p.get_blob(hash).await,
+        }
+    }
+
+    async fn get_blob_modified(&self, hash: &str) -> StorageResult<DateTime<Utc>> {
+        match self {
+            Self::Dummy(p) => p.get_blob_modified(hash).await,
+            Self::Local(p) => p.get_blob_modified(hash).await,
+            Self::S3(p) => p.get_blob_modified(hash).await,
+        }
+    }
+
+    async fn put_blob(&self, hash: &str, data: Bytes) -> StorageResult<()> {
+        match self {
+            Self::Dummy(p) => p.put_blob(hash, data).await,
+            Self::Local(p) => p.put_blob(hash, data).await,
+            Self::S3(p) => p.put_blob(hash, data).await,
+        }
+    }
+
+    async fn delete_blob(&self, hash: &str) -> StorageResult<()> {
+        match self {
+            Self::Dummy(p) => p.delete_blob(hash).await,
+            Self::Local(p) => p.delete_blob(hash).await,
+            Self::S3(p) => p.delete_blob(hash).await,
+        }
+    }
+
+    async fn presigned_event_url(
+        &self,
+        filename: &Path,
+        expiry: Duration,
+    ) -> StorageResult<String> {
+        validate_filename(filename)?;
+
+        match self {
+            Self::Dummy(p) => p.presigned_event_url(filename, expiry).await,
+            Self::Local(p) => p.presigned_event_url(filename, expiry).await,
+            Self::S3(p) => p.presigned_event_url(filename, expiry).await,
+        }
+    }
+
+    async fn presigned_segment_url(
+        &self,
+        camera_name: &str,
+        filename: &Path,
+        expiry: Duration,
+    ) -> StorageResult<String> {
+        validate_segment_name(camera_name, filename)?;
+
+        match self {
+            Self::Dummy(p) => p.presigned_segment_url(camera_name, filename, expiry).await,
+            Self::Local(p) => p.presigned_segment_url(camera_name, filename, expiry).await,
+            Self::S3(p) => p.presigned_segment_url(camera_name, filename, expiry).await,
+        }
+    }
 }
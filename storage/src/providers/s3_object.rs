@@ -1,14 +1,20 @@
 use crate::{
-    encryption::KeyOperations, EncryptionConfig, StorageError, StorageProvider, StorageResult,
+    blob::{self, SegmentPointer},
+    encryption::KeyOperations,
+    note, EncryptionConfig, EventNote, ObjectMetadata, Page, RetryConfig, StorageError,
+    StorageProvider, StorageResult,
 };
 use async_trait::async_trait;
 use bytes::Bytes;
-use s3::{creds::Credentials, region::Region, Bucket};
+use chrono::{DateTime, Utc};
+use s3::{creds::Credentials, error::S3Error, region::Region, Bucket};
 use satori_common::Event;
 use serde::Deserialize;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    future::Future,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 #[derive(Debug, Deserialize)]
@@ -18,12 +24,115 @@ pub struct S3Config {
     endpoint: String,
     #[serde(default)]
     encryption: EncryptionConfig,
+    /// Store the content of segments in blobs addressed by their hash, such that identical
+    /// segments are only uploaded once.
+    ///
+    /// With segment encryption the hash is keyed by the encryption key, so blob names do not
+    /// reveal the content to anyone without the key. For HPKE this is the public key.
+    #[serde(default)]
+    content_addressed: bool,
+    /// Policy for retrying requests that fail due to transient errors.
+    #[serde(default)]
+    retry: RetryConfig,
+    /// Options applied to objects when they are written.
+    #[serde(default)]
+    upload: S3UploadConfig,
+}
+
+/// Options applied to objects when they are written.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3UploadConfig {
+    /// Server-side encryption applied to all objects.
+    server_side_encryption: Option<ServerSideEncryption>,
+    /// Storage class of segments (and the blobs holding their content).
+    ///
+    /// Events are always stored using the default storage class, as they are read often. Note
+    /// that segments stored in an archival class (e.g. `GLACIER`) cannot be retrieved until they
+    /// have been restored.
+    segment_storage_class: Option<StorageClass>,
+}
+
+impl S3UploadConfig {
+    /// Headers to set when writing an object, `segment` selects if the object is a segment.
+    fn headers(&self, segment: bool) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+
+        match &self.server_side_encryption {
+            Some(ServerSideEncryption::Aes256 {}) => {
+                headers.push(("x-amz-server-side-encryption", "AES256".into()));
+            }
+            Some(ServerSideEncryption::AwsKms { key_id }) => {
+                headers.push(("x-amz-server-side-encryption", "aws:kms".into()));
+                if let Some(key_id) = key_id {
+                    headers.push((
+                        "x-amz-server-side-encryption-aws-kms-key-id",
+                        key_id.clone(),
+                    ));
+                }
+            }
+            None => {}
+        }
+
+        if segment {
+            if let Some(class) = self.segment_storage_class {
+                headers.push(("x-amz-storage-class", class.as_str().into()));
+            }
+        }
+
+        headers
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", deny_unknown_fields)]
+pub enum ServerSideEncryption {
+    /// Encryption using keys managed by S3.
+    #[serde(rename = "AES256")]
+    Aes256 {},
+
+    /// Encryption using a key managed by KMS, the default key for the bucket is used if no key is
+    /// specified.
+    #[serde(rename = "aws:kms")]
+    AwsKms { key_id: Option<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StorageClass {
+    Standard,
+    StandardIa,
+    OnezoneIa,
+    IntelligentTiering,
+    GlacierIr,
+    Glacier,
+    DeepArchive,
+}
+
+impl StorageClass {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "STANDARD",
+            Self::StandardIa => "STANDARD_IA",
+            Self::OnezoneIa => "ONEZONE_IA",
+            Self::IntelligentTiering => "INTELLIGENT_TIERING",
+            Self::GlacierIr => "GLACIER_IR",
+            Self::Glacier => "GLACIER",
+            Self::DeepArchive => "DEEP_ARCHIVE",
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct S3Storage {
     bucket: Bucket,
+    /// Bucket with the headers used to write events.
+    event_writer: Bucket,
+    /// Bucket with the headers used to write segments.
+    segment_writer: Bucket,
     encryption: EncryptionConfig,
+    content_addressed: bool,
+    retry: RetryConfig,
 }
 
 impl S3Storage {
@@ -39,12 +148,42 @@ impl S3Storage {
         .unwrap()
         .with_path_style();
 
+        let writer = |segment: bool| {
+            let mut bucket = bucket.clone();
+            for (name, value) in config.upload.headers(segment) {
+                bucket.add_header(name, &value);
+            }
+            bucket
+        };
+        let event_writer = writer(false);
+        let segment_writer = writer(true);
+
         Self {
             bucket,
+            event_writer,
+            segment_writer,
             encryption: config.encryption,
+            content_addressed: config.content_addressed,
+            retry: config.retry,
+        }
+    }
+
+    pub(crate) fn with_encryption(&self, encryption: EncryptionConfig) -> Self {
+        Self {
+            encryption,
+            ..self.clone()
         }
     }
 
+    /// Makes a request to S3, retrying it if it fails due to a transient error.
+    async fn request<T, F, Fut>(&self, operation: &'static str, f: F) -> Result<T, S3Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, S3Error>>,
+    {
+        self.retry.run(operation, is_transient, f).await
+    }
+
     fn get_events_path(&self) -> PathBuf {
         PathBuf::from("events")
     }
@@ -65,11 +204,74 @@ impl S3Storage {
         self.get_segments_path(camera_name).join(filename)
     }
 
+    fn get_blobs_path(&self) -> PathBuf {
+        PathBuf::from("blobs")
+    }
+
+    fn get_blob_filename(&self, hash: &str) -> PathBuf {
+        self.get_blobs_path().join(hash)
+    }
+
+    /// Generates a presigned URL to get an object.
+    async fn presign_get(&self, path: &Path, expiry: Duration) -> StorageResult<String> {
+        let expiry = u32::try_from(expiry.as_secs()).unwrap_or(u32::MAX);
+        Ok(self
+            .bucket
+            .presign_get(path.to_str().unwrap(), expiry, None)
+            .await?)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn blob_modified(&self, hash: &str) -> StorageResult<Option<DateTime<Utc>>> {
+        let path = self.get_blob_filename(hash);
+
+        match self
+            .request("head_object", || {
+                self.bucket.head_object(path.to_str().unwrap())
+            })
+            .await
+        {
+            Ok((head, 200)) => head
+                .last_modified
+                .and_then(|m| DateTime::parse_from_rfc2822(&m).ok())
+                .map(|m| Some(m.into()))
+                .ok_or_else(|| StorageError::S3InvalidLastModified(hash.to_owned())),
+            Ok((_, 404)) | Err(s3::error::S3Error::HttpFailWithBody(404, _)) => Ok(None),
+            Ok((_, status_code)) => Err(StorageError::S3Failure(status_code)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Retrieves and decrypts a segment, without following any pointer to a blob.
+    #[tracing::instrument(skip(self))]
+    async fn read_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes> {
+        let path = self.get_segment_filename(camera_name, filename);
+
+        let response = self
+            .request("get_object", || {
+                self.bucket.get_object(path.to_str().unwrap())
+            })
+            .await?;
+
+        if response.status_code() == 200 {
+            let data = response.bytes().to_owned();
+
+            let info = crate::encryption::info::segment_info_from_camera_and_filename(
+                camera_name,
+                filename,
+            );
+            self.encryption.segment.decrypt(info, data)
+        } else {
+            Err(StorageError::S3Failure(response.status_code()))
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     async fn list_path(&self, path: &Path) -> StorageResult<Vec<PathBuf>> {
         let response = self
-            .bucket
-            .list(path.to_str().unwrap().into(), None)
+            .request("list", || {
+                self.bucket.list(path.to_str().unwrap().into(), None)
+            })
             .await?;
 
         Ok(response
@@ -83,11 +285,63 @@ impl S3Storage {
             .collect())
     }
 
+    /// Lists the filenames and sizes of objects directly under a path.
+    async fn list_path_with_meta(&self, path: &Path) -> StorageResult<Vec<ObjectMetadata>> {
+        let response = self
+            .request("list", || {
+                self.bucket.list(path.to_str().unwrap().into(), None)
+            })
+            .await?;
+
+        Ok(response
+            .into_iter()
+            .flat_map(|i| i.contents)
+            .map(|i| ObjectMetadata {
+                filename: PathBuf::from(PathBuf::from(i.key).file_name().unwrap()),
+                size: i.size,
+            })
+            .collect())
+    }
+
+    /// Lists a page of the filenames of objects directly under a path.
+    #[tracing::instrument(skip(self))]
+    async fn list_path_paginated(
+        &self,
+        path: &Path,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> StorageResult<Page> {
+        let prefix = format!("{}/", path.display());
+        let start_after = start_after.map(|s| format!("{prefix}{}", s.display()));
+
+        let (response, _) = self
+            .request("list_page", || {
+                self.bucket
+                    .list_page(prefix.clone(), None, None, start_after.clone(), Some(limit))
+            })
+            .await?;
+
+        let items: Vec<PathBuf> = response
+            .contents
+            .into_iter()
+            .map(|i| PathBuf::from(PathBuf::from(i.key).file_name().unwrap()))
+            .collect();
+
+        let next = if response.is_truncated {
+            items.last().cloned()
+        } else {
+            None
+        };
+
+        Ok(Page { items, next })
+    }
+
     #[tracing::instrument(skip(self))]
     async fn delete_path(&self, path: &Path) -> StorageResult<()> {
         let status_code = self
-            .bucket
-            .delete_object(path.to_str().unwrap())
+            .request("delete_object", || {
+                self.bucket.delete_object(path.to_str().unwrap())
+            })
             .await?
             .status_code();
 
@@ -112,8 +366,9 @@ impl StorageProvider for S3Storage {
         let data = self.encryption.event.encrypt(info, data.into())?;
 
         let status_code = self
-            .bucket
-            .put_object(path.to_str().unwrap(), &data)
+            .request("put_object", || {
+                self.event_writer.put_object(path.to_str().unwrap(), &data)
+            })
             .await?
             .status_code();
 
@@ -131,14 +386,43 @@ impl StorageProvider for S3Storage {
             .await?
             .into_iter()
             .map(|p| PathBuf::from(p.file_name().unwrap().to_str().unwrap()))
+            .filter(|p| !note::is_note_filename(p))
             .collect())
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn list_events_paginated(
+        &self,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> StorageResult<Page> {
+        let mut page = self
+            .list_path_paginated(&self.get_events_path(), start_after, limit)
+            .await?;
+
+        // Notes are only removed after the next page is known, so a page may contain fewer than
+        // `limit` events
+        page.items.retain(|p| !note::is_note_filename(p));
+
+        Ok(page)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_events_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        let mut events = self.list_path_with_meta(&self.get_events_path()).await?;
+        events.retain(|o| !note::is_note_filename(&o.filename));
+        Ok(events)
+    }
+
     #[tracing::instrument(skip(self))]
     async fn get_event(&self, filename: &Path) -> StorageResult<Event> {
         let path = self.get_events_path().join(filename);
 
-        let response = self.bucket.get_object(path.to_str().unwrap()).await?;
+        let response = self
+            .request("get_object", || {
+                self.bucket.get_object(path.to_str().unwrap())
+            })
+            .await?;
 
         if response.status_code() == 200 {
             let data = response.bytes().to_owned();
@@ -154,15 +438,74 @@ impl StorageProvider for S3Storage {
 
     #[tracing::instrument(skip(self))]
     async fn delete_event(&self, event: &Event) -> StorageResult<()> {
-        self.delete_path(&self.get_event_filename(event)).await
+        self.delete_event_filename(&event.metadata.get_filename())
+            .await
     }
 
     #[tracing::instrument(skip(self))]
     async fn delete_event_filename(&self, filename: &Path) -> StorageResult<()> {
         self.delete_path(&self.get_events_path().join(filename))
+            .await?;
+
+        // Delete the note of the event too, deleting an object that does not exist succeeds
+        self.delete_path(&self.get_events_path().join(note::note_filename(filename)))
             .await
     }
 
+    #[tracing::instrument(skip(self, note))]
+    async fn put_event_note(&self, filename: &Path, note: &EventNote) -> StorageResult<()> {
+        let note_filename = note::note_filename(filename);
+        let path = self.get_events_path().join(&note_filename);
+
+        let data = serde_json::to_vec_pretty(&note)?;
+
+        let info = crate::encryption::info::event_info_from_filename(&note_filename);
+        let data = self.encryption.event.encrypt(info, data.into())?;
+
+        let status_code = self
+            .request("put_object", || {
+                self.event_writer.put_object(path.to_str().unwrap(), &data)
+            })
+            .await?
+            .status_code();
+
+        if status_code == 200 {
+            Ok(())
+        } else {
+            Err(StorageError::S3Failure(status_code))
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_event_note(&self, filename: &Path) -> StorageResult<Option<EventNote>> {
+        let note_filename = note::note_filename(filename);
+        let path = self.get_events_path().join(&note_filename);
+
+        let response = match self
+            .request("get_object", || {
+                self.bucket.get_object(path.to_str().unwrap())
+            })
+            .await
+        {
+            Ok(response) => response,
+            Err(s3::error::S3Error::HttpFailWithBody(404, _)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        match response.status_code() {
+            200 => {
+                let data = response.bytes().to_owned();
+
+                let info = crate::encryption::info::event_info_from_filename(&note_filename);
+                let data = self.encryption.event.decrypt(info, data)?;
+
+                Ok(Some(serde_json::from_slice(&data)?))
+            }
+            404 => Ok(None),
+            status_code => Err(StorageError::S3Failure(status_code)),
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     async fn list_cameras(&self) -> StorageResult<Vec<String>> {
         let mut cameras = HashSet::new();
@@ -190,13 +533,34 @@ impl StorageProvider for S3Storage {
     ) -> StorageResult<()> {
         let path = self.get_segment_filename(camera_name, filename);
 
+        let data = if self.content_addressed {
+            let hash = blob::blob_hash(self.encryption.segment.hash_key(), &data);
+
+            // Identical content has already been uploaded, only the pointer needs to be written.
+            // Objects cannot be touched, so a blob that could be pruned before the pointer is
+            // written is uploaded again.
+            let recent = match self.blob_modified(&hash).await? {
+                Some(modified) => Utc::now() - modified < blob::BLOB_REFRESH_INTERVAL,
+                None => false,
+            };
+            if !recent {
+                self.put_blob(&hash, data).await?;
+            }
+
+            SegmentPointer::new(hash).to_bytes()?
+        } else {
+            data
+        };
+
         let info =
             crate::encryption::info::segment_info_from_camera_and_filename(camera_name, filename);
         let data = self.encryption.segment.encrypt(info, data)?;
 
         let status_code = self
-            .bucket
-            .put_object(path.to_str().unwrap(), &data)
+            .request("put_object", || {
+                self.segment_writer
+                    .put_object(path.to_str().unwrap(), &data)
+            })
             .await?
             .status_code();
 
@@ -217,31 +581,199 @@ impl StorageProvider for S3Storage {
             .collect())
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn list_segments_paginated(
+        &self,
+        camera_name: &str,
+        start_after: Option<&Path>,
+        limit: usize,
+    ) -> StorageResult<Page> {
+        self.list_path_paginated(&self.get_segments_path(camera_name), start_after, limit)
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_segments_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        let mut segments = self.list_segment_objects_with_meta(camera_name).await?;
+
+        // Report the size of the content rather than that of the pointer
+        if self.content_addressed {
+            let blob_sizes: HashMap<PathBuf, u64> = self
+                .list_blobs_with_meta()
+                .await?
+                .into_iter()
+                .map(|b| (b.filename, b.size))
+                .collect();
+
+            for segment in &mut segments {
+                let data = self.read_segment(camera_name, &segment.filename).await?;
+
+                if let Some(pointer) = SegmentPointer::from_bytes(&data) {
+                    segment.size = *blob_sizes
+                        .get(Path::new(&pointer.blob))
+                        .ok_or(StorageError::NotFound)?;
+                }
+            }
+        }
+
+        Ok(segments)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_segment_objects_with_meta(
+        &self,
+        camera_name: &str,
+    ) -> StorageResult<Vec<ObjectMetadata>> {
+        self.list_path_with_meta(&self.get_segments_path(camera_name))
+            .await
+    }
+
     #[tracing::instrument(skip(self))]
     async fn get_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<Bytes> {
-        let path = self.get_segment_filename(camera_name, filename);
+        let data = self.read_segment(camera_name, filename).await?;
+
+        match SegmentPointer::from_bytes(&data) {
+            Some(pointer) => self.get_blob(&pointer.blob).await,
+            None => Ok(data),
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<()> {
+        self.delete_path(&self.get_segment_filename(camera_name, filename))
+            .await
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn presigned_event_url(
+        &self,
+        filename: &Path,
+        expiry: Duration,
+    ) -> StorageResult<String> {
+        if self.encryption.event.is_some() {
+            return Err(StorageError::Unsupported(
+                "presigned URLs cannot be used when events are encrypted",
+            ));
+        }
+
+        self.presign_get(&self.get_events_path().join(filename), expiry)
+            .await
+    }
 
-        let response = self.bucket.get_object(path.to_str().unwrap()).await?;
+    #[tracing::instrument(skip(self))]
+    async fn presigned_segment_url(
+        &self,
+        camera_name: &str,
+        filename: &Path,
+        expiry: Duration,
+    ) -> StorageResult<String> {
+        if self.encryption.segment.is_some() {
+            return Err(StorageError::Unsupported(
+                "presigned URLs cannot be used when segments are encrypted",
+            ));
+        }
+
+        // The content of a content addressed segment is held in a blob
+        let path = match self.content_addressed {
+            true => match self.get_segment_blob(camera_name, filename).await? {
+                Some(hash) => self.get_blob_filename(&hash),
+                None => self.get_segment_filename(camera_name, filename),
+            },
+            false => self.get_segment_filename(camera_name, filename),
+        };
+
+        self.presign_get(&path, expiry).await
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_blobs(&self) -> StorageResult<Vec<String>> {
+        Ok(self
+            .list_path(&self.get_blobs_path())
+            .await?
+            .into_iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list_blobs_with_meta(&self) -> StorageResult<Vec<ObjectMetadata>> {
+        self.list_path_with_meta(&self.get_blobs_path()).await
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_segment_blob(
+        &self,
+        camera_name: &str,
+        filename: &Path,
+    ) -> StorageResult<Option<String>> {
+        let data = self.read_segment(camera_name, filename).await?;
+        Ok(SegmentPointer::from_bytes(&data).map(|p| p.blob))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_blob(&self, hash: &str) -> StorageResult<Bytes> {
+        let path = self.get_blob_filename(hash);
+
+        let response = self
+            .request("get_object", || {
+                self.bucket.get_object(path.to_str().unwrap())
+            })
+            .await?;
 
         if response.status_code() == 200 {
             let data = response.bytes().to_owned();
 
-            let info = crate::encryption::info::segment_info_from_camera_and_filename(
-                camera_name,
-                filename,
-            );
-            let data = self.encryption.segment.decrypt(info, data)?;
-
-            Ok(data)
+            let info = crate::encryption::info::blob_info_from_hash(hash);
+            self.encryption.segment.decrypt(info, data)
         } else {
             Err(StorageError::S3Failure(response.status_code()))
         }
     }
 
     #[tracing::instrument(skip(self))]
-    async fn delete_segment(&self, camera_name: &str, filename: &Path) -> StorageResult<()> {
-        self.delete_path(&self.get_segment_filename(camera_name, filename))
-            .await
+    async fn get_blob_modified(&self, hash: &str) -> StorageResult<DateTime<Utc>> {
+        self.blob_modified(hash)
+            .await?
+            .ok_or(StorageError::NotFound)
+    }
+
+    #[tracing::instrument(skip(self, data))]
+    async fn put_blob(&self, hash: &str, data: Bytes) -> StorageResult<()> {
+        let path = self.get_blob_filename(hash);
+
+        let info = crate::encryption::info::blob_info_from_hash(hash);
+        let data = self.encryption.segment.encrypt(info, data)?;
+
+        let status_code = self
+            .request("put_object", || {
+                self.segment_writer
+                    .put_object(path.to_str().unwrap(), &data)
+            })
+            .await?
+            .status_code();
+
+        if status_code == 200 {
+            Ok(())
+        } else {
+            Err(StorageError::S3Failure(status_code))
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_blob(&self, hash: &str) -> StorageResult<()> {
+        self.delete_path(&self.get_blob_filename(hash)).await
+    }
+}
+
+/// Checks if a failed request may succeed if it is retried.
+fn is_transient(err: &S3Error) -> bool {
+    match err {
+        S3Error::HttpFailWithBody(status_code, _) => *status_code >= 500 || *status_code == 429,
+        S3Error::HttpFail | S3Error::Io(_) | S3Error::Hyper(_) => true,
+        _ => false,
     }
 }
 
@@ -270,6 +802,98 @@ mod test {
         drop(minio);
     }
 
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&S3Error::HttpFailWithBody(503, "".into())));
+        assert!(is_transient(&S3Error::HttpFailWithBody(429, "".into())));
+        assert!(is_transient(&S3Error::HttpFail));
+        assert!(!is_transient(&S3Error::HttpFailWithBody(404, "".into())));
+        assert!(!is_transient(&S3Error::HttpFailWithBody(403, "".into())));
+    }
+
+    #[test]
+    fn test_upload_headers() {
+        let config: S3Config = toml::from_str(
+            r#"
+bucket = "satori"
+region = "eu-west-1"
+endpoint = "http://localhost:9000"
+
+[upload]
+segment_storage_class = "GLACIER"
+
+[upload.server_side_encryption]
+kind = "aws:kms"
+key_id = "my-key"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.upload.headers(false),
+            vec![
+                ("x-amz-server-side-encryption", "aws:kms".to_string()),
+                (
+                    "x-amz-server-side-encryption-aws-kms-key-id",
+                    "my-key".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            config.upload.headers(true),
+            vec![
+                ("x-amz-server-side-encryption", "aws:kms".to_string()),
+                (
+                    "x-amz-server-side-encryption-aws-kms-key-id",
+                    "my-key".to_string()
+                ),
+                ("x-amz-storage-class", "GLACIER".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upload_headers_default() {
+        let config = S3UploadConfig::default();
+        assert!(config.headers(false).is_empty());
+        assert!(config.headers(true).is_empty());
+
+        let config: S3UploadConfig = toml::from_str(
+            r#"
+segment_storage_class = "INTELLIGENT_TIERING"
+server_side_encryption = { kind = "AES256" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.headers(true),
+            vec![
+                ("x-amz-server-side-encryption", "AES256".to_string()),
+                ("x-amz-storage-class", "INTELLIGENT_TIERING".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upload_config_invalid() {
+        for config in [
+            r#"segment_storage_class = "COLD""#,
+            r#"server_side_encryption = { kind = "aws:kms:dsse" }"#,
+            r#"server_side_encryption = { kind = "AES256", key_id = "my-key" }"#,
+            r#"storage_class = "GLACIER""#,
+        ] {
+            assert!(
+                toml::from_str::<S3UploadConfig>(config).is_err(),
+                "{config}"
+            );
+        }
+
+        let err = toml::from_str::<S3UploadConfig>(r#"segment_storage_class = "COLD""#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown variant `COLD`"), "{err}");
+    }
+
     fn generate_random_bucket_name() -> String {
         let id = rand::thread_rng()
             .sample_iter(&rand::distributions::Alphanumeric)
@@ -301,6 +925,9 @@ mod test {
                         region: "".into(),
                         endpoint: minio.endpoint(),
                         encryption: EncryptionConfig::default(),
+                        content_addressed: false,
+                        retry: RetryConfig::default(),
+                        upload: Default::default(),
                     })
                     .create_provider();
 
@@ -360,6 +987,83 @@ MC4CAQAwBQYDK2VuBCIEILhAcPMmERCi9QmBwH26wXzVo/6e5Lqw9lvA+8hf//xJ
 ",
                         )
                         .unwrap(),
+                        content_addressed: false,
+                        retry: RetryConfig::default(),
+                        upload: Default::default(),
+                    })
+                    .create_provider();
+
+                    crate::providers::test::$test(provider).await;
+                }
+            };
+        }
+
+        crate::providers::test::all_storage_tests!(test);
+    }
+
+    mod encryption_aes256_gcm {
+        use super::*;
+
+        macro_rules! test {
+            ( $test:ident ) => {
+                #[tokio::test]
+                async fn $test() {
+                    let minio = MINIO.lock().await;
+                    let minio = minio.as_ref().unwrap();
+
+                    minio.wait_for_ready().await;
+
+                    let bucket = super::generate_random_bucket_name();
+                    minio.create_bucket(&bucket).await;
+
+                    let provider = crate::StorageConfig::S3(S3Config {
+                        bucket,
+                        region: "".into(),
+                        endpoint: minio.endpoint(),
+                        encryption: toml::from_str(
+                            crate::providers::test::AES256_GCM_ENCRYPTION_CONFIG,
+                        )
+                        .unwrap(),
+                        content_addressed: false,
+                        retry: RetryConfig::default(),
+                        upload: Default::default(),
+                    })
+                    .create_provider();
+
+                    crate::providers::test::$test(provider).await;
+                }
+            };
+        }
+
+        crate::providers::test::all_storage_tests!(test);
+    }
+
+    mod content_addressed {
+        use super::*;
+
+        macro_rules! test {
+            ( $test:ident ) => {
+                #[tokio::test]
+                async fn $test() {
+                    let minio = MINIO.lock().await;
+                    let minio = minio.as_ref().unwrap();
+
+                    minio.wait_for_ready().await;
+
+                    let bucket = super::generate_random_bucket_name();
+                    minio.create_bucket(&bucket).await;
+
+                    let provider = crate::StorageConfig::S3(S3Config {
+                        bucket,
+                        region: "".into(),
+                        endpoint: minio.endpoint(),
+                        encryption: toml::from_str(
+                            crate::providers::test::AES256_GCM_ENCRYPTION_CONFIG,
+                        )
+                        .unwrap(),
+                        content_addressed: true,
+                        retry: RetryConfig::default(),
+                        upload: Default::default(),
                     })
                     .create_provider();
 
@@ -1,4 +1,4 @@
-use crate::{Provider, StorageProvider};
+use crate::{EventNote, Provider, StorageProvider};
 use bytes::Bytes;
 use chrono::Utc;
 use satori_common::{Event, EventMetadata};
@@ -73,6 +73,36 @@ pub(crate) async fn test_delete_event_filename(provider: Provider) {
     assert_eq!(events, vec![event2.metadata.get_filename(),]);
 }
 
+pub(crate) async fn test_delete_event_deletes_note(provider: Provider) {
+    let event = Event {
+        metadata: EventMetadata {
+            id: "test-1".into(),
+            timestamp: Utc::now().into(),
+        },
+        start: Utc::now().into(),
+        end: Utc::now().into(),
+        reasons: Default::default(),
+        cameras: Default::default(),
+    };
+    let filename = event.metadata.get_filename();
+
+    provider.put_event(&event).await.unwrap();
+    provider
+        .put_event_note(&filename, &EventNote::new("A fox".into()))
+        .await
+        .unwrap();
+
+    provider.delete_event(&event).await.unwrap();
+
+    // Storing the event again does not bring back the note of the deleted event
+    provider.put_event(&event).await.unwrap();
+    assert_eq!(provider.get_event_note(&filename).await.unwrap(), None);
+
+    // Events without a note can still be deleted
+    provider.delete_event_filename(&filename).await.unwrap();
+    assert!(provider.list_events().await.unwrap().is_empty());
+}
+
 pub(crate) async fn test_delete_segment(provider: Provider) {
     provider
         .put_segment("camera1", Path::new("1.ts"), Bytes::default())
@@ -127,3 +157,22 @@ pub(crate) async fn test_delete_last_segment_deletes_camera(provider: Provider)
 
     assert!(provider.list_cameras().await.unwrap().is_empty());
 }
+
+pub(crate) async fn test_delete_segments_batch(provider: Provider) {
+    for filename in ["1.ts", "2.ts", "3.ts", "4.ts"] {
+        provider
+            .put_segment("camera1", Path::new(filename), Bytes::default())
+            .await
+            .unwrap();
+    }
+
+    let errors = provider
+        .delete_segments_batch("camera1", &[PathBuf::from("1.ts"), PathBuf::from("3.ts")])
+        .await;
+    assert!(errors.is_empty());
+
+    assert_eq!(
+        provider.list_segments("camera1").await.unwrap(),
+        vec![PathBuf::from("2.ts"), PathBuf::from("4.ts")]
+    );
+}
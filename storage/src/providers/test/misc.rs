@@ -1,6 +1,78 @@
-use crate::{Provider, StorageProvider};
+use crate::{Provider, StorageError, StorageProvider};
+use bytes::Bytes;
+use std::path::{Path, PathBuf};
 
 pub(crate) async fn test_init(provider: Provider) {
     assert!(provider.list_events().await.unwrap().is_empty());
     assert!(provider.list_cameras().await.unwrap().is_empty());
 }
+
+pub(crate) async fn test_invalid_names(provider: Provider) {
+    provider
+        .put_segment("camera1", Path::new("1.ts"), Bytes::from("segment"))
+        .await
+        .unwrap();
+
+    for camera in ["", "..", "../events", "camera1/..", "camera 1", "/tmp"] {
+        assert!(matches!(
+            provider
+                .put_segment(camera, Path::new("2.ts"), Bytes::default())
+                .await,
+            Err(StorageError::InvalidName(_))
+        ));
+        assert!(matches!(
+            provider.get_segment(camera, Path::new("1.ts")).await,
+            Err(StorageError::InvalidName(_))
+        ));
+        assert!(matches!(
+            provider.delete_segment(camera, Path::new("1.ts")).await,
+            Err(StorageError::InvalidName(_))
+        ));
+        assert!(matches!(
+            provider.list_segments(camera).await,
+            Err(StorageError::InvalidName(_))
+        ));
+    }
+
+    for filename in ["", "..", "../1.ts", "../../events/1.json", "/tmp/1.ts"] {
+        let filename = Path::new(filename);
+
+        assert!(matches!(
+            provider
+                .put_segment("camera1", filename, Bytes::default())
+                .await,
+            Err(StorageError::InvalidName(_))
+        ));
+        assert!(matches!(
+            provider.get_segment("camera1", filename).await,
+            Err(StorageError::InvalidName(_))
+        ));
+        assert!(matches!(
+            provider.delete_segment("camera1", filename).await,
+            Err(StorageError::InvalidName(_))
+        ));
+        assert!(matches!(
+            provider.get_event(filename).await,
+            Err(StorageError::InvalidName(_))
+        ));
+        assert!(matches!(
+            provider.delete_event_filename(filename).await,
+            Err(StorageError::InvalidName(_))
+        ));
+    }
+
+    // Invalid names in a batch are reported without affecting the valid ones
+    let errors = provider
+        .delete_segments_batch(
+            "camera1",
+            &[PathBuf::from("../1.ts"), PathBuf::from("1.ts")],
+        )
+        .await;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, PathBuf::from("../1.ts"));
+    assert!(matches!(errors[0].1, StorageError::InvalidName(_)));
+
+    // Nothing was stored, and nothing outside of the camera was touched
+    assert!(provider.list_events().await.unwrap().is_empty());
+    assert!(provider.list_cameras().await.unwrap().is_empty());
+}
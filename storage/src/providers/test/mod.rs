@@ -10,6 +10,21 @@ pub(super) use misc::*;
 mod retrieval;
 pub(super) use retrieval::*;
 
+pub(super) const AES256_GCM_ENCRYPTION_CONFIG: &str = "
+[event]
+kind = \"aes256_gcm\"
+key = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+]
+[segment]
+kind = \"aes256_gcm\"
+key = [
+    31, 30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 18, 17, 16,
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+]
+";
+
 macro_rules! all_storage_tests {
     ( $test_macro:ident ) => {
         $test_macro!(test_add_first_event);
@@ -19,13 +34,22 @@ macro_rules! all_storage_tests {
 
         $test_macro!(test_delete_event);
         $test_macro!(test_delete_event_filename);
+        $test_macro!(test_delete_event_deletes_note);
         $test_macro!(test_delete_segment);
         $test_macro!(test_delete_last_segment_deletes_camera);
+        $test_macro!(test_delete_segments_batch);
 
         $test_macro!(test_init);
+        $test_macro!(test_invalid_names);
 
         $test_macro!(test_event_getters);
+        $test_macro!(test_event_offset_preserved);
+        $test_macro!(test_event_note);
         $test_macro!(test_segment_getters);
+        $test_macro!(test_list_segments_fmp4);
+        $test_macro!(test_segment_metadata);
+        $test_macro!(test_storage_stats);
+        $test_macro!(test_list_paginated);
     };
 }
 
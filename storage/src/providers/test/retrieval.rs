@@ -1,7 +1,7 @@
-use crate::{Provider, StorageProvider};
+use crate::{EventNote, Provider, StorageProvider};
 use bytes::Bytes;
-use chrono::Utc;
-use satori_common::{Event, EventMetadata};
+use chrono::{DateTime, Utc};
+use satori_common::{CameraSegments, Event, EventMetadata, EventReason};
 use std::path::{Path, PathBuf};
 
 pub(crate) async fn test_event_getters(provider: Provider) {
@@ -47,6 +47,40 @@ pub(crate) async fn test_event_getters(provider: Provider) {
     );
 }
 
+pub(crate) async fn test_event_offset_preserved(provider: Provider) {
+    let timestamp = DateTime::parse_from_rfc3339("2023-03-01T17:30:00+05:30").unwrap();
+
+    let event = Event {
+        metadata: EventMetadata {
+            id: "test-1".into(),
+            timestamp,
+        },
+        start: timestamp,
+        end: timestamp,
+        reasons: vec![EventReason {
+            timestamp,
+            reason: "Something happened".into(),
+            trigger_id: None,
+        }],
+        cameras: Default::default(),
+    };
+
+    provider.put_event(&event).await.unwrap();
+
+    let filename = PathBuf::from("2023-03-01T17:30:00+05:30_test-1.json");
+    assert_eq!(
+        provider.list_events().await.unwrap(),
+        vec![filename.clone()]
+    );
+
+    let retrieved = provider.get_event(&filename).await.unwrap();
+    assert_eq!(retrieved, event);
+    assert_eq!(retrieved.metadata.timestamp.offset(), timestamp.offset());
+    assert_eq!(retrieved.start.offset(), timestamp.offset());
+    assert_eq!(retrieved.end.offset(), timestamp.offset());
+    assert_eq!(retrieved.reasons[0].timestamp.offset(), timestamp.offset());
+}
+
 pub(crate) async fn test_segment_getters(provider: Provider) {
     provider
         .put_segment("camera1", Path::new("1_1.ts"), Bytes::from("camera1_one"))
@@ -113,3 +147,259 @@ pub(crate) async fn test_segment_getters(provider: Provider) {
         Bytes::from("camera2_three"),
     );
 }
+
+pub(crate) async fn test_event_note(provider: Provider) {
+    let mut event = Event {
+        metadata: EventMetadata {
+            id: "test-1".into(),
+            timestamp: Utc::now().into(),
+        },
+        start: Utc::now().into(),
+        end: Utc::now().into(),
+        reasons: Default::default(),
+        cameras: Default::default(),
+    };
+    let filename = event.metadata.get_filename();
+
+    provider.put_event(&event).await.unwrap();
+    assert_eq!(provider.get_event_note(&filename).await.unwrap(), None);
+
+    let note = EventNote::new("A fox, not a burglar".into());
+    provider.put_event_note(&filename, &note).await.unwrap();
+    assert_eq!(
+        provider.get_event_note(&filename).await.unwrap(),
+        Some(note.clone())
+    );
+
+    // Notes are not listed as events
+    assert_eq!(
+        provider.list_events().await.unwrap(),
+        vec![filename.clone()]
+    );
+
+    // Storing the event again does not affect the note
+    event.cameras.push(CameraSegments {
+        name: "camera1".into(),
+        segment_list: vec!["one.ts".into()],
+    });
+    provider.put_event(&event).await.unwrap();
+    assert_eq!(provider.get_event(&filename).await.unwrap(), event);
+    assert_eq!(
+        provider.get_event_note(&filename).await.unwrap(),
+        Some(note)
+    );
+
+    // Replacing the note
+    let note = EventNote::new("A badger, not a fox".into());
+    provider.put_event_note(&filename, &note).await.unwrap();
+    assert_eq!(
+        provider.get_event_note(&filename).await.unwrap(),
+        Some(note)
+    );
+}
+
+pub(crate) async fn test_list_segments_fmp4(provider: Provider) {
+    for filename in ["1_2.m4s", "init.mp4", "1_1.m4s"] {
+        provider
+            .put_segment("camera1", Path::new(filename), Bytes::from(filename))
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        provider.list_segments("camera1").await.unwrap(),
+        vec![
+            PathBuf::from("1_1.m4s"),
+            PathBuf::from("1_2.m4s"),
+            PathBuf::from("init.mp4"),
+        ]
+    );
+
+    let segments = provider.list_segments_with_meta("camera1").await.unwrap();
+    assert_eq!(segments.len(), 3);
+    assert_eq!(segments[2].filename, PathBuf::from("init.mp4"));
+
+    assert_eq!(
+        provider
+            .get_segment("camera1", Path::new("1_2.m4s"))
+            .await
+            .unwrap(),
+        Bytes::from("1_2.m4s")
+    );
+}
+
+pub(crate) async fn test_segment_metadata(provider: Provider) {
+    provider
+        .put_segment("camera1", Path::new("1_2.ts"), Bytes::from("a"))
+        .await
+        .unwrap();
+    provider
+        .put_segment("camera1", Path::new("1_1.ts"), Bytes::from("bbbbbbbbbb"))
+        .await
+        .unwrap();
+
+    let segments = provider.list_segments_with_meta("camera1").await.unwrap();
+
+    assert_eq!(
+        segments
+            .iter()
+            .map(|s| s.filename.clone())
+            .collect::<Vec<_>>(),
+        provider.list_segments("camera1").await.unwrap()
+    );
+
+    // Sizes include any encryption overhead
+    assert!(segments[0].size >= 10);
+    assert!(segments[1].size >= 1);
+    assert!(segments[0].size > segments[1].size);
+}
+
+pub(crate) async fn test_storage_stats(provider: Provider) {
+    let event = Event {
+        metadata: EventMetadata {
+            id: "test-1".into(),
+            timestamp: Utc::now().into(),
+        },
+        start: Utc::now().into(),
+        end: Utc::now().into(),
+        reasons: Default::default(),
+        cameras: Default::default(),
+    };
+    provider.put_event(&event).await.unwrap();
+    provider
+        .put_event_note(
+            &event.metadata.get_filename(),
+            &EventNote::new("note".into()),
+        )
+        .await
+        .unwrap();
+
+    provider
+        .put_segment("camera1", Path::new("1_1.ts"), Bytes::from(vec![0; 100]))
+        .await
+        .unwrap();
+    provider
+        .put_segment("camera1", Path::new("1_2.ts"), Bytes::from(vec![1; 100]))
+        .await
+        .unwrap();
+    provider
+        .put_segment("camera2", Path::new("2_1.ts"), Bytes::from(vec![2; 10]))
+        .await
+        .unwrap();
+
+    let stats = provider.storage_stats().await.unwrap();
+
+    // Notes are not counted as events
+    assert_eq!(stats.events.count, 1);
+    assert_eq!(
+        stats.events.bytes,
+        provider
+            .list_events_with_meta()
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.size)
+            .sum::<u64>()
+    );
+
+    assert_eq!(stats.cameras.len(), 2);
+    assert_eq!(stats.cameras["camera1"].count, 2);
+    assert_eq!(stats.cameras["camera2"].count, 1);
+
+    for (camera, camera_stats) in &stats.cameras {
+        assert_eq!(
+            camera_stats.bytes,
+            provider
+                .list_segment_objects_with_meta(camera)
+                .await
+                .unwrap()
+                .iter()
+                .map(|s| s.size)
+                .sum::<u64>()
+        );
+    }
+    assert_eq!(stats.segments().count, 3);
+
+    // Sizes include any encryption overhead, content is held either in the segments or in blobs
+    assert!(stats.segments().bytes + stats.blobs.bytes >= 210);
+    assert_eq!(stats.total().count, 4 + stats.blobs.count);
+}
+
+pub(crate) async fn test_list_paginated(provider: Provider) {
+    for i in 0..5 {
+        let event = Event {
+            metadata: EventMetadata {
+                id: format!("test-{i}"),
+                timestamp: Utc::now().into(),
+            },
+            start: Utc::now().into(),
+            end: Utc::now().into(),
+            reasons: Default::default(),
+            cameras: Default::default(),
+        };
+        provider.put_event(&event).await.unwrap();
+
+        provider
+            .put_segment(
+                "camera1",
+                &PathBuf::from(format!("{i}.ts")),
+                Bytes::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // Notes are not listed as events
+    let events = provider.list_events().await.unwrap();
+    provider
+        .put_event_note(&events[1], &EventNote::new("note".into()))
+        .await
+        .unwrap();
+
+    let mut paginated_events = Vec::new();
+    let mut start_after = None;
+    loop {
+        let page = provider
+            .list_events_paginated(start_after.as_deref(), 2)
+            .await
+            .unwrap();
+        assert!(page.items.len() <= 2);
+
+        paginated_events.extend(page.items);
+
+        match page.next {
+            Some(next) => start_after = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(paginated_events, events);
+
+    let segments = provider.list_segments("camera1").await.unwrap();
+    assert_eq!(segments.len(), 5);
+
+    let mut paginated_segments = Vec::new();
+    let mut start_after = None;
+    loop {
+        let page = provider
+            .list_segments_paginated("camera1", start_after.as_deref(), 2)
+            .await
+            .unwrap();
+        assert!(page.items.len() <= 2);
+
+        paginated_segments.extend(page.items);
+
+        match page.next {
+            Some(next) => start_after = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(paginated_segments, segments);
+
+    // Listing can start anywhere
+    let page = provider
+        .list_segments_paginated("camera1", Some(Path::new("2.ts")), 10)
+        .await
+        .unwrap();
+    assert_eq!(page.items, segments[3..].to_vec());
+    assert_eq!(page.next, None);
+}
@@ -0,0 +1,177 @@
+use serde::Deserialize;
+use serde_with::{serde_as, DurationMilliSeconds};
+use std::{future::Future, time::Duration};
+use tracing::{warn, Instrument};
+
+/// Counter of storage operations that were retried after a transient failure.
+pub const METRIC_RETRIES: &str = "satori_storage_retries";
+
+/// Policy for retrying storage operations that fail due to transient errors.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Maximum number of times an operation is attempted, including the first attempt.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, in milliseconds, doubled for each subsequent retry.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub base_delay: Duration,
+
+    /// Maximum delay between retries, in milliseconds.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Delay before a given retry, starting from 1 for the first retry.
+    fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_delay)
+    }
+
+    /// Runs `f` until it succeeds, fails with an error that `is_transient` rejects or the
+    /// maximum number of attempts is reached.
+    pub(crate) async fn run<T, E, F, Fut>(
+        &self,
+        operation: &'static str,
+        is_transient: impl Fn(&E) -> bool,
+        mut f: F,
+    ) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let span = tracing::debug_span!("storage_attempt", operation, attempt);
+
+            match f().instrument(span).await {
+                Err(err) if attempt < self.max_attempts && is_transient(&err) => {
+                    let delay = self.delay(attempt);
+                    warn!(
+                        "Storage operation {operation} failed (attempt {attempt} of {}), retrying in {delay:?}, error: {err}",
+                        self.max_attempts
+                    );
+                    metrics::counter!(METRIC_RETRIES, 1, "operation" => operation);
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    /// An operation that fails a number of times before succeeding, counting its attempts.
+    async fn flaky(
+        attempts: &AtomicU32,
+        failures: u32,
+        error: &'static str,
+    ) -> Result<u32, String> {
+        let attempt = attempts.fetch_add(1, Ordering::Relaxed) + 1;
+        if attempt <= failures {
+            Err(error.into())
+        } else {
+            Ok(attempt)
+        }
+    }
+
+    fn is_transient(err: &String) -> bool {
+        err == "transient"
+    }
+
+    #[test]
+    fn test_deserialize() {
+        assert_eq!(
+            toml::from_str::<RetryConfig>("").unwrap(),
+            RetryConfig::default()
+        );
+        assert_eq!(
+            toml::from_str::<RetryConfig>("max_attempts = 2\nbase_delay = 50").unwrap(),
+            RetryConfig {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(50),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_delay() {
+        let config = RetryConfig {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+
+        assert_eq!(config.delay(1), Duration::from_millis(100));
+        assert_eq!(config.delay(2), Duration::from_millis(200));
+        assert_eq!(config.delay(3), Duration::from_millis(400));
+        assert_eq!(config.delay(4), Duration::from_millis(500));
+        assert_eq!(config.delay(100), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_succeeds_after_transient_failures() {
+        let attempts = AtomicU32::new(0);
+
+        let result = config()
+            .run("test", is_transient, || flaky(&attempts, 3, "transient"))
+            .await;
+
+        assert_eq!(result, Ok(4));
+        assert_eq!(attempts.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let attempts = AtomicU32::new(0);
+
+        let result = config()
+            .run("test", is_transient, || flaky(&attempts, 10, "transient"))
+            .await;
+
+        assert_eq!(result, Err("transient".into()));
+        assert_eq!(attempts.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_failure_not_retried() {
+        let attempts = AtomicU32::new(0);
+
+        let result = config()
+            .run("test", is_transient, || flaky(&attempts, 10, "permanent"))
+            .await;
+
+        assert_eq!(result, Err("permanent".into()));
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::ObjectMetadata;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Number and total size of a set of stored objects.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ObjectStats {
+    pub count: usize,
+    /// Total size of the stored objects in bytes, including any encryption overhead.
+    pub bytes: u64,
+}
+
+impl ObjectStats {
+    pub(crate) fn from_objects(objects: &[ObjectMetadata]) -> Self {
+        Self {
+            count: objects.len(),
+            bytes: objects.iter().map(|o| o.size).sum(),
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            count: self.count + other.count,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+/// Usage of a storage provider.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageStats {
+    pub events: ObjectStats,
+    /// Segments stored for each camera.
+    ///
+    /// For content addressed segments this is the size of the pointers, the content is counted
+    /// in `blobs`.
+    pub cameras: BTreeMap<String, ObjectStats>,
+    /// Blobs holding the content of content addressed segments, each counted once.
+    pub blobs: ObjectStats,
+}
+
+impl StorageStats {
+    /// Segments stored for all cameras.
+    pub fn segments(&self) -> ObjectStats {
+        self.cameras
+            .values()
+            .fold(ObjectStats::default(), |total, s| total.add(*s))
+    }
+
+    /// All stored events, segments and blobs.
+    pub fn total(&self) -> ObjectStats {
+        self.events.add(self.segments()).add(self.blobs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{providers::dummy::DummyConfig, StorageConfig, StorageProvider};
+    use bytes::Bytes;
+    use chrono::Utc;
+    use satori_common::{Event, EventMetadata};
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn test_storage_stats() {
+        let provider = StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        for (camera, filename, size) in [
+            ("camera1", "1.ts", 100),
+            ("camera1", "2.ts", 150),
+            ("camera2", "1.ts", 1000),
+        ] {
+            provider
+                .put_segment(camera, Path::new(filename), Bytes::from(vec![0; size]))
+                .await
+                .unwrap();
+        }
+
+        let event = Event {
+            metadata: EventMetadata {
+                id: "test-1".into(),
+                timestamp: Utc::now().into(),
+            },
+            start: Utc::now().into(),
+            end: Utc::now().into(),
+            reasons: Default::default(),
+            cameras: Default::default(),
+        };
+        provider.put_event(&event).await.unwrap();
+        let event_size = serde_json::to_vec_pretty(&event).unwrap().len() as u64;
+
+        let stats = provider.storage_stats().await.unwrap();
+
+        assert_eq!(
+            stats,
+            StorageStats {
+                events: ObjectStats {
+                    count: 1,
+                    bytes: event_size,
+                },
+                cameras: BTreeMap::from([
+                    (
+                        "camera1".to_string(),
+                        ObjectStats {
+                            count: 2,
+                            bytes: 250,
+                        }
+                    ),
+                    (
+                        "camera2".to_string(),
+                        ObjectStats {
+                            count: 1,
+                            bytes: 1000,
+                        }
+                    ),
+                ]),
+                blobs: ObjectStats::default(),
+            }
+        );
+        assert_eq!(
+            stats.segments(),
+            ObjectStats {
+                count: 3,
+                bytes: 1250,
+            }
+        );
+        assert_eq!(
+            stats.total(),
+            ObjectStats {
+                count: 4,
+                bytes: 1250 + event_size,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storage_stats_empty() {
+        let provider = StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        let stats = provider.storage_stats().await.unwrap();
+        assert_eq!(stats, StorageStats::default());
+        assert_eq!(stats.total(), ObjectStats::default());
+    }
+}
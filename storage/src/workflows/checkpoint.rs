@@ -0,0 +1,132 @@
+use crate::StorageResult;
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tracing::info;
+
+/// Records the items a workflow has completed, so that an interrupted workflow can be resumed
+/// without repeating work.
+///
+/// Completed items are appended to a file, one per line, as soon as they are marked as complete.
+#[derive(Clone)]
+pub struct Checkpoint {
+    inner: Arc<Mutex<CheckpointInner>>,
+}
+
+struct CheckpointInner {
+    completed: HashSet<String>,
+    file: File,
+}
+
+impl Checkpoint {
+    /// Starts a new checkpoint, discarding any existing progress recorded in the file.
+    pub fn new(path: &Path) -> StorageResult<Self> {
+        let file = File::create(path)?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(CheckpointInner {
+                completed: HashSet::new(),
+                file,
+            })),
+        })
+    }
+
+    /// Continues from the progress recorded in an existing checkpoint file.
+    pub fn resume(path: &Path) -> StorageResult<Self> {
+        let completed = BufReader::new(File::open(path)?)
+            .lines()
+            .collect::<Result<HashSet<String>, _>>()?;
+        info!(
+            "Resuming from checkpoint with {} completed item(s)",
+            completed.len()
+        );
+
+        let file = OpenOptions::new().append(true).open(path)?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(CheckpointInner { completed, file })),
+        })
+    }
+
+    pub(crate) fn is_complete(&self, item: &str) -> bool {
+        self.inner.lock().unwrap().completed.contains(item)
+    }
+
+    pub(crate) fn mark_complete(&self, item: &str) -> StorageResult<()> {
+        let mut inner = self.inner.lock().unwrap();
+
+        writeln!(inner.file, "{item}")?;
+        inner.file.flush()?;
+
+        inner.completed.insert(item.to_owned());
+
+        Ok(())
+    }
+
+    /// Returns the number of completed items recorded in the checkpoint that are not in `items`.
+    pub(crate) fn count_missing<'a>(&self, items: impl Iterator<Item = &'a str>) -> usize {
+        let items: HashSet<&str> = items.collect();
+
+        self.inner
+            .lock()
+            .unwrap()
+            .completed
+            .iter()
+            .filter(|i| !items.contains(i.as_str()))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_new_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+
+        let checkpoint = Checkpoint::new(&path).unwrap();
+        assert!(!checkpoint.is_complete("one"));
+        checkpoint.mark_complete("one").unwrap();
+        checkpoint.mark_complete("two").unwrap();
+        assert!(checkpoint.is_complete("one"));
+        drop(checkpoint);
+
+        let checkpoint = Checkpoint::resume(&path).unwrap();
+        assert!(checkpoint.is_complete("one"));
+        assert!(checkpoint.is_complete("two"));
+        assert!(!checkpoint.is_complete("three"));
+        checkpoint.mark_complete("three").unwrap();
+        drop(checkpoint);
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\nthree\n");
+
+        // Starting a new checkpoint discards previous progress
+        let checkpoint = Checkpoint::new(&path).unwrap();
+        assert!(!checkpoint.is_complete("one"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn test_resume_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Checkpoint::resume(&dir.path().join("checkpoint")).is_err());
+    }
+
+    #[test]
+    fn test_count_missing() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let checkpoint = Checkpoint::new(&dir.path().join("checkpoint")).unwrap();
+        checkpoint.mark_complete("one").unwrap();
+        checkpoint.mark_complete("two").unwrap();
+
+        assert_eq!(checkpoint.count_missing(["one", "two"].into_iter()), 0);
+        assert_eq!(checkpoint.count_missing(["two", "three"].into_iter()), 1);
+    }
+}
@@ -1,30 +1,252 @@
+use super::{progress::ProgressReporter, ProgressCallback};
 use crate::{Provider, StorageError, StorageProvider, StorageResult};
 use bytes::{BufMut, Bytes};
-use satori_common::{CameraSegments, Event};
+use chrono::{DateTime, FixedOffset};
+use satori_common::{hls::parse_segment_start, CameraSegments, Event, SegmentFormat};
 use std::path::{Path, PathBuf};
-use tracing::info;
+use tracing::{info, warn};
+
+/// Container format of an exported video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    Mp4,
+    Mkv,
+    /// MPEG-TS, as the segments are stored.
+    Ts,
+}
+
+impl VideoFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Mkv => "mkv",
+            Self::Ts => "ts",
+        }
+    }
+}
 
 pub fn generate_video_filename(
     event: &Event,
     camera_name: Option<String>,
+    format: VideoFormat,
 ) -> StorageResult<PathBuf> {
     let timestamp = event.metadata.timestamp.to_rfc3339();
     let camera = get_camera_from_event_by_name(event, camera_name)?;
-    Ok(PathBuf::from(format!("{timestamp}_{0}.mp4", camera.name)))
+    Ok(PathBuf::from(format!(
+        "{timestamp}_{0}.{1}",
+        camera.name,
+        format.extension()
+    )))
 }
 
+/// Retrieves an event and the concatenated video segments of one of its cameras.
+///
+/// If provided, `progress` is called each time a segment has been retrieved.
 pub async fn export_event_video(
     storage: Provider,
     event_filename: &Path,
     camera_name: Option<String>,
+    progress: Option<ProgressCallback>,
+) -> StorageResult<(Event, Bytes)> {
+    info!("Getting event: {}", event_filename.display());
+    let event = storage.get_event(event_filename).await?;
+    let camera = get_camera_from_event_by_name(&event, camera_name)?;
+    let video_data = get_file_from_segments(storage, camera, None, progress).await?;
+    Ok((event, video_data))
+}
+
+/// Retrieves an event and the concatenated video segments of one of its cameras, saving each
+/// segment in `cache_dir` as it is retrieved.
+///
+/// Segments already in `cache_dir` are not retrieved again, so an interrupted export can be
+/// resumed by calling this again with the same directory.
+pub async fn export_event_video_resumable(
+    storage: Provider,
+    event_filename: &Path,
+    camera_name: Option<String>,
+    cache_dir: &Path,
+    progress: Option<ProgressCallback>,
 ) -> StorageResult<(Event, Bytes)> {
     info!("Getting event: {}", event_filename.display());
     let event = storage.get_event(event_filename).await?;
     let camera = get_camera_from_event_by_name(&event, camera_name)?;
-    let video_data = get_file_from_segments(storage, camera).await?;
+    std::fs::create_dir_all(cache_dir)?;
+    let video_data = get_file_from_segments(storage, camera, Some(cache_dir), progress).await?;
     Ok((event, video_data))
 }
 
+/// Generates the filename of a video of a camera covering a period of time starting at `start`.
+pub fn generate_camera_video_filename(
+    camera_name: &str,
+    start: DateTime<FixedOffset>,
+    format: VideoFormat,
+) -> PathBuf {
+    PathBuf::from(format!(
+        "{}_{camera_name}.{}",
+        start.to_rfc3339(),
+        format.extension()
+    ))
+}
+
+/// Retrieves the concatenated video segments of a camera covering the period from `start` to
+/// `end`, independent of any event.
+///
+/// Segments are selected as by [`list_segments_between`]. If provided, segments are saved in
+/// `cache_dir` in the same way as [`export_event_video_resumable`].
+pub async fn export_camera_video(
+    storage: Provider,
+    camera_name: &str,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    segment_filename_format: Option<&str>,
+    cache_dir: Option<&Path>,
+    progress: Option<ProgressCallback>,
+) -> StorageResult<(CameraSegments, Bytes)> {
+    let camera = CameraSegments {
+        name: camera_name.into(),
+        segment_list: list_segments_between(
+            &storage,
+            camera_name,
+            start,
+            end,
+            segment_filename_format,
+        )
+        .await?,
+    };
+    if camera.segment_list.is_empty() {
+        return Err(StorageError::NotFound);
+    }
+
+    if let Some(cache_dir) = cache_dir {
+        std::fs::create_dir_all(cache_dir)?;
+    }
+    let video_data = get_file_from_segments(storage, &camera, cache_dir, progress).await?;
+    Ok((camera, video_data))
+}
+
+/// Lists the segments of a camera that cover the period from `start` to `end`, in the order they
+/// were recorded.
+///
+/// The start time of each segment is parsed from its filename using the strftime format
+/// `segment_filename_format`, or the default format for the type of segment if not provided.
+/// See [`select_segments_between`] for how segments are selected.
+pub async fn list_segments_between(
+    storage: &Provider,
+    camera_name: &str,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    segment_filename_format: Option<&str>,
+) -> StorageResult<Vec<PathBuf>> {
+    info!("Listing segments for camera: {camera_name}");
+    let segments = storage.list_segments(camera_name).await?;
+    Ok(select_segments_between(
+        segments,
+        start,
+        end,
+        segment_filename_format,
+    ))
+}
+
+/// Selects the segments that overlap the period from `start` to `end`, in order of their start
+/// time, which is given by their filename.
+///
+/// The duration of segments is not stored, so each segment is assumed to last until the next one
+/// starts and the last segment is assumed to be as long as the one before it.
+/// Fragmented MP4 segments are preceded by the initialisation section that applies to them, i.e.
+/// the latest one named after a segment that is not after them, or one that is not named after a
+/// segment if there is no such initialisation section.
+/// Segments whose filename does not match the filename format are ignored, with a warning.
+fn select_segments_between(
+    segments: Vec<PathBuf>,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    segment_filename_format: Option<&str>,
+) -> Vec<PathBuf> {
+    let filename_format =
+        |format: SegmentFormat| segment_filename_format.unwrap_or(format.segment_filename_format());
+
+    let mut init_sections = Vec::new();
+    let mut timed_segments = Vec::new();
+    let mut unparsed_segments = Vec::new();
+
+    for segment in segments {
+        match SegmentFormat::from_path(&segment) {
+            Some(format) => {
+                match segment
+                    .to_str()
+                    .and_then(|s| parse_segment_start(s, filename_format(format)))
+                {
+                    Some(segment_start) => timed_segments.push((segment_start, format, segment)),
+                    None => unparsed_segments.push(segment),
+                }
+            }
+            None => init_sections.push(segment),
+        }
+    }
+    timed_segments.sort_by_key(|(segment_start, _, _)| *segment_start);
+
+    if let Some(example) = unparsed_segments.first() {
+        warn!(
+            "Ignoring {} segment(s) not matching the segment filename format, e.g. {}",
+            unparsed_segments.len(),
+            example.display()
+        );
+    }
+
+    let segment_end = |idx: usize| match (timed_segments.get(idx + 1), idx.checked_sub(1)) {
+        (Some((next_start, _, _)), _) => *next_start,
+        (None, Some(prev)) => {
+            timed_segments[idx].0 + (timed_segments[idx].0 - timed_segments[prev].0)
+        }
+        (None, None) => timed_segments[idx].0,
+    };
+
+    let selected: Vec<_> = timed_segments
+        .iter()
+        .enumerate()
+        .filter(|(idx, (segment_start, _, _))| *segment_start <= end && start < segment_end(*idx))
+        .map(|(_, segment)| segment)
+        .collect();
+
+    // Initialisation sections named after the first segment they apply to, in order, and those
+    // that are not
+    let mut timed_init_sections = Vec::new();
+    let mut untimed_init_section = None;
+    for init in init_sections {
+        match satori_common::init_section_first_segment(&init, SegmentFormat::Fmp4).and_then(
+            |first| parse_segment_start(first.to_str()?, filename_format(SegmentFormat::Fmp4)),
+        ) {
+            Some(first_start) => timed_init_sections.push((first_start, init)),
+            None => {
+                untimed_init_section.get_or_insert(init);
+            }
+        }
+    }
+    timed_init_sections.sort_by_key(|(first_start, _)| *first_start);
+
+    let mut filenames = Vec::new();
+    let mut current_init = None;
+    for (segment_start, format, segment) in selected {
+        if *format == SegmentFormat::Fmp4 {
+            let init = timed_init_sections
+                .iter()
+                .rev()
+                .find(|(first_start, _)| first_start <= segment_start)
+                .map(|(_, init)| init)
+                .or(untimed_init_section.as_ref());
+
+            if let Some(init) = init {
+                if current_init != Some(init) {
+                    filenames.push(init.clone());
+                    current_init = Some(init);
+                }
+            }
+        }
+        filenames.push(segment.clone());
+    }
+    filenames
+}
+
 fn get_camera_from_event_by_name(
     event: &Event,
     camera_name: Option<String>,
@@ -48,21 +270,52 @@ fn get_camera_from_event_by_name(
 async fn get_file_from_segments(
     storage: Provider,
     camera: &CameraSegments,
+    cache_dir: Option<&Path>,
+    progress: Option<ProgressCallback>,
 ) -> StorageResult<Bytes> {
     let mut file_content: Vec<u8> = Vec::new();
+    let progress = ProgressReporter::new(progress, camera.segment_list.len());
 
     for segment_filename in &camera.segment_list {
-        info!("Getting segment: {}", segment_filename.display());
-        file_content.put(storage.get_segment(&camera.name, segment_filename).await?);
+        file_content.put(get_segment(&storage, &camera.name, segment_filename, cache_dir).await?);
+        progress.increment();
     }
 
     Ok(file_content.into())
 }
 
+/// Gets a segment from the cache directory if it is there, otherwise retrieves it from storage
+/// and adds it to the cache directory.
+async fn get_segment(
+    storage: &Provider,
+    camera_name: &str,
+    segment_filename: &Path,
+    cache_dir: Option<&Path>,
+) -> StorageResult<Bytes> {
+    let cached_filename = cache_dir.map(|dir| dir.join(segment_filename));
+
+    if let Some(cached_filename) = &cached_filename {
+        if cached_filename.is_file() {
+            info!("Using cached segment: {}", segment_filename.display());
+            return Ok(std::fs::read(cached_filename)?.into());
+        }
+    }
+
+    info!("Getting segment: {}", segment_filename.display());
+    let data = storage.get_segment(camera_name, segment_filename).await?;
+
+    // Written atomically, so that an interrupted write is not mistaken for a complete segment
+    if let Some(cached_filename) = &cached_filename {
+        satori_common::write_file_atomic(cached_filename, &data)?;
+    }
+
+    Ok(data)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::providers::dummy::DummyConfig;
+    use crate::{providers::dummy::DummyConfig, workflows::Progress};
     use bytes::Bytes;
     use chrono::Utc;
     use satori_common::{Event, EventMetadata};
@@ -90,9 +343,17 @@ mod test {
         };
 
         assert_eq!(
-            generate_video_filename(&event, None).unwrap(),
+            generate_video_filename(&event, None, VideoFormat::Mp4).unwrap(),
             PathBuf::from("2022-12-30T18:08:00+00:00_camera1.mp4")
         );
+        assert_eq!(
+            generate_video_filename(&event, None, VideoFormat::Mkv).unwrap(),
+            PathBuf::from("2022-12-30T18:08:00+00:00_camera1.mkv")
+        );
+        assert_eq!(
+            generate_video_filename(&event, None, VideoFormat::Ts).unwrap(),
+            PathBuf::from("2022-12-30T18:08:00+00:00_camera1.ts")
+        );
     }
 
     #[test]
@@ -123,7 +384,7 @@ mod test {
         };
 
         assert_eq!(
-            generate_video_filename(&event, Some("camera2".into())).unwrap(),
+            generate_video_filename(&event, Some("camera2".into()), VideoFormat::Mp4).unwrap(),
             PathBuf::from("2022-12-30T18:08:00+00:00_camera2.mp4")
         );
     }
@@ -161,15 +422,313 @@ mod test {
 
         provider.put_event(&event).await.unwrap();
 
+        let (callback, reports) = crate::workflows::progress::test::recording_callback();
+
         let (returned_event, video_bytes) = export_event_video(
             provider,
             &event.metadata.get_filename(),
             Some("camera1".into()),
+            Some(callback),
         )
         .await
         .unwrap();
 
         assert_eq!(returned_event, event);
         assert_eq!(video_bytes, Bytes::from("twothree"));
+
+        // Progress is reported once per segment
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![
+                Progress {
+                    processed: 1,
+                    total: 2
+                },
+                Progress {
+                    processed: 2,
+                    total: 2
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_event_video_resumable() {
+        let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
+        let cache_dir = tempfile::tempdir().unwrap();
+
+        provider
+            .put_segment("camera1", Path::new("1_1.ts"), Bytes::from("one"))
+            .await
+            .unwrap();
+        provider
+            .put_segment("camera1", Path::new("1_2.ts"), Bytes::from("two"))
+            .await
+            .unwrap();
+
+        let event = Event {
+            metadata: EventMetadata {
+                id: "test".into(),
+                timestamp: Utc::now().into(),
+            },
+            start: Utc::now().into(),
+            end: Utc::now().into(),
+            reasons: Default::default(),
+            cameras: vec![CameraSegments {
+                name: "camera1".into(),
+                segment_list: vec![
+                    PathBuf::from("1_1.ts"),
+                    PathBuf::from("1_2.ts"),
+                    PathBuf::from("1_3.ts"),
+                ],
+            }],
+        };
+        provider.put_event(&event).await.unwrap();
+
+        // The first run is interrupted by a segment that cannot be retrieved
+        assert!(export_event_video_resumable(
+            provider.clone(),
+            &event.metadata.get_filename(),
+            None,
+            cache_dir.path(),
+            None,
+        )
+        .await
+        .is_err());
+        assert_eq!(
+            std::fs::read(cache_dir.path().join("1_1.ts")).unwrap(),
+            b"one"
+        );
+        assert_eq!(
+            std::fs::read(cache_dir.path().join("1_2.ts")).unwrap(),
+            b"two"
+        );
+        assert!(!cache_dir.path().join("1_3.ts").exists());
+
+        // Segments retrieved by the first run are not retrieved again
+        provider
+            .delete_segment("camera1", Path::new("1_1.ts"))
+            .await
+            .unwrap();
+        provider
+            .put_segment("camera1", Path::new("1_3.ts"), Bytes::from("three"))
+            .await
+            .unwrap();
+
+        let (callback, reports) = crate::workflows::progress::test::recording_callback();
+
+        let (_, video_bytes) = export_event_video_resumable(
+            provider,
+            &event.metadata.get_filename(),
+            None,
+            cache_dir.path(),
+            Some(callback),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(video_bytes, Bytes::from("onetwothree"));
+        assert_eq!(reports.lock().unwrap().len(), 3);
+        assert_eq!(
+            std::fs::read(cache_dir.path().join("1_3.ts")).unwrap(),
+            b"three"
+        );
+    }
+
+    fn timestamp(minute: u32, second: u32) -> DateTime<FixedOffset> {
+        chrono::NaiveDate::from_ymd_opt(2023, 1, 1)
+            .unwrap()
+            .and_hms_opt(14, minute, second)
+            .unwrap()
+            .and_utc()
+            .fixed_offset()
+    }
+
+    fn ts_segments() -> Vec<PathBuf> {
+        [
+            "2023-01-01T13_59_54+0000.ts",
+            "2023-01-01T14_00_00+0000.ts",
+            "2023-01-01T14_00_06+0000.ts",
+            "2023-01-01T14_00_12+0000.ts",
+            "2023-01-01T14_00_18+0000.ts",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect()
+    }
+
+    #[test]
+    fn test_select_segments_between() {
+        // A segment that started before the period but was still recording is included
+        assert_eq!(
+            select_segments_between(ts_segments(), timestamp(0, 3), timestamp(0, 10), None),
+            vec![
+                PathBuf::from("2023-01-01T14_00_00+0000.ts"),
+                PathBuf::from("2023-01-01T14_00_06+0000.ts"),
+            ]
+        );
+
+        // Segment boundaries
+        assert_eq!(
+            select_segments_between(ts_segments(), timestamp(0, 6), timestamp(0, 12), None),
+            vec![
+                PathBuf::from("2023-01-01T14_00_06+0000.ts"),
+                PathBuf::from("2023-01-01T14_00_12+0000.ts"),
+            ]
+        );
+
+        // The last segment is assumed to be as long as the one before it
+        assert_eq!(
+            select_segments_between(ts_segments(), timestamp(0, 20), timestamp(1, 0), None),
+            vec![PathBuf::from("2023-01-01T14_00_18+0000.ts")]
+        );
+        assert!(
+            select_segments_between(ts_segments(), timestamp(0, 25), timestamp(1, 0), None)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_select_segments_between_unordered_and_invalid() {
+        let mut segments = ts_segments();
+        segments.reverse();
+        segments.push(PathBuf::from("not-a-timestamp.ts"));
+
+        assert_eq!(
+            select_segments_between(segments, timestamp(0, 0), timestamp(0, 5), None),
+            vec![PathBuf::from("2023-01-01T14_00_00+0000.ts")]
+        );
+    }
+
+    #[test]
+    fn test_select_segments_between_fmp4() {
+        let segments = vec![
+            PathBuf::from("init.mp4"),
+            PathBuf::from("2023-01-01T14_00_00+0000.m4s"),
+            PathBuf::from("2023-01-01T14_00_06+0000.m4s"),
+        ];
+
+        assert_eq!(
+            select_segments_between(segments, timestamp(0, 7), timestamp(0, 8), None),
+            vec![
+                PathBuf::from("init.mp4"),
+                PathBuf::from("2023-01-01T14_00_06+0000.m4s"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_segments_between_fmp4_named_init_sections() {
+        let segments = vec![
+            PathBuf::from("init_2023-01-01T14_00_00+0000.mp4"),
+            PathBuf::from("2023-01-01T14_00_00+0000.m4s"),
+            PathBuf::from("2023-01-01T14_00_06+0000.m4s"),
+            PathBuf::from("init_2023-01-01T14_00_12+0000.mp4"),
+            PathBuf::from("2023-01-01T14_00_12+0000.m4s"),
+            PathBuf::from("2023-01-01T14_00_18+0000.m4s"),
+        ];
+
+        // Each segment is preceded by the initialisation section that applies to it
+        assert_eq!(
+            select_segments_between(segments.clone(), timestamp(0, 7), timestamp(0, 13), None),
+            vec![
+                PathBuf::from("init_2023-01-01T14_00_00+0000.mp4"),
+                PathBuf::from("2023-01-01T14_00_06+0000.m4s"),
+                PathBuf::from("init_2023-01-01T14_00_12+0000.mp4"),
+                PathBuf::from("2023-01-01T14_00_12+0000.m4s"),
+            ]
+        );
+
+        assert_eq!(
+            select_segments_between(segments, timestamp(0, 19), timestamp(0, 20), None),
+            vec![
+                PathBuf::from("init_2023-01-01T14_00_12+0000.mp4"),
+                PathBuf::from("2023-01-01T14_00_18+0000.m4s"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_segments_between_custom_filename_format() {
+        let segments = vec![
+            PathBuf::from("20230101-140000.ts"),
+            PathBuf::from("20230101-140006.ts"),
+            PathBuf::from("20230101-140012.ts"),
+        ];
+
+        // Segments are not selected if the format does not match
+        assert!(
+            select_segments_between(segments.clone(), timestamp(0, 7), timestamp(0, 8), None)
+                .is_empty()
+        );
+
+        assert_eq!(
+            select_segments_between(
+                segments,
+                timestamp(0, 7),
+                timestamp(0, 8),
+                Some("%Y%m%d-%H%M%S.ts")
+            ),
+            vec![PathBuf::from("20230101-140006.ts")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_camera_video() {
+        let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        for (i, segment) in ts_segments().iter().enumerate() {
+            provider
+                .put_segment("camera1", segment, Bytes::from(i.to_string()))
+                .await
+                .unwrap();
+        }
+        provider
+            .put_segment(
+                "camera2",
+                Path::new("2023-01-01T14_00_06+0000.ts"),
+                Bytes::from("other camera"),
+            )
+            .await
+            .unwrap();
+
+        let (camera, video_bytes) = export_camera_video(
+            provider.clone(),
+            "camera1",
+            timestamp(0, 0),
+            timestamp(0, 10),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(camera.name, "camera1");
+        assert_eq!(camera.segment_list.len(), 2);
+        assert_eq!(video_bytes, Bytes::from("12"));
+
+        // No video in the period
+        assert!(matches!(
+            export_camera_video(
+                provider,
+                "camera1",
+                timestamp(30, 0),
+                timestamp(40, 0),
+                None,
+                None,
+                None,
+            )
+            .await,
+            Err(StorageError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_generate_camera_video_filename() {
+        assert_eq!(
+            generate_camera_video_filename("camera1", timestamp(0, 0), VideoFormat::Mkv),
+            PathBuf::from("2023-01-01T14:00:00+00:00_camera1.mkv")
+        );
     }
 }
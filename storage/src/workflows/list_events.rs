@@ -0,0 +1,253 @@
+use crate::{Provider, StorageProvider, StorageResult, DEFAULT_PAGE_SIZE};
+use chrono::{DateTime, FixedOffset};
+use satori_common::EventMetadata;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Criteria used to select events.
+#[derive(Debug, Default, Clone)]
+pub struct EventFilter {
+    /// Only select events with a timestamp at or after this time.
+    pub since: Option<DateTime<FixedOffset>>,
+
+    /// Only select events with a timestamp at or before this time.
+    pub until: Option<DateTime<FixedOffset>>,
+
+    /// Only select events that reference this camera.
+    pub camera: Option<String>,
+
+    /// Select at most this many events.
+    pub limit: Option<usize>,
+}
+
+impl EventFilter {
+    fn matches_time(&self, filename: &Path) -> bool {
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+
+        match EventMetadata::from_filename(filename) {
+            Ok(metadata) => {
+                self.since.is_none_or(|since| metadata.timestamp >= since)
+                    && self.until.is_none_or(|until| metadata.timestamp <= until)
+            }
+            Err(_) => {
+                warn!(
+                    "Failed to parse metadata from filename {}, skipping",
+                    filename.display()
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Lists the filenames of events matching a filter, in the order they are listed by the storage
+/// provider.
+///
+/// Time filtering uses the timestamp in the event filename. Camera filtering requires retrieving
+/// each event that passes the time filter.
+pub async fn list_events_filtered(
+    storage: Provider,
+    filter: &EventFilter,
+) -> StorageResult<Vec<PathBuf>> {
+    let limit = filter.limit.unwrap_or(usize::MAX);
+
+    let mut events = Vec::new();
+    let mut start_after = None;
+
+    // Events are listed a page at a time so that listing stops early once the limit is reached
+    loop {
+        let page = storage
+            .list_events_paginated(start_after.as_deref(), DEFAULT_PAGE_SIZE)
+            .await?;
+
+        for filename in page.items {
+            if events.len() >= limit {
+                return Ok(events);
+            }
+
+            if !filter.matches_time(&filename) {
+                continue;
+            }
+
+            if let Some(camera) = &filter.camera {
+                let event = storage.get_event(&filename).await?;
+                if !event.cameras.iter().any(|c| &c.name == camera) {
+                    continue;
+                }
+            }
+
+            events.push(filename);
+        }
+
+        match page.next {
+            Some(next) => start_after = Some(next),
+            None => return Ok(events),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::providers::dummy::DummyConfig;
+    use chrono::{TimeZone, Utc};
+    use satori_common::{CameraSegments, Event};
+
+    fn timestamp(day: u32) -> DateTime<FixedOffset> {
+        Utc.with_ymd_and_hms(2023, 3, day, 12, 0, 0).unwrap().into()
+    }
+
+    async fn build_test_storage() -> Provider {
+        let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        for (day, cameras) in [
+            (1, vec!["camera1"]),
+            (2, vec!["camera2"]),
+            (3, vec!["camera1", "camera2"]),
+            (4, vec![]),
+            (5, vec!["camera1"]),
+        ] {
+            provider
+                .put_event(&Event {
+                    metadata: EventMetadata {
+                        id: format!("test-{day}"),
+                        timestamp: timestamp(day),
+                    },
+                    start: timestamp(day),
+                    end: timestamp(day),
+                    reasons: Default::default(),
+                    cameras: cameras
+                        .into_iter()
+                        .map(|name| CameraSegments {
+                            name: name.into(),
+                            segment_list: Default::default(),
+                        })
+                        .collect(),
+                })
+                .await
+                .unwrap();
+        }
+
+        provider
+    }
+
+    async fn list(filter: EventFilter) -> Vec<String> {
+        let storage = build_test_storage().await;
+
+        list_events_filtered(storage, &filter)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|f| EventMetadata::from_filename(&f).unwrap().id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_no_filter() {
+        assert_eq!(
+            list(EventFilter::default()).await,
+            vec!["test-1", "test-2", "test-3", "test-4", "test-5"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_time_range() {
+        assert_eq!(
+            list(EventFilter {
+                since: Some(timestamp(2)),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-2", "test-3", "test-4", "test-5"]
+        );
+
+        assert_eq!(
+            list(EventFilter {
+                until: Some(timestamp(2)),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-1", "test-2"]
+        );
+
+        assert_eq!(
+            list(EventFilter {
+                since: Some(timestamp(2)),
+                until: Some(timestamp(4)),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-2", "test-3", "test-4"]
+        );
+
+        // Timestamps in other timezones are compared correctly
+        assert_eq!(
+            list(EventFilter {
+                since: Some(DateTime::parse_from_rfc3339("2023-03-04T13:00:00+01:00").unwrap()),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-4", "test-5"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_camera() {
+        assert_eq!(
+            list(EventFilter {
+                camera: Some("camera1".into()),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-1", "test-3", "test-5"]
+        );
+
+        assert_eq!(
+            list(EventFilter {
+                camera: Some("camera2".into()),
+                since: Some(timestamp(3)),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-3"]
+        );
+
+        assert!(list(EventFilter {
+            camera: Some("camera3".into()),
+            ..Default::default()
+        })
+        .await
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_limit() {
+        assert_eq!(
+            list(EventFilter {
+                limit: Some(2),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-1", "test-2"]
+        );
+
+        assert_eq!(
+            list(EventFilter {
+                camera: Some("camera1".into()),
+                limit: Some(2),
+                ..Default::default()
+            })
+            .await,
+            vec!["test-1", "test-3"]
+        );
+
+        assert!(list(EventFilter {
+            limit: Some(0),
+            ..Default::default()
+        })
+        .await
+        .is_empty());
+    }
+}
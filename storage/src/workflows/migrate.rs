@@ -0,0 +1,352 @@
+use crate::{Provider, StorageError, StorageProvider, StorageResult};
+use std::{collections::HashSet, path::PathBuf};
+use tracing::{info, warn};
+
+#[derive(Debug)]
+enum Job {
+    Event(PathBuf),
+    Segment(String, PathBuf),
+}
+
+/// Copies every event and segment from one storage provider to another.
+///
+/// Objects are decrypted when read from `source` and encrypted when written to `dest`, each using
+/// the encryption configuration of their own provider. Event notes are copied along with their
+/// event.
+///
+/// Objects that already exist in `dest` are skipped, so an interrupted migration can be completed
+/// by running it again.
+pub async fn migrate(source: Provider, dest: Provider, num_workers: usize) -> StorageResult<()> {
+    let mut jobs = Vec::new();
+
+    info!("Getting event lists");
+    let existing_events: HashSet<PathBuf> = dest.list_events().await?.into_iter().collect();
+    for filename in source.list_events().await? {
+        if !existing_events.contains(&filename) {
+            jobs.push(Job::Event(filename));
+        }
+    }
+
+    info!("Getting camera lists");
+    let existing_cameras: HashSet<String> = dest.list_cameras().await?.into_iter().collect();
+    for camera in source.list_cameras().await? {
+        info!("Getting segment lists for camera \"{camera}\"");
+        let existing_segments: HashSet<PathBuf> = if existing_cameras.contains(&camera) {
+            dest.list_segments(&camera).await?.into_iter().collect()
+        } else {
+            HashSet::new()
+        };
+
+        for filename in source.list_segments(&camera).await? {
+            if !existing_segments.contains(&filename) {
+                jobs.push(Job::Segment(camera.clone(), filename));
+            }
+        }
+    }
+
+    info!("Copying {} item(s)", jobs.len());
+
+    // Channel that forms the job queue for workers
+    let (tx, rx) = async_channel::unbounded();
+
+    for job in jobs {
+        tx.send(job).await.expect("task channel should be open");
+    }
+
+    // Workers will terminate when the channel is empty and closed
+    tx.close();
+
+    let mut workers = Vec::new();
+    for worker_idx in 0..num_workers {
+        let source = source.clone();
+        let dest = dest.clone();
+        let rx = rx.clone();
+
+        workers.push(tokio::spawn(async move {
+            let mut result = Ok(());
+
+            while let Ok(job) = rx.recv().await {
+                info!("(worker {worker_idx}) Copying {job:?}");
+
+                if let Err(err) = copy_object(&source, &dest, &job).await {
+                    result = Err(StorageError::WorkflowPartialError);
+                    warn!("Failed to copy {job:?}, error: {err}");
+                }
+            }
+
+            result
+        }));
+    }
+
+    // Wait for all workers to terminate, returning an error if any one job failed
+    if futures::future::join_all(workers)
+        .await
+        .iter()
+        .any(|r| match r {
+            Err(_) => true,
+            Ok(Err(_)) => true,
+            Ok(_) => false,
+        })
+    {
+        Err(StorageError::WorkflowPartialError)
+    } else {
+        Ok(())
+    }
+}
+
+async fn copy_object(source: &Provider, dest: &Provider, job: &Job) -> StorageResult<()> {
+    match job {
+        Job::Event(filename) => {
+            let event = source.get_event(filename).await?;
+
+            // The note is copied first, as an event that exists in the destination is skipped
+            if let Some(note) = source.get_event_note(filename).await? {
+                dest.put_event_note(filename, &note).await?;
+            }
+
+            dest.put_event(&event).await
+        }
+        Job::Segment(camera, filename) => {
+            let data = source.get_segment(camera, filename).await?;
+            dest.put_segment(camera, filename, data).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventNote, StorageConfig};
+    use bytes::Bytes;
+    use chrono::Utc;
+    use satori_common::{CameraSegments, Event, EventMetadata};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    const SOURCE_KEY: &str = "
+[encryption.event]
+kind = \"aes256_gcm\"
+key = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+]
+[encryption.segment]
+kind = \"aes256_gcm\"
+key = [
+    31, 30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 18, 17, 16,
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+]
+";
+
+    const DEST_KEY: &str = "
+[encryption.event]
+kind = \"aes256_gcm\"
+key = [
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+]
+[encryption.segment]
+kind = \"aes256_gcm\"
+key = [
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+]
+";
+
+    fn build_test_storage(dir: &Path, extra: &str) -> Provider {
+        let config: StorageConfig = toml::from_str(&format!(
+            "kind = \"local\"\npath = \"{}\"\n{extra}",
+            dir.display()
+        ))
+        .unwrap();
+        config.create_provider()
+    }
+
+    fn test_event(id: &str, segments: &[&str]) -> Event {
+        Event {
+            metadata: EventMetadata {
+                id: id.into(),
+                timestamp: Utc::now().into(),
+            },
+            start: Utc::now().into(),
+            end: Utc::now().into(),
+            reasons: Default::default(),
+            cameras: vec![CameraSegments {
+                name: "camera1".into(),
+                segment_list: segments.iter().map(PathBuf::from).collect(),
+            }],
+        }
+    }
+
+    async fn populate(storage: &Provider) -> (Event, Event) {
+        let event1 = test_event("test-1", &["one.ts"]);
+        let event2 = test_event("test-2", &["two.ts"]);
+
+        storage.put_event(&event1).await.unwrap();
+        storage
+            .put_event_note(
+                &event1.metadata.get_filename(),
+                &EventNote::new("note".into()),
+            )
+            .await
+            .unwrap();
+        storage.put_event(&event2).await.unwrap();
+
+        for (camera, segment) in [
+            ("camera1", "one.ts"),
+            ("camera1", "two.ts"),
+            ("camera2", "one.ts"),
+        ] {
+            storage
+                .put_segment(
+                    camera,
+                    Path::new(segment),
+                    Bytes::from(format!("{camera} {segment}")),
+                )
+                .await
+                .unwrap();
+        }
+
+        (event1, event2)
+    }
+
+    async fn assert_contents(storage: &Provider, events: &[&Event]) {
+        assert_eq!(storage.list_events().await.unwrap().len(), events.len());
+        for event in events {
+            let filename = event.metadata.get_filename();
+            assert_eq!(storage.get_event(&filename).await.unwrap(), **event);
+        }
+
+        assert_eq!(
+            storage
+                .get_event_note(&events[0].metadata.get_filename())
+                .await
+                .unwrap()
+                .unwrap()
+                .text,
+            "note"
+        );
+
+        assert_eq!(
+            storage.list_cameras().await.unwrap(),
+            vec!["camera1".to_string(), "camera2".to_string()]
+        );
+        for (camera, segment) in [
+            ("camera1", "one.ts"),
+            ("camera1", "two.ts"),
+            ("camera2", "one.ts"),
+        ] {
+            assert_eq!(
+                storage
+                    .get_segment(camera, Path::new(segment))
+                    .await
+                    .unwrap(),
+                Bytes::from(format!("{camera} {segment}"))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+        let source = build_test_storage(source_dir.path(), SOURCE_KEY);
+        let dest = build_test_storage(dest_dir.path(), DEST_KEY);
+
+        let (event1, event2) = populate(&source).await;
+
+        migrate(source.clone(), dest.clone(), 2).await.unwrap();
+
+        // Everything can be read from the destination, using its own key
+        assert_contents(&dest, &[&event1, &event2]).await;
+
+        // The source is unchanged
+        assert_contents(&source, &[&event1, &event2]).await;
+
+        // Nothing in the destination is encrypted with the source key
+        let dest_with_source_key = build_test_storage(dest_dir.path(), SOURCE_KEY);
+        assert!(dest_with_source_key
+            .get_event(&event1.metadata.get_filename())
+            .await
+            .is_err());
+        assert!(dest_with_source_key
+            .get_segment("camera1", Path::new("one.ts"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_from_memory() {
+        let source: StorageConfig = serde_json::from_str(
+            r#"{"kind": "dummy", "initial_state": {"events": {}, "segments": {}}}"#,
+        )
+        .unwrap();
+        let source = source.create_provider();
+
+        let dest_dir = TempDir::new().unwrap();
+        let dest = build_test_storage(dest_dir.path(), DEST_KEY);
+
+        let (event1, event2) = populate(&source).await;
+
+        migrate(source, dest.clone(), 2).await.unwrap();
+
+        assert_contents(&dest, &[&event1, &event2]).await;
+    }
+
+    #[tokio::test]
+    async fn test_migrate_skips_existing() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+        let source = build_test_storage(source_dir.path(), SOURCE_KEY);
+        let dest = build_test_storage(dest_dir.path(), DEST_KEY);
+
+        let (event1, event2) = populate(&source).await;
+
+        // Some objects were already copied by a previous, interrupted, migration
+        dest.put_segment("camera1", Path::new("one.ts"), Bytes::from("existing"))
+            .await
+            .unwrap();
+        dest.put_event(&event2).await.unwrap();
+
+        migrate(source, dest.clone(), 2).await.unwrap();
+
+        assert_eq!(
+            dest.get_segment("camera1", Path::new("one.ts"))
+                .await
+                .unwrap(),
+            Bytes::from("existing")
+        );
+        assert_eq!(
+            dest.get_segment("camera1", Path::new("two.ts"))
+                .await
+                .unwrap(),
+            Bytes::from("camera1 two.ts")
+        );
+        assert_eq!(dest.list_events().await.unwrap().len(), 2);
+        assert_eq!(
+            dest.get_event(&event1.metadata.get_filename())
+                .await
+                .unwrap(),
+            event1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrate_unreadable_source() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+        let source = build_test_storage(source_dir.path(), SOURCE_KEY);
+        let dest = build_test_storage(dest_dir.path(), DEST_KEY);
+
+        populate(&source).await;
+
+        // The source cannot be decrypted with the wrong key
+        let source = build_test_storage(source_dir.path(), DEST_KEY);
+
+        assert!(matches!(
+            migrate(source, dest.clone(), 2).await,
+            Err(StorageError::WorkflowPartialError)
+        ));
+        assert!(dest.list_events().await.unwrap().is_empty());
+    }
+}
@@ -1,10 +1,36 @@
+mod checkpoint;
+pub use checkpoint::Checkpoint;
+
 mod export_event_video;
-pub use export_event_video::{export_event_video, generate_video_filename};
+pub use export_event_video::{
+    export_camera_video, export_event_video, export_event_video_resumable,
+    generate_camera_video_filename, generate_video_filename, list_segments_between, VideoFormat,
+};
+
+mod list_events;
+pub use list_events::{list_events_filtered, EventFilter};
+
+mod migrate;
+pub use migrate::migrate;
+
+mod progress;
+pub use progress::{Progress, ProgressCallback};
 
 mod prune_events;
-pub use prune_events::prune_events_older_than;
+pub use prune_events::{prune_events_bounded, prune_events_keep_last, prune_events_older_than};
 
 mod prune_segments;
 pub use prune_segments::{
     calculate_unreferenced_segments, delete_unreferenced_segments, UnreferencedSegments,
 };
+
+mod reencrypt;
+pub use reencrypt::reencrypt_archive;
+
+mod segment_stats;
+pub use segment_stats::{
+    segment_size_stats, HistogramBucket, SegmentSizeReport, SegmentSizeThresholds,
+};
+
+mod verify;
+pub use verify::{verify_archive, VerifyReport};
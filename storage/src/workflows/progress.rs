@@ -0,0 +1,92 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Progress of a workflow, reported each time an item has been processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub processed: usize,
+    pub total: usize,
+}
+
+/// Callback that is called with the progress of a workflow.
+pub type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Counts processed items and reports progress to an optional callback.
+#[derive(Clone)]
+pub(crate) struct ProgressReporter {
+    callback: Option<ProgressCallback>,
+    processed: Arc<AtomicUsize>,
+    total: usize,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(callback: Option<ProgressCallback>, total: usize) -> Self {
+        Self {
+            callback,
+            processed: Default::default(),
+            total,
+        }
+    }
+
+    /// Records that one more item has been processed.
+    pub(crate) fn increment(&self) {
+        let processed = self.processed.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if let Some(callback) = &self.callback {
+            callback(Progress {
+                processed,
+                total: self.total,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Returns a callback that records every report it receives.
+    pub(crate) fn recording_callback() -> (ProgressCallback, Arc<Mutex<Vec<Progress>>>) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+
+        let callback: ProgressCallback = {
+            let reports = reports.clone();
+            Arc::new(move |progress| reports.lock().unwrap().push(progress))
+        };
+
+        (callback, reports)
+    }
+
+    #[test]
+    fn test_reporter() {
+        let (callback, reports) = recording_callback();
+
+        let reporter = ProgressReporter::new(Some(callback), 2);
+        reporter.increment();
+        reporter.clone().increment();
+
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![
+                Progress {
+                    processed: 1,
+                    total: 2
+                },
+                Progress {
+                    processed: 2,
+                    total: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reporter_without_callback() {
+        let reporter = ProgressReporter::new(None, 2);
+        reporter.increment();
+        reporter.increment();
+    }
+}
@@ -1,45 +1,163 @@
 use crate::{Provider, StorageError, StorageProvider, StorageResult};
 use chrono::{DateTime, FixedOffset};
 use satori_common::EventMetadata;
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 use tracing::{error, info};
 
+/// Deletes all events with a timestamp before `time`.
+///
+/// Returns the filenames of the events that were pruned (or would have been pruned, if `dry_run`
+/// is set).
 pub async fn prune_events_older_than(
     storage: Provider,
     time: DateTime<FixedOffset>,
-) -> StorageResult<()> {
+    dry_run: bool,
+) -> StorageResult<Vec<PathBuf>> {
+    prune_events_bounded(storage, Some(time), None, dry_run).await
+}
+
+/// Deletes all events with a timestamp before `older_than`, then the oldest of the remaining
+/// events such that at most `max_events` are kept.
+///
+/// Returns the filenames of the events that were pruned (or would have been pruned, if `dry_run`
+/// is set).
+pub async fn prune_events_bounded(
+    storage: Provider,
+    older_than: Option<DateTime<FixedOffset>>,
+    max_events: Option<usize>,
+    dry_run: bool,
+) -> StorageResult<Vec<PathBuf>> {
     info!("Getting event list");
     let event_filenames = storage.list_events().await?;
 
     let mut result = Ok(());
 
-    // Filter the list of event filenames, removing those that we want to keep
-    let event_files_to_delete: Vec<PathBuf> = event_filenames
+    // Get the timestamp of each event, events with an unknown timestamp are always kept
+    let mut events: Vec<(DateTime<FixedOffset>, PathBuf)> = event_filenames
         .into_iter()
-        .filter(|event| match EventMetadata::from_filename(event) {
-            Ok(metadata) => metadata.timestamp < time,
+        .filter_map(|filename| match EventMetadata::from_filename(&filename) {
+            Ok(metadata) => Some((metadata.timestamp, filename)),
             Err(_) => {
                 error!("Failed to parse metadata from filename");
                 result = Err(StorageError::WorkflowPartialError);
-                false
+                None
             }
         })
         .collect();
 
+    // Sort events, oldest first
+    events.sort();
+
+    // Events that are too old
+    let old_event_count = match older_than {
+        Some(time) => events.partition_point(|(timestamp, _)| *timestamp < time),
+        None => 0,
+    };
+
+    // Events in excess of the maximum number that are kept, after those that are too old
+    let surplus_event_count = match max_events {
+        Some(max) => (events.len() - old_event_count).saturating_sub(max),
+        None => 0,
+    };
+
+    let event_files_to_delete: Vec<PathBuf> = events
+        .into_iter()
+        .take(old_event_count + surplus_event_count)
+        .map(|(_, filename)| filename)
+        .collect();
+
     // Delete all the events marked for deletion
-    for filename in event_files_to_delete {
-        info!("Pruning event: {}", filename.display());
-        if let Err(err) = storage.delete_event_filename(&filename).await {
-            error!(
-                "Failed to remove event file {}, reason: {}",
-                filename.display(),
-                err
-            );
-            result = Err(StorageError::WorkflowPartialError);
+    for filename in &event_files_to_delete {
+        if dry_run {
+            info!("Would prune event: {}", filename.display());
+        } else {
+            info!("Pruning event: {}", filename.display());
+            if let Err(err) = storage.delete_event_filename(filename).await {
+                error!(
+                    "Failed to remove event file {}, reason: {}",
+                    filename.display(),
+                    err
+                );
+                result = Err(StorageError::WorkflowPartialError);
+            }
         }
     }
 
-    result
+    result.map(|_| event_files_to_delete)
+}
+
+/// Keeps the `keep` most recent events for each camera, deleting all others.
+///
+/// An event is kept if it is one of the `keep` most recent events referencing any of its cameras.
+/// Events that do not reference any cameras are always kept.
+///
+/// Returns the filenames of the events that were pruned (or would have been pruned, if `dry_run`
+/// is set).
+pub async fn prune_events_keep_last(
+    storage: Provider,
+    keep: usize,
+    dry_run: bool,
+) -> StorageResult<Vec<PathBuf>> {
+    info!("Getting event list");
+    let event_filenames = storage.list_events().await?;
+
+    let mut result = Ok(());
+
+    let mut events = Vec::new();
+    for filename in event_filenames {
+        match storage.get_event(&filename).await {
+            Ok(event) => events.push((filename, event)),
+            Err(err) => {
+                error!(
+                    "Failed to get event file {}, reason: {}",
+                    filename.display(),
+                    err
+                );
+                result = Err(StorageError::WorkflowPartialError);
+            }
+        }
+    }
+
+    // Sort events, most recent first
+    events.sort_by_key(|(_, event)| std::cmp::Reverse(event.metadata.timestamp));
+
+    // Find the events that are not within the most recent events of any of the cameras they reference
+    let mut kept_event_count: HashMap<String, usize> = HashMap::new();
+    let event_files_to_delete: Vec<PathBuf> = events
+        .into_iter()
+        .filter_map(|(filename, event)| {
+            let mut keep_event = event.cameras.is_empty();
+
+            for camera in &event.cameras {
+                let count = kept_event_count.entry(camera.name.clone()).or_default();
+                if *count < keep {
+                    *count += 1;
+                    keep_event = true;
+                }
+            }
+
+            (!keep_event).then_some(filename)
+        })
+        .collect();
+
+    // Delete all the events marked for deletion
+    for filename in &event_files_to_delete {
+        if dry_run {
+            info!("Would prune event: {}", filename.display());
+        } else {
+            info!("Pruning event: {}", filename.display());
+            if let Err(err) = storage.delete_event_filename(filename).await {
+                error!(
+                    "Failed to remove event file {}, reason: {}",
+                    filename.display(),
+                    err
+                );
+                result = Err(StorageError::WorkflowPartialError);
+            }
+        }
+    }
+
+    result.map(|_| event_files_to_delete)
 }
 
 #[cfg(test)]
@@ -47,7 +165,7 @@ mod test {
     use super::*;
     use crate::providers::dummy::DummyConfig;
     use chrono::{FixedOffset, NaiveDate, Utc};
-    use satori_common::{Event, EventMetadata};
+    use satori_common::{CameraSegments, Event, EventMetadata};
 
     async fn build_test_storage() -> Provider {
         let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
@@ -124,6 +242,7 @@ mod test {
                 .unwrap()
                 .and_local_timezone(FixedOffset::east_opt(0).unwrap())
                 .unwrap(),
+            false,
         )
         .await
         .unwrap();
@@ -144,6 +263,7 @@ mod test {
                 .unwrap()
                 .and_local_timezone(FixedOffset::east_opt(0).unwrap())
                 .unwrap(),
+            false,
         )
         .await
         .unwrap();
@@ -151,4 +271,232 @@ mod test {
         let events = provider.list_events().await.unwrap();
         assert_eq!(events.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_prune_events_older_than_dry_run() {
+        let provider = build_test_storage().await;
+
+        let pruned = prune_events_older_than(
+            provider.clone(),
+            NaiveDate::from_ymd_opt(2023, 3, 1)
+                .unwrap()
+                .and_hms_opt(21, 0, 0)
+                .unwrap()
+                .and_local_timezone(FixedOffset::east_opt(0).unwrap())
+                .unwrap(),
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            pruned,
+            vec![
+                PathBuf::from("2023-03-01T12:00:00+00:00_test-1.json"),
+                PathBuf::from("2023-03-01T12:10:00+00:00_test-2.json"),
+            ]
+        );
+
+        // Nothing was removed
+        let events = provider.list_events().await.unwrap();
+        assert_eq!(events.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_bounded_count() {
+        let provider = build_test_storage().await;
+
+        let pruned = prune_events_bounded(provider.clone(), None, Some(2), false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            pruned,
+            vec![PathBuf::from("2023-03-01T12:00:00+00:00_test-1.json")]
+        );
+        assert_eq!(provider.list_events().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_bounded_age_and_count() {
+        let provider = build_test_storage().await;
+
+        // Age removes one event, the count is then already within the limit
+        let pruned = prune_events_bounded(
+            provider.clone(),
+            Some(
+                NaiveDate::from_ymd_opt(2023, 3, 1)
+                    .unwrap()
+                    .and_hms_opt(12, 5, 0)
+                    .unwrap()
+                    .and_local_timezone(FixedOffset::east_opt(0).unwrap())
+                    .unwrap(),
+            ),
+            Some(2),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            pruned,
+            vec![PathBuf::from("2023-03-01T12:00:00+00:00_test-1.json")]
+        );
+
+        // Age removes one event, the count then removes the oldest of the remaining events
+        let pruned = prune_events_bounded(
+            provider.clone(),
+            Some(
+                NaiveDate::from_ymd_opt(2023, 3, 1)
+                    .unwrap()
+                    .and_hms_opt(12, 5, 0)
+                    .unwrap()
+                    .and_local_timezone(FixedOffset::east_opt(0).unwrap())
+                    .unwrap(),
+            ),
+            Some(1),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            pruned,
+            vec![
+                PathBuf::from("2023-03-01T12:00:00+00:00_test-1.json"),
+                PathBuf::from("2023-03-01T12:10:00+00:00_test-2.json"),
+            ]
+        );
+
+        assert_eq!(
+            provider.list_events().await.unwrap(),
+            vec![PathBuf::from("2023-03-02T07:00:00+00:00_test-3.json")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_bounded_zero() {
+        let provider = build_test_storage().await;
+
+        let pruned = prune_events_bounded(provider.clone(), None, Some(0), false)
+            .await
+            .unwrap();
+
+        assert_eq!(pruned.len(), 3);
+        assert!(provider.list_events().await.unwrap().is_empty());
+    }
+
+    async fn build_test_storage_with_cameras() -> Provider {
+        let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        for (id, hour, cameras) in [
+            ("test-1", 1, vec!["camera-1"]),
+            ("test-2", 2, vec!["camera-1", "camera-2"]),
+            ("test-3", 3, vec!["camera-2"]),
+            ("test-4", 4, vec!["camera-1"]),
+            ("test-5", 5, vec![]),
+            ("test-6", 6, vec!["camera-2"]),
+            ("test-7", 7, vec!["camera-1"]),
+        ] {
+            provider
+                .put_event(&Event {
+                    metadata: EventMetadata {
+                        id: id.into(),
+                        timestamp: NaiveDate::from_ymd_opt(2023, 3, 1)
+                            .unwrap()
+                            .and_hms_opt(hour, 0, 0)
+                            .unwrap()
+                            .and_local_timezone(FixedOffset::east_opt(0).unwrap())
+                            .unwrap(),
+                    },
+                    start: Utc::now().into(),
+                    end: Utc::now().into(),
+                    reasons: Default::default(),
+                    cameras: cameras
+                        .into_iter()
+                        .map(|name| CameraSegments {
+                            name: name.into(),
+                            segment_list: Default::default(),
+                        })
+                        .collect(),
+                })
+                .await
+                .unwrap();
+        }
+
+        provider
+    }
+
+    async fn remaining_event_ids(provider: &Provider) -> Vec<String> {
+        let mut ids: Vec<String> = provider
+            .list_events()
+            .await
+            .unwrap()
+            .iter()
+            .map(|f| EventMetadata::from_filename(f).unwrap().id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_keep_last() {
+        let provider = build_test_storage_with_cameras().await;
+
+        let pruned = prune_events_keep_last(provider.clone(), 2, false)
+            .await
+            .unwrap();
+        assert_eq!(pruned.len(), 2);
+
+        // camera-1 keeps test-7 and test-4, camera-2 keeps test-6 and test-3, test-5 references no
+        // cameras
+        assert_eq!(
+            remaining_event_ids(&provider).await,
+            vec!["test-3", "test-4", "test-5", "test-6", "test-7"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_keep_last_shared_event() {
+        let provider = build_test_storage_with_cameras().await;
+
+        prune_events_keep_last(provider.clone(), 3, false)
+            .await
+            .unwrap();
+
+        // test-2 is outside of the keep set of camera-1, but within the keep set of camera-2
+        assert_eq!(
+            remaining_event_ids(&provider).await,
+            vec!["test-2", "test-3", "test-4", "test-5", "test-6", "test-7"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_keep_last_zero() {
+        let provider = build_test_storage_with_cameras().await;
+
+        prune_events_keep_last(provider.clone(), 0, false)
+            .await
+            .unwrap();
+
+        assert_eq!(remaining_event_ids(&provider).await, vec!["test-5"]);
+    }
+
+    #[tokio::test]
+    async fn test_prune_events_keep_last_dry_run() {
+        let provider = build_test_storage_with_cameras().await;
+
+        let mut pruned = prune_events_keep_last(provider.clone(), 2, true)
+            .await
+            .unwrap();
+        pruned.sort();
+        assert_eq!(
+            pruned
+                .iter()
+                .map(|f| EventMetadata::from_filename(f).unwrap().id)
+                .collect::<Vec<_>>(),
+            vec!["test-1", "test-2"]
+        );
+
+        // Nothing should have been deleted
+        assert_eq!(provider.list_events().await.unwrap().len(), 7);
+    }
 }
@@ -1,4 +1,6 @@
+use super::{progress::ProgressReporter, ProgressCallback};
 use crate::{Provider, StorageError, StorageProvider, StorageResult};
+use chrono::Utc;
 use satori_common::Event;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -10,6 +12,10 @@ use std::{
 };
 use tracing::{info, warn};
 
+/// Maximum number of segments to delete in a single batch, this matches the limit for S3
+/// multi-object deletes.
+const MAX_DELETE_BATCH_SIZE: usize = 1000;
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct UnreferencedSegments {
     #[serde(flatten)]
@@ -26,6 +32,17 @@ impl UnreferencedSegments {
     pub fn load(file: &Path) -> StorageResult<Self> {
         Ok(toml::from_str(&std::fs::read_to_string(file)?)?)
     }
+
+    /// Unreferenced segments for each camera, ordered by camera name.
+    pub fn cameras(&self) -> Vec<(&str, &[PathBuf])> {
+        let mut cameras: Vec<_> = self
+            .inner
+            .iter()
+            .map(|(camera, segments)| (camera.as_str(), segments.as_slice()))
+            .collect();
+        cameras.sort_by_key(|(camera, _)| *camera);
+        cameras
+    }
 }
 
 /// Retrieves a list of segments that are referred to by any event in a given storage provider.
@@ -38,6 +55,7 @@ impl UnreferencedSegments {
 async fn get_referenced_segments(
     storage: Provider,
     num_workers: usize,
+    progress: Option<ProgressCallback>,
 ) -> StorageResult<UniqueCameraSegmentCollection> {
     info!("Getting event list");
     let event_filenames = storage.list_events().await?;
@@ -47,6 +65,7 @@ async fn get_referenced_segments(
         event_filenames.len()
     );
     let referenced_segments = UniqueCameraSegmentCollection::default();
+    let progress = ProgressReporter::new(progress, event_filenames.len());
 
     // Channel that forms the job queue for workers
     let (tx, rx) = async_channel::unbounded();
@@ -66,6 +85,7 @@ async fn get_referenced_segments(
         let storage = storage.clone();
         let rx = rx.clone();
         let referenced_segments = referenced_segments.clone();
+        let progress = progress.clone();
 
         workers.push(tokio::spawn(async move {
             while let Ok(filename) = rx.recv().await {
@@ -78,6 +98,7 @@ async fn get_referenced_segments(
                 match storage.get_event(&filename).await {
                     Ok(event) => {
                         referenced_segments.add_from_event(event);
+                        progress.increment();
                     }
                     Err(err) => {
                         warn!(
@@ -110,23 +131,89 @@ async fn get_referenced_segments(
     }
 }
 
+/// Retrieves the list of segments stored for each of a set of cameras.
+async fn get_camera_segments(
+    storage: Provider,
+    cameras: &[String],
+    num_workers: usize,
+) -> StorageResult<HashMap<String, Vec<PathBuf>>> {
+    let camera_segment_cache: Arc<Mutex<HashMap<String, Vec<PathBuf>>>> = Default::default();
+
+    // Channel that forms the job queue for workers
+    let (tx, rx) = async_channel::unbounded();
+
+    // Fill the channel with the camera names then immediately close it
+    // Workers will terminate when the channel is empty and closed
+    for camera in cameras {
+        tx.send(camera.clone())
+            .await
+            .expect("task channel should be open");
+    }
+    tx.close();
+
+    // Start as many workers as were requested
+    let mut workers = Vec::new();
+    for worker_idx in 0..num_workers {
+        let storage = storage.clone();
+        let rx = rx.clone();
+        let camera_segment_cache = camera_segment_cache.clone();
+
+        workers.push(tokio::spawn(async move {
+            while let Ok(camera) = rx.recv().await {
+                info!("(worker {worker_idx}) Getting segment list for camera \"{camera}\"");
+
+                // Get a list of all segments stored for the camera
+                match storage.list_segments(&camera).await {
+                    Ok(segments) => {
+                        camera_segment_cache
+                            .lock()
+                            .unwrap()
+                            .insert(camera, segments);
+                    }
+                    Err(err) => {
+                        warn!("Failed to list segments for camera \"{camera}\", error: {err}");
+                        return Err(StorageError::WorkflowPartialError);
+                    }
+                }
+            }
+
+            Ok(())
+        }));
+    }
+
+    // Wait for all workers to terminate, collecting results and returning an error if any one job
+    // failed
+    if futures::future::join_all(workers)
+        .await
+        .iter()
+        .any(|r| match r {
+            Err(_) => true,
+            Ok(Err(_)) => true,
+            Ok(_) => false,
+        })
+    {
+        Err(StorageError::WorkflowPartialError)
+    } else {
+        Ok(std::mem::take(&mut *camera_segment_cache.lock().unwrap()))
+    }
+}
+
+/// Calculates the segments that are not referenced by any event.
+///
+/// If provided, `progress` is called each time an event has been processed.
 pub async fn calculate_unreferenced_segments(
     storage: Provider,
     num_workers: usize,
+    progress: Option<ProgressCallback>,
 ) -> StorageResult<UnreferencedSegments> {
     info!("Getting camera list");
     let cameras = storage.list_cameras().await?;
 
     info!("Getting segment list(s)");
-    let mut camera_segment_cache: HashMap<String, Vec<PathBuf>> = HashMap::new();
-    // For each camera that has segments in the archive
-    for camera in &cameras {
-        info!("Getting segment list for camera \"{camera}\"");
-        // Get a list of all segments stored for the camera
-        camera_segment_cache.insert(camera.clone(), storage.list_segments(camera).await?);
-    }
+    let mut camera_segment_cache =
+        get_camera_segments(storage.clone(), &cameras, num_workers).await?;
 
-    let referenced_segments = get_referenced_segments(storage, num_workers).await?;
+    let referenced_segments = get_referenced_segments(storage, num_workers, progress).await?;
 
     let mut all_unreferenced_segments = UnreferencedSegments::default();
 
@@ -170,8 +257,17 @@ pub async fn delete_unreferenced_segments(
 
         let (tx, rx) = async_channel::unbounded();
 
-        for s in segments {
-            tx.send(s).await.expect("task channel should be open");
+        // Split segments into batches, such that each worker gets at least one batch where
+        // possible
+        let batch_size = segments
+            .len()
+            .div_ceil(num_workers.max(1))
+            .clamp(1, MAX_DELETE_BATCH_SIZE);
+
+        for batch in segments.chunks(batch_size) {
+            tx.send(batch.to_vec())
+                .await
+                .expect("task channel should be open");
         }
         tx.close();
 
@@ -184,13 +280,14 @@ pub async fn delete_unreferenced_segments(
             workers.push(tokio::spawn(async move {
                 let mut result = Ok(());
 
-                while let Ok(segment) = rx.recv().await {
+                while let Ok(batch) = rx.recv().await {
                     info!(
-                        "(worker {worker_idx}) Deleting segment {}",
-                        segment.display()
+                        "(worker {worker_idx}) Deleting {} segment(s), from {}",
+                        batch.len(),
+                        batch[0].display()
                     );
 
-                    if let Err(err) = storage.delete_segment(&camera, &segment).await {
+                    for (segment, err) in storage.delete_segments_batch(&camera, &batch).await {
                         result = Err(StorageError::WorkflowPartialError);
                         warn!(
                             "Failed to delete segment {}, error: {err}",
@@ -220,6 +317,10 @@ pub async fn delete_unreferenced_segments(
         );
     }
 
+    // Blobs are only deleted once no segment refers to them, which can only be known once
+    // segments have been deleted
+    results.push(delete_unreferenced_blobs(storage, num_workers).await);
+
     if results.iter().any(|r| r.is_err()) {
         Err(StorageError::WorkflowPartialError)
     } else {
@@ -227,6 +328,105 @@ pub async fn delete_unreferenced_segments(
     }
 }
 
+/// Deletes blobs that no content addressed segment refers to.
+///
+/// A segment that reuses a blob only refers to it once its pointer is written, so blobs that
+/// were written or reused within [`crate::blob::BLOB_GRACE_PERIOD`] are kept even if they are
+/// not referenced, allowing segments to be archived while this runs.
+async fn delete_unreferenced_blobs(storage: Provider, num_workers: usize) -> StorageResult<()> {
+    info!("Getting blob list");
+    let blobs = storage.list_blobs().await?;
+
+    // Nothing to do if segments are not content addressed
+    if blobs.is_empty() {
+        return Ok(());
+    }
+
+    // Channel that forms the job queue for workers
+    let (tx, rx) = async_channel::unbounded();
+
+    info!("Getting segment list(s)");
+    for camera in storage.list_cameras().await? {
+        for filename in storage.list_segments(&camera).await? {
+            tx.send((camera.clone(), filename))
+                .await
+                .expect("task channel should be open");
+        }
+    }
+    tx.close();
+
+    info!("Calculating referenced blobs");
+    let referenced_blobs: Arc<Mutex<HashSet<String>>> = Default::default();
+
+    let mut workers = Vec::new();
+    for _ in 0..num_workers {
+        let storage = storage.clone();
+        let rx = rx.clone();
+        let referenced_blobs = referenced_blobs.clone();
+
+        workers.push(tokio::spawn(async move {
+            while let Ok((camera, filename)) = rx.recv().await {
+                match storage.get_segment_blob(&camera, &filename).await {
+                    Ok(Some(blob)) => {
+                        referenced_blobs.lock().unwrap().insert(blob);
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        warn!(
+                            "Failed to retrieve segment {} for camera \"{camera}\", error: {err}",
+                            filename.display()
+                        );
+                        return Err(StorageError::WorkflowPartialError);
+                    }
+                }
+            }
+
+            Ok(())
+        }));
+    }
+
+    // A blob could be referenced by a segment that could not be read, so delete nothing
+    if futures::future::join_all(workers)
+        .await
+        .iter()
+        .any(|r| match r {
+            Err(_) => true,
+            Ok(Err(_)) => true,
+            Ok(_) => false,
+        })
+    {
+        return Err(StorageError::WorkflowPartialError);
+    }
+
+    let referenced_blobs = referenced_blobs.lock().unwrap().clone();
+
+    let mut result = Ok(());
+    for blob in blobs.iter().filter(|b| !referenced_blobs.contains(*b)) {
+        // Checked immediately before deleting, as the blob may have been reused since it was
+        // listed
+        match storage.get_blob_modified(blob).await {
+            Ok(modified) if Utc::now() - modified < crate::blob::BLOB_GRACE_PERIOD => {
+                info!("Keeping recently used unreferenced blob {blob}");
+                continue;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                result = Err(StorageError::WorkflowPartialError);
+                warn!("Failed to get modification time of blob {blob}, error: {err}");
+                continue;
+            }
+        }
+
+        info!("Deleting unreferenced blob {blob}");
+        if let Err(err) = storage.delete_blob(blob).await {
+            result = Err(StorageError::WorkflowPartialError);
+            warn!("Failed to delete blob {blob}, error: {err}");
+        }
+    }
+
+    result
+}
+
 #[derive(Debug, Default, Clone)]
 struct UniqueCameraSegmentCollection {
     inner: Arc<Mutex<HashMap<String, HashSet<PathBuf>>>>,
@@ -254,7 +454,6 @@ mod test {
     use super::*;
     use crate::providers::dummy::DummyConfig;
     use bytes::Bytes;
-    use chrono::Utc;
     use satori_common::{CameraSegments, EventMetadata};
     use std::path::{Path, PathBuf};
 
@@ -359,7 +558,7 @@ mod test {
             .await
             .unwrap();
 
-        let unreferenced_segments = calculate_unreferenced_segments(provider.clone(), 2)
+        let unreferenced_segments = calculate_unreferenced_segments(provider.clone(), 2, None)
             .await
             .unwrap();
 
@@ -444,9 +643,18 @@ mod test {
             .await
             .unwrap();
 
-        let unreferenced_segments = calculate_unreferenced_segments(provider.clone(), 2)
-            .await
-            .unwrap();
+        let (callback, reports) = crate::workflows::progress::test::recording_callback();
+
+        let unreferenced_segments =
+            calculate_unreferenced_segments(provider.clone(), 2, Some(callback))
+                .await
+                .unwrap();
+
+        // Progress is reported once per event
+        let reports = reports.lock().unwrap().clone();
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|p| p.total == 2));
+        assert_eq!(reports.iter().map(|p| p.processed).max(), Some(2));
 
         delete_unreferenced_segments(provider.clone(), unreferenced_segments, 2)
             .await
@@ -473,4 +681,171 @@ mod test {
             ]
         );
     }
+
+    #[tokio::test]
+    async fn test_get_camera_segments() {
+        let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        for camera in 0..10 {
+            for segment in 0..camera {
+                provider
+                    .put_segment(
+                        &format!("camera{camera}"),
+                        Path::new(&format!("{segment}.ts")),
+                        Bytes::default(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let cameras = provider.list_cameras().await.unwrap();
+        assert_eq!(cameras.len(), 9);
+
+        // Identical to listing the segments of each camera in turn
+        let mut expected = HashMap::new();
+        for camera in &cameras {
+            expected.insert(
+                camera.clone(),
+                provider.list_segments(camera).await.unwrap(),
+            );
+        }
+
+        for num_workers in [1, 3, 16] {
+            assert_eq!(
+                get_camera_segments(provider.clone(), &cameras, num_workers)
+                    .await
+                    .unwrap(),
+                expected
+            );
+        }
+
+        // Listing a camera with no segments fails
+        assert!(matches!(
+            get_camera_segments(provider.clone(), &["camera0".to_string()], 2).await,
+            Err(StorageError::WorkflowPartialError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_prune_segments_content_addressed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let provider: crate::StorageConfig = toml::from_str(&format!(
+            "kind = \"local\"\npath = \"{}\"\ncontent_addressed = true",
+            temp_dir.path().display()
+        ))
+        .unwrap();
+        let provider = provider.create_provider();
+
+        // Two segments with identical content share a blob
+        for (filename, data) in [("1.ts", "one"), ("2.ts", "one"), ("3.ts", "three")] {
+            provider
+                .put_segment("camera1", Path::new(filename), Bytes::from(data))
+                .await
+                .unwrap();
+        }
+        assert_eq!(provider.list_blobs().await.unwrap().len(), 2);
+        age_blobs(temp_dir.path());
+
+        provider
+            .put_event(&Event {
+                metadata: EventMetadata {
+                    id: "test-1".into(),
+                    timestamp: Utc::now().into(),
+                },
+                start: Utc::now().into(),
+                end: Utc::now().into(),
+                reasons: Default::default(),
+                cameras: vec![CameraSegments {
+                    name: "camera1".into(),
+                    segment_list: vec![PathBuf::from("1.ts")],
+                }],
+            })
+            .await
+            .unwrap();
+
+        let unreferenced_segments = calculate_unreferenced_segments(provider.clone(), 2, None)
+            .await
+            .unwrap();
+
+        delete_unreferenced_segments(provider.clone(), unreferenced_segments, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            provider.list_segments("camera1").await.unwrap(),
+            vec![Path::new("1.ts").to_owned()]
+        );
+
+        // The blob shared with a deleted segment is kept, the other is deleted
+        assert_eq!(
+            provider.list_blobs().await.unwrap(),
+            vec![crate::blob::blob_hash(None, b"one")]
+        );
+        assert_eq!(
+            provider
+                .get_segment("camera1", Path::new("1.ts"))
+                .await
+                .unwrap(),
+            Bytes::from("one")
+        );
+    }
+
+    /// Makes every blob of a local provider appear to have last been used before the grace
+    /// period.
+    fn age_blobs(path: &Path) {
+        let modified = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 60 * 60);
+        for blob in std::fs::read_dir(path.join("blobs")).unwrap() {
+            std::fs::File::options()
+                .write(true)
+                .open(blob.unwrap().path())
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_unreferenced_blobs_keeps_recently_used() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let provider: crate::StorageConfig = toml::from_str(&format!(
+            "kind = \"local\"\npath = \"{}\"\ncontent_addressed = true",
+            temp_dir.path().display()
+        ))
+        .unwrap();
+        let provider = provider.create_provider();
+
+        for (filename, data) in [("1.ts", "one"), ("2.ts", "two")] {
+            provider
+                .put_segment("camera1", Path::new(filename), Bytes::from(data))
+                .await
+                .unwrap();
+        }
+        age_blobs(temp_dir.path());
+
+        // Reusing a blob marks it as recently used, even though the segment (standing in for one
+        // whose pointer is yet to be written) does not refer to it by the time blobs are pruned
+        provider
+            .put_segment("camera1", Path::new("3.ts"), Bytes::from("one"))
+            .await
+            .unwrap();
+
+        for filename in ["1.ts", "2.ts", "3.ts"] {
+            provider
+                .delete_segment("camera1", Path::new(filename))
+                .await
+                .unwrap();
+        }
+
+        delete_unreferenced_blobs(provider.clone(), 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            provider.list_blobs().await.unwrap(),
+            vec![crate::blob::blob_hash(None, b"one")]
+        );
+    }
 }
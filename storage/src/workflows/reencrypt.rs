@@ -0,0 +1,438 @@
+use super::Checkpoint;
+use crate::{EncryptionConfig, Provider, StorageError, StorageProvider, StorageResult};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+#[derive(Debug)]
+enum Job {
+    Event(PathBuf),
+    Segment(String, PathBuf),
+}
+
+impl Job {
+    fn checkpoint_item(&self) -> String {
+        match self {
+            Self::Event(filename) => format!("event/{}", filename.display()),
+            Self::Segment(camera, filename) => {
+                format!("segment/{camera}/{}", filename.display())
+            }
+        }
+    }
+}
+
+/// Re-encrypts every event and segment in a storage provider.
+///
+/// Each object is read and decrypted using `old_key`, then encrypted using `new_key` and written
+/// back in place. Event notes are re-encrypted along with their event.
+///
+/// Blobs holding the content of content addressed segments are named by a hash keyed by the
+/// segment encryption key, so are stored again under their new name as each segment is
+/// re-encrypted. The blobs of the old key are left unreferenced, to be deleted by pruning
+/// unreferenced blobs.
+///
+/// If a checkpoint is provided then objects it records as complete are skipped, and each object
+/// is recorded in it once re-encrypted.
+pub async fn reencrypt_archive(
+    storage: Provider,
+    old_key: EncryptionConfig,
+    new_key: EncryptionConfig,
+    num_workers: usize,
+    checkpoint: Option<Checkpoint>,
+) -> StorageResult<()> {
+    let old_storage = storage.with_encryption(old_key);
+    let new_storage = storage.with_encryption(new_key);
+
+    let mut jobs = Vec::new();
+
+    info!("Getting event list");
+    for filename in storage.list_events().await? {
+        jobs.push(Job::Event(filename));
+    }
+
+    info!("Getting camera list");
+    for camera in storage.list_cameras().await? {
+        info!("Getting segment list for camera \"{camera}\"");
+        for filename in storage.list_segments(&camera).await? {
+            jobs.push(Job::Segment(camera.clone(), filename));
+        }
+    }
+
+    if let Some(checkpoint) = &checkpoint {
+        let items: Vec<String> = jobs.iter().map(|j| j.checkpoint_item()).collect();
+
+        let missing = checkpoint.count_missing(items.iter().map(|i| i.as_str()));
+        if missing > 0 {
+            warn!("{missing} completed item(s) in checkpoint no longer exist, ignoring them");
+        }
+
+        jobs.retain(|j| !checkpoint.is_complete(&j.checkpoint_item()));
+        info!("{} item(s) remaining", jobs.len());
+    }
+
+    run_jobs(&old_storage, &new_storage, jobs, num_workers, &checkpoint).await
+}
+
+async fn run_jobs(
+    old_storage: &Provider,
+    new_storage: &Provider,
+    jobs: Vec<Job>,
+    num_workers: usize,
+    checkpoint: &Option<Checkpoint>,
+) -> StorageResult<()> {
+    // Channel that forms the job queue for workers
+    let (tx, rx) = async_channel::unbounded();
+
+    for job in jobs {
+        tx.send(job).await.expect("task channel should be open");
+    }
+
+    // Workers will terminate when the channel is empty and closed
+    tx.close();
+
+    let mut workers = Vec::new();
+    for worker_idx in 0..num_workers {
+        let old_storage = old_storage.clone();
+        let new_storage = new_storage.clone();
+        let rx = rx.clone();
+        let checkpoint = checkpoint.clone();
+
+        workers.push(tokio::spawn(async move {
+            let mut result = Ok(());
+
+            while let Ok(job) = rx.recv().await {
+                info!("(worker {worker_idx}) Re-encrypting {job:?}");
+
+                match reencrypt_object(&old_storage, &new_storage, &job).await {
+                    Ok(()) => {
+                        if let Some(checkpoint) = &checkpoint {
+                            if let Err(err) = checkpoint.mark_complete(&job.checkpoint_item()) {
+                                result = Err(StorageError::WorkflowPartialError);
+                                warn!("Failed to record {job:?} in checkpoint, error: {err}");
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        result = Err(StorageError::WorkflowPartialError);
+                        warn!("Failed to re-encrypt {job:?}, error: {err}");
+                    }
+                }
+            }
+
+            result
+        }));
+    }
+
+    // Wait for all workers to terminate, returning an error if any one job failed
+    if futures::future::join_all(workers)
+        .await
+        .iter()
+        .any(|r| match r {
+            Err(_) => true,
+            Ok(Err(_)) => true,
+            Ok(_) => false,
+        })
+    {
+        Err(StorageError::WorkflowPartialError)
+    } else {
+        Ok(())
+    }
+}
+
+async fn reencrypt_object(
+    old_storage: &Provider,
+    new_storage: &Provider,
+    job: &Job,
+) -> StorageResult<()> {
+    match job {
+        Job::Event(filename) => {
+            let event = old_storage.get_event(filename).await?;
+            let note = old_storage.get_event_note(filename).await?;
+
+            new_storage.put_event(&event).await?;
+            if let Some(note) = note {
+                new_storage.put_event_note(filename, &note).await?;
+            }
+
+            Ok(())
+        }
+        Job::Segment(camera, filename) => {
+            let data = old_storage.get_segment(camera, filename).await?;
+            new_storage.put_segment(camera, filename, data).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventNote, StorageConfig};
+    use bytes::Bytes;
+    use chrono::Utc;
+    use satori_common::{CameraSegments, Event, EventMetadata};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    const OLD_KEY: &str = "
+[event]
+kind = \"aes256_gcm\"
+key = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+]
+[segment]
+kind = \"aes256_gcm\"
+key = [
+    31, 30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 18, 17, 16,
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+]
+";
+
+    const NEW_KEY: &str = "
+[event]
+kind = \"aes256_gcm\"
+key = [
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+]
+[segment]
+kind = \"aes256_gcm\"
+key = [
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+]
+";
+
+    fn build_test_storage(dir: &Path) -> Provider {
+        let config: StorageConfig =
+            toml::from_str(&format!("kind = \"local\"\npath = \"{}\"", dir.display())).unwrap();
+        config.create_provider()
+    }
+
+    fn test_event() -> Event {
+        Event {
+            metadata: EventMetadata {
+                id: "test-1".into(),
+                timestamp: Utc::now().into(),
+            },
+            start: Utc::now().into(),
+            end: Utc::now().into(),
+            reasons: Default::default(),
+            cameras: vec![CameraSegments {
+                name: "camera1".into(),
+                segment_list: vec!["one.ts".into()],
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_archive() {
+        let dir = TempDir::new().unwrap();
+        let storage = build_test_storage(dir.path());
+
+        let old_key: EncryptionConfig = toml::from_str(OLD_KEY).unwrap();
+        let new_key: EncryptionConfig = toml::from_str(NEW_KEY).unwrap();
+
+        let event = test_event();
+        let segment_filename = PathBuf::from("one.ts");
+        let segment_data = Bytes::from("camera1 segment one");
+
+        let event_filename = event.metadata.get_filename();
+        let note = EventNote::new("note".into());
+
+        let old_storage = storage.with_encryption(old_key.clone());
+        old_storage.put_event(&event).await.unwrap();
+        old_storage
+            .put_event_note(&event_filename, &note)
+            .await
+            .unwrap();
+        old_storage
+            .put_segment("camera1", &segment_filename, segment_data.clone())
+            .await
+            .unwrap();
+
+        reencrypt_archive(storage.clone(), old_key, new_key.clone(), 2, None)
+            .await
+            .unwrap();
+
+        // Everything can be read back using the new key
+        let new_storage = storage.with_encryption(new_key);
+        assert_eq!(new_storage.get_event(&event_filename).await.unwrap(), event);
+        assert_eq!(
+            new_storage.get_event_note(&event_filename).await.unwrap(),
+            Some(note)
+        );
+        assert_eq!(
+            new_storage
+                .get_segment("camera1", &segment_filename)
+                .await
+                .unwrap(),
+            segment_data
+        );
+
+        // Nothing can be read back using the old key
+        assert!(old_storage.get_event(&event_filename).await.is_err());
+        assert!(old_storage.get_event_note(&event_filename).await.is_err());
+        assert!(old_storage
+            .get_segment("camera1", &segment_filename)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_archive_content_addressed() {
+        let dir = TempDir::new().unwrap();
+        let config: StorageConfig = toml::from_str(&format!(
+            "kind = \"local\"\npath = \"{}\"\ncontent_addressed = true",
+            dir.path().display()
+        ))
+        .unwrap();
+        let storage = config.create_provider();
+
+        let old_key: EncryptionConfig = toml::from_str(OLD_KEY).unwrap();
+        let new_key: EncryptionConfig = toml::from_str(NEW_KEY).unwrap();
+
+        // Both segments share a single blob
+        let old_storage = storage.with_encryption(old_key.clone());
+        for filename in ["one.ts", "two.ts"] {
+            old_storage
+                .put_segment("camera1", Path::new(filename), Bytes::from("segment"))
+                .await
+                .unwrap();
+        }
+        let old_blobs = storage.list_blobs().await.unwrap();
+        assert_eq!(old_blobs.len(), 1);
+
+        reencrypt_archive(storage.clone(), old_key, new_key.clone(), 2, None)
+            .await
+            .unwrap();
+
+        let new_storage = storage.with_encryption(new_key);
+        for filename in ["one.ts", "two.ts"] {
+            assert_eq!(
+                new_storage
+                    .get_segment("camera1", Path::new(filename))
+                    .await
+                    .unwrap(),
+                Bytes::from("segment")
+            );
+            assert!(old_storage
+                .get_segment("camera1", Path::new(filename))
+                .await
+                .is_err());
+        }
+
+        // Both segments share a single new blob, named by a hash keyed by the new key. The old
+        // blob is left to be pruned.
+        let blobs = storage.list_blobs().await.unwrap();
+        assert_eq!(blobs.len(), 2);
+        assert!(blobs.contains(&old_blobs[0]));
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_archive_wrong_old_key() {
+        let dir = TempDir::new().unwrap();
+        let storage = build_test_storage(dir.path());
+
+        let old_key: EncryptionConfig = toml::from_str(OLD_KEY).unwrap();
+        let new_key: EncryptionConfig = toml::from_str(NEW_KEY).unwrap();
+
+        let event = test_event();
+        storage
+            .with_encryption(old_key.clone())
+            .put_event(&event)
+            .await
+            .unwrap();
+
+        // Objects encrypted with a key other than the old key cannot be re-encrypted
+        let result = reencrypt_archive(storage.clone(), new_key, old_key.clone(), 2, None).await;
+        assert!(matches!(result, Err(StorageError::WorkflowPartialError)));
+
+        // The object is left untouched
+        assert_eq!(
+            storage
+                .with_encryption(old_key)
+                .get_event(&event.metadata.get_filename())
+                .await
+                .unwrap(),
+            event
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_archive_resume() {
+        let dir = TempDir::new().unwrap();
+        let storage = build_test_storage(dir.path());
+        let checkpoint_file = dir.path().join("checkpoint");
+
+        let old_key: EncryptionConfig = toml::from_str(OLD_KEY).unwrap();
+        let new_key: EncryptionConfig = toml::from_str(NEW_KEY).unwrap();
+
+        let old_storage = storage.with_encryption(old_key.clone());
+        let new_storage = storage.with_encryption(new_key.clone());
+
+        let mut events = Vec::new();
+        for id in ["one", "two", "three"] {
+            let mut event = test_event();
+            event.metadata.id = id.into();
+            old_storage.put_event(&event).await.unwrap();
+            events.push(event);
+        }
+        old_storage
+            .put_segment("camera1", Path::new("one.ts"), Bytes::from("one"))
+            .await
+            .unwrap();
+
+        // Simulate an interrupted run that re-encrypted only the first event
+        {
+            let checkpoint = Checkpoint::new(&checkpoint_file).unwrap();
+            new_storage.put_event(&events[0]).await.unwrap();
+            checkpoint
+                .mark_complete(&Job::Event(events[0].metadata.get_filename()).checkpoint_item())
+                .unwrap();
+            // An item that no longer exists in the archive
+            checkpoint.mark_complete("event/deleted.json").unwrap();
+        }
+
+        // Re-encrypting the first event again would fail, as it is no longer encrypted with the
+        // old key
+        reencrypt_archive(
+            storage.clone(),
+            old_key,
+            new_key,
+            2,
+            Some(Checkpoint::resume(&checkpoint_file).unwrap()),
+        )
+        .await
+        .unwrap();
+
+        for event in &events {
+            assert_eq!(
+                new_storage
+                    .get_event(&event.metadata.get_filename())
+                    .await
+                    .unwrap(),
+                *event
+            );
+        }
+        assert_eq!(
+            new_storage
+                .get_segment("camera1", Path::new("one.ts"))
+                .await
+                .unwrap(),
+            Bytes::from("one")
+        );
+
+        // Each item is recorded as complete exactly once
+        let checkpoint = std::fs::read_to_string(&checkpoint_file).unwrap();
+        let mut lines: Vec<&str> = checkpoint.lines().collect();
+        lines.sort();
+        let mut expected: Vec<String> = events
+            .iter()
+            .map(|e| format!("event/{}", e.metadata.get_filename().display()))
+            .collect();
+        expected.push("event/deleted.json".into());
+        expected.push("segment/camera1/one.ts".into());
+        expected.sort();
+        assert_eq!(lines, expected);
+    }
+}
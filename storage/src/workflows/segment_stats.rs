@@ -0,0 +1,219 @@
+use crate::{Provider, StorageProvider, StorageResult};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// Parameters used to decide if the recent segments of a camera are anomalously small.
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentSizeThresholds {
+    /// Number of most recent segments to compare against all segments of a camera.
+    pub recent: usize,
+
+    /// A camera is flagged if the median size of its recent segments is less than this fraction
+    /// of the median size of all its segments.
+    pub min_ratio: f64,
+}
+
+impl Default for SegmentSizeThresholds {
+    fn default() -> Self {
+        Self {
+            recent: 10,
+            min_ratio: 0.5,
+        }
+    }
+}
+
+/// Number of segments with a size in a range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistogramBucket {
+    /// Upper bound (inclusive) of the segment sizes in this bucket, in bytes.
+    pub max_size: u64,
+    pub count: usize,
+}
+
+/// Segment size statistics for a single camera.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SegmentSizeReport {
+    pub camera: String,
+    pub count: usize,
+    pub median_size: u64,
+    pub recent_median_size: u64,
+    /// Histogram of segment sizes, with each bucket being twice the size of the previous.
+    pub histogram: Vec<HistogramBucket>,
+    /// True if the recent segments are anomalously small, which may indicate a failing camera.
+    pub anomalous: bool,
+}
+
+/// Smallest histogram bucket, in bytes.
+const MIN_BUCKET_SIZE: u64 = 1024;
+
+/// Computes segment size statistics for every camera in the archive.
+///
+/// Segments are assumed to be listed oldest first, as is the case for segment filenames that are
+/// timestamps.
+pub async fn segment_size_stats(
+    storage: Provider,
+    thresholds: SegmentSizeThresholds,
+) -> StorageResult<Vec<SegmentSizeReport>> {
+    let mut reports = Vec::new();
+
+    info!("Getting camera list");
+    for camera in storage.list_cameras().await? {
+        info!("Getting segment list for camera \"{camera}\"");
+        let sizes: Vec<u64> = storage
+            .list_segments_with_meta(&camera)
+            .await?
+            .into_iter()
+            .map(|s| s.size)
+            .collect();
+
+        reports.push(analyse_segment_sizes(camera, &sizes, thresholds));
+    }
+
+    Ok(reports)
+}
+
+fn analyse_segment_sizes(
+    camera: String,
+    sizes: &[u64],
+    thresholds: SegmentSizeThresholds,
+) -> SegmentSizeReport {
+    let median_size = median(sizes);
+
+    let recent = &sizes[sizes.len().saturating_sub(thresholds.recent)..];
+    let recent_median_size = median(recent);
+
+    let anomalous = (recent_median_size as f64) < (median_size as f64) * thresholds.min_ratio;
+
+    let mut histogram = BTreeMap::new();
+    for size in sizes {
+        let max_size = size.max(&MIN_BUCKET_SIZE).next_power_of_two();
+        *histogram.entry(max_size).or_insert(0) += 1;
+    }
+
+    SegmentSizeReport {
+        camera,
+        count: sizes.len(),
+        median_size,
+        recent_median_size,
+        histogram: histogram
+            .into_iter()
+            .map(|(max_size, count)| HistogramBucket { max_size, count })
+            .collect(),
+        anomalous,
+    }
+}
+
+fn median(values: &[u64]) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+
+    let mut values = values.to_vec();
+    values.sort_unstable();
+
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::providers::dummy::DummyConfig;
+    use bytes::Bytes;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[]), 0);
+        assert_eq!(median(&[3]), 3);
+        assert_eq!(median(&[3, 1, 2]), 2);
+        assert_eq!(median(&[4, 1, 3, 2]), 2);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let report = analyse_segment_sizes(
+            "camera1".into(),
+            &[10, 1024, 1025, 2048, 5000],
+            SegmentSizeThresholds::default(),
+        );
+
+        assert_eq!(
+            report.histogram,
+            vec![
+                HistogramBucket {
+                    max_size: 1024,
+                    count: 2
+                },
+                HistogramBucket {
+                    max_size: 2048,
+                    count: 2
+                },
+                HistogramBucket {
+                    max_size: 8192,
+                    count: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_segments() {
+        let report = analyse_segment_sizes("camera1".into(), &[], SegmentSizeThresholds::default());
+
+        assert_eq!(report.count, 0);
+        assert!(report.histogram.is_empty());
+        assert!(!report.anomalous);
+    }
+
+    #[tokio::test]
+    async fn test_segment_size_stats() {
+        let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        let thresholds = SegmentSizeThresholds {
+            recent: 3,
+            min_ratio: 0.5,
+        };
+
+        for i in 0..10 {
+            let filename = PathBuf::from(format!("{i:02}.ts"));
+
+            // A healthy camera, with some variation in segment size
+            provider
+                .put_segment(
+                    "healthy",
+                    &filename,
+                    Bytes::from(vec![0; 1000 + (i % 3) * 200]),
+                )
+                .await
+                .unwrap();
+
+            // A camera that has recently started producing tiny segments
+            let size = if i < 7 { 1000 } else { 10 };
+            provider
+                .put_segment("failing", &filename, Bytes::from(vec![0; size]))
+                .await
+                .unwrap();
+        }
+
+        let reports = segment_size_stats(provider, thresholds).await.unwrap();
+        assert_eq!(reports.len(), 2);
+
+        let failing = &reports[0];
+        assert_eq!(failing.camera, "failing");
+        assert_eq!(failing.count, 10);
+        assert_eq!(failing.median_size, 1000);
+        assert_eq!(failing.recent_median_size, 10);
+        assert!(failing.anomalous);
+
+        let healthy = &reports[1];
+        assert_eq!(healthy.camera, "healthy");
+        assert_eq!(healthy.count, 10);
+        assert!(!healthy.anomalous);
+    }
+}
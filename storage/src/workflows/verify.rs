@@ -0,0 +1,262 @@
+use crate::{Provider, StorageError, StorageProvider, StorageResult};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tracing::{info, warn};
+
+/// Problems found when verifying the integrity of an archive.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    /// Number of events that were checked.
+    pub events: usize,
+
+    /// Events that could not be retrieved.
+    pub unreadable_events: BTreeSet<PathBuf>,
+
+    /// Segments that are referenced by an event but are not stored, grouped by camera.
+    pub missing_segments: BTreeMap<String, BTreeSet<PathBuf>>,
+
+    /// Segments that are stored but could not be retrieved, grouped by camera.
+    pub corrupt_segments: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+impl VerifyReport {
+    /// True if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.unreadable_events.is_empty()
+            && self.missing_segments.is_empty()
+            && self.corrupt_segments.is_empty()
+    }
+}
+
+/// Checks that every segment referenced by an event exists in the archive.
+///
+/// Existence is checked against the list of stored segments for each camera. If `deep` is set,
+/// each referenced segment is also fully retrieved (and decrypted).
+pub async fn verify_archive(
+    storage: Provider,
+    num_workers: usize,
+    deep: bool,
+) -> StorageResult<VerifyReport> {
+    info!("Getting camera list");
+    let mut stored_segments: HashMap<String, HashSet<PathBuf>> = HashMap::new();
+    for camera in storage.list_cameras().await? {
+        info!("Getting segment list for camera \"{camera}\"");
+        let segments = storage.list_segments(&camera).await?;
+        stored_segments.insert(camera, segments.into_iter().collect());
+    }
+    let stored_segments = Arc::new(stored_segments);
+
+    info!("Getting event list");
+    let event_filenames = storage.list_events().await?;
+
+    info!("Verifying {} events", event_filenames.len());
+    let report = Arc::new(Mutex::new(VerifyReport {
+        events: event_filenames.len(),
+        ..Default::default()
+    }));
+
+    // Channel that forms the job queue for workers
+    let (tx, rx) = async_channel::unbounded();
+
+    // Fill the channel with the event filenames then immediately close it
+    // Workers will terminate when the channel is empty and closed
+    for filename in event_filenames {
+        tx.send(filename)
+            .await
+            .expect("task channel should be open");
+    }
+    tx.close();
+
+    // Start as many workers as were requested
+    let mut workers = Vec::new();
+    for worker_idx in 0..num_workers {
+        let storage = storage.clone();
+        let rx = rx.clone();
+        let stored_segments = stored_segments.clone();
+        let report = report.clone();
+
+        workers.push(tokio::spawn(async move {
+            while let Ok(filename) = rx.recv().await {
+                info!(
+                    "(worker {worker_idx}) Verifying event {}",
+                    filename.display()
+                );
+
+                let event = match storage.get_event(&filename).await {
+                    Ok(event) => event,
+                    Err(err) => {
+                        warn!(
+                            "Failed to retrieve event {}, error: {err}",
+                            filename.display()
+                        );
+                        report.lock().unwrap().unreadable_events.insert(filename);
+                        continue;
+                    }
+                };
+
+                for camera in event.cameras {
+                    for segment in camera.segment_list {
+                        let stored = stored_segments
+                            .get(&camera.name)
+                            .is_some_and(|s| s.contains(&segment));
+
+                        if !stored {
+                            warn!(
+                                "Segment {} for camera \"{}\" is missing",
+                                segment.display(),
+                                camera.name
+                            );
+                            report
+                                .lock()
+                                .unwrap()
+                                .missing_segments
+                                .entry(camera.name.clone())
+                                .or_default()
+                                .insert(segment);
+                        } else if deep {
+                            if let Err(err) = storage.get_segment(&camera.name, &segment).await {
+                                warn!(
+                                    "Failed to retrieve segment {} for camera \"{}\", error: {err}",
+                                    segment.display(),
+                                    camera.name
+                                );
+                                report
+                                    .lock()
+                                    .unwrap()
+                                    .corrupt_segments
+                                    .entry(camera.name.clone())
+                                    .or_default()
+                                    .insert(segment);
+                            }
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    // Wait for all workers to terminate
+    if futures::future::join_all(workers)
+        .await
+        .iter()
+        .any(|r| r.is_err())
+    {
+        return Err(StorageError::WorkflowPartialError);
+    }
+
+    let report = report.lock().unwrap().clone();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::providers::dummy::DummyConfig;
+    use bytes::Bytes;
+    use chrono::Utc;
+    use satori_common::{CameraSegments, Event, EventMetadata};
+    use std::path::Path;
+
+    async fn build_test_storage() -> Provider {
+        let provider = crate::StorageConfig::Dummy(DummyConfig::default()).create_provider();
+
+        for (camera, segment) in [
+            ("camera1", "1_1.ts"),
+            ("camera1", "1_2.ts"),
+            ("camera2", "2_1.ts"),
+            ("camera2", "2_2.ts"),
+        ] {
+            provider
+                .put_segment(camera, Path::new(segment), Bytes::from("segment"))
+                .await
+                .unwrap();
+        }
+
+        provider
+            .put_event(&Event {
+                metadata: EventMetadata {
+                    id: "test-1".into(),
+                    timestamp: Utc::now().into(),
+                },
+                start: Utc::now().into(),
+                end: Utc::now().into(),
+                reasons: Default::default(),
+                cameras: vec![
+                    CameraSegments {
+                        name: "camera1".into(),
+                        segment_list: vec![PathBuf::from("1_1.ts"), PathBuf::from("1_2.ts")],
+                    },
+                    CameraSegments {
+                        name: "camera2".into(),
+                        segment_list: vec![PathBuf::from("2_1.ts"), PathBuf::from("2_2.ts")],
+                    },
+                ],
+            })
+            .await
+            .unwrap();
+
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_verify_ok() {
+        let provider = build_test_storage().await;
+
+        for deep in [false, true] {
+            let report = verify_archive(provider.clone(), 2, deep).await.unwrap();
+            assert_eq!(report.events, 1);
+            assert!(report.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_missing_segment() {
+        let provider = build_test_storage().await;
+
+        provider
+            .delete_segment("camera2", Path::new("2_1.ts"))
+            .await
+            .unwrap();
+
+        for deep in [false, true] {
+            let report = verify_archive(provider.clone(), 2, deep).await.unwrap();
+            assert!(!report.is_ok());
+            assert_eq!(
+                report.missing_segments,
+                BTreeMap::from([(
+                    "camera2".to_string(),
+                    BTreeSet::from([PathBuf::from("2_1.ts")])
+                )])
+            );
+            assert!(report.corrupt_segments.is_empty());
+            assert!(report.unreadable_events.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_missing_camera() {
+        let provider = build_test_storage().await;
+
+        provider
+            .delete_segment("camera1", Path::new("1_1.ts"))
+            .await
+            .unwrap();
+        provider
+            .delete_segment("camera1", Path::new("1_2.ts"))
+            .await
+            .unwrap();
+
+        let report = verify_archive(provider, 2, false).await.unwrap();
+        assert_eq!(
+            report.missing_segments,
+            BTreeMap::from([(
+                "camera1".to_string(),
+                BTreeSet::from([PathBuf::from("1_1.ts"), PathBuf::from("1_2.ts")])
+            )])
+        );
+    }
+}
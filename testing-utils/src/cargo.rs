@@ -15,15 +15,18 @@ use tokio::{
 use tracing::{debug, info};
 
 type SharedPid = Arc<Mutex<Option<Pid>>>;
+type SharedOutput = Arc<Mutex<Vec<String>>>;
 
 pub struct CargoBinaryRunner {
     pid: SharedPid,
+    stdout: SharedOutput,
     handle: Option<JoinHandle<()>>,
 }
 
 impl CargoBinaryRunner {
     pub fn new(binary: String, args: Vec<String>, env: Vec<(String, String)>) -> Self {
         let pid = SharedPid::default();
+        let stdout = SharedOutput::default();
 
         let mut workspace_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         workspace_dir.pop();
@@ -32,6 +35,7 @@ impl CargoBinaryRunner {
             let name = binary.clone();
 
             let pid = pid.clone();
+            let stdout_lines = stdout.clone();
 
             Some(tokio::spawn(async move {
                 let mut cargo_process = unsafe {
@@ -76,7 +80,10 @@ impl CargoBinaryRunner {
                     tokio::select! {
                         line = stdout_reader.next_line() => {
                             match line {
-                                Ok(Some(line)) => debug!("{name} stdout: {line}"),
+                                Ok(Some(line)) => {
+                                    debug!("{name} stdout: {line}");
+                                    stdout_lines.lock().unwrap().push(line);
+                                }
                                 Err(_) => break,
                                 _ => (),
                             }
@@ -91,6 +98,13 @@ impl CargoBinaryRunner {
                         // Wait for process to exit
                         result = cargo_process.wait() => {
                             info!("{name} cargo exited, ok={}", result.is_ok());
+
+                            // Collect any output that has not been read yet
+                            while let Ok(Some(line)) = stdout_reader.next_line().await {
+                                debug!("{name} stdout: {line}");
+                                stdout_lines.lock().unwrap().push(line);
+                            }
+
                             *pid.lock().unwrap() = None;
                             break;
                         }
@@ -99,7 +113,21 @@ impl CargoBinaryRunner {
             }))
         };
 
-        Self { pid, handle }
+        Self {
+            pid,
+            stdout,
+            handle,
+        }
+    }
+
+    /// Returns everything the process has written to stdout so far.
+    pub fn stdout(&self) -> String {
+        self.stdout
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect()
     }
 
     pub fn stop(&self) {
@@ -6,7 +6,8 @@ use axum::{
     Router,
 };
 use chrono::{DateTime, Utc};
-use m3u8_rs::{MediaPlaylist, MediaSegment};
+use m3u8_rs::{Map, MediaPlaylist, MediaSegment};
+use satori_common::SegmentFormat;
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
     time::Duration,
@@ -17,6 +18,7 @@ pub struct DummyStreamParams {
     start_time: DateTime<Utc>,
     segment_duration: Duration,
     segment_count: usize,
+    segment_format: SegmentFormat,
 }
 
 impl DummyStreamParams {
@@ -25,6 +27,7 @@ impl DummyStreamParams {
             start_time: DateTime::parse_from_rfc3339(start_time).unwrap().into(),
             segment_duration,
             segment_count,
+            segment_format: SegmentFormat::default(),
         }
     }
 
@@ -35,8 +38,16 @@ impl DummyStreamParams {
             start_time,
             segment_duration,
             segment_count,
+            segment_format: SegmentFormat::default(),
         }
     }
+
+    /// Lists segments of the given format, fragmented MP4 segments share the initialisation
+    /// section "init.mp4".
+    pub fn with_segment_format(mut self, segment_format: SegmentFormat) -> Self {
+        self.segment_format = segment_format;
+        self
+    }
 }
 
 impl From<DummyStreamParams> for MediaPlaylist {
@@ -49,11 +60,21 @@ impl From<DummyStreamParams> for MediaPlaylist {
                 let segment_timestamp =
                     params.start_time + (params.segment_duration * i.try_into().unwrap());
 
-                let filename = segment_timestamp.format(satori_common::SEGMENT_FILENAME_FORMAT);
+                let filename =
+                    segment_timestamp.format(params.segment_format.segment_filename_format());
+
+                let map = match params.segment_format {
+                    SegmentFormat::Mpegts => None,
+                    SegmentFormat::Fmp4 => Some(Map {
+                        uri: "init.mp4".to_string(),
+                        ..Default::default()
+                    }),
+                };
 
                 MediaSegment {
                     uri: filename.to_string(),
                     duration: segment_duration,
+                    map,
                     ..Default::default()
                 }
             })
@@ -118,6 +139,12 @@ async fn handler(name: String, filename: String, playlist: MediaPlaylist) -> Res
     } else if filename.ends_with(".ts") {
         let s = format!("Dummy MPEG-TS segment for dummy HLS stream \"{name}\"\n{filename}\n");
         Html(s).into_response()
+    } else if filename.ends_with(".m4s") {
+        let s = format!("Dummy fMP4 segment for dummy HLS stream \"{name}\"\n{filename}\n");
+        Html(s).into_response()
+    } else if filename == "init.mp4" {
+        let s = format!("Dummy fMP4 initialisation section for dummy HLS stream \"{name}\"\n");
+        Html(s).into_response()
     } else {
         (StatusCode::NOT_FOUND, "Not found").into_response()
     }
@@ -0,0 +1,41 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tracing::{subscriber::DefaultGuard, Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+/// Counts the tracing events logged at a given level, e.g. to check that repeated errors are
+/// throttled.
+#[derive(Clone)]
+pub struct EventCounter {
+    level: Level,
+    count: Arc<AtomicUsize>,
+}
+
+impl EventCounter {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            count: Default::default(),
+        }
+    }
+
+    /// Number of events logged at the level so far.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Counts events logged on the current thread, until the returned guard is dropped.
+    pub fn set_default(&self) -> DefaultGuard {
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(self.clone()))
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EventCounter {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() == self.level {
+            self.count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
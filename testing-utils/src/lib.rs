@@ -1,7 +1,9 @@
 mod cargo;
 mod dummy_hls_server;
+mod event_counter;
 mod minio;
 mod mosquitto;
+mod mqtt_capture;
 mod mqtt_client;
 mod network;
 mod podman;
@@ -9,8 +11,10 @@ mod podman;
 pub use self::{
     cargo::CargoBinaryRunner,
     dummy_hls_server::{DummyHlsServer, DummyStreamParams},
+    event_counter::EventCounter,
     minio::MinioDriver,
     mosquitto::MosquittoDriver,
+    mqtt_capture::MqttCapture,
     mqtt_client::TestMqttClient,
     network::wait_for_url,
     podman::PodmanDriver,
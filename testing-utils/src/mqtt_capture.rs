@@ -0,0 +1,115 @@
+use bytes::BytesMut;
+use rumqttc::{
+    mqttbytes::{self, v4::Packet},
+    ConnAck, ConnectReturnCode, PingResp, PubAck, PubComp, PubRec, Publish, QoS, SubAck,
+    SubscribeReasonCode,
+};
+use std::sync::{Arc, Mutex};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// Maximum size of a packet accepted by the broker.
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+/// A minimal in-process MQTT broker that records every message published to it, for testing
+/// what a client publishes without running a real broker.
+///
+/// Messages are not forwarded to subscribers.
+pub struct MqttCapture {
+    port: u16,
+    published: Arc<Mutex<Vec<Publish>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MqttCapture {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let published: Arc<Mutex<Vec<Publish>>> = Default::default();
+
+        let handle = {
+            let published = published.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(stream, published.clone()));
+                }
+            })
+        };
+
+        Self {
+            port,
+            published,
+            handle,
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Messages published so far, in the order they were received.
+    pub fn published(&self) -> Vec<Publish> {
+        self.published.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MqttCapture {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, published: Arc<Mutex<Vec<Publish>>>) {
+    let mut incoming = BytesMut::new();
+
+    loop {
+        let packet = match mqttbytes::v4::read(&mut incoming, MAX_PACKET_SIZE) {
+            Ok(packet) => packet,
+            Err(mqttbytes::Error::InsufficientBytes(_)) => {
+                match stream.read_buf(&mut incoming).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) => continue,
+                }
+            }
+            Err(_) => return,
+        };
+
+        let mut outgoing = BytesMut::new();
+        let result = match packet {
+            Packet::Connect(_) => {
+                ConnAck::new(ConnectReturnCode::Success, false).write(&mut outgoing)
+            }
+            Packet::Subscribe(subscribe) => SubAck::new(
+                subscribe.pkid,
+                subscribe
+                    .filters
+                    .iter()
+                    .map(|f| SubscribeReasonCode::Success(f.qos))
+                    .collect(),
+            )
+            .write(&mut outgoing),
+            Packet::Publish(publish) => {
+                let pkid = publish.pkid;
+                let qos = publish.qos;
+                published.lock().unwrap().push(publish);
+                match qos {
+                    QoS::AtMostOnce => Ok(0),
+                    QoS::AtLeastOnce => PubAck::new(pkid).write(&mut outgoing),
+                    QoS::ExactlyOnce => PubRec::new(pkid).write(&mut outgoing),
+                }
+            }
+            Packet::PubRel(pubrel) => PubComp::new(pubrel.pkid).write(&mut outgoing),
+            Packet::PingReq => PingResp.write(&mut outgoing),
+            Packet::Disconnect => return,
+            _ => Ok(0),
+        };
+
+        if result.is_err() || stream.write_all(&outgoing).await.is_err() {
+            return;
+        }
+    }
+}